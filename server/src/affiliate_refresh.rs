@@ -0,0 +1,119 @@
+use crate::services::affiliate::retrieve_stripe_state;
+use affect_status::Status;
+use affect_storage::{
+    database::{
+        client::DatabaseClient,
+        store::{OnDemandStore, TransactionalStore},
+    },
+    models::affiliate::AffiliateRow,
+    stores::affiliate::AffiliateStore,
+};
+use chrono::{Duration, Utc};
+use log::{info, warn};
+use std::{marker::PhantomData, sync::Arc};
+
+#[cfg(test)]
+mod tests;
+
+/// Maximum number of affiliates refreshed by a single tick, so one tick can't
+/// exceed stripe's rate limits.
+const MAX_REFRESHES_PER_TICK: i64 = 100;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("storage error: {0:?}")]
+    Storage(#[from] affect_storage::Error),
+
+    #[error("stripe error: {0:?}")]
+    Stripe(#[from] Status),
+}
+
+/// Refreshes the stripe account state of affiliates which were not refreshed
+/// recently. Account updates are normally received through webhooks, this
+/// catches up on missed or failed deliveries.
+pub struct AffiliateRefresher<Db, Store, TStore> {
+    database: Arc<Db>,
+    stripe: Arc<stripe::Client>,
+    stale_after: Duration,
+    _marker: PhantomData<(Store, TStore)>,
+}
+
+impl<Db, Store, TStore> AffiliateRefresher<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore> + 'static,
+    Store: AffiliateStore + OnDemandStore + 'static,
+    TStore: TransactionalStore + 'static,
+{
+    pub fn new(database: Arc<Db>, stripe: Arc<stripe::Client>, stale_after: Duration) -> Self {
+        Self {
+            database,
+            stripe,
+            stale_after,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Refreshes stale affiliates every interval, forever.
+    pub async fn run(&self, interval: std::time::Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.refresh_stale().await {
+                Ok(0) => {}
+                Ok(refreshed) => info!("Refreshed {} affiliates", refreshed),
+                Err(e) => warn!("Failed to refresh affiliates: {:?}", e),
+            }
+        }
+    }
+
+    /// Refreshes the affiliates which are stale, returning the number of
+    /// refreshed affiliates.
+    pub async fn refresh_stale(&self) -> Result<usize, Error> {
+        let affiliate_rows = self
+            .database
+            .on_demand()
+            .list_affiliates_for_stripe_refresh(
+                Utc::now() - self.stale_after,
+                MAX_REFRESHES_PER_TICK,
+            )
+            .await?;
+
+        let mut refreshed = 0;
+        for affiliate_row in affiliate_rows {
+            match self.refresh(&affiliate_row).await {
+                Ok(()) => refreshed += 1,
+                Err(e) => {
+                    warn!(
+                        "Failed to refresh affiliate {}: {:?}",
+                        affiliate_row.affiliate_id, e
+                    );
+                    // Retried once it is stale again, so that affiliates which
+                    // keep failing don't starve the others.
+                    self.database
+                        .on_demand()
+                        .update_affiliate_stripe_refresh_time(
+                            &affiliate_row.stripe_account_id,
+                            Utc::now(),
+                        )
+                        .await?;
+                }
+            }
+        }
+        Ok(refreshed)
+    }
+
+    async fn refresh(&self, affiliate_row: &AffiliateRow) -> Result<(), Error> {
+        let refresh_time = Utc::now();
+        let state = retrieve_stripe_state(&self.stripe, &affiliate_row.stripe_account_id).await?;
+        self.database
+            .on_demand()
+            .update_affiliate_stripe_state(
+                &affiliate_row.stripe_account_id,
+                state,
+                refresh_time,
+                Utc::now(),
+            )
+            .await?;
+        Ok(())
+    }
+}
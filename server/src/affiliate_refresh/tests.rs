@@ -0,0 +1,75 @@
+use crate::{
+    affiliate_refresh::AffiliateRefresher,
+    testing::{affiliate_row, FakeStripe},
+};
+use affect_storage_mocks::*;
+use chrono::Duration;
+use hyper::Method;
+use mockall::Sequence;
+use serde_json::json;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn failed_refresh_is_recorded() -> Result<(), anyhow::Error> {
+    // Stripe only knows "acct_123", "acct_456" fails to refresh.
+    let stripe = FakeStripe::start(vec![(
+        Method::GET,
+        "/v1/accounts/acct_123",
+        json!({
+            "id": "acct_123",
+            "object": "account",
+            "charges_enabled": true,
+            "payouts_enabled": true,
+        }),
+    )]);
+    let mut database = MockDatabaseClient::new();
+    let mut seq = Sequence::new();
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(|| {
+            let mut store = MockStore::new();
+            store
+                .expect_list_affiliates_for_stripe_refresh()
+                .times(1)
+                .return_once(|_, _| Ok(vec![affiliate_row("acct_456"), affiliate_row("acct_123")]));
+            store
+        });
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(|| {
+            let mut store = MockStore::new();
+            store
+                .expect_update_affiliate_stripe_refresh_time()
+                .withf(|stripe_account_id, _| stripe_account_id == "acct_456")
+                .times(1)
+                .return_once(|_, _| Ok(()));
+            store
+        });
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(|| {
+            let mut store = MockStore::new();
+            store
+                .expect_update_affiliate_stripe_state()
+                .withf(|stripe_account_id, state, _, _| {
+                    stripe_account_id == "acct_123" && state.charges_enabled
+                })
+                .times(1)
+                .return_once(|_, _, _, _| Ok(true));
+            store
+        });
+
+    let refresher = AffiliateRefresher::new(
+        Arc::new(database),
+        Arc::new(stripe.client()),
+        Duration::hours(1),
+    );
+    assert_eq!(refresher.refresh_stale().await?, 1);
+    Ok(())
+}
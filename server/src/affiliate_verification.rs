@@ -0,0 +1,173 @@
+use affect_storage::models::{affiliate::AffiliateRow, nonprofit::NonprofitRow};
+
+/// Checks run when reviewing an affiliate, comparing what the affiliate
+/// provided against the nonprofit it claims to represent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerificationChecks {
+    /// The affiliate's EIN is the nonprofit's EIN.
+    pub ein_matches: bool,
+    /// The affiliate's contact email is on the nonprofit's email domain, or
+    /// one of its subdomains.
+    pub email_domain_matches: bool,
+}
+
+impl VerificationChecks {
+    pub fn new(affiliate: &AffiliateRow, nonprofit: &NonprofitRow) -> Self {
+        let ein_matches = match affiliate.ein.as_deref().map(normalize_ein) {
+            Some(Some(ein)) => normalize_ein(&nonprofit.ein) == Some(ein),
+            _ => false,
+        };
+        let email_domain_matches = match (
+            email_domain(&affiliate.contact_email),
+            nonprofit.email.as_deref().and_then(email_domain),
+        ) {
+            (Some(contact_domain), Some(nonprofit_domain)) => {
+                contact_domain == nonprofit_domain
+                    || contact_domain.ends_with(&format!(".{}", nonprofit_domain))
+            }
+            _ => false,
+        };
+        Self {
+            ein_matches,
+            email_domain_matches,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.ein_matches && self.email_domain_matches
+    }
+
+    /// Descriptions of the checks which failed.
+    pub fn failures(&self) -> Vec<&'static str> {
+        let mut failures = Vec::new();
+        if !self.ein_matches {
+            failures.push("ein does not match the nonprofit's ein");
+        }
+        if !self.email_domain_matches {
+            failures.push("contact email domain does not match the nonprofit's email domain");
+        }
+        failures
+    }
+}
+
+/// The nine digits of an EIN, ignoring formatting such as "12-3456789". None
+/// if the EIN doesn't have nine digits.
+pub fn normalize_ein(ein: &str) -> Option<String> {
+    let digits: String = ein
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect();
+    match digits.len() == 9 && digits.chars().all(|c| c.is_ascii_digit()) {
+        true => Some(digits),
+        false => None,
+    }
+}
+
+/// Lowercased domain of an email address.
+fn email_domain(email: &str) -> Option<String> {
+    match email.trim().rsplit_once('@') {
+        Some((local, domain)) if !local.is_empty() && !domain.is_empty() => {
+            Some(domain.to_lowercase())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use affect_storage::models::affiliate::{AffiliateVerificationState, BusinessType};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn affiliate_row(ein: Option<&str>, contact_email: &str) -> AffiliateRow {
+        AffiliateRow {
+            affiliate_id: Uuid::new_v4(),
+            create_time: Utc::now(),
+            update_time: Utc::now(),
+            stripe_account_id: "acct_1".to_string(),
+            company_name: "Food Bank".to_string(),
+            contact_email: contact_email.to_string(),
+            business_type: BusinessType::Nonprofit,
+            asserted_nonprofit_id: Uuid::new_v4(),
+            charges_enabled: false,
+            payouts_enabled: false,
+            country: None,
+            business_name: None,
+            requirements_currently_due: Vec::new(),
+            disabled_reason: None,
+            stripe_refresh_time: None,
+            ein: ein.map(str::to_string),
+            verification_state: AffiliateVerificationState::Pending,
+            rejection_reason: None,
+            verification_time: None,
+            currencies: Vec::new(),
+        }
+    }
+
+    fn nonprofit_row(ein: &str, email: Option<&str>) -> NonprofitRow {
+        NonprofitRow {
+            nonprofit_id: Uuid::new_v4(),
+            create_time: Utc::now(),
+            update_time: Utc::now(),
+            change_nonprofit_id: None,
+            icon_url: "icon_url".to_string(),
+            name: "Food Bank".to_string(),
+            ein: ein.to_string(),
+            mission: "mission".to_string(),
+            category: "category".to_string(),
+            affiliate_id: None,
+            email: email.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn normalize_ein_ignores_formatting() {
+        assert_eq!(normalize_ein("12-3456789"), Some("123456789".to_string()));
+        assert_eq!(normalize_ein(" 123456789 "), Some("123456789".to_string()));
+        assert_eq!(normalize_ein("12-345678"), None);
+        assert_eq!(normalize_ein("12-34567AB"), None);
+    }
+
+    #[test]
+    fn checks_pass() {
+        let nonprofit = nonprofit_row("12-3456789", Some("info@foodbank.org"));
+        let checks = VerificationChecks::new(
+            &affiliate_row(Some("123456789"), "Jane@FoodBank.org"),
+            &nonprofit,
+        );
+        assert!(checks.passed());
+        assert!(checks.failures().is_empty());
+
+        let checks = VerificationChecks::new(
+            &affiliate_row(Some("123456789"), "jane@mail.foodbank.org"),
+            &nonprofit,
+        );
+        assert!(checks.email_domain_matches);
+    }
+
+    #[test]
+    fn checks_fail() {
+        let nonprofit = nonprofit_row("12-3456789", Some("info@foodbank.org"));
+        let checks = VerificationChecks::new(
+            &affiliate_row(Some("98-7654321"), "jane@notfoodbank.org"),
+            &nonprofit,
+        );
+        assert_eq!(
+            checks,
+            VerificationChecks {
+                ein_matches: false,
+                email_domain_matches: false,
+            }
+        );
+        assert_eq!(checks.failures().len(), 2);
+
+        // Nothing to compare against.
+        let checks = VerificationChecks::new(
+            &affiliate_row(None, "jane@foodbank.org"),
+            &nonprofit_row("12-3456789", None),
+        );
+        assert!(!checks.ein_matches);
+        assert!(!checks.email_domain_matches);
+    }
+}
@@ -1,4 +1,5 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -10,6 +11,17 @@ pub struct ServerConfig {
     pub change: ChangeConfig,
     pub plaid: PlaidConfig,
     pub stripe: StripeConfig,
+    pub web: WebConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub round_up: RoundUpConfig,
+    #[serde(default)]
+    pub recurring_donations: RecurringDonationsConfig,
+    #[serde(default)]
+    pub fees: FeesConfig,
+    #[serde(default)]
+    pub affiliate_refresh: AffiliateRefreshConfig,
 }
 
 #[derive(Deserialize)]
@@ -38,10 +50,149 @@ pub struct PlaidConfig {
     pub client_id: String,
     pub secret_key: String,
     pub env: String,
+    /// Url plaid sends item webhooks to. Webhooks are not requested if unset.
+    pub webhook_url: Option<String>,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct StripeConfig {
     pub secret: String,
+    /// Secret used to verify the signatures of webhook requests.
+    pub webhook_secret: String,
+}
+
+/// Url templates of the web app, which outbound links point to. Placeholders
+/// such as `{affiliate_id}` are replaced when a link is built.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct WebConfig {
+    /// Page stripe sends affiliates to when their onboarding link expired.
+    /// Placeholders: `{affiliate_id}`.
+    pub affiliate_onboarding_url: String,
+    /// Page stripe sends affiliates to when they leave onboarding or the
+    /// express dashboard. Placeholders: `{affiliate_id}`.
+    pub affiliate_return_url: String,
+    /// Page invitees accept affiliate manager invitations on. Placeholders:
+    /// `{token}`.
+    pub affiliate_invitation_url: String,
+    /// Page plaid link redirects to after OAuth with an institution. Plaid
+    /// link isn't redirected if unset.
+    pub plaid_redirect_url: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct AuthConfig {
+    /// Firebase uids which are privileged in addition to users with the
+    /// `privileged` custom claim.
+    #[serde(default)]
+    pub privileged_firebase_uids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RoundUpConfig {
+    /// Pending round-ups are donated once they reach this amount.
+    pub threshold_cents: i64,
+    /// Seconds between pulling transactions and charging round-ups.
+    pub interval_secs: u64,
+}
+
+impl Default for RoundUpConfig {
+    fn default() -> Self {
+        Self {
+            threshold_cents: 500,
+            interval_secs: 3600,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RecurringDonationsConfig {
+    /// Seconds between polls for recurring donations which are due.
+    pub poll_interval_secs: u64,
+}
+
+impl Default for RecurringDonationsConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: 60,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct AffiliateRefreshConfig {
+    /// Seconds between refreshes of stale affiliate stripe accounts.
+    pub interval_secs: u64,
+    /// Seconds after which the stripe account state of an affiliate is
+    /// refreshed, in case webhooks were missed.
+    pub stale_after_secs: i64,
+}
+
+impl Default for AffiliateRefreshConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 3600,
+            stale_after_secs: 86400,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct FeesConfig {
+    /// Stripe's fees per currency code, e.g. "USD". Donations are only
+    /// accepted in currencies with a schedule.
+    pub currencies: HashMap<String, CurrencyFeesConfig>,
+    /// Fee kept by the platform, in basis points of the donation.
+    pub platform_basis_points: i64,
+}
+
+impl Default for FeesConfig {
+    fn default() -> Self {
+        Self {
+            currencies: HashMap::from([(
+                "USD".to_string(),
+                CurrencyFeesConfig {
+                    card: ProcessingFeeConfig {
+                        basis_points: 290,
+                        fixed_subunits: 30,
+                        cap_subunits: None,
+                    },
+                    ach: ProcessingFeeConfig {
+                        basis_points: 80,
+                        fixed_subunits: 0,
+                        cap_subunits: Some(500),
+                    },
+                },
+            )]),
+            platform_basis_points: 0,
+        }
+    }
+}
+
+/// Stripe's fee schedule for charges in a currency.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CurrencyFeesConfig {
+    /// Stripe's fee for card charges.
+    pub card: ProcessingFeeConfig,
+    /// Stripe's fee for ACH charges from linked bank accounts.
+    pub ach: ProcessingFeeConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProcessingFeeConfig {
+    /// Percentage of the charge, in basis points.
+    pub basis_points: i64,
+    /// Fixed amount per charge, in subunits of the currency (e.g. cents).
+    pub fixed_subunits: i64,
+    /// Maximum fee per charge in subunits of the currency, uncapped if unset.
+    #[serde(default)]
+    pub cap_subunits: Option<i64>,
 }
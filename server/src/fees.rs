@@ -0,0 +1,356 @@
+use crate::{
+    config::{CurrencyFeesConfig, FeesConfig, ProcessingFeeConfig},
+    money::{self, Money},
+};
+use affect_status::{internal, invalid_argument, Status};
+use iso_currency::Currency;
+use std::cmp::min;
+
+const BASIS_POINTS: i128 = 10_000;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("no fee schedule for currency: {0}")]
+    UnsupportedCurrency(&'static str),
+
+    #[error(transparent)]
+    Money(#[from] money::Error),
+
+    #[error("invalid fee config {name} ({value}): {reason}")]
+    InvalidConfig {
+        name: String,
+        value: i64,
+        reason: &'static str,
+    },
+}
+
+impl From<Error> for Status {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::UnsupportedCurrency(code) => {
+                invalid_argument!("donations in {0} are not supported", code)
+            }
+            Error::Money(error) => error.into(),
+            Error::InvalidConfig { .. } => internal!("{0}", error),
+        }
+    }
+}
+
+/// Payment method of a charge, which determines stripe's processing fee.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaymentMethod {
+    Card,
+    Ach,
+}
+
+/// Fees of a donation. The donor is charged the donation amount, or the amount
+/// grossed up by the fees if the donor covers them, and the recipients receive
+/// the charge minus the fees.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeeBreakdown {
+    pub cover_fees: bool,
+    pub charge_amount: Money,
+    pub processing_fee: Money,
+    pub platform_fee: Money,
+}
+
+impl FeeBreakdown {
+    /// Amount received by the recipients.
+    pub fn net_amount(&self) -> Result<Money, money::Error> {
+        self.charge_amount
+            .checked_sub(&self.processing_fee)?
+            .checked_sub(&self.platform_fee)
+    }
+
+    /// Both fees, which the platform keeps from a charge it settles.
+    pub fn total_fee(&self) -> Result<Money, money::Error> {
+        self.processing_fee.checked_add(&self.platform_fee)
+    }
+}
+
+/// Computes stripe's processing fee and the platform fee of donations. Fees
+/// are computed in whole subunits of the donation's currency. The config is
+/// validated on construction, so fees can't exceed the charge or be negative.
+#[derive(Clone, Debug)]
+pub struct FeeCalculator {
+    config: FeesConfig,
+}
+
+impl FeeCalculator {
+    pub fn new(config: FeesConfig) -> Result<Self, Error> {
+        validate_basis_points("platform-basis-points", config.platform_basis_points)?;
+        for (code, currency_config) in &config.currencies {
+            for (method, processing_fee_config) in [
+                ("card", &currency_config.card),
+                ("ach", &currency_config.ach),
+            ] {
+                let name = |field| format!("currencies.{0}.{1}.{2}", code, method, field);
+                validate_basis_points(&name("basis-points"), processing_fee_config.basis_points)?;
+                validate_subunits(
+                    &name("fixed-subunits"),
+                    processing_fee_config.fixed_subunits,
+                )?;
+                if let Some(cap_subunits) = processing_fee_config.cap_subunits {
+                    validate_subunits(&name("cap-subunits"), cap_subunits)?;
+                }
+            }
+        }
+        Ok(Self { config })
+    }
+
+    /// Fails for currencies without a fee schedule, which donations can't be
+    /// made in.
+    pub fn check_currency(&self, currency: Currency) -> Result<(), Error> {
+        self.currency_config(currency).map(|_| ())
+    }
+
+    fn currency_config(&self, currency: Currency) -> Result<&CurrencyFeesConfig, Error> {
+        self.config
+            .currencies
+            .get(currency.code())
+            .ok_or(Error::UnsupportedCurrency(currency.code()))
+    }
+
+    /// Fees of a donation of `amount`. Fractions of a subunit of the amount are
+    /// never charged. Fails for currencies without a fee schedule.
+    pub fn breakdown(
+        &self,
+        amount: &Money,
+        payment_method: PaymentMethod,
+        cover_fees: bool,
+    ) -> Result<FeeBreakdown, Error> {
+        let currency = amount.currency();
+        let currency_config = self.currency_config(currency)?;
+        let processing_fee_config = match payment_method {
+            PaymentMethod::Card => &currency_config.card,
+            PaymentMethod::Ach => &currency_config.ach,
+        };
+        let amount_subunits = amount.subunits_truncated() as i128;
+        let platform_fee = div_round(
+            amount_subunits * self.config.platform_basis_points as i128,
+            BASIS_POINTS,
+        );
+        let charge_amount = match cover_fees {
+            true => gross_up(processing_fee_config, amount_subunits + platform_fee),
+            false => amount_subunits,
+        };
+        let processing_fee = processing_fee(processing_fee_config, charge_amount);
+
+        Ok(FeeBreakdown {
+            cover_fees,
+            charge_amount: from_subunits(currency, charge_amount)?,
+            processing_fee: from_subunits(currency, processing_fee)?,
+            platform_fee: from_subunits(currency, platform_fee)?,
+        })
+    }
+}
+
+/// Fails unless the fee is a share of the charge, from none up to but
+/// excluding all of it. Grossing up a charge by a fee of all of it would
+/// never cover the fee.
+fn validate_basis_points(name: &str, basis_points: i64) -> Result<(), Error> {
+    match (0..BASIS_POINTS as i64).contains(&basis_points) {
+        true => Ok(()),
+        false => Err(Error::InvalidConfig {
+            name: name.to_string(),
+            value: basis_points,
+            reason: "must be at least 0 and less than 10000",
+        }),
+    }
+}
+
+fn validate_subunits(name: &str, subunits: i64) -> Result<(), Error> {
+    match subunits >= 0 {
+        true => Ok(()),
+        false => Err(Error::InvalidConfig {
+            name: name.to_string(),
+            value: subunits,
+            reason: "must not be negative",
+        }),
+    }
+}
+
+fn from_subunits(currency: iso_currency::Currency, subunits: i128) -> Result<Money, money::Error> {
+    let subunits = i64::try_from(subunits).map_err(|_| money::Error::Overflow)?;
+    Ok(Money::from_subunits(currency, subunits))
+}
+
+/// Stripe's processing fee of a charge, in subunits.
+fn processing_fee(config: &ProcessingFeeConfig, charge_amount: i128) -> i128 {
+    let fee = div_round(charge_amount * config.basis_points as i128, BASIS_POINTS)
+        + config.fixed_subunits as i128;
+    match config.cap_subunits {
+        Some(cap_subunits) => min(fee, cap_subunits as i128),
+        None => fee,
+    }
+}
+
+/// Smallest charge which leaves at least `target` after stripe's processing
+/// fee, in subunits.
+fn gross_up(config: &ProcessingFeeConfig, target: i128) -> i128 {
+    let net = |charge_amount| charge_amount - processing_fee(config, charge_amount);
+    // Estimate from the uncapped fee, then step to the exact charge. The fee
+    // grows by at most one subunit per subunit of charge, so the net amount
+    // never decreases and only a few steps are needed.
+    let divisor = BASIS_POINTS - config.basis_points as i128;
+    let mut charge_amount = match divisor > 0 {
+        true => ((target + config.fixed_subunits as i128) * BASIS_POINTS + divisor - 1) / divisor,
+        false => target,
+    };
+    if let Some(cap_subunits) = config.cap_subunits {
+        charge_amount = min(charge_amount, target + cap_subunits as i128);
+    }
+    while net(charge_amount) < target {
+        charge_amount += 1;
+    }
+    while charge_amount > target && net(charge_amount - 1) >= target {
+        charge_amount -= 1;
+    }
+    charge_amount
+}
+
+/// Divides a non-negative dividend, rounding half up.
+fn div_round(dividend: i128, divisor: i128) -> i128 {
+    (dividend + divisor / 2) / divisor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculator(platform_basis_points: i64) -> FeeCalculator {
+        FeeCalculator::new(FeesConfig {
+            platform_basis_points,
+            ..FeesConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn usd(subunits: i64) -> Money {
+        Money::from_subunits(Currency::USD, subunits)
+    }
+
+    #[test]
+    fn card_fees_are_deducted() {
+        let breakdown = calculator(100)
+            .breakdown(&usd(1000), PaymentMethod::Card, false)
+            .unwrap();
+        assert_eq!(
+            breakdown,
+            FeeBreakdown {
+                cover_fees: false,
+                charge_amount: usd(1000),
+                processing_fee: usd(59),
+                platform_fee: usd(10),
+            }
+        );
+        assert_eq!(breakdown.net_amount(), Ok(usd(931)));
+    }
+
+    #[test]
+    fn card_fees_are_grossed_up() {
+        let breakdown = calculator(100)
+            .breakdown(&usd(1000), PaymentMethod::Card, true)
+            .unwrap();
+        // 1071 - round(1071 * 2.9%) - 30 = 1010.
+        assert_eq!(breakdown.charge_amount, usd(1071));
+        assert_eq!(breakdown.processing_fee, usd(61));
+        assert_eq!(breakdown.platform_fee, usd(10));
+        assert_eq!(breakdown.net_amount(), Ok(usd(1000)));
+    }
+
+    #[test]
+    fn ach_fee_is_capped() {
+        let calculator = calculator(0);
+        let breakdown = calculator
+            .breakdown(&usd(100_000), PaymentMethod::Ach, false)
+            .unwrap();
+        assert_eq!(breakdown.processing_fee, usd(500));
+
+        let breakdown = calculator
+            .breakdown(&usd(100_000), PaymentMethod::Ach, true)
+            .unwrap();
+        assert_eq!(breakdown.charge_amount, usd(100_500));
+        assert_eq!(breakdown.net_amount(), Ok(usd(100_000)));
+    }
+
+    #[test]
+    fn rejects_invalid_config() {
+        // Platform fee of the whole donation.
+        assert!(FeeCalculator::new(FeesConfig {
+            platform_basis_points: 10_000,
+            ..FeesConfig::default()
+        })
+        .is_err());
+
+        // Negative platform fee.
+        assert!(FeeCalculator::new(FeesConfig {
+            platform_basis_points: -1,
+            ..FeesConfig::default()
+        })
+        .is_err());
+
+        // Processing fee of the whole charge, which can't be grossed up.
+        let mut config = FeesConfig::default();
+        config.currencies.get_mut("USD").unwrap().card.basis_points = 10_000;
+        assert!(FeeCalculator::new(config).is_err());
+
+        // Negative processing fee.
+        let mut config = FeesConfig::default();
+        config.currencies.get_mut("USD").unwrap().ach.basis_points = -80;
+        assert!(FeeCalculator::new(config).is_err());
+
+        // Negative fixed fee.
+        let mut config = FeesConfig::default();
+        config
+            .currencies
+            .get_mut("USD")
+            .unwrap()
+            .card
+            .fixed_subunits = -30;
+        assert!(FeeCalculator::new(config).is_err());
+
+        // Negative cap.
+        let mut config = FeesConfig::default();
+        config.currencies.get_mut("USD").unwrap().ach.cap_subunits = Some(-1);
+        assert!(FeeCalculator::new(config).is_err());
+    }
+
+    #[test]
+    fn currency_without_schedule_is_rejected() {
+        assert_eq!(
+            calculator(0).breakdown(
+                &Money::from_subunits(Currency::EUR, 1000),
+                PaymentMethod::Card,
+                false
+            ),
+            Err(Error::UnsupportedCurrency("EUR"))
+        );
+    }
+
+    #[test]
+    fn gross_up_is_the_smallest_covering_charge() {
+        let calculator = calculator(250);
+        for subunits in 1..2000 {
+            for payment_method in [PaymentMethod::Card, PaymentMethod::Ach] {
+                let breakdown = calculator
+                    .breakdown(&usd(subunits), payment_method, true)
+                    .unwrap();
+                let net_amount = breakdown.net_amount().unwrap().subunits_truncated();
+                assert!(net_amount >= subunits);
+                let smaller = breakdown.charge_amount.subunits_truncated() - 1;
+                let currency_config = &calculator.config.currencies["USD"];
+                let config = match payment_method {
+                    PaymentMethod::Card => &currency_config.card,
+                    PaymentMethod::Ach => &currency_config.ach,
+                };
+                assert!(
+                    smaller as i128
+                        - processing_fee(config, smaller as i128)
+                        - (breakdown.platform_fee.subunits_truncated() as i128)
+                        < (subunits as i128)
+                );
+            }
+        }
+    }
+}
@@ -13,6 +13,8 @@ pub struct DecodedIdToken {
     pub email_verified: bool,
     pub auth_time: DateTime<Utc>,
     pub expire_time: DateTime<Utc>,
+    /// Whether the `privileged` custom claim is set on the user.
+    pub privileged: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -53,10 +55,23 @@ impl FirebaseAuth {
             email_verified: claims.email_verified,
             auth_time: Utc.timestamp(claims.auth_time, 0),
             expire_time: Utc.timestamp(claims.exp, 0),
+            privileged: claims.privileged,
         })
     }
 }
 
+/// Verifies firebase id tokens, so tests can stand in for firebase.
+#[cfg_attr(test, mockall::automock)]
+pub trait IdTokenVerifier: Sync + Send {
+    fn verify_id_token(&self, id_token: String) -> Result<DecodedIdToken, Error>;
+}
+
+impl IdTokenVerifier for FirebaseAuth {
+    fn verify_id_token(&self, id_token: String) -> Result<DecodedIdToken, Error> {
+        FirebaseAuth::verify_id_token(self, id_token)
+    }
+}
+
 #[derive(Deserialize, Debug)]
 struct Claims {
     // The audience the token was issued for
@@ -71,4 +86,7 @@ struct Claims {
     pub auth_time: i64,
     // The expiry date -- as epoch seconds
     pub exp: i64,
+    // Custom claim granting privileged access
+    #[serde(default)]
+    pub privileged: bool,
 }
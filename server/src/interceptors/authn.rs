@@ -1,12 +1,16 @@
-use crate::{firebase::FirebaseAuth, tonic::async_interceptor::AsyncInterceptor};
+use crate::{firebase::IdTokenVerifier, tonic::async_interceptor::AsyncInterceptor};
 use affect_api::affect::{auth_metadata::PeerToken, AuthMetadata};
 use affect_storage::{models::user::UserRow, stores::user::UserStore};
 use async_trait::async_trait;
 use hyper::{Body, Request};
-use log::debug;
+use log::{debug, info};
 use prost::Message;
-use std::{io::Cursor, sync::Arc};
+use std::{collections::HashSet, io::Cursor, sync::Arc};
 use tonic::Status;
+use uuid::Uuid;
+
+#[cfg(test)]
+mod tests;
 
 #[derive(Clone, Debug)]
 pub enum Peer {
@@ -19,23 +23,114 @@ pub enum Peer {
     Anonymous,
 }
 
-impl Peer {}
+impl Peer {
+    /// Returns the peer authenticated by `AuthnInterceptor`, or anonymous if the
+    /// request was not intercepted.
+    pub fn from_request<T>(request: &tonic::Request<T>) -> Peer {
+        request
+            .extensions()
+            .get::<Peer>()
+            .cloned()
+            .unwrap_or(Peer::Anonymous)
+    }
+
+    /// The user the peer acts as, none if anonymous.
+    pub fn user(&self) -> Option<&UserRow> {
+        match self {
+            Peer::User(user) | Peer::Privileged(user) | Peer::Impersonated { user, .. } => {
+                Some(user)
+            }
+            Peer::Anonymous => None,
+        }
+    }
+}
+
+/// Email of an end user peer, present only if its id token claims that firebase
+/// verified the user owns the email.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifiedEmail(pub String);
+
+impl VerifiedEmail {
+    /// Returns the verified email of the peer authenticated by
+    /// `AuthnInterceptor`, none if the peer has none.
+    pub fn from_request<T>(request: &tonic::Request<T>) -> Option<VerifiedEmail> {
+        request.extensions().get::<VerifiedEmail>().cloned()
+    }
+}
 
 #[derive(Clone)]
 pub struct AuthnInterceptor {
-    firebase_auth: Arc<FirebaseAuth>,
+    firebase_auth: Arc<dyn IdTokenVerifier>,
     user_store: Arc<dyn UserStore>,
+    privileged_firebase_uids: Arc<HashSet<String>>,
 }
 
 impl AuthnInterceptor {
-    pub fn new(firebase_auth: Arc<FirebaseAuth>, user_store: Arc<dyn UserStore>) -> Self {
+    pub fn new(
+        firebase_auth: Arc<dyn IdTokenVerifier>,
+        user_store: Arc<dyn UserStore>,
+        privileged_firebase_uids: HashSet<String>,
+    ) -> Self {
         Self {
             firebase_auth,
             user_store,
+            privileged_firebase_uids: Arc::new(privileged_firebase_uids),
         }
     }
 
-    pub async fn authenticate_bytes(&self, u8: Option<&[u8]>) -> Result<Peer, Status> {
+    /// Verifies the id token and returns the user it belongs to, with the
+    /// token's email if it's verified.
+    async fn authenticate_id_token(
+        &self,
+        firebase_id_token: String,
+    ) -> Result<(UserRow, Option<VerifiedEmail>), Status> {
+        let decoded_id_token = self
+            .firebase_auth
+            .verify_id_token(firebase_id_token)
+            .map_err(|_| Status::unauthenticated("failed to decoded end user firebase id token"))?;
+        let verified_email = match decoded_id_token.email_verified {
+            true => Some(VerifiedEmail(decoded_id_token.email)),
+            false => None,
+        };
+        let user = self
+            .user_store
+            .find_user_by_firebase_uid(decoded_id_token.uid)
+            .await?
+            .ok_or(Status::unauthenticated("end user not found"))?;
+        Ok((user, verified_email))
+    }
+
+    /// Verifies the id token belongs to a privileged user, either by the `privileged`
+    /// custom claim or by the configured allowlist, and returns that user.
+    async fn authenticate_privileged_id_token(
+        &self,
+        firebase_id_token: String,
+    ) -> Result<UserRow, Status> {
+        let decoded_id_token = self
+            .firebase_auth
+            .verify_id_token(firebase_id_token)
+            .map_err(|_| {
+                Status::unauthenticated("failed to decoded privileged firebase id token")
+            })?;
+        if !decoded_id_token.privileged
+            && !self
+                .privileged_firebase_uids
+                .contains(&decoded_id_token.uid)
+        {
+            return Err(Status::permission_denied("user is not privileged"));
+        }
+        self.user_store
+            .find_user_by_firebase_uid(decoded_id_token.uid)
+            .await?
+            .ok_or(Status::unauthenticated("privileged user not found"))
+    }
+
+    /// Authenticates the peer of the auth metadata, with its verified email if
+    /// it's an end user.
+    pub async fn authenticate_bytes(
+        &self,
+        u8: Option<&[u8]>,
+    ) -> Result<(Peer, Option<VerifiedEmail>), Status> {
         let auth_metadata_from_bytes = u8
             .map(|u8| AuthMetadata::decode(&mut Cursor::new(u8)))
             .transpose()
@@ -43,24 +138,41 @@ impl AuthnInterceptor {
                 Status::unauthenticated(format!("'auth-bin' header could not be decoded: {:?}", e))
             })?;
 
+        let mut verified_email = None;
         let peer = match auth_metadata_from_bytes {
             Some(auth_metadata) => match auth_metadata.peer_token {
                 Some(PeerToken::EndUser(end_user)) => {
-                    let decoded_id_token = self
-                        .firebase_auth
-                        .verify_id_token(end_user.firebase_id_token)
-                        .map_err(|_| {
-                            Status::unauthenticated("failed to decoded end user firebase id token")
-                        })?;
-                    let user_row = self
+                    let (user, email) = self
+                        .authenticate_id_token(end_user.firebase_id_token)
+                        .await?;
+                    verified_email = email;
+                    Peer::User(user)
+                }
+                Some(PeerToken::Privileged(privileged)) => Peer::Privileged(
+                    self.authenticate_privileged_id_token(privileged.firebase_id_token)
+                        .await?,
+                ),
+                Some(PeerToken::ImpersonatedUser(impersonated_user)) => {
+                    let privileged_user = self
+                        .authenticate_privileged_id_token(impersonated_user.firebase_id_token)
+                        .await?;
+                    let user_id = Uuid::parse_str(&impersonated_user.user_id).map_err(|_| {
+                        Status::unauthenticated("impersonated user id could not be parsed")
+                    })?;
+                    let user = self
                         .user_store
-                        .find_user_by_firebase_uid(decoded_id_token.uid)
+                        .find_user_by_id(user_id)
                         .await?
-                        .ok_or(Status::unauthenticated("end user not found"))?;
-                    Peer::User(user_row)
+                        .ok_or(Status::unauthenticated("impersonated user not found"))?;
+                    info!(
+                        "User {} is impersonating user {}",
+                        privileged_user.user_id, user.user_id
+                    );
+                    Peer::Impersonated {
+                        user,
+                        privileged_user,
+                    }
                 }
-                Some(PeerToken::Privileged(_)) => Peer::Anonymous,
-                Some(PeerToken::ImpersonatedUser(_)) => Peer::Anonymous,
                 Some(PeerToken::Anonymous(_)) => Peer::Anonymous,
                 None => Peer::Anonymous,
             },
@@ -69,13 +181,13 @@ impl AuthnInterceptor {
 
         debug!("Request from {:?}", peer);
 
-        Ok(peer)
+        Ok((peer, verified_email))
     }
 
     pub async fn authenticate_hyper_request(
         &self,
         req: &hyper::Request<Body>,
-    ) -> Result<Peer, Status> {
+    ) -> Result<(Peer, Option<VerifiedEmail>), Status> {
         let base64_bytes = match req.headers().get("auth-bin").map(|value| value.as_ref()) {
             Some(bytes) => bytes,
             None => return self.authenticate_bytes(None).await,
@@ -92,7 +204,7 @@ impl AuthnInterceptor {
     pub async fn authenticate_tonic_request<T>(
         &self,
         req: &tonic::Request<T>,
-    ) -> Result<Peer, Status> {
+    ) -> Result<(Peer, Option<VerifiedEmail>), Status> {
         let base64_bytes = match req
             .metadata()
             .get_bin("auth-bin")
@@ -114,8 +226,11 @@ impl AuthnInterceptor {
 #[async_trait]
 impl AsyncInterceptor for AuthnInterceptor {
     async fn intercept(&self, req: &mut Request<Body>) -> Result<(), Status> {
-        let peer = self.authenticate_hyper_request(req).await?;
+        let (peer, verified_email) = self.authenticate_hyper_request(req).await?;
         req.extensions_mut().insert(peer);
+        if let Some(verified_email) = verified_email {
+            req.extensions_mut().insert(verified_email);
+        }
         Ok(())
     }
 }
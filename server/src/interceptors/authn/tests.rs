@@ -0,0 +1,242 @@
+use crate::{
+    firebase::{self, DecodedIdToken, MockIdTokenVerifier},
+    interceptors::authn::{AuthnInterceptor, Peer, VerifiedEmail},
+    testing::user_row,
+};
+use affect_api::affect::{auth_metadata::*, AuthMetadata};
+use affect_status::Code;
+use affect_storage::models::user::UserRow;
+use affect_storage_mocks::*;
+use chrono::Utc;
+use prost::Message;
+use std::{collections::HashSet, sync::Arc};
+use uuid::Uuid;
+
+/// Firebase which issued "user-token" to uid "user", "unverified-token" to uid
+/// "unverified" whose email isn't verified, "admin-token" to uid "admin" with
+/// the privileged claim and "allowlisted-token" to uid "allowlisted" without
+/// it. Other tokens fail verification.
+fn firebase_auth() -> MockIdTokenVerifier {
+    let mut firebase_auth = MockIdTokenVerifier::new();
+    firebase_auth
+        .expect_verify_id_token()
+        .returning(|id_token| {
+            let (uid, privileged) = match id_token.as_str() {
+                "user-token" => ("user", false),
+                "unverified-token" => ("unverified", false),
+                "admin-token" => ("admin", true),
+                "allowlisted-token" => ("allowlisted", false),
+                _ => return Err(firebase::Error::InvalidProject(id_token)),
+            };
+            Ok(DecodedIdToken {
+                uid: uid.to_string(),
+                email: format!("{0}@affect.app", uid),
+                email_verified: uid != "unverified",
+                auth_time: Utc::now(),
+                expire_time: Utc::now(),
+                privileged,
+            })
+        });
+    firebase_auth
+}
+
+fn firebase_user_row(firebase_uid: &str) -> UserRow {
+    UserRow {
+        firebase_uid: firebase_uid.to_string(),
+        ..user_row(Uuid::new_v4())
+    }
+}
+
+/// Interceptor whose store has a user for every firebase uid and the user
+/// which can be impersonated.
+fn interceptor(impersonated_user: UserRow) -> AuthnInterceptor {
+    let mut store = MockStore::new();
+    store
+        .expect_find_user_by_firebase_uid()
+        .returning(|firebase_uid| Ok(Some(firebase_user_row(&firebase_uid))));
+    store.expect_find_user_by_id().returning(move |user_id| {
+        Ok(Some(impersonated_user.clone()).filter(|user| user.user_id == user_id))
+    });
+    AuthnInterceptor::new(
+        Arc::new(firebase_auth()),
+        Arc::new(store),
+        HashSet::from(["allowlisted".to_string()]),
+    )
+}
+
+async fn authenticate(
+    interceptor: &AuthnInterceptor,
+    peer_token: PeerToken,
+) -> Result<Peer, tonic::Status> {
+    let auth_metadata = AuthMetadata {
+        peer_token: Some(peer_token),
+    };
+    let (peer, _) = interceptor
+        .authenticate_bytes(Some(&auth_metadata.encode_to_vec()))
+        .await?;
+    Ok(peer)
+}
+
+fn impersonated_user(firebase_id_token: &str, user_id: Uuid) -> PeerToken {
+    PeerToken::ImpersonatedUser(ImpersonatedUser {
+        firebase_id_token: firebase_id_token.to_string(),
+        user_id: user_id.to_string(),
+    })
+}
+
+#[tokio::test]
+async fn authenticates_end_user() -> Result<(), anyhow::Error> {
+    let interceptor = interceptor(user_row(Uuid::new_v4()));
+    let peer = authenticate(
+        &interceptor,
+        PeerToken::EndUser(EndUser {
+            firebase_id_token: "user-token".to_string(),
+        }),
+    )
+    .await?;
+    assert!(matches!(peer, Peer::User(user) if user.firebase_uid == "user"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn end_user_email_is_verified_by_claim() -> Result<(), anyhow::Error> {
+    let interceptor = interceptor(user_row(Uuid::new_v4()));
+    let verified_email = |firebase_id_token: &str| {
+        let auth_metadata = AuthMetadata {
+            peer_token: Some(PeerToken::EndUser(EndUser {
+                firebase_id_token: firebase_id_token.to_string(),
+            })),
+        };
+        let interceptor = interceptor.clone();
+        async move {
+            interceptor
+                .authenticate_bytes(Some(&auth_metadata.encode_to_vec()))
+                .await
+                .map(|(_, verified_email)| verified_email)
+        }
+    };
+    assert_eq!(
+        verified_email("user-token").await?,
+        Some(VerifiedEmail("user@affect.app".to_string()))
+    );
+    assert_eq!(verified_email("unverified-token").await?, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn authenticates_privileged_user_by_claim_or_allowlist() -> Result<(), anyhow::Error> {
+    let interceptor = interceptor(user_row(Uuid::new_v4()));
+    for (firebase_id_token, firebase_uid) in [
+        ("admin-token", "admin"),
+        ("allowlisted-token", "allowlisted"),
+    ] {
+        let peer = authenticate(
+            &interceptor,
+            PeerToken::Privileged(Privileged {
+                firebase_id_token: firebase_id_token.to_string(),
+            }),
+        )
+        .await?;
+        assert!(matches!(peer, Peer::Privileged(user) if user.firebase_uid == firebase_uid));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejects_unprivileged_user_as_privileged() {
+    let interceptor = interceptor(user_row(Uuid::new_v4()));
+    let status = authenticate(
+        &interceptor,
+        PeerToken::Privileged(Privileged {
+            firebase_id_token: "user-token".to_string(),
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn rejects_invalid_id_token() {
+    let interceptor = interceptor(user_row(Uuid::new_v4()));
+    let status = authenticate(
+        &interceptor,
+        PeerToken::Privileged(Privileged {
+            firebase_id_token: "forged-token".to_string(),
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn privileged_user_impersonates_user() -> Result<(), anyhow::Error> {
+    let user = user_row(Uuid::new_v4());
+    let user_id = user.user_id;
+    let interceptor = interceptor(user);
+    let peer = authenticate(&interceptor, impersonated_user("admin-token", user_id)).await?;
+    match peer {
+        Peer::Impersonated {
+            user,
+            privileged_user,
+        } => {
+            assert_eq!(user.user_id, user_id);
+            assert_eq!(privileged_user.firebase_uid, "admin");
+        }
+        peer => panic!("expected impersonated peer: {:?}", peer),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejects_impersonation_by_unprivileged_user() {
+    let mut store = MockStore::new();
+    store
+        .expect_find_user_by_firebase_uid()
+        .returning(|firebase_uid| Ok(Some(firebase_user_row(&firebase_uid))));
+    store.expect_find_user_by_id().never();
+    let interceptor =
+        AuthnInterceptor::new(Arc::new(firebase_auth()), Arc::new(store), HashSet::new());
+
+    let status = authenticate(
+        &interceptor,
+        impersonated_user("user-token", Uuid::new_v4()),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn rejects_impersonation_of_unknown_user() {
+    let interceptor = interceptor(user_row(Uuid::new_v4()));
+    let status = authenticate(
+        &interceptor,
+        impersonated_user("admin-token", Uuid::new_v4()),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let status = authenticate(
+        &interceptor,
+        PeerToken::ImpersonatedUser(ImpersonatedUser {
+            firebase_id_token: "admin-token".to_string(),
+            user_id: "not-a-uuid".to_string(),
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn requests_without_token_are_anonymous() -> Result<(), anyhow::Error> {
+    let interceptor = interceptor(user_row(Uuid::new_v4()));
+    assert!(matches!(
+        interceptor.authenticate_bytes(None).await?,
+        (Peer::Anonymous, None)
+    ));
+    Ok(())
+}
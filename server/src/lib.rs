@@ -1,10 +1,23 @@
+pub mod affiliate_refresh;
+pub mod affiliate_verification;
 pub mod change;
 pub mod config;
+pub mod fees;
 pub mod firebase;
 pub mod interceptors;
 pub mod money;
+pub mod plaid_api;
+pub mod policy;
 pub mod protobuf;
 pub mod protos;
+pub mod recurring_donation;
+pub mod round_up;
+pub mod saga;
 pub mod seed;
 pub mod services;
 pub mod tonic;
+pub mod web;
+pub mod webhooks;
+
+#[cfg(test)]
+pub mod testing;
@@ -1,19 +1,33 @@
 use affect_api::affect::{
     affiliate_service_server::AffiliateServiceServer, cause_service_server::CauseServiceServer,
     donation_service_server::DonationServiceServer, item_service_server::ItemServiceServer,
-    nonprofit_service_server::NonprofitServiceServer, user_service_server::UserServiceServer,
+    nonprofit_service_server::NonprofitServiceServer,
+    recurring_donation_service_server::RecurringDonationServiceServer,
+    round_up_service_server::RoundUpServiceServer, user_service_server::UserServiceServer,
 };
 use affect_server::{
+    affiliate_refresh::AffiliateRefresher,
     change::client::{ChangeClient, ChangeCredentials},
     config::ServerConfig,
+    fees::FeeCalculator,
     firebase::FirebaseAuth,
     interceptors::authn::AuthnInterceptor,
+    recurring_donation::RecurringDonationScheduler,
+    round_up::{transactions::PlaidTransactionsClient, RoundUpProcessor},
     seed,
     services::{
         affiliate::AffiliateServiceImpl, cause::CauseServiceImpl, donation::DonationServiceImpl,
-        item::ItemServiceImpl, nonprofit::NonprofitServiceImpl, user::UserServiceImpl,
+        item::ItemServiceImpl, nonprofit::NonprofitServiceImpl,
+        recurring_donation::RecurringDonationServiceImpl, round_up::RoundUpServiceImpl,
+        user::UserServiceImpl,
+    },
+    tonic::{async_interceptor::AsyncInterceptorLayer, idempotency::IdempotencyLayer},
+    web::WebLinks,
+    webhooks::{
+        plaid::{PlaidWebhookHandler, PlaidWebhookVerifier},
+        stripe::StripeWebhookHandler,
+        WebhookLayer,
     },
-    tonic::async_interceptor::AsyncInterceptorLayer,
 };
 use affect_storage::{database::client::DatabaseClient, sqlx::client::PgDatabaseClient};
 use log::info;
@@ -44,6 +58,8 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
 
     info!("Loading config");
     let config = load_config()?;
+    let web_links = WebLinks::new(config.web)?;
+    let fee_calculator = FeeCalculator::new(config.fees)?;
 
     // Database connection and stores:
     info!("Connecting to database");
@@ -61,8 +77,8 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
         config.change.secret_key,
     )));
     let plaid_client = Arc::new(plaid::Client::new(
-        config.plaid.client_id,
-        config.plaid.secret_key,
+        config.plaid.client_id.clone(),
+        config.plaid.secret_key.clone(),
         config.plaid.env.parse()?,
     ));
     let stripe_client = Arc::new(stripe::Client::new(config.stripe.secret));
@@ -70,12 +86,83 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Seed database with data.
     seed::insert_nonprofits(store.clone(), change_client).await?;
 
+    // Webhooks:
+    let stripe_webhook_handler = Arc::new(StripeWebhookHandler::new(
+        database.clone(),
+        config.stripe.webhook_secret,
+    ));
+    info!("Replaying unprocessed stripe events");
+    let replayed = stripe_webhook_handler.replay_unprocessed().await?;
+    info!("Replayed {} stripe events", replayed);
+    let plaid_webhook_handler = Arc::new(PlaidWebhookHandler::new(
+        database.clone(),
+        PlaidWebhookVerifier::new(
+            config.plaid.client_id.clone(),
+            config.plaid.secret_key.clone(),
+            &config.plaid.env,
+        ),
+    ));
+    let webhook_layer = WebhookLayer::new()
+        .route("/webhooks/stripe", stripe_webhook_handler)
+        .route("/webhooks/plaid", plaid_webhook_handler);
+
+    // Background processing:
+    let round_up_processor = RoundUpProcessor::new(
+        database.clone(),
+        stripe_client.clone(),
+        fee_calculator.clone(),
+        PlaidTransactionsClient::new(
+            config.plaid.client_id,
+            config.plaid.secret_key,
+            &config.plaid.env,
+        ),
+        config.round_up.threshold_cents,
+    );
+    let round_up_interval = Duration::from_secs(config.round_up.interval_secs);
+    tokio::spawn(async move { round_up_processor.run(round_up_interval).await });
+    let recurring_donation_scheduler = RecurringDonationScheduler::new(
+        database.clone(),
+        stripe_client.clone(),
+        fee_calculator.clone(),
+    );
+    let recurring_donation_interval =
+        Duration::from_secs(config.recurring_donations.poll_interval_secs);
+    tokio::spawn(async move {
+        recurring_donation_scheduler
+            .run(recurring_donation_interval)
+            .await
+    });
+    let affiliate_refresher = AffiliateRefresher::new(
+        database.clone(),
+        stripe_client.clone(),
+        chrono::Duration::seconds(config.affiliate_refresh.stale_after_secs),
+    );
+    let affiliate_refresh_interval = Duration::from_secs(config.affiliate_refresh.interval_secs);
+    tokio::spawn(async move { affiliate_refresher.run(affiliate_refresh_interval).await });
+
     // Interceptors/middleware:
-    let authn_interceptor_layer =
-        AsyncInterceptorLayer::new(AuthnInterceptor::new(firebase_auth.clone(), store.clone()));
+    let authn_interceptor_layer = AsyncInterceptorLayer::new(AuthnInterceptor::new(
+        firebase_auth.clone(),
+        store.clone(),
+        config.auth.privileged_firebase_uids.into_iter().collect(),
+    ));
+    let idempotency_layer = IdempotencyLayer::new(
+        store.clone(),
+        [
+            "/affect.DonationService/CreateDonation",
+            "/affect.ItemService/CreateItem",
+            "/affect.ItemService/ReconcileItem",
+            "/affect.AffiliateService/CreateAffiliate",
+            "/affect.RecurringDonationService/CreateRecurringDonation",
+        ],
+    );
+    let idempotency_purger = idempotency_layer.clone();
+    tokio::spawn(async move { idempotency_purger.purge(Duration::from_secs(3600)).await });
     let middleware = ServiceBuilder::new()
         .timeout(Duration::from_secs(30))
+        .layer(webhook_layer)
         .layer(authn_interceptor_layer)
+        .layer(idempotency_layer)
         .into_inner();
 
     // Services:
@@ -89,10 +176,20 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
         database.clone(),
         plaid_client.clone(),
         stripe_client.clone(),
+        config.plaid.webhook_url.clone(),
+        web_links.clone(),
     );
     let cause_service = CauseServiceImpl::new(database.clone());
-    let affiliate_service = AffiliateServiceImpl::new(database.clone(), stripe_client.clone());
-    let donation_service = DonationServiceImpl::new(database.clone(), stripe_client.clone());
+    let affiliate_service =
+        AffiliateServiceImpl::new(database.clone(), stripe_client.clone(), web_links);
+    let donation_service = DonationServiceImpl::new(
+        database.clone(),
+        stripe_client.clone(),
+        fee_calculator.clone(),
+    );
+    let round_up_service = RoundUpServiceImpl::new(database.clone());
+    let recurring_donation_service =
+        RecurringDonationServiceImpl::new(database.clone(), fee_calculator);
 
     let port: u16 = match (config.port, config.port_env_var) {
         (None, Some(port_env_var)) => std::env::var(&port_env_var)?.parse()?,
@@ -102,6 +199,8 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
     let addr = format!("0.0.0.0:{0}", port).parse()?;
     info!("Starting server: {:?}", addr);
     Server::builder()
+        // Webhooks are sent over http1.
+        .accept_http1(true)
         .layer(middleware)
         .add_service(reflection_service)
         .add_service(UserServiceServer::new(user_service))
@@ -110,6 +209,10 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
         .add_service(CauseServiceServer::new(cause_service))
         .add_service(AffiliateServiceServer::new(affiliate_service))
         .add_service(DonationServiceServer::new(donation_service))
+        .add_service(RoundUpServiceServer::new(round_up_service))
+        .add_service(RecurringDonationServiceServer::new(
+            recurring_donation_service,
+        ))
         .serve(addr)
         .await?;
 
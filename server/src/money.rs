@@ -1,11 +1,47 @@
+use affect_status::{internal, Status};
 use iso_currency::Currency;
 
+const NANOS_PER_UNIT: i128 = 1_000_000_000;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("nanos must be between -999,999,999 and +999,999,999: {0}")]
+    NanosOutOfRange(i32),
+
+    #[error("units and nanos must have the same sign: {units} units, {nanos} nanos")]
+    SignMismatch { units: i64, nanos: i32 },
+
+    #[error("currencies must match: {0} and {1}")]
+    CurrencyMismatch(&'static str, &'static str),
+
+    #[error("amount overflows")]
+    Overflow,
+
+    #[error("ratio denominator must be positive")]
+    InvalidRatio,
+
+    #[error("weights must be non-negative with a positive sum")]
+    InvalidWeights,
+
+    #[error("unknown currency: {0}")]
+    UnknownCurrency(String),
+}
+
+impl From<Error> for Status {
+    fn from(error: Error) -> Self {
+        internal!("money error: {0}", error)
+    }
+}
+
+/// An amount of money in a currency. Amounts are validated on construction and
+/// all arithmetic is done on integers, so no precision is lost.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Money {
-    pub currency: Currency,
+    currency: Currency,
 
     /// The whole units of the amount.
     /// For example if `currencyCode` is `"USD"`, then 1 unit is one US dollar.
-    pub units: i64,
+    units: i64,
 
     /// Number of nano (10^-9) units of the amount.
     /// The value must be between -999,999,999 and +999,999,999 inclusive.
@@ -13,58 +49,461 @@ pub struct Money {
     /// If `units` is zero, `nanos` can be positive, zero, or negative.
     /// If `units` is negative, `nanos` must be negative or zero.
     /// For example $-1.75 is represented as `units`=-1 and `nanos`=-750,000,000.
-    pub nanos: i32,
+    nanos: i32,
 }
 
 impl Money {
-    pub fn units_as_subunits(&self) -> i64 {
-        let subunit_fraction = self.currency.subunit_fraction().unwrap_or(1u16);
-        self.units * (subunit_fraction as i64)
+    /// Money from units and nanos following the sign rules of `nanos`. The
+    /// amount must also be representable in subunits as an `i64`.
+    pub fn new(currency: Currency, units: i64, nanos: i32) -> Result<Self, Error> {
+        if nanos <= -1_000_000_000 || nanos >= 1_000_000_000 {
+            return Err(Error::NanosOutOfRange(nanos));
+        }
+        if (units > 0 && nanos < 0) || (units < 0 && nanos > 0) {
+            return Err(Error::SignMismatch { units, nanos });
+        }
+        Self::from_total_nanos(currency, units as i128 * NANOS_PER_UNIT + nanos as i128)
+    }
+
+    /// Money in the currency with the provided ISO 4217 code, e.g. "USD".
+    pub fn from_code(currency_code: &str, units: i64, nanos: i32) -> Result<Self, Error> {
+        let currency = Currency::from_code(currency_code)
+            .ok_or(Error::UnknownCurrency(currency_code.to_string()))?;
+        Self::new(currency, units, nanos)
+    }
+
+    /// Money from separately summed units and nanos, so nanos may exceed a
+    /// unit or have a different sign than the units.
+    pub fn from_sums(currency: Currency, units: i64, nanos: i64) -> Result<Self, Error> {
+        Self::from_total_nanos(currency, units as i128 * NANOS_PER_UNIT + nanos as i128)
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Money {
+            currency,
+            units: 0,
+            nanos: 0,
+        }
+    }
+
+    /// Money from an amount in the smallest unit of the currency (e.g. cents).
+    pub fn from_subunits(currency: Currency, subunits: i64) -> Self {
+        let subunit_fraction = subunit_fraction(currency);
+        let total_nanos = subunits as i128 * NANOS_PER_UNIT / subunit_fraction;
+        Money {
+            currency,
+            units: (total_nanos / NANOS_PER_UNIT) as i64,
+            nanos: (total_nanos % NANOS_PER_UNIT) as i32,
+        }
+    }
+
+    fn from_total_nanos(currency: Currency, total_nanos: i128) -> Result<Self, Error> {
+        let money = Money {
+            currency,
+            units: i64::try_from(total_nanos / NANOS_PER_UNIT).map_err(|_| Error::Overflow)?,
+            nanos: (total_nanos % NANOS_PER_UNIT) as i32,
+        };
+        // Subunits are what gets charged, so they must never overflow.
+        i64::try_from(money.subunits_rounded_wide()).map_err(|_| Error::Overflow)?;
+        Ok(money)
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn units(&self) -> i64 {
+        self.units
     }
 
-    pub fn nanos_as_subunits(&self) -> f32 {
-        let subunit_fraction = self.currency.subunit_fraction().unwrap_or(1u16);
-        ((self.nanos as f32) / (10_i32.pow(9) as f32)) * (subunit_fraction as f32)
+    pub fn nanos(&self) -> i32 {
+        self.nanos
     }
 
+    fn total_nanos(&self) -> i128 {
+        self.units as i128 * NANOS_PER_UNIT + self.nanos as i128
+    }
+
+    fn subunits_rounded_wide(&self) -> i128 {
+        div_round(
+            self.total_nanos() * subunit_fraction(self.currency),
+            NANOS_PER_UNIT,
+        )
+    }
+
+    /// The amount in subunits, dropping fractions of a subunit.
     pub fn subunits_truncated(&self) -> i64 {
-        self.units_as_subunits() + (self.nanos_as_subunits() as i64)
+        (self.total_nanos() * subunit_fraction(self.currency) / NANOS_PER_UNIT) as i64
     }
 
+    /// The amount in subunits, rounding half a subunit away from zero.
     pub fn subunits_rounded(&self) -> i64 {
-        self.units_as_subunits() + (self.nanos_as_subunits().round() as i64)
+        self.subunits_rounded_wide() as i64
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.total_nanos() == 0
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.total_nanos() > 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.total_nanos() < 0
+    }
+
+    pub fn checked_add(&self, other: &Money) -> Result<Money, Error> {
+        self.check_currency(other)?;
+        Self::from_total_nanos(self.currency, self.total_nanos() + other.total_nanos())
+    }
+
+    pub fn checked_sub(&self, other: &Money) -> Result<Money, Error> {
+        self.check_currency(other)?;
+        Self::from_total_nanos(self.currency, self.total_nanos() - other.total_nanos())
+    }
+
+    /// Multiplies the amount by `numerator / denominator`, rounding half a nano
+    /// away from zero.
+    pub fn checked_mul_ratio(&self, numerator: i64, denominator: i64) -> Result<Money, Error> {
+        if denominator <= 0 {
+            return Err(Error::InvalidRatio);
+        }
+        let product = self
+            .total_nanos()
+            .checked_mul(numerator as i128)
+            .ok_or(Error::Overflow)?;
+        Self::from_total_nanos(self.currency, div_round(product, denominator as i128))
+    }
+
+    /// Splits the amount proportionally to the weights. Every share is a whole
+    /// number of subunits, except the first which also receives any fraction
+    /// of a subunit, and the shares always sum to the amount.
+    pub fn allocate(&self, weights: &[i32]) -> Result<Vec<Money>, Error> {
+        if weights.iter().any(|weight| *weight < 0)
+            || weights.iter().map(|weight| *weight as i64).sum::<i64>() <= 0
+        {
+            return Err(Error::InvalidWeights);
+        }
+        let subunits = self.subunits_truncated();
+        let mut shares: Vec<Money> = allocate_subunits(subunits.abs(), weights)
+            .into_iter()
+            .map(|share| Money::from_subunits(self.currency, share * subunits.signum()))
+            .collect();
+        let fraction =
+            self.total_nanos() - Money::from_subunits(self.currency, subunits).total_nanos();
+        shares[0] = Self::from_total_nanos(self.currency, shares[0].total_nanos() + fraction)?;
+        Ok(shares)
     }
 
     pub fn stripe_currency(&self) -> Result<stripe::Currency, stripe::ParseCurrencyError> {
         self.currency.code().to_string().to_lowercase().parse()
     }
+
+    fn check_currency(&self, other: &Money) -> Result<(), Error> {
+        if self.currency != other.currency {
+            return Err(Error::CurrencyMismatch(
+                self.currency.code(),
+                other.currency.code(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Number of subunits in a unit, 1 for currencies without subunits.
+fn subunit_fraction(currency: Currency) -> i128 {
+    currency.subunit_fraction().unwrap_or(1u16) as i128
+}
+
+/// Divides by a positive divisor, rounding half away from zero.
+fn div_round(dividend: i128, divisor: i128) -> i128 {
+    let quotient = dividend / divisor;
+    let remainder = dividend % divisor;
+    if remainder.abs() * 2 >= divisor {
+        quotient + dividend.signum()
+    } else {
+        quotient
+    }
+}
+
+/// Allocates a non-negative amount of subunits proportionally to the weights.
+/// Shares sum to the amount: subunits lost to rounding go to the shares with
+/// the largest remainders, earlier shares first on ties.
+pub fn allocate_subunits(subunits: i64, weights: &[i32]) -> Vec<i64> {
+    let total_weight: i128 = weights.iter().map(|weight| *weight as i128).sum();
+    if total_weight <= 0 {
+        return vec![0; weights.len()];
+    }
+    let mut shares = Vec::new();
+    let mut remainders = Vec::new();
+    for (i, weight) in weights.iter().enumerate() {
+        let numerator = subunits as i128 * *weight as i128;
+        shares.push((numerator / total_weight) as i64);
+        remainders.push((numerator % total_weight, i));
+    }
+    let leftover = subunits - shares.iter().sum::<i64>();
+    remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for (_, i) in remainders.into_iter().take(leftover as usize) {
+        shares[i] += 1;
+    }
+    shares
 }
 
 #[cfg(test)]
 mod tests {
     use iso_currency::Currency;
+    use proptest::prelude::*;
 
     use super::*;
 
+    fn usd(units: i64, nanos: i32) -> Money {
+        Money::new(Currency::USD, units, nanos).unwrap()
+    }
+
     #[test]
     pub fn subunits_truncated() {
         // $1.75 and 6/10 of cent
-        let money = Money {
-            currency: Currency::USD,
-            units: 1,
-            nanos: 756_000_000,
-        };
+        let money = usd(1, 756_000_000);
         assert_eq!(money.subunits_truncated(), 175);
+        assert_eq!(usd(-1, -756_000_000).subunits_truncated(), -175);
     }
 
     #[test]
     pub fn subunits_rounded() {
         // $1.75 and 6/10 of cent
-        let money = Money {
-            currency: Currency::USD,
-            units: 1,
-            nanos: 756_000_000,
-        };
+        let money = usd(1, 756_000_000);
         assert_eq!(money.subunits_rounded(), 176);
+        assert_eq!(usd(0, 5_000_000).subunits_rounded(), 1);
+        assert_eq!(usd(0, -5_000_000).subunits_rounded(), -1);
+        assert_eq!(usd(0, 4_999_999).subunits_rounded(), 0);
+    }
+
+    #[test]
+    pub fn subunits_of_large_amounts_are_exact() {
+        let money = usd(90_071_992_547_409, 930_000_000);
+        assert_eq!(money.subunits_truncated(), 9_007_199_254_740_993);
+    }
+
+    #[test]
+    pub fn subunits_follow_the_currency() {
+        let kwd = Money::new(Currency::KWD, 1, 234_500_000).unwrap();
+        assert_eq!(kwd.subunits_truncated(), 1234);
+        assert_eq!(kwd.subunits_rounded(), 1235);
+        assert_eq!(
+            Money::from_subunits(Currency::KWD, 1234),
+            kwd.checked_sub(&Money::new(Currency::KWD, 0, 500_000).unwrap())
+                .unwrap()
+        );
+
+        let jpy = Money::new(Currency::JPY, 500, 0).unwrap();
+        assert_eq!(jpy.subunits_truncated(), 500);
+        assert_eq!(Money::from_subunits(Currency::JPY, 500), jpy);
+    }
+
+    #[test]
+    pub fn from_subunits() {
+        let money = Money::from_subunits(Currency::USD, 175);
+        assert_eq!(money.units(), 1);
+        assert_eq!(money.nanos(), 750_000_000);
+        assert_eq!(money.subunits_truncated(), 175);
+        assert_eq!(
+            Money::from_subunits(Currency::USD, -175),
+            usd(-1, -750_000_000)
+        );
+    }
+
+    #[test]
+    pub fn new_validates_nanos() {
+        assert_eq!(
+            Money::new(Currency::USD, 0, 1_000_000_000),
+            Err(Error::NanosOutOfRange(1_000_000_000))
+        );
+        assert_eq!(
+            Money::new(Currency::USD, 1, -1),
+            Err(Error::SignMismatch {
+                units: 1,
+                nanos: -1
+            })
+        );
+        assert_eq!(
+            Money::new(Currency::USD, -1, 1),
+            Err(Error::SignMismatch {
+                units: -1,
+                nanos: 1
+            })
+        );
+        assert!(Money::new(Currency::USD, 0, -1).is_ok());
+        assert_eq!(Money::new(Currency::USD, i64::MAX, 0), Err(Error::Overflow));
+    }
+
+    #[test]
+    pub fn from_code() {
+        assert_eq!(
+            Money::from_code("USD", 1, 750_000_000),
+            Ok(usd(1, 750_000_000))
+        );
+        assert_eq!(
+            Money::from_code("XYZ", 1, 0),
+            Err(Error::UnknownCurrency("XYZ".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn from_sums() {
+        assert_eq!(
+            Money::from_sums(Currency::USD, 3, 2_500_000_000),
+            Ok(usd(5, 500_000_000))
+        );
+        assert_eq!(
+            Money::from_sums(Currency::USD, 3, -3_500_000_000),
+            Ok(usd(0, -500_000_000))
+        );
+    }
+
+    #[test]
+    pub fn checked_arithmetic() {
+        assert_eq!(
+            usd(1, 750_000_000).checked_add(&usd(0, 500_000_000)),
+            Ok(usd(2, 250_000_000))
+        );
+        assert_eq!(
+            usd(1, 0).checked_sub(&usd(1, 750_000_000)),
+            Ok(usd(0, -750_000_000))
+        );
+        assert_eq!(
+            usd(1, 0).checked_add(&Money::zero(Currency::EUR)),
+            Err(Error::CurrencyMismatch("USD", "EUR"))
+        );
+        let max = Money::from_subunits(Currency::USD, i64::MAX);
+        assert_eq!(max.checked_add(&usd(1, 0)), Err(Error::Overflow));
+    }
+
+    #[test]
+    pub fn checked_mul_ratio() {
+        assert_eq!(
+            usd(10, 0).checked_mul_ratio(29, 1000),
+            Ok(usd(0, 290_000_000))
+        );
+        assert_eq!(usd(1, 0).checked_mul_ratio(1, 3), Ok(usd(0, 333_333_333)));
+        assert_eq!(usd(2, 0).checked_mul_ratio(1, 3), Ok(usd(0, 666_666_667)));
+        assert_eq!(usd(1, 0).checked_mul_ratio(1, 0), Err(Error::InvalidRatio));
+        assert_eq!(
+            usd(1, 0).checked_mul_ratio(i64::MAX, 1),
+            Err(Error::Overflow)
+        );
+    }
+
+    #[test]
+    pub fn allocate_keeps_fractions() {
+        assert_eq!(
+            usd(10, 5_000_000).allocate(&[1, 1, 1]),
+            Ok(vec![
+                usd(3, 345_000_000),
+                usd(3, 330_000_000),
+                usd(3, 330_000_000)
+            ])
+        );
+        assert_eq!(
+            usd(0, -20_000_000).allocate(&[1, 1, 1]),
+            Ok(vec![usd(0, -10_000_000), usd(0, -10_000_000), usd(0, 0)])
+        );
+        assert_eq!(usd(1, 0).allocate(&[]), Err(Error::InvalidWeights));
+        assert_eq!(usd(1, 0).allocate(&[0, 0]), Err(Error::InvalidWeights));
+        assert_eq!(usd(1, 0).allocate(&[2, -1]), Err(Error::InvalidWeights));
+    }
+
+    #[test]
+    pub fn allocate_subunits_by_weight() {
+        assert_eq!(allocate_subunits(1000, &[7000, 3000]), vec![700, 300]);
+        assert_eq!(allocate_subunits(999, &[7000, 3000]), vec![699, 300]);
+        assert_eq!(allocate_subunits(1, &[7000, 3000]), vec![1, 0]);
+    }
+
+    #[test]
+    pub fn allocate_subunits_distributes_remainder() {
+        assert_eq!(allocate_subunits(1000, &[1, 1, 1]), vec![334, 333, 333]);
+        assert_eq!(
+            allocate_subunits(1000, &[1, 1, 1, 1]),
+            vec![250, 250, 250, 250]
+        );
+        assert_eq!(allocate_subunits(2, &[1, 1, 1]), vec![1, 1, 0]);
+        // Largest remainder first: 0.5 * 3 = 1.5, 0.25 * 3 = 0.75.
+        assert_eq!(allocate_subunits(3, &[2, 1, 1]), vec![1, 1, 1]);
+        assert_eq!(allocate_subunits(5, &[5000, 2500, 2500]), vec![3, 1, 1]);
+        assert!(allocate_subunits(1000, &[]).is_empty());
+    }
+
+    fn currency() -> impl Strategy<Value = Currency> {
+        prop_oneof![
+            Just(Currency::USD),
+            Just(Currency::JPY),
+            Just(Currency::KWD)
+        ]
+    }
+
+    fn money() -> impl Strategy<Value = Money> {
+        (
+            currency(),
+            -1_000_000_000_000i64..1_000_000_000_000i64,
+            0..1_000_000_000i32,
+        )
+            .prop_map(|(currency, units, nanos)| {
+                let nanos = if units < 0 { -nanos } else { nanos };
+                Money::new(currency, units, nanos).unwrap()
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn new_accepts_only_valid_signs(units: i64, nanos: i32) {
+            if let Ok(money) = Money::new(Currency::USD, units, nanos) {
+                prop_assert!(money.nanos().abs() < 1_000_000_000);
+                prop_assert!(money.units() >= 0 || money.nanos() <= 0);
+                prop_assert!(money.units() <= 0 || money.nanos() >= 0);
+            }
+        }
+
+        #[test]
+        fn from_subunits_round_trips(currency in currency(), subunits in -1_000_000_000_000_000i64..1_000_000_000_000_000i64) {
+            let money = Money::from_subunits(currency, subunits);
+            prop_assert_eq!(money.subunits_truncated(), subunits);
+            prop_assert_eq!(money.subunits_rounded(), subunits);
+            prop_assert_eq!(Money::new(currency, money.units(), money.nanos()), Ok(money));
+        }
+
+        #[test]
+        fn add_then_sub_is_identity(a in money(), b in money()) {
+            let b = Money::new(a.currency(), b.units(), b.nanos()).unwrap();
+            prop_assert_eq!(a.checked_add(&b).unwrap().checked_sub(&b), Ok(a));
+        }
+
+        #[test]
+        fn truncation_is_within_a_subunit(money in money()) {
+            let truncated = Money::from_subunits(money.currency(), money.subunits_truncated());
+            let difference = money.checked_sub(&truncated).unwrap();
+            prop_assert_eq!(difference.subunits_truncated(), 0);
+            prop_assert!(!difference.is_negative() || money.is_negative());
+            prop_assert!((money.subunits_rounded() - money.subunits_truncated()).abs() <= 1);
+        }
+
+        #[test]
+        fn mul_ratio_by_one_is_identity(money in money(), denominator in 1i64..1_000_000) {
+            prop_assert_eq!(money.checked_mul_ratio(denominator, denominator), Ok(money));
+        }
+
+        #[test]
+        fn allocate_sums_to_amount(money in money(), weights in prop::collection::vec(0..10_000i32, 1..10)) {
+            prop_assume!(weights.iter().sum::<i32>() > 0);
+            let shares = money.allocate(&weights).unwrap();
+            prop_assert_eq!(shares.len(), weights.len());
+            let sum = shares
+                .iter()
+                .try_fold(Money::zero(money.currency()), |sum, share| sum.checked_add(share))
+                .unwrap();
+            prop_assert_eq!(sum, money);
+            let subunits: i64 = shares.iter().map(|share| share.subunits_truncated()).sum();
+            prop_assert_eq!(subunits, money.subunits_truncated());
+        }
     }
 }
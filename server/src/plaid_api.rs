@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// Link token plaid link is opened with.
+#[derive(Clone, Debug)]
+pub struct PlaidLinkToken {
+    pub link_token: String,
+    pub expiration: DateTime<Utc>,
+}
+
+/// Item created by exchanging the public token of plaid link.
+#[derive(Clone, Debug)]
+pub struct PlaidItem {
+    pub item_id: String,
+    pub access_token: String,
+}
+
+/// Account of a plaid item.
+#[derive(Clone, Debug)]
+pub struct PlaidAccount {
+    pub account_id: String,
+    pub name: String,
+    pub mask: Option<String>,
+    pub r#type: plaid::AccountType,
+    pub subtype: Option<plaid::AccountSubtype>,
+}
+
+/// Accounts of a plaid item, with the error plaid reports for the item, e.g.
+/// "ITEM_LOGIN_REQUIRED", if any.
+#[derive(Clone, Debug)]
+pub struct PlaidItemAccounts {
+    pub accounts: Vec<PlaidAccount>,
+    pub item_error_code: Option<String>,
+}
+
+/// Plaid api calls the item service makes, so tests can stand in for plaid.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait PlaidApi: Sync + Send {
+    async fn create_link_token(
+        &self,
+        request: &plaid::CreateLinkTokenRequest,
+    ) -> Result<PlaidLinkToken, anyhow::Error>;
+
+    async fn exchange_public_token(&self, public_token: &str) -> Result<PlaidItem, anyhow::Error>;
+
+    async fn accounts(&self, access_token: &str) -> Result<PlaidItemAccounts, anyhow::Error>;
+
+    /// Returns a stripe bank account token for the account of the item.
+    async fn stripe_create_bank_account_token(
+        &self,
+        access_token: &str,
+        account_id: &str,
+    ) -> Result<String, anyhow::Error>;
+}
+
+#[async_trait]
+impl PlaidApi for plaid::Client {
+    async fn create_link_token(
+        &self,
+        request: &plaid::CreateLinkTokenRequest,
+    ) -> Result<PlaidLinkToken, anyhow::Error> {
+        let response = plaid::Client::create_link_token(self, request)
+            .await
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        Ok(PlaidLinkToken {
+            link_token: response.link_token,
+            expiration: response.expiration,
+        })
+    }
+
+    async fn exchange_public_token(&self, public_token: &str) -> Result<PlaidItem, anyhow::Error> {
+        let response = plaid::Client::exchange_public_token(self, public_token)
+            .await
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        Ok(PlaidItem {
+            item_id: response.item_id,
+            access_token: response.access_token,
+        })
+    }
+
+    async fn accounts(&self, access_token: &str) -> Result<PlaidItemAccounts, anyhow::Error> {
+        let response = plaid::Client::accounts(self, access_token)
+            .await
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        Ok(PlaidItemAccounts {
+            accounts: response
+                .accounts
+                .into_iter()
+                .map(|account| PlaidAccount {
+                    account_id: account.account_id,
+                    name: account.name,
+                    mask: account.mask,
+                    r#type: account.r#type,
+                    subtype: account.subtype,
+                })
+                .collect(),
+            item_error_code: response.item.error.map(|error| error.error_code),
+        })
+    }
+
+    async fn stripe_create_bank_account_token(
+        &self,
+        access_token: &str,
+        account_id: &str,
+    ) -> Result<String, anyhow::Error> {
+        let response =
+            plaid::Client::stripe_create_bank_account_token(self, access_token, account_id)
+                .await
+                .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        Ok(response.stripe_bank_account_token)
+    }
+}
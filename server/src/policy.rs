@@ -0,0 +1,184 @@
+use crate::interceptors::authn::Peer;
+use affect_status::Status;
+use affect_storage::models::affiliate::{
+    AffiliateManagerRole, AffiliateManagerRow, FullAffiliateRow,
+};
+use uuid::Uuid;
+
+/// Declares which peers may call an rpc.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Policy {
+    /// Any peer, including anonymous ones.
+    Anyone,
+
+    /// The user owning the resource. An impersonating peer acts as the
+    /// impersonated user.
+    Owner(Uuid),
+
+    /// Any of the provided affiliate managers.
+    AffiliateManager(Vec<Uuid>),
+
+    /// Privileged peers only.
+    Privileged,
+}
+
+impl Policy {
+    /// Policy allowing the managers of the affiliate.
+    pub fn affiliate_managers(affiliate: &FullAffiliateRow) -> Self {
+        Policy::managers_with_roles(
+            &affiliate.affiliate_managers.clone().inner(),
+            &[
+                AffiliateManagerRole::Owner,
+                AffiliateManagerRole::Admin,
+                AffiliateManagerRole::Viewer,
+            ],
+        )
+    }
+
+    /// Policy allowing the owners and admins of the affiliate.
+    pub fn affiliate_admins(affiliate: &FullAffiliateRow) -> Self {
+        Policy::managers_with_roles(
+            &affiliate.affiliate_managers.clone().inner(),
+            &[AffiliateManagerRole::Owner, AffiliateManagerRole::Admin],
+        )
+    }
+
+    /// Policy allowing the provided affiliate managers which have one of the
+    /// roles.
+    pub fn managers_with_roles(
+        affiliate_managers: &[AffiliateManagerRow],
+        roles: &[AffiliateManagerRole],
+    ) -> Self {
+        Policy::AffiliateManager(
+            affiliate_managers
+                .iter()
+                .filter(|affiliate_manager_row| roles.contains(&affiliate_manager_row.role))
+                .map(|affiliate_manager_row| affiliate_manager_row.user_id)
+                .collect(),
+        )
+    }
+
+    /// Returns ok if the peer satisfies the policy. Privileged peers satisfy
+    /// every policy.
+    pub fn authorize(&self, peer: &Peer) -> Result<(), Status> {
+        let user_id = match (self, peer) {
+            (Policy::Anyone, _) => return Ok(()),
+            (_, Peer::Anonymous) => {
+                return Err(Status::unauthenticated("request must be authenticated"))
+            }
+            (_, Peer::Privileged(_)) => return Ok(()),
+            (_, Peer::User(user)) => user.user_id,
+            (_, Peer::Impersonated { user, .. }) => user.user_id,
+        };
+
+        let allowed = match self {
+            Policy::Anyone => true,
+            Policy::Owner(owner_id) => *owner_id == user_id,
+            Policy::AffiliateManager(manager_ids) => manager_ids.contains(&user_id),
+            Policy::Privileged => false,
+        };
+        if !allowed {
+            return Err(Status::permission_denied(
+                "peer is not allowed to perform this request",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::user_row;
+    use affect_status::Code;
+    use chrono::Utc;
+
+    #[test]
+    pub fn anyone_allows_anonymous() {
+        assert!(Policy::Anyone.authorize(&Peer::Anonymous).is_ok());
+    }
+
+    #[test]
+    pub fn anonymous_is_unauthenticated() {
+        let status = Policy::Owner(Uuid::new_v4())
+            .authorize(&Peer::Anonymous)
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    pub fn owner() {
+        let user_id = Uuid::new_v4();
+        let peer = Peer::User(user_row(user_id));
+        assert!(Policy::Owner(user_id).authorize(&peer).is_ok());
+
+        let status = Policy::Owner(Uuid::new_v4()).authorize(&peer).unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+    }
+
+    #[test]
+    pub fn impersonated_acts_as_user() {
+        let user_id = Uuid::new_v4();
+        let peer = Peer::Impersonated {
+            user: user_row(user_id),
+            privileged_user: user_row(Uuid::new_v4()),
+        };
+        assert!(Policy::Owner(user_id).authorize(&peer).is_ok());
+        assert_eq!(
+            Policy::Privileged.authorize(&peer).unwrap_err().code(),
+            Code::PermissionDenied
+        );
+    }
+
+    #[test]
+    pub fn affiliate_manager() {
+        let user_id = Uuid::new_v4();
+        let peer = Peer::User(user_row(user_id));
+        assert!(Policy::AffiliateManager(vec![Uuid::new_v4(), user_id])
+            .authorize(&peer)
+            .is_ok());
+        assert_eq!(
+            Policy::AffiliateManager(vec![Uuid::new_v4()])
+                .authorize(&peer)
+                .unwrap_err()
+                .code(),
+            Code::PermissionDenied
+        );
+    }
+
+    #[test]
+    pub fn managers_with_roles() {
+        let affiliate_id = Uuid::new_v4();
+        let manager = |role| AffiliateManagerRow {
+            affiliate_id,
+            user_id: Uuid::new_v4(),
+            create_time: Utc::now(),
+            update_time: Utc::now(),
+            role,
+        };
+        let owner = manager(AffiliateManagerRole::Owner);
+        let viewer = manager(AffiliateManagerRole::Viewer);
+        let policy = Policy::managers_with_roles(
+            &[owner.clone(), viewer.clone()],
+            &[AffiliateManagerRole::Owner, AffiliateManagerRole::Admin],
+        );
+        assert!(policy
+            .authorize(&Peer::User(user_row(owner.user_id)))
+            .is_ok());
+        assert_eq!(
+            policy
+                .authorize(&Peer::User(user_row(viewer.user_id)))
+                .unwrap_err()
+                .code(),
+            Code::PermissionDenied
+        );
+    }
+
+    #[test]
+    pub fn privileged_satisfies_every_policy() {
+        let peer = Peer::Privileged(user_row(Uuid::new_v4()));
+        assert!(Policy::Privileged.authorize(&peer).is_ok());
+        assert!(Policy::Owner(Uuid::new_v4()).authorize(&peer).is_ok());
+        assert!(Policy::AffiliateManager(vec![]).authorize(&peer).is_ok());
+    }
+}
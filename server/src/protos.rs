@@ -1,6 +1,9 @@
 pub mod affiliate;
 pub mod cause;
+pub mod donation;
 pub mod item;
 pub mod nonprofit;
+pub mod recurring_donation;
+pub mod round_up;
 pub mod user;
 pub mod well_known;
@@ -1,15 +1,23 @@
 use crate::protobuf::{from::ProtoFrom, into::IntoProto};
-use affect_api::affect::{Affiliate, AffiliateManager};
-use affect_storage::models::affiliate::FullAffiliateRow;
+use affect_api::affect::{Affiliate, AffiliateManager, AffiliateManagerInvitation};
+use affect_storage::models::affiliate::{
+    AffiliateManagerInvitationRow, AffiliateManagerRow, FullAffiliateRow,
+};
 use tonic::Status;
 
 // Same type name.
+use affect_api::affect::AffiliateManagerRole as ProtoAffiliateManagerRole;
+use affect_api::affect::AffiliateVerificationState as ProtoAffiliateVerificationState;
 use affect_api::affect::BusinessType as ProtoBusinessType;
+use affect_storage::models::affiliate::AffiliateManagerRole as StoreAffiliateManagerRole;
+use affect_storage::models::affiliate::AffiliateVerificationState as StoreAffiliateVerificationState;
 use affect_storage::models::affiliate::BusinessType as StoreBusinessType;
 
 impl ProtoFrom<FullAffiliateRow> for Affiliate {
     fn proto_from(value: FullAffiliateRow) -> Result<Self, Status> {
         let business_type: ProtoBusinessType = value.affiliate.business_type.into_proto()?;
+        let verification_state: ProtoAffiliateVerificationState =
+            value.affiliate.verification_state.into_proto()?;
         Ok(Affiliate {
             affiliate_id: value.affiliate.affiliate_id.into_proto()?,
             create_time: Some(value.affiliate.create_time.into_proto()?),
@@ -21,13 +29,26 @@ impl ProtoFrom<FullAffiliateRow> for Affiliate {
                 .affiliate_managers
                 .inner()
                 .into_iter()
-                .map(|affiliate_manager_row| {
-                    return AffiliateManager {
-                        user_id: affiliate_manager_row.user_id.to_string(),
-                    };
-                })
-                .collect(),
+                .map(|affiliate_manager_row| affiliate_manager_row.into_proto())
+                .collect::<Result<Vec<AffiliateManager>, Status>>()?,
             asserted_nonprofit_id: value.affiliate.asserted_nonprofit_id.into_proto()?,
+            charges_enabled: value.affiliate.charges_enabled,
+            payouts_enabled: value.affiliate.payouts_enabled,
+            country: value.affiliate.country.unwrap_or_default(),
+            business_name: value.affiliate.business_name.unwrap_or_default(),
+            requirements_currently_due: value.affiliate.requirements_currently_due,
+            disabled_reason: value.affiliate.disabled_reason.unwrap_or_default(),
+            stripe_refresh_time: match value.affiliate.stripe_refresh_time {
+                Some(stripe_refresh_time) => Some(stripe_refresh_time.into_proto()?),
+                None => None,
+            },
+            ein: value.affiliate.ein.unwrap_or_default(),
+            verification_state: verification_state as i32,
+            rejection_reason: value.affiliate.rejection_reason.unwrap_or_default(),
+            verification_time: match value.affiliate.verification_time {
+                Some(verification_time) => Some(verification_time.into_proto()?),
+                None => None,
+            },
         })
     }
 }
@@ -42,3 +63,55 @@ impl ProtoFrom<StoreBusinessType> for ProtoBusinessType {
         }
     }
 }
+
+impl ProtoFrom<StoreAffiliateVerificationState> for ProtoAffiliateVerificationState {
+    fn proto_from(value: StoreAffiliateVerificationState) -> Result<Self, Status> {
+        match value {
+            StoreAffiliateVerificationState::Pending => Ok(Self::Pending),
+            StoreAffiliateVerificationState::Approved => Ok(Self::Approved),
+            StoreAffiliateVerificationState::Rejected => Ok(Self::Rejected),
+        }
+    }
+}
+
+impl ProtoFrom<AffiliateManagerRow> for AffiliateManager {
+    fn proto_from(value: AffiliateManagerRow) -> Result<Self, Status> {
+        let role: ProtoAffiliateManagerRole = value.role.into_proto()?;
+        Ok(AffiliateManager {
+            user_id: value.user_id.to_string(),
+            role: role as i32,
+            create_time: Some(value.create_time.into_proto()?),
+        })
+    }
+}
+
+impl ProtoFrom<AffiliateManagerInvitationRow> for AffiliateManagerInvitation {
+    fn proto_from(value: AffiliateManagerInvitationRow) -> Result<Self, Status> {
+        let role: ProtoAffiliateManagerRole = value.role.into_proto()?;
+        Ok(AffiliateManagerInvitation {
+            affiliate_manager_invitation_id: value.affiliate_manager_invitation_id.into_proto()?,
+            affiliate_id: value.affiliate_id.into_proto()?,
+            create_time: Some(value.create_time.into_proto()?),
+            email: value.email,
+            role: role as i32,
+            expire_time: Some(value.expire_time.into_proto()?),
+            accept_time: match value.accept_time {
+                Some(accept_time) => Some(accept_time.into_proto()?),
+                None => None,
+            },
+            // Only known when the invitation is created.
+            token: String::new(),
+            url: String::new(),
+        })
+    }
+}
+
+impl ProtoFrom<StoreAffiliateManagerRole> for ProtoAffiliateManagerRole {
+    fn proto_from(value: StoreAffiliateManagerRole) -> Result<Self, Status> {
+        match value {
+            StoreAffiliateManagerRole::Owner => Ok(Self::Owner),
+            StoreAffiliateManagerRole::Admin => Ok(Self::Admin),
+            StoreAffiliateManagerRole::Viewer => Ok(Self::Viewer),
+        }
+    }
+}
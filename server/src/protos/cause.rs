@@ -1,22 +1,34 @@
-use crate::protobuf::from::ProtoFrom;
+use crate::protobuf::{from::ProtoFrom, into::IntoProto};
 use affect_api::affect::{Cause, CauseRecipient};
 use affect_storage::models::cause::{CauseRecipientRow, CauseRow, FullCauseRow};
 use prost_types::Timestamp;
 use tonic::Status;
 
+// Same type name.
+use affect_api::affect::CauseState as ProtoCauseState;
+use affect_storage::models::cause::CauseState as StoreCauseState;
+
 impl ProtoFrom<(CauseRow, Vec<CauseRecipientRow>)> for Cause {
     fn proto_from(value: (CauseRow, Vec<CauseRecipientRow>)) -> Result<Self, Status> {
         let (cause_row, cause_recipient_rows) = value;
+        let state: ProtoCauseState = cause_row.state.into_proto()?;
         Ok(Cause {
             cause_id: cause_row.cause_id.to_string(),
             create_time: Some(Timestamp::proto_from(cause_row.create_time)?),
             update_time: Some(Timestamp::proto_from(cause_row.update_time)?),
             user_id: cause_row.user_id.to_string(),
+            name: cause_row.name,
+            archive_time: cause_row
+                .archive_time
+                .map(Timestamp::proto_from)
+                .transpose()?,
+            state: state as i32,
             recipients: cause_recipient_rows
                 .into_iter()
                 .map(|cause_recipient_row| CauseRecipient {
                     cause_id: cause_recipient_row.cause_id.to_string(),
                     nonprofit_id: cause_recipient_row.nonprofit_id.to_string(),
+                    weight_basis_points: cause_recipient_row.weight_basis_points,
                 })
                 .collect(),
         })
@@ -25,11 +37,19 @@ impl ProtoFrom<(CauseRow, Vec<CauseRecipientRow>)> for Cause {
 
 impl ProtoFrom<FullCauseRow> for Cause {
     fn proto_from(value: FullCauseRow) -> Result<Self, Status> {
+        let state: ProtoCauseState = value.cause.state.into_proto()?;
         Ok(Cause {
             cause_id: value.cause.cause_id.to_string(),
             create_time: Some(Timestamp::proto_from(value.cause.create_time)?),
             update_time: Some(Timestamp::proto_from(value.cause.update_time)?),
             user_id: value.cause.user_id.to_string(),
+            name: value.cause.name,
+            archive_time: value
+                .cause
+                .archive_time
+                .map(Timestamp::proto_from)
+                .transpose()?,
+            state: state as i32,
             recipients: value
                 .cause_recipients
                 .inner()
@@ -37,8 +57,18 @@ impl ProtoFrom<FullCauseRow> for Cause {
                 .map(|cause_recipient_row| CauseRecipient {
                     cause_id: cause_recipient_row.cause_id.to_string(),
                     nonprofit_id: cause_recipient_row.nonprofit_id.to_string(),
+                    weight_basis_points: cause_recipient_row.weight_basis_points,
                 })
                 .collect(),
         })
     }
 }
+
+impl ProtoFrom<StoreCauseState> for ProtoCauseState {
+    fn proto_from(value: StoreCauseState) -> Result<Self, Status> {
+        match value {
+            StoreCauseState::Draft => Ok(Self::Draft),
+            StoreCauseState::Active => Ok(Self::Active),
+        }
+    }
+}
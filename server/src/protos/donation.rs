@@ -0,0 +1,91 @@
+use crate::{
+    money::Money,
+    protobuf::{from::ProtoFrom, into::IntoProto},
+};
+use affect_api::affect::{Donation, DonationAllocation};
+use affect_storage::models::donation::{DonationAllocationRow, DonationRow};
+use tonic::Status;
+
+// Same type name.
+use affect_api::affect::DonationStatus as ProtoDonationStatus;
+use affect_storage::models::donation::DonationStatus as StoreDonationStatus;
+
+impl ProtoFrom<(DonationRow, Vec<DonationAllocationRow>)> for Donation {
+    fn proto_from(value: (DonationRow, Vec<DonationAllocationRow>)) -> Result<Self, Status> {
+        let (value, allocation_rows) = value;
+        let amount =
+            Money::from_code(&value.currency_code, value.amount_units, value.amount_nanos)?;
+        let charge_amount = Money::from_code(
+            &value.currency_code,
+            value.charge_amount_units,
+            value.charge_amount_nanos,
+        )?;
+        let processing_fee = Money::from_code(
+            &value.currency_code,
+            value.processing_fee_units,
+            value.processing_fee_nanos,
+        )?;
+        let platform_fee = Money::from_code(
+            &value.currency_code,
+            value.platform_fee_units,
+            value.platform_fee_nanos,
+        )?;
+        let status: ProtoDonationStatus = value.status.into_proto()?;
+        let mut allocations = Vec::new();
+        for allocation_row in allocation_rows {
+            let amount = Money::from_code(
+                &value.currency_code,
+                allocation_row.amount_units,
+                allocation_row.amount_nanos,
+            )?;
+            allocations.push(DonationAllocation {
+                nonprofit_id: allocation_row.nonprofit_id.into_proto()?,
+                affiliate_id: allocation_row.affiliate_id.into_proto()?,
+                amount: Some(amount.into_proto()?),
+            });
+        }
+        Ok(Donation {
+            donation_id: value.donation_id.into_proto()?,
+            create_time: Some(value.create_time.into_proto()?),
+            update_time: Some(value.update_time.into_proto()?),
+            nonprofit_id: value
+                .nonprofit_id
+                .map(|nonprofit_id| nonprofit_id.into_proto())
+                .transpose()?
+                .unwrap_or_default(),
+            user_id: value.user_id.into_proto()?,
+            amount: Some(amount.into_proto()?),
+            cause_id: value
+                .cause_id
+                .map(|cause_id| cause_id.into_proto())
+                .transpose()?
+                .unwrap_or_default(),
+            status: status as i32,
+            account_id: value
+                .account_id
+                .map(|account_id| account_id.into_proto())
+                .transpose()?
+                .unwrap_or_default(),
+            settle_time: value
+                .settle_time
+                .map(|settle_time| settle_time.into_proto())
+                .transpose()?,
+            cover_fees: value.cover_fees,
+            charge_amount: Some(charge_amount.into_proto()?),
+            processing_fee: Some(processing_fee.into_proto()?),
+            platform_fee: Some(platform_fee.into_proto()?),
+            allocations,
+        })
+    }
+}
+
+impl ProtoFrom<StoreDonationStatus> for ProtoDonationStatus {
+    fn proto_from(value: StoreDonationStatus) -> Result<Self, Status> {
+        match value {
+            StoreDonationStatus::Pending => Ok(Self::Pending),
+            StoreDonationStatus::Succeeded => Ok(Self::Succeeded),
+            StoreDonationStatus::Failed => Ok(Self::Failed),
+            StoreDonationStatus::Refunded => Ok(Self::Refunded),
+        }
+    }
+}
@@ -1,12 +1,17 @@
-use crate::protobuf::from::ProtoFrom;
+use crate::protobuf::{from::ProtoFrom, into::IntoProto};
 use affect_api::affect::{Account, Item};
 use affect_storage::models::{account::AccountRow, item::ItemRow};
 use prost_types::Timestamp;
 use tonic::Status;
 
+// Same type name.
+use affect_api::affect::ItemStatus as ProtoItemStatus;
+use affect_storage::models::item::ItemStatus as StoreItemStatus;
+
 impl ProtoFrom<(ItemRow, Vec<AccountRow>)> for Item {
     fn proto_from(value: (ItemRow, Vec<AccountRow>)) -> Result<Self, Status> {
         let (item_row, account_rows) = value;
+        let status: ProtoItemStatus = item_row.status.into_proto()?;
         Ok(Item {
             item_id: item_row.item_id.to_string(),
             create_time: Some(Timestamp::proto_from(item_row.create_time)?),
@@ -25,6 +30,20 @@ impl ProtoFrom<(ItemRow, Vec<AccountRow>)> for Item {
                     })
                 })
                 .collect::<Result<Vec<Account>, Status>>()?,
+            status: status as i32,
+            new_accounts_available: item_row.new_accounts_available,
         })
     }
 }
+
+impl ProtoFrom<StoreItemStatus> for ProtoItemStatus {
+    fn proto_from(value: StoreItemStatus) -> Result<Self, Status> {
+        match value {
+            StoreItemStatus::Healthy => Ok(Self::Healthy),
+            StoreItemStatus::LoginRequired => Ok(Self::LoginRequired),
+            StoreItemStatus::PendingExpiration => Ok(Self::PendingExpiration),
+            StoreItemStatus::PermissionRevoked => Ok(Self::PermissionRevoked),
+            StoreItemStatus::Error => Ok(Self::Error),
+        }
+    }
+}
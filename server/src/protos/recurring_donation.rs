@@ -0,0 +1,82 @@
+use crate::{
+    money::Money,
+    protobuf::{from::ProtoFrom, into::IntoProto},
+};
+use affect_api::affect::RecurringDonation;
+use affect_storage::models::recurring_donation::RecurringDonationRow;
+use tonic::Status;
+
+// Same type names.
+use affect_api::affect::{
+    RecurringDonationFrequency as ProtoRecurringDonationFrequency,
+    RecurringDonationState as ProtoRecurringDonationState,
+};
+use affect_storage::models::recurring_donation::{
+    RecurringDonationFrequency as StoreRecurringDonationFrequency,
+    RecurringDonationState as StoreRecurringDonationState,
+};
+
+impl ProtoFrom<RecurringDonationRow> for RecurringDonation {
+    fn proto_from(value: RecurringDonationRow) -> Result<Self, Status> {
+        let amount =
+            Money::from_code(&value.currency_code, value.amount_units, value.amount_nanos)?;
+        let frequency: ProtoRecurringDonationFrequency = value.frequency.into_proto()?;
+        let state: ProtoRecurringDonationState = value.state.into_proto()?;
+        Ok(RecurringDonation {
+            recurring_donation_id: value.recurring_donation_id.into_proto()?,
+            create_time: Some(value.create_time.into_proto()?),
+            update_time: Some(value.update_time.into_proto()?),
+            user_id: value.user_id.into_proto()?,
+            nonprofit_id: value
+                .nonprofit_id
+                .map(|nonprofit_id| nonprofit_id.into_proto())
+                .transpose()?
+                .unwrap_or_default(),
+            cause_id: value
+                .cause_id
+                .map(|cause_id| cause_id.into_proto())
+                .transpose()?
+                .unwrap_or_default(),
+            account_id: value
+                .account_id
+                .map(|account_id| account_id.into_proto())
+                .transpose()?
+                .unwrap_or_default(),
+            amount: Some(amount.into_proto()?),
+            frequency: frequency as i32,
+            state: state as i32,
+            start_time: Some(value.start_time.into_proto()?),
+            next_run_time: Some(
+                value
+                    .retry_time
+                    .unwrap_or(value.next_run_time)
+                    .into_proto()?,
+            ),
+            failure_count: value.failure_count,
+            last_donation_id: value
+                .last_donation_id
+                .map(|last_donation_id| last_donation_id.into_proto())
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl ProtoFrom<StoreRecurringDonationFrequency> for ProtoRecurringDonationFrequency {
+    fn proto_from(value: StoreRecurringDonationFrequency) -> Result<Self, Status> {
+        match value {
+            StoreRecurringDonationFrequency::Weekly => Ok(Self::Weekly),
+            StoreRecurringDonationFrequency::Monthly => Ok(Self::Monthly),
+        }
+    }
+}
+
+impl ProtoFrom<StoreRecurringDonationState> for ProtoRecurringDonationState {
+    fn proto_from(value: StoreRecurringDonationState) -> Result<Self, Status> {
+        match value {
+            StoreRecurringDonationState::Active => Ok(Self::Active),
+            StoreRecurringDonationState::Paused => Ok(Self::Paused),
+            StoreRecurringDonationState::Canceled => Ok(Self::Canceled),
+        }
+    }
+}
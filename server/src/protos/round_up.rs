@@ -0,0 +1,26 @@
+use crate::{
+    money::Money,
+    protobuf::{from::ProtoFrom, into::IntoProto},
+};
+use affect_api::affect::RoundUpSettings;
+use affect_storage::models::round_up::RoundUpSettingsRow;
+use tonic::Status;
+
+impl ProtoFrom<(RoundUpSettingsRow, Money)> for RoundUpSettings {
+    fn proto_from(value: (RoundUpSettingsRow, Money)) -> Result<Self, Status> {
+        let (settings_row, pending_amount) = value;
+        Ok(RoundUpSettings {
+            user_id: settings_row.user_id.into_proto()?,
+            create_time: Some(settings_row.create_time.into_proto()?),
+            update_time: Some(settings_row.update_time.into_proto()?),
+            cause_id: settings_row.cause_id.into_proto()?,
+            account_id: settings_row
+                .account_id
+                .map(|account_id| account_id.into_proto())
+                .transpose()?
+                .unwrap_or_default(),
+            enabled: settings_row.enabled,
+            pending_amount: Some(pending_amount.into_proto()?),
+        })
+    }
+}
@@ -54,20 +54,17 @@ impl FromProto<MoneyProto> for Money {
             "invalid currency: {0}",
             proto.currency_code
         ))?;
-        Ok(Money {
-            currency,
-            units: proto.units,
-            nanos: proto.nanos,
-        })
+        Money::new(currency, proto.units, proto.nanos)
+            .map_err(|e| invalid_argument!("invalid money: {0}", e))
     }
 }
 
 impl ProtoFrom<Money> for MoneyProto {
     fn proto_from(value: Money) -> Result<Self, Status> {
         Ok(MoneyProto {
-            currency_code: value.currency.code().to_string(),
-            units: value.units,
-            nanos: value.nanos,
+            currency_code: value.currency().code().to_string(),
+            units: value.units(),
+            nanos: value.nanos(),
         })
     }
 }
@@ -0,0 +1,635 @@
+use crate::{
+    fees::FeeCalculator, money::Money, saga::Saga, services::donation::DonationServiceImpl,
+    tonic::idempotency::IdempotencyKey,
+};
+use affect_status::{internal, well_known::entity_not_found, Status};
+use affect_storage::{
+    database::{
+        client::DatabaseClient,
+        store::{OnDemandStore, TransactionalStore},
+    },
+    models::{
+        donation::DonationRow,
+        recurring_donation::{
+            RecurringDonationFrequency, RecurringDonationRow, RecurringDonationState,
+        },
+    },
+    stores::{
+        account::AccountStore, cause::CauseStore, donation::DonationStore,
+        nonprofit::NonprofitStore, recurring_donation::RecurringDonationStore, user::UserStore,
+    },
+};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use log::{info, warn};
+use std::{cmp::min, sync::Arc};
+
+/// Number of failed charges after which a scheduled donation is skipped.
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// Delay before the first retry of a failed charge, doubled for every retry.
+const RETRY_BASE_SECS: i64 = 3600;
+
+/// Maximum number of donations run by a single tick, so one tick can't starve
+/// the others.
+const MAX_RUNS_PER_TICK: usize = 100;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("storage error: {0:?}")]
+    Storage(#[from] affect_storage::Error),
+
+    #[error("donation error: {0:?}")]
+    Donation(#[from] Status),
+}
+
+/// Adds calendar months to a time. The day is clamped to the last day of the
+/// resulting month, e.g. January 31st plus one month is February 28th.
+pub fn add_months(time: DateTime<Utc>, months: u32) -> DateTime<Utc> {
+    let naive = time.naive_utc();
+    let month0 = naive.month0() + months;
+    let year = naive.year() + (month0 / 12) as i32;
+    let month = month0 % 12 + 1;
+    let day = min(naive.day(), days_in_month(year, month));
+    DateTime::from_utc(
+        NaiveDate::from_ymd(year, month, day).and_time(naive.time()),
+        Utc,
+    )
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = match month {
+        12 => (year + 1, 1),
+        _ => (year, month + 1),
+    };
+    NaiveDate::from_ymd(next_year, next_month, 1).pred().day()
+}
+
+/// The nth donation of a schedule starting at `start_time`, the first being 0.
+/// Occurrences are computed from the start time rather than the previous
+/// occurrence, so clamped days don't drift.
+pub fn occurrence(
+    start_time: DateTime<Utc>,
+    frequency: &RecurringDonationFrequency,
+    n: u32,
+) -> DateTime<Utc> {
+    match frequency {
+        RecurringDonationFrequency::Weekly => start_time + Duration::weeks(n as i64),
+        RecurringDonationFrequency::Monthly => add_months(start_time, n),
+    }
+}
+
+/// The first donation of a schedule which is strictly after `after`.
+pub fn next_run_time(
+    start_time: DateTime<Utc>,
+    frequency: &RecurringDonationFrequency,
+    after: DateTime<Utc>,
+) -> DateTime<Utc> {
+    if after < start_time {
+        return start_time;
+    }
+    // Lower bound of the occurrences at or before `after`.
+    let mut n = match frequency {
+        RecurringDonationFrequency::Weekly => (after - start_time).num_weeks() as u32,
+        RecurringDonationFrequency::Monthly => {
+            let start = start_time.naive_utc();
+            let end = after.naive_utc();
+            ((end.year() - start.year()) * 12 + end.month0() as i32 - start.month0() as i32).max(0)
+                as u32
+        }
+    };
+    while occurrence(start_time, frequency, n) <= after {
+        n += 1;
+    }
+    occurrence(start_time, frequency, n)
+}
+
+/// Time of the retry after the provided number of consecutive failures.
+pub fn retry_time(now: DateTime<Utc>, failure_count: i32) -> DateTime<Utc> {
+    now + Duration::seconds(RETRY_BASE_SECS << (failure_count - 1).clamp(0, 16))
+}
+
+/// Runs the recurring donations which are due. Several replicas may run the
+/// scheduler concurrently: every donation is claimed with a row lock for the
+/// duration of its run, and donations locked by another replica are skipped.
+pub struct RecurringDonationScheduler<Db, Store, TStore> {
+    database: Arc<Db>,
+    donations: DonationServiceImpl<Db, Store, TStore>,
+}
+
+impl<Db, Store, TStore> RecurringDonationScheduler<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore> + 'static,
+    Store: AccountStore
+        + CauseStore
+        + DonationStore
+        + NonprofitStore
+        + UserStore
+        + OnDemandStore
+        + 'static,
+    TStore: DonationStore + RecurringDonationStore + TransactionalStore + 'static,
+{
+    pub fn new(database: Arc<Db>, stripe: Arc<stripe::Client>, fees: FeeCalculator) -> Self {
+        Self {
+            database: database.clone(),
+            donations: DonationServiceImpl::new(database, stripe, fees),
+        }
+    }
+
+    /// Runs due donations every interval, forever.
+    pub async fn run(&self, interval: std::time::Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.run_due().await {
+                Ok(0) => {}
+                Ok(runs) => info!("Ran {} recurring donations", runs),
+                Err(e) => warn!("Failed to run recurring donations: {:?}", e),
+            }
+        }
+    }
+
+    /// Runs the donations which are due, returning the number of runs.
+    pub async fn run_due(&self) -> Result<usize, Error> {
+        let mut runs = 0;
+        while runs < MAX_RUNS_PER_TICK && self.run_next().await? {
+            runs += 1;
+        }
+        Ok(runs)
+    }
+
+    /// Claims and runs the donation which is due the longest. Returns false if
+    /// no donation is due.
+    async fn run_next(&self) -> Result<bool, Error> {
+        let now = Utc::now();
+        let txn = self.database.begin().await?;
+        let row = match txn.claim_due_recurring_donation(now).await? {
+            Some(row) => row,
+            None => {
+                txn.rollback().await?;
+                return Ok(false);
+            }
+        };
+
+        // Charges can't succeed without an account, so the schedule waits for
+        // the user to choose another one.
+        if row.account_id.is_none() {
+            info!(
+                "Pausing recurring donation without account: {}",
+                row.recurring_donation_id
+            );
+            txn.update_recurring_donation_state(
+                row.recurring_donation_id,
+                RecurringDonationState::Paused,
+                row.next_run_time,
+                now,
+            )
+            .await?;
+            txn.commit().await?;
+            return Ok(true);
+        }
+
+        match self.donate(&txn, &row).await {
+            Ok((donation_row, mut saga)) => {
+                // The donation is committed together with the advanced
+                // schedule, so a run which stops in between is repeated as a
+                // whole, and the repeated run finds the donation of the charge
+                // stripe replays instead of refunding it.
+                let result = async {
+                    txn.update_recurring_donation_schedule(
+                        row.recurring_donation_id,
+                        next_run_time(row.start_time, &row.frequency, now),
+                        None,
+                        0,
+                        Some(donation_row.donation_id),
+                        now,
+                    )
+                    .await?;
+                    txn.commit().await?;
+                    Ok(donation_row.clone())
+                }
+                .await;
+                self.donations
+                    .check_recorded(&mut saga, &donation_row.stripe_charge_id, result)
+                    .await?;
+                return Ok(true);
+            }
+            Err(status) => {
+                let failure_count = row.failure_count + 1;
+                warn!(
+                    "Failed to charge recurring donation {} (attempt {}): {:?}",
+                    row.recurring_donation_id, failure_count, status
+                );
+                if failure_count >= MAX_ATTEMPTS {
+                    // Skip the donation, the next one is attempted as scheduled.
+                    txn.update_recurring_donation_schedule(
+                        row.recurring_donation_id,
+                        next_run_time(row.start_time, &row.frequency, now),
+                        None,
+                        0,
+                        None,
+                        now,
+                    )
+                    .await?;
+                } else {
+                    txn.update_recurring_donation_schedule(
+                        row.recurring_donation_id,
+                        row.next_run_time,
+                        Some(retry_time(now, failure_count)),
+                        failure_count,
+                        None,
+                        now,
+                    )
+                    .await?;
+                }
+            }
+        }
+        txn.commit().await?;
+        Ok(true)
+    }
+
+    /// Donates the recurring donation, adding the donation to the transaction.
+    async fn donate(
+        &self,
+        txn: &TStore,
+        row: &RecurringDonationRow,
+    ) -> Result<(DonationRow, Saga), Status> {
+        let store = self.database.on_demand();
+        let user = store
+            .find_user_by_id(row.user_id)
+            .await?
+            .ok_or(entity_not_found("user"))?;
+        let account_id = row.account_id.ok_or(entity_not_found("account"))?;
+        let account_row = store
+            .find_account_by_id_for_user(account_id, row.user_id)
+            .await?
+            .ok_or(entity_not_found("account"))?;
+        let amount = Money::from_code(&row.currency_code, row.amount_units, row.amount_nanos)?;
+
+        // A run which didn't record its outcome, e.g. because the server
+        // stopped, is repeated with the same idempotency key, so a charge it
+        // made isn't repeated. Recorded failures were declined or refunded, and
+        // stripe would replay them for the same key, so retries after them use
+        // a new key.
+        let idempotency_key = Some(IdempotencyKey::new(format!(
+            "recurring-donation-{0}-{1}-{2}",
+            row.recurring_donation_id,
+            row.next_run_time.timestamp(),
+            row.failure_count
+        )));
+        match (row.nonprofit_id, row.cause_id) {
+            (Some(nonprofit_id), None) => {
+                self.donations
+                    .add_nonprofit_donation(
+                        txn,
+                        &user,
+                        nonprofit_id,
+                        amount,
+                        Some(account_row),
+                        false,
+                        &idempotency_key,
+                    )
+                    .await
+            }
+            (None, Some(cause_id)) => {
+                self.donations
+                    .add_cause_donation(
+                        txn,
+                        &user,
+                        cause_id,
+                        amount,
+                        Some(account_row),
+                        false,
+                        &idempotency_key,
+                    )
+                    .await
+            }
+            _ => Err(internal!(
+                "recurring donation must have exactly one of nonprofit or cause"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::FeesConfig,
+        testing::{account_row, affiliate_row, donation_row, nonprofit_row, user_row, FakeStripe},
+    };
+    use affect_storage::models::donation::{DonationStatus, NewDonationRow};
+    use affect_storage_mocks::*;
+    use chrono::TimeZone;
+    use hyper::Method;
+    use mockall::Sequence;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[test]
+    fn add_months_clamps_day() {
+        let start = Utc.ymd(2022, 1, 31).and_hms(9, 30, 0);
+        assert_eq!(add_months(start, 1), Utc.ymd(2022, 2, 28).and_hms(9, 30, 0));
+        assert_eq!(add_months(start, 2), Utc.ymd(2022, 3, 31).and_hms(9, 30, 0));
+        assert_eq!(
+            add_months(start, 13),
+            Utc.ymd(2023, 2, 28).and_hms(9, 30, 0)
+        );
+        assert_eq!(
+            add_months(Utc.ymd(2023, 12, 31).and_hms(0, 0, 0), 2),
+            Utc.ymd(2024, 2, 29).and_hms(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn next_run_time_weekly() {
+        let start = Utc.ymd(2022, 5, 2).and_hms(12, 0, 0);
+        let weekly = RecurringDonationFrequency::Weekly;
+        assert_eq!(
+            next_run_time(start, &weekly, Utc.ymd(2022, 4, 1).and_hms(0, 0, 0)),
+            start
+        );
+        assert_eq!(
+            next_run_time(start, &weekly, start),
+            Utc.ymd(2022, 5, 9).and_hms(12, 0, 0)
+        );
+        assert_eq!(
+            next_run_time(start, &weekly, Utc.ymd(2022, 5, 20).and_hms(0, 0, 0)),
+            Utc.ymd(2022, 5, 23).and_hms(12, 0, 0)
+        );
+    }
+
+    #[test]
+    fn next_run_time_monthly_does_not_drift() {
+        let start = Utc.ymd(2022, 1, 31).and_hms(12, 0, 0);
+        let monthly = RecurringDonationFrequency::Monthly;
+        let february = next_run_time(start, &monthly, start);
+        assert_eq!(february, Utc.ymd(2022, 2, 28).and_hms(12, 0, 0));
+        assert_eq!(
+            next_run_time(start, &monthly, february),
+            Utc.ymd(2022, 3, 31).and_hms(12, 0, 0)
+        );
+        assert_eq!(
+            next_run_time(start, &monthly, Utc.ymd(2022, 3, 1).and_hms(0, 0, 0)),
+            Utc.ymd(2022, 3, 31).and_hms(12, 0, 0)
+        );
+    }
+
+    #[test]
+    fn retry_time_backs_off() {
+        let now = Utc.ymd(2022, 5, 2).and_hms(12, 0, 0);
+        assert_eq!(retry_time(now, 1), now + Duration::hours(1));
+        assert_eq!(retry_time(now, 2), now + Duration::hours(2));
+        assert_eq!(retry_time(now, 4), now + Duration::hours(8));
+    }
+
+    fn recurring_donation_row() -> RecurringDonationRow {
+        let start_time = Utc::now() - Duration::days(1);
+        RecurringDonationRow {
+            recurring_donation_id: Uuid::new_v4(),
+            create_time: start_time,
+            update_time: start_time,
+            user_id: Uuid::new_v4(),
+            nonprofit_id: Some(Uuid::new_v4()),
+            cause_id: None,
+            account_id: Some(Uuid::new_v4()),
+            currency_code: "USD".to_string(),
+            amount_units: 10,
+            amount_nanos: 0,
+            frequency: RecurringDonationFrequency::Monthly,
+            state: RecurringDonationState::Active,
+            start_time,
+            next_run_time: start_time,
+            retry_time: None,
+            failure_count: 0,
+            last_donation_id: None,
+        }
+    }
+
+    fn recorded_donation_row(row: &RecurringDonationRow) -> DonationRow {
+        donation_row(NewDonationRow {
+            create_time: row.next_run_time,
+            update_time: row.next_run_time,
+            nonprofit_id: row.nonprofit_id,
+            cause_id: None,
+            user_id: row.user_id,
+            affiliate_id: None,
+            currency_code: "USD".to_string(),
+            amount_units: 10,
+            amount_nanos: 0,
+            stripe_charge_id: "ch_123".to_string(),
+            stripe_transfer_group: None,
+            status: DonationStatus::Pending,
+            account_id: row.account_id,
+            cover_fees: false,
+            charge_amount_units: 10,
+            charge_amount_nanos: 0,
+            processing_fee_units: 0,
+            processing_fee_nanos: 80_000_000,
+            platform_fee_units: 0,
+            platform_fee_nanos: 0,
+        })
+    }
+
+    /// Stripe where charges succeed.
+    fn stripe() -> FakeStripe {
+        FakeStripe::start(vec![
+            (
+                Method::POST,
+                "/v1/charges",
+                json!({
+                    "id": "ch_123",
+                    "object": "charge",
+                    "amount": 1000,
+                    "amount_captured": 1000,
+                    "amount_refunded": 0,
+                    "billing_details": {},
+                    "captured": true,
+                    "created": 1,
+                    "currency": "usd",
+                    "disputed": false,
+                    "livemode": false,
+                    "metadata": {},
+                    "paid": true,
+                    "refunded": false,
+                    "refunds": {"object": "list", "data": [], "has_more": false, "url": "/v1/refunds"},
+                    "status": "pending",
+                }),
+            ),
+            (
+                Method::POST,
+                "/v1/refunds",
+                json!({
+                    "id": "re_123",
+                    "object": "refund",
+                    "amount": 1000,
+                    "created": 1,
+                    "currency": "usd",
+                    "metadata": {},
+                }),
+            ),
+        ])
+    }
+
+    /// Database where the recurring donation is due and its donation of the
+    /// charge is recorded if some. Without a recorded donation, the donation
+    /// is added in the transaction which claims the recurring donation, and
+    /// the transaction commits with the result.
+    fn database(
+        row: RecurringDonationRow,
+        recorded: Option<DonationRow>,
+        commit: Result<(), affect_storage::Error>,
+    ) -> MockDatabaseClient {
+        let recurring_donation_id = row.recurring_donation_id;
+        let expected_donation_id = recorded.as_ref().map(|recorded| recorded.donation_id);
+        let mut database = MockDatabaseClient::new();
+        database.expect_on_demand().returning(move || {
+            let mut store = MockStore::new();
+            store
+                .expect_find_user_by_id()
+                .returning(|user_id| Ok(Some(user_row(user_id))));
+            store
+                .expect_find_account_by_id_for_user()
+                .returning(|account_id, _| Ok(Some(account_row(account_id))));
+            store
+                .expect_find_nonprofit_by_id()
+                .returning(|nonprofit_id| {
+                    Ok(Some(nonprofit_row(
+                        nonprofit_id,
+                        Some(affiliate_row("acct_123")),
+                    )))
+                });
+            let recorded = recorded.clone();
+            store
+                .expect_find_donation_by_stripe_charge_id()
+                .returning(move |_| Ok(recorded.clone()));
+            store
+        });
+        database.expect_begin().times(1).return_once(move || {
+            let mut txn = MockStore::new();
+            let mut seq = Sequence::new();
+            txn.expect_claim_due_recurring_donation()
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(move |_| Ok(Some(row)));
+            match expected_donation_id {
+                Some(_) => {
+                    txn.expect_add_donation().never();
+                }
+                None => {
+                    txn.expect_add_donation()
+                        .times(1)
+                        .in_sequence(&mut seq)
+                        .return_once(|new_row| Ok(donation_row(new_row)));
+                }
+            }
+            txn.expect_update_recurring_donation_schedule()
+                .withf(
+                    move |id, _, retry_time, failure_count, last_donation_id, _| {
+                        *id == recurring_donation_id
+                            && retry_time.is_none()
+                            && *failure_count == 0
+                            && match expected_donation_id {
+                                Some(donation_id) => *last_donation_id == Some(donation_id),
+                                None => last_donation_id.is_some(),
+                            }
+                    },
+                )
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(|_, _, _, _, _, _| Ok(()));
+            txn.expect_commit()
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(|| commit);
+            Ok(txn)
+        });
+        database
+    }
+
+    fn scheduler(
+        database: MockDatabaseClient,
+        stripe: &FakeStripe,
+    ) -> RecurringDonationScheduler<MockDatabaseClient, MockStore, MockStore> {
+        RecurringDonationScheduler::new(
+            Arc::new(database),
+            Arc::new(stripe.client()),
+            FeeCalculator::new(FeesConfig::default()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn run_next_advances_schedule_with_recorded_donation() -> Result<(), anyhow::Error> {
+        // An earlier run recorded the donation but stopped before advancing the
+        // schedule. The repeated run gets the charge replayed for the same key
+        // and advances the schedule with the recorded donation, without adding
+        // or refunding it.
+        let row = recurring_donation_row();
+        let recorded = recorded_donation_row(&row);
+        let stripe = stripe();
+
+        assert!(
+            scheduler(database(row.clone(), Some(recorded), Ok(())), &stripe)
+                .run_next()
+                .await?
+        );
+
+        let charge_request = stripe
+            .requests()
+            .into_iter()
+            .find(|request| request.path == "/v1/charges")
+            .unwrap();
+        assert_eq!(
+            charge_request.idempotency_key,
+            Some(format!(
+                "recurring-donation-{0}-{1}-0-charge",
+                row.recurring_donation_id,
+                row.next_run_time.timestamp()
+            ))
+        );
+        assert!(!stripe
+            .calls()
+            .contains(&(Method::POST, "/v1/refunds".to_string())));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_next_adds_donation_with_schedule() -> Result<(), anyhow::Error> {
+        let stripe = stripe();
+
+        assert!(
+            scheduler(database(recurring_donation_row(), None, Ok(())), &stripe)
+                .run_next()
+                .await?
+        );
+
+        assert!(!stripe
+            .calls()
+            .contains(&(Method::POST, "/v1/refunds".to_string())));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_next_refunds_charge_if_run_is_not_committed() {
+        // Neither the donation nor the schedule were committed, so the charge
+        // is undone and the donation is run again.
+        let stripe = stripe();
+
+        let result = scheduler(
+            database(
+                recurring_donation_row(),
+                None,
+                Err(affect_storage::Error::Other(anyhow::anyhow!(
+                    "database unavailable"
+                ))),
+            ),
+            &stripe,
+        )
+        .run_next()
+        .await;
+
+        assert!(matches!(result, Err(Error::Donation(_))));
+        assert!(stripe
+            .calls()
+            .contains(&(Method::POST, "/v1/refunds".to_string())));
+    }
+}
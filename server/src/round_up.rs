@@ -0,0 +1,728 @@
+use crate::{
+    fees::FeeCalculator,
+    money::{self, Money},
+    round_up::transactions::{PlaidTransactionsClient, Transaction},
+    services::donation::DonationServiceImpl,
+    tonic::idempotency::IdempotencyKey,
+};
+use affect_status::{well_known::entity_not_found, Status};
+use affect_storage::{
+    database::{
+        client::DatabaseClient,
+        store::{OnDemandStore, TransactionalStore},
+    },
+    models::{
+        item::{ItemPageToken, ItemRow},
+        round_up::{NewRoundUpRow, RoundUpRow, RoundUpSettingsRow},
+    },
+    page_token::PageTokenable,
+    stores::{
+        account::AccountStore, cause::CauseStore, donation::DonationStore, item::ItemStore,
+        nonprofit::NonprofitStore, round_up::RoundUpStore, user::UserStore,
+    },
+};
+use chrono::{DateTime, Utc};
+use iso_currency::Currency;
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+use std::{sync::Arc, time::Duration};
+use uuid::Uuid;
+
+pub mod transactions;
+
+/// Number of items listed per page when syncing the items of a user.
+const ITEM_PAGE_SIZE: i64 = 100;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("storage error: {0:?}")]
+    Storage(#[from] affect_storage::Error),
+
+    #[error("plaid request error: {0:?}")]
+    Plaid(#[from] reqwest::Error),
+
+    #[error("donation error: {0:?}")]
+    Donation(#[from] Status),
+
+    #[error("money error: {0:?}")]
+    Money(#[from] money::Error),
+}
+
+/// Spare change needed to round a debit of the provided subunits up to the
+/// next whole unit. Credits and whole amounts have no spare change.
+pub fn round_up_subunits(amount_subunits: i64) -> i64 {
+    if amount_subunits <= 0 {
+        return 0;
+    }
+    (100 - amount_subunits % 100) % 100
+}
+
+/// Round-up of a transaction, none if the transaction is not rounded up.
+/// Only posted USD debits dated after the settings were last updated are
+/// rounded up, so enabling round-ups does not charge the item's history.
+pub fn new_round_up_row(
+    settings: &RoundUpSettingsRow,
+    transaction: &Transaction,
+    now: DateTime<Utc>,
+) -> Option<NewRoundUpRow> {
+    if transaction.pending
+        || transaction.iso_currency_code.as_deref() != Some("USD")
+        || transaction.date < settings.update_time.naive_utc().date()
+    {
+        return None;
+    }
+    let subunits = round_up_subunits((transaction.amount * 100.0).round() as i64);
+    if subunits == 0 {
+        return None;
+    }
+    let amount = Money::from_subunits(Currency::USD, subunits);
+    Some(NewRoundUpRow {
+        create_time: now,
+        update_time: now,
+        user_id: settings.user_id,
+        plaid_transaction_id: transaction.transaction_id.clone(),
+        plaid_account_id: transaction.account_id.clone(),
+        currency_code: Currency::USD.code().to_string(),
+        amount_units: amount.units(),
+        amount_nanos: amount.nanos(),
+    })
+}
+
+/// Sum of the round-ups. Round-ups are only made in USD, so a round-up in any
+/// other currency is an error rather than being mixed in.
+pub fn pending_amount(round_up_rows: &[RoundUpRow]) -> Result<Money, money::Error> {
+    round_up_rows
+        .iter()
+        .try_fold(Money::zero(Currency::USD), |sum, round_up_row| {
+            sum.checked_add(&Money::from_code(
+                &round_up_row.currency_code,
+                round_up_row.amount_units,
+                round_up_row.amount_nanos,
+            )?)
+        })
+}
+
+/// Idempotency key of the charge of the round-ups, derived from the exact
+/// round-ups and their amount. A retry of a failed run which charges the same
+/// round-ups reuses the key, while a run whose round-ups changed, e.g. because
+/// new ones were added, uses a new key rather than one stripe would reject for
+/// its different amount.
+pub fn idempotency_key(round_up_rows: &[RoundUpRow], amount: &Money) -> String {
+    let mut round_up_ids: Vec<Uuid> = round_up_rows
+        .iter()
+        .map(|round_up_row| round_up_row.round_up_id)
+        .collect();
+    round_up_ids.sort();
+    let mut hasher = Sha256::new();
+    for round_up_id in round_up_ids {
+        hasher.update(round_up_id.as_bytes());
+    }
+    format!(
+        "round-up-{0}-{1}",
+        hex::encode(hasher.finalize()),
+        amount.subunits_rounded()
+    )
+}
+
+/// Pulls the transactions of users with round-ups enabled, accumulates their
+/// spare change and donates it to the user's cause once it reaches the
+/// threshold.
+pub struct RoundUpProcessor<Db, Store, TStore> {
+    database: Arc<Db>,
+    transactions: PlaidTransactionsClient,
+    donations: DonationServiceImpl<Db, Store, TStore>,
+    threshold_subunits: i64,
+}
+
+impl<Db, Store, TStore> RoundUpProcessor<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore> + 'static,
+    Store: AccountStore
+        + CauseStore
+        + DonationStore
+        + ItemStore
+        + NonprofitStore
+        + RoundUpStore
+        + UserStore
+        + OnDemandStore
+        + 'static,
+    TStore: DonationStore + ItemStore + RoundUpStore + TransactionalStore + 'static,
+{
+    pub fn new(
+        database: Arc<Db>,
+        stripe: Arc<stripe::Client>,
+        fees: FeeCalculator,
+        transactions: PlaidTransactionsClient,
+        threshold_subunits: i64,
+    ) -> Self {
+        Self {
+            database: database.clone(),
+            transactions,
+            donations: DonationServiceImpl::new(database, stripe, fees),
+            threshold_subunits,
+        }
+    }
+
+    /// Processes round-ups every interval, forever.
+    pub async fn run(&self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.process_all().await {
+                warn!("Failed to process round-ups: {:?}", e);
+            }
+        }
+    }
+
+    /// Processes the round-ups of every user with round-ups enabled. Failures
+    /// of a single user are logged and retried on the next run.
+    pub async fn process_all(&self) -> Result<(), Error> {
+        let settings_rows = self
+            .database
+            .on_demand()
+            .list_enabled_round_up_settings()
+            .await?;
+        for settings in settings_rows {
+            if let Err(e) = self.process_user(&settings).await {
+                warn!(
+                    "Failed to process round-ups of user {}: {:?}",
+                    settings.user_id, e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn process_user(&self, settings: &RoundUpSettingsRow) -> Result<(), Error> {
+        for item_row in self.list_items(settings.user_id).await? {
+            // Items needing to be relinked fail to sync until they are repaired.
+            if item_row.status.needs_relink() {
+                continue;
+            }
+            self.sync_item(settings, &item_row).await?;
+        }
+        self.charge_if_due(settings.user_id).await
+    }
+
+    async fn list_items(&self, user_id: Uuid) -> Result<Vec<ItemRow>, Error> {
+        let store = self.database.on_demand();
+        let mut item_rows = Vec::new();
+        let mut page_token: Option<ItemPageToken> = None;
+        loop {
+            let mut rows = store
+                .list_items_for_user(ITEM_PAGE_SIZE + 1, page_token, user_id)
+                .await?;
+            page_token = match rows.len() > ITEM_PAGE_SIZE as usize {
+                true => rows.pop().map(|row| row.page_token()),
+                false => None,
+            };
+            item_rows.extend(rows);
+            if page_token.is_none() {
+                return Ok(item_rows);
+            }
+        }
+    }
+
+    /// Adds the round-ups of the item's transactions since its cursor. Every
+    /// page of transactions is stored together with the cursor after it, so
+    /// an interrupted sync resumes where it stopped.
+    async fn sync_item(
+        &self,
+        settings: &RoundUpSettingsRow,
+        item_row: &ItemRow,
+    ) -> Result<(), Error> {
+        let mut cursor = item_row.transactions_cursor.clone();
+        loop {
+            let response = self
+                .transactions
+                .sync(&item_row.plaid_access_token, cursor.as_deref())
+                .await?;
+
+            let now = Utc::now();
+            let txn = self.database.begin().await?;
+            for transaction in &response.added {
+                if let Some(new_row) = new_round_up_row(settings, transaction, now) {
+                    txn.add_round_up(new_row).await?;
+                }
+            }
+            txn.update_item_transactions_cursor(
+                item_row.item_id,
+                response.next_cursor.clone(),
+                now,
+            )
+            .await?;
+            txn.commit().await?;
+
+            if !response.has_more {
+                return Ok(());
+            }
+            cursor = Some(response.next_cursor);
+        }
+    }
+
+    /// Donates the user's pending round-ups once they reach the threshold. The
+    /// user's settings stay locked while charging, so the same round-ups are
+    /// never donated twice.
+    async fn charge_if_due(&self, user_id: Uuid) -> Result<(), Error> {
+        let txn = self.database.begin().await?;
+        let settings = match txn
+            .lock_enabled_round_up_settings_by_user_id(user_id)
+            .await?
+        {
+            Some(settings) => settings,
+            None => {
+                txn.rollback().await?;
+                return Ok(());
+            }
+        };
+        let round_up_rows = txn.list_pending_round_ups_for_user(user_id).await?;
+        let amount = pending_amount(&round_up_rows)?;
+        if amount.subunits_rounded() < self.threshold_subunits {
+            txn.rollback().await?;
+            return Ok(());
+        }
+        let account_id = match settings.account_id {
+            Some(account_id) => account_id,
+            None => {
+                info!(
+                    "Not charging round-ups of user without account: {}",
+                    user_id
+                );
+                txn.rollback().await?;
+                return Ok(());
+            }
+        };
+
+        let store = self.database.on_demand();
+        let user = store
+            .find_user_by_id(user_id)
+            .await?
+            .ok_or(entity_not_found("user"))?;
+        let account_row = store
+            .find_account_by_id(account_id)
+            .await?
+            .ok_or(entity_not_found("account"))?;
+
+        let idempotency_key = Some(IdempotencyKey::new(idempotency_key(
+            &round_up_rows,
+            &amount,
+        )));
+        let (donation_row, mut saga) = self
+            .donations
+            .add_cause_donation(
+                &txn,
+                &user,
+                settings.cause_id,
+                amount,
+                Some(account_row),
+                false,
+                &idempotency_key,
+            )
+            .await?;
+
+        // The round-ups are marked in the donation's transaction, so they are
+        // either donated or still pending, never charged twice.
+        let result = async {
+            txn.mark_round_ups_donated(
+                round_up_rows
+                    .iter()
+                    .map(|round_up_row| round_up_row.round_up_id)
+                    .collect(),
+                donation_row.donation_id,
+                Utc::now(),
+            )
+            .await?;
+            txn.commit().await?;
+            Ok(donation_row.clone())
+        }
+        .await;
+        let donation_row = self
+            .donations
+            .check_recorded(&mut saga, &donation_row.stripe_charge_id, result)
+            .await?;
+        info!(
+            "Donated {} round-ups of user {}: {}",
+            round_up_rows.len(),
+            user_id,
+            donation_row.donation_id
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::FeesConfig,
+        testing::{account_row, affiliate_row, donation_row, nonprofit_row, user_row, FakeStripe},
+    };
+    use affect_storage::models::{
+        cause::{CauseRecipientRow, CauseRecipientRowVec, CauseRow, CauseState, FullCauseRow},
+        donation::DonationAllocationRow,
+    };
+    use affect_storage_mocks::*;
+    use chrono::NaiveDate;
+    use hyper::Method;
+    use mockall::Sequence;
+    use serde_json::json;
+
+    fn settings() -> RoundUpSettingsRow {
+        RoundUpSettingsRow {
+            user_id: Uuid::new_v4(),
+            create_time: Utc::now(),
+            update_time: DateTime::parse_from_rfc3339("2022-05-01T12:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            cause_id: Uuid::new_v4(),
+            account_id: Some(Uuid::new_v4()),
+            enabled: true,
+        }
+    }
+
+    fn transaction(amount: f64) -> Transaction {
+        Transaction {
+            transaction_id: "transaction_id".to_string(),
+            account_id: "account_id".to_string(),
+            amount,
+            iso_currency_code: Some("USD".to_string()),
+            date: NaiveDate::from_ymd(2022, 5, 1),
+            pending: false,
+        }
+    }
+
+    #[test]
+    fn round_up_subunits_of_debits() {
+        assert_eq!(round_up_subunits(433), 67);
+        assert_eq!(round_up_subunits(1), 99);
+        assert_eq!(round_up_subunits(99), 1);
+        assert_eq!(round_up_subunits(500), 0);
+        assert_eq!(round_up_subunits(0), 0);
+        assert_eq!(round_up_subunits(-433), 0);
+    }
+
+    #[test]
+    fn round_up_row_of_debit() {
+        let new_row = new_round_up_row(&settings(), &transaction(4.33), Utc::now()).unwrap();
+        assert_eq!(new_row.amount_units, 0);
+        assert_eq!(new_row.amount_nanos, 670_000_000);
+        assert_eq!(new_row.plaid_transaction_id, "transaction_id");
+    }
+
+    #[test]
+    fn round_up_row_skips_ineligible_transactions() {
+        let settings = settings();
+        let now = Utc::now();
+        assert!(new_round_up_row(&settings, &transaction(-4.33), now).is_none());
+        assert!(new_round_up_row(&settings, &transaction(5.0), now).is_none());
+
+        let mut pending = transaction(4.33);
+        pending.pending = true;
+        assert!(new_round_up_row(&settings, &pending, now).is_none());
+
+        let mut foreign = transaction(4.33);
+        foreign.iso_currency_code = Some("CAD".to_string());
+        assert!(new_round_up_row(&settings, &foreign, now).is_none());
+
+        let mut historical = transaction(4.33);
+        historical.date = NaiveDate::from_ymd(2022, 4, 30);
+        assert!(new_round_up_row(&settings, &historical, now).is_none());
+    }
+
+    fn round_up_rows(user_id: Uuid, subunits: &[i64]) -> Vec<RoundUpRow> {
+        let now = Utc::now();
+        subunits
+            .iter()
+            .map(|subunits| {
+                let amount = Money::from_subunits(Currency::USD, *subunits);
+                RoundUpRow {
+                    round_up_id: Uuid::new_v4(),
+                    create_time: now,
+                    update_time: now,
+                    user_id,
+                    plaid_transaction_id: "transaction_id".to_string(),
+                    plaid_account_id: "account_id".to_string(),
+                    currency_code: "USD".to_string(),
+                    amount_units: amount.units(),
+                    amount_nanos: amount.nanos(),
+                    donation_id: None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pending_amount_sums_round_ups() {
+        let round_up_rows = round_up_rows(Uuid::new_v4(), &[67, 1, 99]);
+        assert_eq!(
+            pending_amount(&round_up_rows),
+            Ok(Money::from_subunits(Currency::USD, 167))
+        );
+    }
+
+    #[test]
+    fn idempotency_key_depends_on_exact_round_ups() {
+        let rows = round_up_rows(Uuid::new_v4(), &[67, 1, 99]);
+        let amount = pending_amount(&rows).unwrap();
+        let key = idempotency_key(&rows, &amount);
+
+        let mut reordered = rows.clone();
+        reordered.reverse();
+        assert_eq!(idempotency_key(&reordered, &amount), key);
+
+        let mut added = rows.clone();
+        added.extend(round_up_rows(Uuid::new_v4(), &[50]));
+        assert_ne!(
+            idempotency_key(&added, &pending_amount(&added).unwrap()),
+            key
+        );
+    }
+
+    fn full_cause_row(user_id: Uuid, nonprofit_id: Uuid) -> FullCauseRow {
+        let cause_id = Uuid::new_v4();
+        FullCauseRow {
+            cause: CauseRow {
+                cause_id,
+                create_time: Utc::now(),
+                update_time: Utc::now(),
+                user_id,
+                name: "Climate".to_string(),
+                archive_time: None,
+                state: CauseState::Active,
+            },
+            cause_recipients: CauseRecipientRowVec::new(vec![CauseRecipientRow {
+                cause_id,
+                nonprofit_id,
+                create_time: Utc::now(),
+                update_time: Utc::now(),
+                weight_basis_points: 10000,
+            }]),
+        }
+    }
+
+    /// Stripe where charges and transfers succeed.
+    fn stripe() -> FakeStripe {
+        FakeStripe::start(vec![
+            (
+                Method::POST,
+                "/v1/charges",
+                json!({
+                    "id": "ch_123",
+                    "object": "charge",
+                    "amount": 600,
+                    "amount_captured": 600,
+                    "amount_refunded": 0,
+                    "billing_details": {},
+                    "captured": true,
+                    "created": 1,
+                    "currency": "usd",
+                    "disputed": false,
+                    "livemode": false,
+                    "metadata": {},
+                    "paid": true,
+                    "refunded": false,
+                    "refunds": {"object": "list", "data": [], "has_more": false, "url": "/v1/refunds"},
+                    "status": "pending",
+                }),
+            ),
+            (
+                Method::POST,
+                "/v1/transfers",
+                json!({
+                    "id": "tr_123",
+                    "object": "transfer",
+                    "amount": 580,
+                    "amount_reversed": 0,
+                    "created": 1,
+                    "currency": "usd",
+                    "livemode": false,
+                    "metadata": {},
+                    "reversals": {"object": "list", "data": [], "has_more": false, "url": "/v1/transfers/tr_123/reversals"},
+                    "reversed": false,
+                }),
+            ),
+            (
+                Method::POST,
+                "/v1/refunds",
+                json!({
+                    "id": "re_123",
+                    "object": "refund",
+                    "amount": 600,
+                    "created": 1,
+                    "currency": "usd",
+                    "metadata": {},
+                }),
+            ),
+            (
+                Method::POST,
+                "/v1/transfers/tr_123/reversals",
+                json!({
+                    "id": "trr_123",
+                    "object": "transfer_reversal",
+                    "amount": 580,
+                    "created": 1,
+                    "currency": "usd",
+                    "metadata": {},
+                    "transfer": "tr_123",
+                }),
+            ),
+        ])
+    }
+
+    /// Database where the user has round-ups due for a cause with one
+    /// affiliated recipient. The round-ups are marked donated with the result
+    /// in the transaction which adds the donation.
+    fn database(
+        settings: RoundUpSettingsRow,
+        round_up_rows: Vec<RoundUpRow>,
+        mark_round_ups_donated: Result<(), affect_storage::Error>,
+    ) -> MockDatabaseClient {
+        let user_id = settings.user_id;
+        let nonprofit_id = Uuid::new_v4();
+        let round_up_ids: Vec<Uuid> = round_up_rows
+            .iter()
+            .map(|round_up_row| round_up_row.round_up_id)
+            .collect();
+        let marked = mark_round_ups_donated.is_ok();
+
+        let mut database = MockDatabaseClient::new();
+        database.expect_on_demand().returning(move || {
+            let mut store = MockStore::new();
+            store
+                .expect_find_user_by_id()
+                .returning(|user_id| Ok(Some(user_row(user_id))));
+            store
+                .expect_find_account_by_id()
+                .returning(|account_id| Ok(Some(account_row(account_id))));
+            store
+                .expect_find_cause_by_id()
+                .returning(move |_| Ok(Some(full_cause_row(user_id, nonprofit_id))));
+            store
+                .expect_find_nonprofit_by_id()
+                .returning(|nonprofit_id| {
+                    Ok(Some(nonprofit_row(
+                        nonprofit_id,
+                        Some(affiliate_row("acct_123")),
+                    )))
+                });
+            store
+                .expect_find_donation_by_stripe_charge_id()
+                .returning(|_| Ok(None));
+            store
+        });
+        database.expect_begin().times(1).return_once(move || {
+            let mut txn = MockStore::new();
+            let mut seq = Sequence::new();
+            txn.expect_lock_enabled_round_up_settings_by_user_id()
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(move |_| Ok(Some(settings)));
+            txn.expect_list_pending_round_ups_for_user()
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(move |_| Ok(round_up_rows));
+            txn.expect_add_donation()
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(|new_row| Ok(donation_row(new_row)));
+            txn.expect_add_donation_allocation()
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(|donation_id, new_row, create_time| {
+                    Ok(DonationAllocationRow {
+                        donation_id,
+                        nonprofit_id: new_row.nonprofit_id,
+                        create_time,
+                        update_time: create_time,
+                        affiliate_id: new_row.affiliate_id,
+                        amount_units: new_row.amount_units,
+                        amount_nanos: new_row.amount_nanos,
+                        stripe_transfer_id: new_row.stripe_transfer_id,
+                    })
+                });
+            txn.expect_mark_round_ups_donated()
+                .withf(move |marked_ids, _, _| *marked_ids == round_up_ids)
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(move |_, _, _| mark_round_ups_donated);
+            if marked {
+                txn.expect_commit()
+                    .times(1)
+                    .in_sequence(&mut seq)
+                    .return_once(|| Ok(()));
+            } else {
+                txn.expect_commit().never();
+            }
+            Ok(txn)
+        });
+        database
+    }
+
+    fn processor(
+        database: MockDatabaseClient,
+        stripe: &FakeStripe,
+    ) -> RoundUpProcessor<MockDatabaseClient, MockStore, MockStore> {
+        RoundUpProcessor::new(
+            Arc::new(database),
+            Arc::new(stripe.client()),
+            FeeCalculator::new(FeesConfig::default()).unwrap(),
+            PlaidTransactionsClient::new("client_id".to_string(), "secret".to_string(), "sandbox"),
+            500,
+        )
+    }
+
+    #[tokio::test]
+    async fn charge_if_due_marks_round_ups_with_donation() -> Result<(), anyhow::Error> {
+        let settings = settings();
+        let user_id = settings.user_id;
+        let round_up_rows = round_up_rows(user_id, &[300, 300]);
+        let amount = pending_amount(&round_up_rows)?;
+        let key = idempotency_key(&round_up_rows, &amount);
+        let stripe = stripe();
+
+        processor(database(settings, round_up_rows, Ok(())), &stripe)
+            .charge_if_due(user_id)
+            .await?;
+
+        let charge_request = stripe
+            .requests()
+            .into_iter()
+            .find(|request| request.path == "/v1/charges")
+            .unwrap();
+        assert_eq!(
+            charge_request.idempotency_key,
+            Some(format!("{0}-charge", key))
+        );
+        assert!(!stripe
+            .calls()
+            .contains(&(Method::POST, "/v1/refunds".to_string())));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn charge_if_due_refunds_charge_if_round_ups_are_not_marked() {
+        let settings = settings();
+        let user_id = settings.user_id;
+        let stripe = stripe();
+
+        let result = processor(
+            database(
+                settings,
+                round_up_rows(user_id, &[300, 300]),
+                Err(affect_storage::Error::Other(anyhow::anyhow!(
+                    "database unavailable"
+                ))),
+            ),
+            &stripe,
+        )
+        .charge_if_due(user_id)
+        .await;
+
+        // Neither the donation nor the round-ups were committed, so the charge
+        // and transfer are undone.
+        assert!(result.is_err());
+        let calls = stripe.calls();
+        assert!(calls.contains(&(Method::POST, "/v1/refunds".to_string())));
+        assert!(calls.contains(&(Method::POST, "/v1/transfers/tr_123/reversals".to_string())));
+    }
+}
@@ -0,0 +1,81 @@
+use crate::round_up::Error;
+use chrono::NaiveDate;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of transactions plaid returns per sync request.
+const SYNC_COUNT: i32 = 500;
+
+/// Pulls transactions of plaid items through plaid's transactions sync.
+pub struct PlaidTransactionsClient {
+    client: Client,
+    url: String,
+    client_id: String,
+    secret: String,
+}
+
+#[derive(Serialize)]
+struct SyncTransactionsRequest<'a> {
+    client_id: &'a str,
+    secret: &'a str,
+    access_token: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor: Option<&'a str>,
+    count: i32,
+}
+
+/// Page of changes to an item's transactions since a cursor.
+#[derive(Debug, Deserialize)]
+pub struct SyncTransactionsResponse {
+    pub added: Vec<Transaction>,
+    /// Cursor to pass to the next request, i.e. the position after this page.
+    pub next_cursor: String,
+    /// Whether more changes are available immediately.
+    pub has_more: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Transaction {
+    pub transaction_id: String,
+    pub account_id: String,
+    /// Amount in whole units, positive when money moves out of the account.
+    pub amount: f64,
+    pub iso_currency_code: Option<String>,
+    pub date: NaiveDate,
+    pub pending: bool,
+}
+
+impl PlaidTransactionsClient {
+    pub fn new(client_id: String, secret: String, env: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: format!("https://{0}.plaid.com/transactions/sync", env),
+            client_id,
+            secret,
+        }
+    }
+
+    /// See https://plaid.com/docs/api/products/transactions/#transactionssync.
+    /// Without a cursor, the changes start from the item's oldest transaction.
+    pub async fn sync(
+        &self,
+        access_token: &str,
+        cursor: Option<&str>,
+    ) -> Result<SyncTransactionsResponse, Error> {
+        Ok(self
+            .client
+            .post(&self.url)
+            .json(&SyncTransactionsRequest {
+                client_id: &self.client_id,
+                secret: &self.secret,
+                access_token,
+                cursor,
+                count: SYNC_COUNT,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
@@ -0,0 +1,328 @@
+use crate::tonic::idempotency::IdempotencyKey;
+use async_trait::async_trait;
+use log::{info, warn};
+use std::collections::HashMap;
+
+/// External side effect which can be undone.
+#[async_trait]
+pub trait Compensation: Sync + Send {
+    /// Human readable description of the undone side effect, for logging.
+    fn describe(&self) -> String;
+
+    /// Undoes the side effect.
+    async fn compensate(&self) -> Result<(), anyhow::Error>;
+}
+
+/// Records the external side effects of a request (e.g. stripe objects which
+/// were created) so they can be undone if a later step, typically a database
+/// write, fails.
+#[derive(Default)]
+pub struct Saga {
+    compensations: Vec<Box<dyn Compensation>>,
+    idempotency_key: Option<IdempotencyKey>,
+}
+
+impl Saga {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Saga for a request whose stripe calls use the request's idempotency key.
+    /// The key is marked compensated on rollback, since stripe would replay the
+    /// undone objects for a retry with the same key.
+    pub fn for_request(idempotency_key: &Option<IdempotencyKey>) -> Self {
+        Self {
+            compensations: Vec::new(),
+            idempotency_key: idempotency_key.clone(),
+        }
+    }
+
+    /// Records a side effect which was performed.
+    pub fn record(&mut self, compensation: impl Compensation + 'static) {
+        self.compensations.push(Box::new(compensation));
+    }
+
+    /// Passes the result through, undoing every recorded side effect first if
+    /// it is an error.
+    pub async fn check<T, E>(&mut self, result: Result<T, E>) -> Result<T, E> {
+        if result.is_err() {
+            self.rollback().await;
+        }
+        result
+    }
+
+    /// Undoes the recorded side effects in reverse order. Failures are logged
+    /// and do not stop the remaining compensations.
+    pub async fn rollback(&mut self) {
+        if let (Some(idempotency_key), false) =
+            (&self.idempotency_key, self.compensations.is_empty())
+        {
+            idempotency_key.mark_compensated();
+        }
+        while let Some(compensation) = self.compensations.pop() {
+            match compensation.compensate().await {
+                Ok(()) => info!("Compensated: {}", compensation.describe()),
+                Err(e) => warn!(
+                    "Failed to compensate, {} must be undone manually: {:?}",
+                    compensation.describe(),
+                    e
+                ),
+            }
+        }
+    }
+}
+
+/// Deletes a stripe customer.
+pub struct DeleteStripeCustomer {
+    pub client: stripe::Client,
+    pub customer_id: stripe::CustomerId,
+}
+
+#[async_trait]
+impl Compensation for DeleteStripeCustomer {
+    fn describe(&self) -> String {
+        format!("delete stripe customer {0}", self.customer_id)
+    }
+
+    async fn compensate(&self) -> Result<(), anyhow::Error> {
+        stripe::Customer::delete(&self.client, &self.customer_id).await?;
+        Ok(())
+    }
+}
+
+/// Deletes a stripe connected account.
+pub struct DeleteStripeAccount {
+    pub client: stripe::Client,
+    pub account_id: stripe::AccountId,
+}
+
+#[async_trait]
+impl Compensation for DeleteStripeAccount {
+    fn describe(&self) -> String {
+        format!("delete stripe account {0}", self.account_id)
+    }
+
+    async fn compensate(&self) -> Result<(), anyhow::Error> {
+        stripe::Account::delete(&self.client, &self.account_id).await?;
+        Ok(())
+    }
+}
+
+/// Detaches a payment source from a stripe customer.
+pub struct DetachStripeSource {
+    pub client: stripe::Client,
+    pub customer_id: stripe::CustomerId,
+    pub source_id: stripe::PaymentSourceId,
+}
+
+#[async_trait]
+impl Compensation for DetachStripeSource {
+    fn describe(&self) -> String {
+        format!(
+            "detach source {0} from stripe customer {1}",
+            self.source_id, self.customer_id
+        )
+    }
+
+    async fn compensate(&self) -> Result<(), anyhow::Error> {
+        stripe::Customer::detach_source(&self.client, &self.customer_id, &self.source_id).await?;
+        Ok(())
+    }
+}
+
+/// Refunds a stripe charge in full.
+pub struct RefundStripeCharge {
+    pub client: stripe::Client,
+    pub charge_id: stripe::ChargeId,
+    /// Whether the application fee of the charge is refunded too.
+    pub refund_application_fee: bool,
+    /// Whether the transfer of a destination charge is reversed too.
+    pub reverse_transfer: bool,
+}
+
+#[async_trait]
+impl Compensation for RefundStripeCharge {
+    fn describe(&self) -> String {
+        format!("refund stripe charge {0}", self.charge_id)
+    }
+
+    async fn compensate(&self) -> Result<(), anyhow::Error> {
+        let mut create_refund = stripe::CreateRefund::new();
+        create_refund.charge = Some(self.charge_id.clone());
+        create_refund.refund_application_fee = Some(self.refund_application_fee);
+        create_refund.reverse_transfer = Some(self.reverse_transfer);
+        stripe::Refund::create(&self.client, create_refund).await?;
+        Ok(())
+    }
+}
+
+/// Reverses a stripe transfer to a connected account in full.
+pub struct ReverseStripeTransfer {
+    pub client: stripe::Client,
+    pub transfer_id: stripe::TransferId,
+}
+
+#[async_trait]
+impl Compensation for ReverseStripeTransfer {
+    fn describe(&self) -> String {
+        format!("reverse stripe transfer {0}", self.transfer_id)
+    }
+
+    async fn compensate(&self) -> Result<(), anyhow::Error> {
+        // Transfer reversals have no typed create request, post the form directly.
+        let _: stripe::TransferReversal = self
+            .client
+            .post_form(
+                &format!("/transfers/{0}/reversals", self.transfer_id),
+                HashMap::<String, String>::new(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use affect_status::Status;
+    use affect_storage::{models::user::NewUserRow, stores::user::UserStore, Error};
+    use affect_storage_mocks::*;
+    use chrono::Utc;
+    use std::sync::{Arc, Mutex};
+
+    /// Compensation which records that it ran.
+    struct RecordCompensation {
+        name: &'static str,
+        compensated: Arc<Mutex<Vec<&'static str>>>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Compensation for RecordCompensation {
+        fn describe(&self) -> String {
+            self.name.to_string()
+        }
+
+        async fn compensate(&self) -> Result<(), anyhow::Error> {
+            self.compensated.lock().unwrap().push(self.name);
+            if self.fail {
+                return Err(anyhow::anyhow!("failed to compensate {0}", self.name));
+            }
+            Ok(())
+        }
+    }
+
+    fn saga(compensated: &Arc<Mutex<Vec<&'static str>>>, failing: &[&'static str]) -> Saga {
+        let mut saga = Saga::new();
+        for name in ["customer", "account", "source"] {
+            saga.record(RecordCompensation {
+                name,
+                compensated: compensated.clone(),
+                fail: failing.contains(&name),
+            });
+        }
+        saga
+    }
+
+    fn new_user_row() -> NewUserRow {
+        NewUserRow {
+            create_time: Utc::now(),
+            update_time: Utc::now(),
+            firebase_uid: "firebase_uid".to_string(),
+            firebase_email: "user@affect.app".to_string(),
+            stripe_customer_id: "cus_123".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn store_failure_undoes_side_effects_in_reverse_order() {
+        let compensated = Arc::new(Mutex::new(Vec::new()));
+        let mut store = MockStore::new();
+        store
+            .expect_add_user()
+            .times(1)
+            .return_once(|_| Err(Error::Other(anyhow::anyhow!("database unavailable"))));
+
+        let mut saga = saga(&compensated, &[]);
+        let result = saga
+            .check(store.add_user(new_user_row()).await.map_err(Status::from))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            *compensated.lock().unwrap(),
+            vec!["source", "account", "customer"]
+        );
+    }
+
+    #[tokio::test]
+    async fn store_success_keeps_side_effects() {
+        let compensated = Arc::new(Mutex::new(Vec::new()));
+        let mut store = MockStore::new();
+        store.expect_add_user().times(1).return_once(|new_row| {
+            Ok(affect_storage::models::user::UserRow {
+                user_id: uuid::Uuid::new_v4(),
+                create_time: new_row.create_time,
+                update_time: new_row.update_time,
+                firebase_uid: new_row.firebase_uid,
+                firebase_email: new_row.firebase_email,
+                stripe_customer_id: new_row.stripe_customer_id,
+            })
+        });
+
+        let mut saga = saga(&compensated, &[]);
+        let result = saga
+            .check(store.add_user(new_user_row()).await.map_err(Status::from))
+            .await;
+
+        assert!(result.is_ok());
+        assert!(compensated.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_compensation_does_not_stop_rollback() {
+        let compensated = Arc::new(Mutex::new(Vec::new()));
+
+        let mut saga = saga(&compensated, &["account"]);
+        let result: Result<(), Status> = saga.check(Err(Status::internal("failed"))).await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            *compensated.lock().unwrap(),
+            vec!["source", "account", "customer"]
+        );
+    }
+
+    #[tokio::test]
+    async fn rollback_runs_compensations_once() {
+        let compensated = Arc::new(Mutex::new(Vec::new()));
+
+        let mut saga = saga(&compensated, &[]);
+        let _: Result<(), Status> = saga.check(Err(Status::internal("failed"))).await;
+        let _: Result<(), Status> = saga.check(Err(Status::internal("failed"))).await;
+
+        assert_eq!(compensated.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn rollback_marks_idempotency_key_compensated() {
+        let compensated = Arc::new(Mutex::new(Vec::new()));
+        let idempotency_key = Some(IdempotencyKey::new("key".to_string()));
+
+        let mut saga = Saga::for_request(&idempotency_key);
+        let _: Result<(), Status> = saga.check(Ok(())).await;
+        assert!(!idempotency_key.as_ref().unwrap().is_compensated());
+
+        // Nothing to undo, the key may still be retried.
+        let _: Result<(), Status> = saga.check(Err(Status::internal("failed"))).await;
+        assert!(!idempotency_key.as_ref().unwrap().is_compensated());
+
+        saga.record(RecordCompensation {
+            name: "charge",
+            compensated: compensated.clone(),
+            fail: false,
+        });
+        let _: Result<(), Status> = saga.check(Err(Status::internal("failed"))).await;
+        assert!(idempotency_key.as_ref().unwrap().is_compensated());
+    }
+}
@@ -40,6 +40,7 @@ pub async fn insert_nonprofits(
             mission: change_nonprofit.mission,
             category: change_nonprofit.category,
             affiliate_id: None,
+            email: change_nonprofit.email,
         };
 
         debug!("Seeding nonprofit: {:?}", new_row);
@@ -3,4 +3,6 @@ pub mod cause;
 pub mod donation;
 pub mod item;
 pub mod nonprofit;
+pub mod recurring_donation;
+pub mod round_up;
 pub mod user;
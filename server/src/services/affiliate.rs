@@ -1,34 +1,61 @@
-use crate::protobuf::into::{IntoProto, ProtoInto};
+use crate::{
+    affiliate_verification::VerificationChecks,
+    interceptors::authn::{Peer, VerifiedEmail},
+    policy::Policy,
+    protobuf::into::{IntoProto, ProtoInto},
+    saga::{DeleteStripeAccount, Saga},
+    tonic::idempotency::{idempotent_stripe_client, IdempotencyKey},
+    web::WebLinks,
+};
 use affect_api::affect::{
-    affiliate_service_server::AffiliateService, Affiliate, AffiliateLink, AffiliateLinkType,
-    BusinessType, CreateAffiliateRequest, GenerateAffiliateLinkRequest, RefreshAffiliateRequest,
+    affiliate_service_server::AffiliateService, AcceptAffiliateManagerInvitationRequest, Affiliate,
+    AffiliateLink, AffiliateLinkType, AffiliateManager, AffiliateManagerInvitation,
+    AffiliateManagerRole as ProtoAffiliateManagerRole, ApproveAffiliateRequest, BusinessType,
+    CreateAffiliateRequest, GenerateAffiliateLinkRequest, InviteAffiliateManagerRequest,
+    ListAffiliateManagersRequest, ListAffiliateManagersResponse, ListAffiliatesForUserRequest,
+    ListAffiliatesForUserResponse, ListAffiliatesRequest, ListAffiliatesResponse,
+    RefreshAffiliateRequest, RejectAffiliateRequest, RemoveAffiliateManagerRequest,
+};
+use affect_status::{
+    failed_precondition, internal, invalid_argument, not_found, well_known::UnwrapField, Status,
 };
-use affect_status::{internal, invalid_argument, not_found, well_known::UnwrapField, Status};
 use affect_storage::{
     database::client::DatabaseClient,
     database::store::{OnDemandStore, TransactionalStore},
     models::affiliate::*,
+    page_token::{PageToken, PageTokenable},
     stores::affiliate::AffiliateStore,
 };
 use async_trait::async_trait;
-use chrono::{TimeZone, Utc};
+use chrono::{Duration, TimeZone, Utc};
+use sha2::{Digest, Sha256};
+use std::cmp::{max, min};
 use std::marker::PhantomData;
 use std::sync::Arc;
 use tonic::{Request, Response};
+use uuid::Uuid;
 
 use affect_storage::models::affiliate::BusinessType as StoreBusinessType;
 
+#[cfg(test)]
+mod tests;
+
+/// Days after which an affiliate manager invitation can't be accepted.
+const INVITATION_TTL_DAYS: i64 = 7;
+
 pub struct AffiliateServiceImpl<Db, Store, TStore> {
     database: Arc<Db>,
     stripe: Arc<stripe::Client>,
+    web_links: WebLinks,
     _marker: PhantomData<(Store, TStore)>,
 }
 
 impl<Db, Store, TStore> AffiliateServiceImpl<Db, Store, TStore> {
-    pub fn new(database: Arc<Db>, stripe: Arc<stripe::Client>) -> Self {
+    pub fn new(database: Arc<Db>, stripe: Arc<stripe::Client>, web_links: WebLinks) -> Self {
         Self {
             database,
             stripe,
+            web_links,
             _marker: PhantomData,
         }
     }
@@ -46,6 +73,8 @@ where
         &self,
         request: Request<CreateAffiliateRequest>,
     ) -> Result<Response<Affiliate>, Status> {
+        let peer = Peer::from_request(&request);
+        let idempotency_key = request.extensions().get::<IdempotencyKey>().cloned();
         let message = request.into_inner();
 
         let user_id = message
@@ -53,6 +82,7 @@ where
             .clone()
             .unwrap_field("user_id")?
             .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
         let company_name = message.company_name.clone().unwrap_field("company_name")?;
         let contact_email = message
             .contact_email
@@ -64,6 +94,10 @@ where
             .clone()
             .unwrap_field("asserted_nonprofit_id")?
             .proto_field_into("asserted_nonprofit_id")?;
+        let ein = match message.ein.is_empty() {
+            true => None,
+            false => Some(message.ein.clone()),
+        };
 
         let stripe_business_type = match message.business_type() {
             BusinessType::Unspecified => {
@@ -92,53 +126,72 @@ where
         company.name = Some(company_name);
         create_stripe_account.company = Some(company);
 
-        let stripe_account = stripe::Account::create(&self.stripe, create_stripe_account)
-            .await
-            .map_err(|e| internal!("failed to create stripe account: {:?}", e))?;
+        let stripe_account = stripe::Account::create(
+            &idempotent_stripe_client(&self.stripe, &idempotency_key, "account"),
+            create_stripe_account,
+        )
+        .await
+        .map_err(|e| internal!("failed to create stripe account: {:?}", e))?;
 
-        let now = Utc::now();
+        let mut saga = Saga::for_request(&idempotency_key);
+        saga.record(DeleteStripeAccount {
+            client: (*self.stripe).clone(),
+            account_id: stripe_account.id.clone(),
+        });
 
-        let txn = self.database.begin().await?;
+        let now = Utc::now();
+        let result: Result<FullAffiliateRow, Status> = async {
+            let txn = self.database.begin().await?;
 
-        let affiliate_row = txn
-            .add_affiliate(NewAffiliateRow {
+            let affiliate_row = txn
+                .add_affiliate(NewAffiliateRow {
+                    create_time: now,
+                    update_time: now,
+                    stripe_account_id: stripe_account.id.to_string(),
+                    company_name: stripe_account
+                        .company
+                        .map(|c| c.name)
+                        .flatten()
+                        .ok_or(internal!("expected stripe account company name"))?,
+                    contact_email: stripe_account
+                        .email
+                        .ok_or(internal!("expected stripe account email"))?,
+                    business_type: match stripe_account.business_type {
+                        Some(stripe::AccountBusinessType::Individual) => {
+                            StoreBusinessType::Individual
+                        }
+                        Some(stripe::AccountBusinessType::Company) => StoreBusinessType::Company,
+                        Some(stripe::AccountBusinessType::NonProfit) => {
+                            StoreBusinessType::Nonprofit
+                        }
+                        Some(stripe::AccountBusinessType::GovernmentEntity) => {
+                            StoreBusinessType::GovernmentEntity
+                        }
+                        None => {
+                            return Err(internal!("expected stripe account business type"));
+                        }
+                    },
+                    asserted_nonprofit_id,
+                    ein,
+                })
+                .await?;
+            txn.add_affiliate_manager(NewAffiliateManagerRow {
+                affiliate_id: affiliate_row.affiliate_id.clone(),
+                user_id,
                 create_time: now,
                 update_time: now,
-                stripe_account_id: stripe_account.id.to_string(),
-                company_name: stripe_account
-                    .company
-                    .map(|c| c.name)
-                    .flatten()
-                    .ok_or(internal!("expected stripe account company name"))?,
-                contact_email: stripe_account
-                    .email
-                    .ok_or(internal!("expected stripe account email"))?,
-                business_type: match stripe_account.business_type {
-                    Some(stripe::AccountBusinessType::Individual) => StoreBusinessType::Individual,
-                    Some(stripe::AccountBusinessType::Company) => StoreBusinessType::Company,
-                    Some(stripe::AccountBusinessType::NonProfit) => StoreBusinessType::Nonprofit,
-                    Some(stripe::AccountBusinessType::GovernmentEntity) => {
-                        StoreBusinessType::GovernmentEntity
-                    }
-                    None => {
-                        return Err(internal!("expected stripe account business type"));
-                    }
-                },
-                asserted_nonprofit_id,
+                role: AffiliateManagerRole::Owner,
             })
             .await?;
-        txn.add_affiliate_manager(NewAffiliateManagerRow {
-            affiliate_id: affiliate_row.affiliate_id.clone(),
-            user_id,
-            create_time: now,
-            update_time: now,
-        })
-        .await?;
-        let affiliate_full_row = txn
-            .find_affiliate_by_id(affiliate_row.affiliate_id.clone())
-            .await?
-            .ok_or(internal!("expected to find created affiliate"))?;
-        txn.commit().await?;
+            let affiliate_full_row = txn
+                .find_affiliate_by_id(affiliate_row.affiliate_id.clone())
+                .await?
+                .ok_or(internal!("expected to find created affiliate"))?;
+            txn.commit().await?;
+            Ok(affiliate_full_row)
+        }
+        .await;
+        let affiliate_full_row = saga.check(result).await?;
 
         Ok(Response::new(affiliate_full_row.into_proto()?))
     }
@@ -147,6 +200,7 @@ where
         &self,
         request: Request<GenerateAffiliateLinkRequest>,
     ) -> Result<Response<AffiliateLink>, Status> {
+        let peer = Peer::from_request(&request);
         let message = request.into_inner();
         let affiliate_id = message
             .affiliate_id
@@ -165,6 +219,7 @@ where
                 return Err(not_found!("affiliate not found"));
             }
         };
+        Policy::affiliate_admins(&full_affiliate_row).authorize(&peer)?;
 
         let stripe_account_id = full_affiliate_row
             .affiliate
@@ -172,6 +227,7 @@ where
             .parse::<stripe::AccountId>()
             .map_err(|e| internal!("failed to parse stripe account id: {:?}", e))?;
 
+        let affiliate_id = full_affiliate_row.affiliate.affiliate_id;
         let link = match message.link_type() {
             AffiliateLinkType::Unspecified => {
                 return Err(invalid_argument!("'link_type' must be specified"));
@@ -184,14 +240,8 @@ where
                         type_: stripe::AccountLinkType::AccountOnboarding,
                         collect: None,
                         expand: &[],
-                        refresh_url: Some(&format!(
-                            "https://web.affect.app/#/affiliate/{}/stripe/onboarding",
-                            full_affiliate_row.affiliate.affiliate_id.to_string()
-                        )),
-                        return_url: Some(&format!(
-                            "https://web.affect.app/#/affiliate/{}/stripe/return",
-                            full_affiliate_row.affiliate.affiliate_id.to_string(),
-                        )),
+                        refresh_url: Some(&self.web_links.affiliate_onboarding_url(affiliate_id)),
+                        return_url: Some(&self.web_links.affiliate_return_url(affiliate_id)),
                     },
                 )
                 .await
@@ -208,10 +258,7 @@ where
                 let login_link = stripe::LoginLink::create(
                     &self.stripe,
                     &stripe_account_id,
-                    &format!(
-                        "https://web.affect.app/#/affiliate/{}/stripe/return",
-                        full_affiliate_row.affiliate.affiliate_id.to_string(),
-                    ),
+                    &self.web_links.affiliate_return_url(affiliate_id),
                 )
                 .await
                 .map_err(|e| internal!("failed to create login link: {:?}", e))?;
@@ -231,6 +278,7 @@ where
         &self,
         request: Request<RefreshAffiliateRequest>,
     ) -> Result<Response<Affiliate>, Status> {
+        let peer = Peer::from_request(&request);
         let message = request.into_inner();
         let affiliate_id = message
             .affiliate_id
@@ -248,23 +296,483 @@ where
                 return Err(not_found!("affiliate not found"));
             }
         };
+        Policy::affiliate_managers(&full_affiliate_row).authorize(&peer)?;
 
-        let stripe_account_id = full_affiliate_row
-            .affiliate
-            .stripe_account_id
-            .parse::<stripe::AccountId>()
-            .map_err(|e| internal!("failed to parse stripe account id: {:?}", e))?;
+        let stripe_account_id = &full_affiliate_row.affiliate.stripe_account_id;
+        let refresh_time = Utc::now();
+        let state = retrieve_stripe_state(&self.stripe, stripe_account_id).await?;
+        let store = self.database.on_demand();
+        store
+            .update_affiliate_stripe_state(stripe_account_id, state, refresh_time, Utc::now())
+            .await?;
+        let full_affiliate_row = store
+            .find_affiliate_by_id(affiliate_id)
+            .await?
+            .ok_or(internal!("expected to find refreshed affiliate"))?;
 
-        let stripe_account = stripe::Account::retrieve(&self.stripe, &stripe_account_id, &[])
-            .await
-            .map_err(|e| internal!("failed to retrieve stripe account: {:?}", e))?;
+        Ok(Response::new(full_affiliate_row.into_proto()?))
+    }
 
-        let _payouts_enabled = stripe_account
-            .payouts_enabled
-            .ok_or(internal!("expected stripe account 'payouts_enabled' field"))?;
-        let _country = stripe_account.country;
-        let _business_name = stripe_account.business_profile.map(|p| p.name).flatten();
+    async fn approve_affiliate(
+        &self,
+        request: Request<ApproveAffiliateRequest>,
+    ) -> Result<Response<Affiliate>, Status> {
+        Policy::Privileged.authorize(&Peer::from_request(&request))?;
+        let message = request.into_inner();
+        let affiliate_id = message
+            .affiliate_id
+            .unwrap_field("affiliate_id")?
+            .proto_field_into("affiliate_id")?;
+
+        let now = Utc::now();
+        let txn = self.database.begin().await?;
+        let full_affiliate_row = txn
+            .find_affiliate_by_id(affiliate_id)
+            .await?
+            .ok_or(not_found!("affiliate not found"))?;
+        if full_affiliate_row.affiliate.verification_state != AffiliateVerificationState::Pending {
+            return Err(failed_precondition!(
+                "affiliate is not pending verification"
+            ));
+        }
+        // Donations to the nonprofit would be transferred to this account.
+        if !full_affiliate_row.affiliate.charges_enabled {
+            return Err(failed_precondition!(
+                "affiliate has not finished stripe onboarding"
+            ));
+        }
+        let nonprofit_row = full_affiliate_row
+            .asserted_nonprofit
+            .ok_or(failed_precondition!("asserted nonprofit not found"))?;
+        let checks = VerificationChecks::new(&full_affiliate_row.affiliate, &nonprofit_row);
+        // Reviewers may approve affiliates they verified by other means.
+        if !checks.passed() && !message.override_checks {
+            return Err(failed_precondition!(
+                "affiliate failed verification: {}",
+                checks.failures().join(", ")
+            ));
+        }
+
+        txn.update_affiliate_verification(
+            affiliate_id,
+            AffiliateVerificationState::Approved,
+            None,
+            now,
+        )
+        .await?
+        .ok_or(failed_precondition!(
+            "affiliate is not pending verification"
+        ))?;
+        if !txn
+            .link_affiliate_to_nonprofit(nonprofit_row.nonprofit_id, affiliate_id, now)
+            .await?
+        {
+            return Err(failed_precondition!("nonprofit is already affiliated"));
+        }
+        let full_affiliate_row = txn
+            .find_affiliate_by_id(affiliate_id)
+            .await?
+            .ok_or(internal!("expected to find approved affiliate"))?;
+        txn.commit().await?;
+
+        Ok(Response::new(full_affiliate_row.into_proto()?))
+    }
+
+    async fn reject_affiliate(
+        &self,
+        request: Request<RejectAffiliateRequest>,
+    ) -> Result<Response<Affiliate>, Status> {
+        Policy::Privileged.authorize(&Peer::from_request(&request))?;
+        let message = request.into_inner();
+        let affiliate_id = message
+            .affiliate_id
+            .unwrap_field("affiliate_id")?
+            .proto_field_into("affiliate_id")?;
+        let reason = message.reason.unwrap_field("reason")?;
+
+        let txn = self.database.begin().await?;
+        txn.update_affiliate_verification(
+            affiliate_id,
+            AffiliateVerificationState::Rejected,
+            Some(reason),
+            Utc::now(),
+        )
+        .await?
+        .ok_or(failed_precondition!(
+            "affiliate does not exist or is not pending verification"
+        ))?;
+        let full_affiliate_row = txn
+            .find_affiliate_by_id(affiliate_id)
+            .await?
+            .ok_or(internal!("expected to find rejected affiliate"))?;
+        txn.commit().await?;
+
+        Ok(Response::new(full_affiliate_row.into_proto()?))
+    }
+
+    async fn list_affiliates(
+        &self,
+        request: Request<ListAffiliatesRequest>,
+    ) -> Result<Response<ListAffiliatesResponse>, Status> {
+        Policy::Privileged.authorize(&Peer::from_request(&request))?;
+        let message = request.into_inner();
+
+        let page_size = min(max(message.page_size, 10), 100);
+        let limit: i64 = (page_size + 1).into();
+        let page_token = AffiliatePageToken::deserialize_page_token(&message.page_token)
+            .map_err(|e| invalid_argument!("'page_token' is invalid: {:?}", e))?;
+
+        let (rows_plus_one, total_count) = self
+            .database
+            .on_demand()
+            .list_and_count_affiliates(limit, page_token)
+            .await?;
+        let (affiliates, next_page_token) = page(rows_plus_one, page_size)?;
+
+        Ok(Response::new(ListAffiliatesResponse {
+            affiliates,
+            next_page_token,
+            total_count,
+        }))
+    }
+
+    async fn list_affiliates_for_user(
+        &self,
+        request: Request<ListAffiliatesForUserRequest>,
+    ) -> Result<Response<ListAffiliatesForUserResponse>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let user_id = message
+            .user_id
+            .clone()
+            .unwrap_field("user_id")?
+            .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
+
+        let page_size = min(max(message.page_size, 10), 100);
+        let limit: i64 = (page_size + 1).into();
+        let page_token = AffiliatePageToken::deserialize_page_token(&message.page_token)
+            .map_err(|e| invalid_argument!("'page_token' is invalid: {:?}", e))?;
+
+        let (rows_plus_one, total_count) = self
+            .database
+            .on_demand()
+            .list_and_count_affiliates_for_user(limit, page_token, user_id)
+            .await?;
+        let (affiliates, next_page_token) = page(rows_plus_one, page_size)?;
+
+        Ok(Response::new(ListAffiliatesForUserResponse {
+            affiliates,
+            next_page_token,
+            total_count,
+        }))
+    }
+
+    async fn invite_affiliate_manager(
+        &self,
+        request: Request<InviteAffiliateManagerRequest>,
+    ) -> Result<Response<AffiliateManagerInvitation>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let affiliate_id = message
+            .affiliate_id
+            .clone()
+            .unwrap_field("affiliate_id")?
+            .proto_field_into("affiliate_id")?;
+        let role = role(message.role())?;
+        let email = message.email.clone().unwrap_field("email")?;
+        if !email.contains('@') {
+            return Err(invalid_argument!("'email' must be an email address"));
+        }
+
+        let full_affiliate_row = self
+            .database
+            .on_demand()
+            .find_affiliate_by_id(affiliate_id)
+            .await?
+            .ok_or(not_found!("affiliate not found"))?;
+        // Only owners can invite owners.
+        let inviter_roles: &[AffiliateManagerRole] = match role {
+            AffiliateManagerRole::Owner => &[AffiliateManagerRole::Owner],
+            _ => &[AffiliateManagerRole::Owner, AffiliateManagerRole::Admin],
+        };
+        Policy::managers_with_roles(
+            &full_affiliate_row.affiliate_managers.inner(),
+            inviter_roles,
+        )
+        .authorize(&peer)?;
+        let inviter = peer
+            .user()
+            .ok_or(internal!("expected authorized peer to be a user"))?;
+
+        let token = Uuid::new_v4().to_simple().to_string();
+        let now = Utc::now();
+        let invitation_row = self
+            .database
+            .on_demand()
+            .add_affiliate_manager_invitation(NewAffiliateManagerInvitationRow {
+                create_time: now,
+                update_time: now,
+                affiliate_id,
+                inviter_user_id: inviter.user_id,
+                email,
+                role,
+                token_hash: token_hash(&token),
+                expire_time: now + Duration::days(INVITATION_TTL_DAYS),
+            })
+            .await?;
+
+        // The token is only ever returned here, for the invitee to accept the
+        // invitation with.
+        let mut invitation: AffiliateManagerInvitation = invitation_row.into_proto()?;
+        invitation.url = self.web_links.affiliate_invitation_url(&token);
+        invitation.token = token;
+        Ok(Response::new(invitation))
+    }
+
+    async fn accept_affiliate_manager_invitation(
+        &self,
+        request: Request<AcceptAffiliateManagerInvitationRequest>,
+    ) -> Result<Response<Affiliate>, Status> {
+        let peer = Peer::from_request(&request);
+        let user = peer
+            .user()
+            .cloned()
+            .ok_or(Status::unauthenticated("request must be authenticated"))?;
+        let verified_email = VerifiedEmail::from_request(&request);
+        let message = request.into_inner();
+        let token = message.token.unwrap_field("token")?;
+
+        let now = Utc::now();
+        let txn = self.database.begin().await?;
+        let invitation_row = txn
+            .find_affiliate_manager_invitation_by_token_hash(&token_hash(&token))
+            .await?
+            .ok_or(not_found!("invitation not found"))?;
+        if invitation_row.accept_time.is_some() {
+            return Err(failed_precondition!("invitation was already accepted"));
+        }
+        if invitation_row.expire_time <= now {
+            return Err(failed_precondition!("invitation expired"));
+        }
+        // Invitations are accepted by the firebase user they were sent to, who
+        // must have proven owning the email.
+        let verified_email = verified_email.ok_or(Status::permission_denied(
+            "email must be verified to accept invitations",
+        ))?;
+        if !invitation_row.email.eq_ignore_ascii_case(&verified_email.0) {
+            return Err(Status::permission_denied(
+                "invitation was sent to another email",
+            ));
+        }
+        let affiliate_id = invitation_row.affiliate_id;
+        let affiliate_manager_rows = txn
+            .lock_affiliate_managers_for_affiliate(affiliate_id)
+            .await?;
+        if affiliate_manager_rows
+            .iter()
+            .any(|affiliate_manager_row| affiliate_manager_row.user_id == user.user_id)
+        {
+            return Err(failed_precondition!(
+                "user is already a manager of the affiliate"
+            ));
+        }
+
+        txn.accept_affiliate_manager_invitation(
+            invitation_row.affiliate_manager_invitation_id,
+            user.user_id,
+            now,
+        )
+        .await?
+        .ok_or(failed_precondition!("invitation was already accepted"))?;
+        txn.add_affiliate_manager(NewAffiliateManagerRow {
+            affiliate_id,
+            user_id: user.user_id,
+            create_time: now,
+            update_time: now,
+            role: invitation_row.role,
+        })
+        .await?;
+        let full_affiliate_row = txn
+            .find_affiliate_by_id(affiliate_id)
+            .await?
+            .ok_or(internal!("expected to find affiliate of invitation"))?;
+        txn.commit().await?;
 
         Ok(Response::new(full_affiliate_row.into_proto()?))
     }
+
+    async fn list_affiliate_managers(
+        &self,
+        request: Request<ListAffiliateManagersRequest>,
+    ) -> Result<Response<ListAffiliateManagersResponse>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let affiliate_id = message
+            .affiliate_id
+            .unwrap_field("affiliate_id")?
+            .proto_field_into("affiliate_id")?;
+
+        let full_affiliate_row = self
+            .database
+            .on_demand()
+            .find_affiliate_by_id(affiliate_id)
+            .await?
+            .ok_or(not_found!("affiliate not found"))?;
+        Policy::affiliate_managers(&full_affiliate_row).authorize(&peer)?;
+
+        Ok(Response::new(ListAffiliateManagersResponse {
+            affiliate_managers: full_affiliate_row
+                .affiliate_managers
+                .inner()
+                .into_iter()
+                .map(|affiliate_manager_row| affiliate_manager_row.into_proto())
+                .collect::<Result<Vec<AffiliateManager>, Status>>()?,
+        }))
+    }
+
+    async fn remove_affiliate_manager(
+        &self,
+        request: Request<RemoveAffiliateManagerRequest>,
+    ) -> Result<Response<()>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let affiliate_id = message
+            .affiliate_id
+            .clone()
+            .unwrap_field("affiliate_id")?
+            .proto_field_into("affiliate_id")?;
+        let user_id = message
+            .user_id
+            .clone()
+            .unwrap_field("user_id")?
+            .proto_field_into("user_id")?;
+
+        let txn = self.database.begin().await?;
+        // Locked so that concurrent removals can't remove every owner.
+        let affiliate_manager_rows = txn
+            .lock_affiliate_managers_for_affiliate(affiliate_id)
+            .await?;
+        let removed_role = affiliate_manager_rows
+            .iter()
+            .find(|affiliate_manager_row| affiliate_manager_row.user_id == user_id)
+            .map(|affiliate_manager_row| affiliate_manager_row.role);
+        // Managers may remove themselves. Otherwise owners and admins may
+        // remove managers, and only owners may remove owners.
+        if Policy::Owner(user_id).authorize(&peer).is_err() {
+            let remover_roles: &[AffiliateManagerRole] = match removed_role {
+                Some(AffiliateManagerRole::Owner) => &[AffiliateManagerRole::Owner],
+                _ => &[AffiliateManagerRole::Owner, AffiliateManagerRole::Admin],
+            };
+            Policy::managers_with_roles(&affiliate_manager_rows, remover_roles).authorize(&peer)?;
+        }
+        let removed_role = removed_role.ok_or(not_found!("affiliate manager not found"))?;
+        if removed_role == AffiliateManagerRole::Owner && owner_count(&affiliate_manager_rows) <= 1
+        {
+            return Err(failed_precondition!(
+                "the last owner of an affiliate can't be removed"
+            ));
+        }
+
+        txn.delete_affiliate_manager(affiliate_id, user_id).await?;
+        txn.commit().await?;
+
+        Ok(Response::new(()))
+    }
+}
+
+/// Maps the rows of a page to protos. `rows_plus_one` holds the first row of
+/// the next page, if any, which the next page token is serialized from.
+fn page(
+    rows_plus_one: Vec<FullAffiliateRow>,
+    page_size: i32,
+) -> Result<(Vec<Affiliate>, String), Status> {
+    let (page_rows, next_page_rows) =
+        rows_plus_one.split_at(min(rows_plus_one.len(), page_size as usize));
+
+    let mut affiliates: Vec<Affiliate> = Vec::new();
+    for row in page_rows {
+        affiliates.push(row.clone().into_proto()?);
+    }
+
+    // Next page token or empty string.
+    let next_page_token = next_page_rows
+        .first()
+        .map(|next_row| next_row.page_token().serialize_page_token())
+        .unwrap_or(Ok("".to_string()))?;
+    Ok((affiliates, next_page_token))
+}
+
+fn role(role: ProtoAffiliateManagerRole) -> Result<AffiliateManagerRole, Status> {
+    match role {
+        ProtoAffiliateManagerRole::Unspecified => {
+            Err(invalid_argument!("'role' must be specified"))
+        }
+        ProtoAffiliateManagerRole::Owner => Ok(AffiliateManagerRole::Owner),
+        ProtoAffiliateManagerRole::Admin => Ok(AffiliateManagerRole::Admin),
+        ProtoAffiliateManagerRole::Viewer => Ok(AffiliateManagerRole::Viewer),
+    }
+}
+
+fn owner_count(affiliate_manager_rows: &[AffiliateManagerRow]) -> usize {
+    affiliate_manager_rows
+        .iter()
+        .filter(|affiliate_manager_row| affiliate_manager_row.role == AffiliateManagerRole::Owner)
+        .count()
+}
+
+/// Hash of an invitation token, which is what gets stored.
+fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Retrieves the state of an affiliate's stripe account.
+pub(crate) async fn retrieve_stripe_state(
+    stripe: &stripe::Client,
+    stripe_account_id: &str,
+) -> Result<AffiliateStripeState, Status> {
+    let stripe_account_id = stripe_account_id
+        .parse::<stripe::AccountId>()
+        .map_err(|e| internal!("failed to parse stripe account id: {:?}", e))?;
+    let stripe_account = stripe::Account::retrieve(stripe, &stripe_account_id, &[])
+        .await
+        .map_err(|e| internal!("failed to retrieve stripe account: {:?}", e))?;
+    stripe_state(stripe_account)
+}
+
+fn stripe_state(stripe_account: stripe::Account) -> Result<AffiliateStripeState, Status> {
+    let (requirements_currently_due, disabled_reason) = match stripe_account.requirements {
+        Some(requirements) => (
+            requirements.currently_due.unwrap_or_default(),
+            requirements.disabled_reason,
+        ),
+        None => (Vec::new(), None),
+    };
+    let mut currencies: Vec<String> = stripe_account
+        .default_currency
+        .iter()
+        .map(|currency| currency.to_string().to_uppercase())
+        .collect();
+    for external_account in stripe_account.external_accounts.data {
+        let currency = match external_account {
+            stripe::ExternalAccount::BankAccount(bank_account) => Some(bank_account.currency),
+            stripe::ExternalAccount::Card(card) => card.currency,
+        };
+        currencies.extend(currency.map(|currency| currency.to_string().to_uppercase()));
+    }
+    currencies.sort();
+    currencies.dedup();
+    Ok(AffiliateStripeState {
+        charges_enabled: stripe_account
+            .charges_enabled
+            .ok_or(internal!("expected stripe account 'charges_enabled' field"))?,
+        payouts_enabled: stripe_account
+            .payouts_enabled
+            .ok_or(internal!("expected stripe account 'payouts_enabled' field"))?,
+        country: stripe_account.country,
+        business_name: stripe_account.business_profile.and_then(|p| p.name),
+        requirements_currently_due,
+        disabled_reason,
+        currencies,
+    })
 }
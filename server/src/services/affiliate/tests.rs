@@ -0,0 +1,555 @@
+use crate::{
+    config::WebConfig,
+    interceptors::authn::{Peer, VerifiedEmail},
+    services::affiliate::AffiliateServiceImpl,
+    testing::{affiliate_row, nonprofit_row, user_row, FakeStripe},
+    tonic::idempotency::IdempotencyKey,
+    web::WebLinks,
+};
+use affect_api::affect::{
+    affiliate_service_server::AffiliateService, AcceptAffiliateManagerInvitationRequest,
+    ApproveAffiliateRequest, BusinessType as ProtoBusinessType, CreateAffiliateRequest,
+    ListAffiliatesForUserRequest, RejectAffiliateRequest, RemoveAffiliateManagerRequest,
+};
+use affect_status::Code;
+use affect_storage::models::affiliate::*;
+use affect_storage::Error;
+use affect_storage_mocks::*;
+use chrono::{Duration, Utc};
+use hyper::Method;
+use mockall::Sequence;
+use serde_json::json;
+use std::sync::Arc;
+use tonic::Request;
+use uuid::Uuid;
+
+fn affiliate_manager_row(
+    affiliate_id: Uuid,
+    user_id: Uuid,
+    role: AffiliateManagerRole,
+) -> AffiliateManagerRow {
+    AffiliateManagerRow {
+        affiliate_id,
+        user_id,
+        create_time: Utc::now(),
+        update_time: Utc::now(),
+        role,
+    }
+}
+
+fn affiliate_service(
+    database: MockDatabaseClient,
+) -> AffiliateServiceImpl<MockDatabaseClient, MockStore, MockStore> {
+    affiliate_service_with_stripe(database, stripe::Client::new("sk_test_xyz"))
+}
+
+fn affiliate_service_with_stripe(
+    database: MockDatabaseClient,
+    stripe: stripe::Client,
+) -> AffiliateServiceImpl<MockDatabaseClient, MockStore, MockStore> {
+    AffiliateServiceImpl::new(
+        Arc::new(database),
+        Arc::new(stripe),
+        WebLinks::new(WebConfig {
+            affiliate_onboarding_url: "https://web.affect.app/{affiliate_id}/onboarding"
+                .to_string(),
+            affiliate_return_url: "https://web.affect.app/{affiliate_id}/return".to_string(),
+            affiliate_invitation_url: "https://web.affect.app/invitation/{token}".to_string(),
+            plaid_redirect_url: None,
+        })
+        .unwrap(),
+    )
+}
+
+fn remove_request(
+    peer_user_id: Uuid,
+    affiliate_id: Uuid,
+    user_id: Uuid,
+) -> Request<RemoveAffiliateManagerRequest> {
+    let mut request = Request::new(RemoveAffiliateManagerRequest {
+        affiliate_id: affiliate_id.to_string(),
+        user_id: user_id.to_string(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(peer_user_id)));
+    request
+}
+
+#[tokio::test]
+async fn remove_affiliate_manager() -> Result<(), anyhow::Error> {
+    let affiliate_id = Uuid::new_v4();
+    let owner_id = Uuid::new_v4();
+    let viewer_id = Uuid::new_v4();
+    let mut database = MockDatabaseClient::new();
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        let mut seq = Sequence::new();
+        txn.expect_lock_affiliate_managers_for_affiliate()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(move |_| {
+                Ok(vec![
+                    affiliate_manager_row(affiliate_id, owner_id, AffiliateManagerRole::Owner),
+                    affiliate_manager_row(affiliate_id, viewer_id, AffiliateManagerRole::Viewer),
+                ])
+            });
+        txn.expect_delete_affiliate_manager()
+            .withf(move |deleted_affiliate_id, deleted_user_id| {
+                *deleted_affiliate_id == affiliate_id && *deleted_user_id == viewer_id
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|_, _| Ok(true));
+        txn.expect_commit()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| Ok(()));
+        Ok(txn)
+    });
+
+    affiliate_service(database)
+        .remove_affiliate_manager(remove_request(owner_id, affiliate_id, viewer_id))
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn remove_last_owner_fails() -> Result<(), anyhow::Error> {
+    let affiliate_id = Uuid::new_v4();
+    let owner_id = Uuid::new_v4();
+    let mut database = MockDatabaseClient::new();
+    // Nothing is deleted or committed.
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        txn.expect_lock_affiliate_managers_for_affiliate()
+            .times(1)
+            .return_once(move |_| {
+                Ok(vec![
+                    affiliate_manager_row(affiliate_id, owner_id, AffiliateManagerRole::Owner),
+                    affiliate_manager_row(
+                        affiliate_id,
+                        Uuid::new_v4(),
+                        AffiliateManagerRole::Admin,
+                    ),
+                ])
+            });
+        Ok(txn)
+    });
+
+    let status = affiliate_service(database)
+        .remove_affiliate_manager(remove_request(owner_id, affiliate_id, owner_id))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_cannot_remove_owner() -> Result<(), anyhow::Error> {
+    let affiliate_id = Uuid::new_v4();
+    let owner_id = Uuid::new_v4();
+    let admin_id = Uuid::new_v4();
+    let mut database = MockDatabaseClient::new();
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        txn.expect_lock_affiliate_managers_for_affiliate()
+            .times(1)
+            .return_once(move |_| {
+                Ok(vec![
+                    affiliate_manager_row(affiliate_id, owner_id, AffiliateManagerRole::Owner),
+                    affiliate_manager_row(
+                        affiliate_id,
+                        Uuid::new_v4(),
+                        AffiliateManagerRole::Owner,
+                    ),
+                    affiliate_manager_row(affiliate_id, admin_id, AffiliateManagerRole::Admin),
+                ])
+            });
+        Ok(txn)
+    });
+
+    let status = affiliate_service(database)
+        .remove_affiliate_manager(remove_request(admin_id, affiliate_id, owner_id))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    Ok(())
+}
+
+/// Database with an invitation sent to the email, which must not be accepted.
+fn invitation_database(email: &'static str) -> MockDatabaseClient {
+    let mut database = MockDatabaseClient::new();
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        txn.expect_find_affiliate_manager_invitation_by_token_hash()
+            .times(1)
+            .return_once(move |token_hash| {
+                Ok(Some(AffiliateManagerInvitationRow {
+                    affiliate_manager_invitation_id: Uuid::new_v4(),
+                    create_time: Utc::now(),
+                    update_time: Utc::now(),
+                    affiliate_id: Uuid::new_v4(),
+                    inviter_user_id: Uuid::new_v4(),
+                    email: email.to_string(),
+                    role: AffiliateManagerRole::Admin,
+                    token_hash: token_hash.to_string(),
+                    expire_time: Utc::now() + Duration::days(1),
+                    accept_time: None,
+                    accepted_user_id: None,
+                }))
+            });
+        txn.expect_accept_affiliate_manager_invitation().never();
+        txn.expect_add_affiliate_manager().never();
+        Ok(txn)
+    });
+    database
+}
+
+fn accept_request(
+    user_id: Uuid,
+    verified_email: Option<&str>,
+) -> Request<AcceptAffiliateManagerInvitationRequest> {
+    let mut request = Request::new(AcceptAffiliateManagerInvitationRequest {
+        token: "token".to_string(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+    if let Some(verified_email) = verified_email {
+        request
+            .extensions_mut()
+            .insert(VerifiedEmail(verified_email.to_string()));
+    }
+    request
+}
+
+#[tokio::test]
+async fn accept_invitation_sent_to_another_email_fails() -> Result<(), anyhow::Error> {
+    let status = affiliate_service(invitation_database("someone.else@affect.app"))
+        .accept_affiliate_manager_invitation(accept_request(
+            Uuid::new_v4(),
+            Some("user@affect.app"),
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    Ok(())
+}
+
+#[tokio::test]
+async fn accept_invitation_with_unverified_email_fails() -> Result<(), anyhow::Error> {
+    // The invitation was sent to the user's email, but the peer's id token
+    // doesn't claim it's verified.
+    let status = affiliate_service(invitation_database("user@affect.app"))
+        .accept_affiliate_manager_invitation(accept_request(Uuid::new_v4(), None))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_affiliates_for_user() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let mut database = MockDatabaseClient::new();
+    database.expect_on_demand().times(1).return_once(move || {
+        let mut store = MockStore::new();
+        store
+            .expect_list_affiliates_for_user()
+            .withf(move |page_size, page_token, listed_user_id| {
+                *page_size == 11 && page_token.is_none() && *listed_user_id == user_id
+            })
+            .times(1)
+            .return_once(|_, _, _| Ok(vec![]));
+        store
+            .expect_count_affiliates_for_user()
+            .times(1)
+            .return_once(|_| Ok(0));
+        store
+    });
+
+    let mut request = Request::new(ListAffiliatesForUserRequest {
+        user_id: user_id.to_string(),
+        page_size: 10,
+        page_token: "".to_string(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+    let response = affiliate_service(database)
+        .list_affiliates_for_user(request)
+        .await?
+        .into_inner();
+    assert!(response.affiliates.is_empty());
+    assert_eq!(response.next_page_token, "");
+    assert_eq!(response.total_count, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_affiliates_for_other_user_fails() -> Result<(), anyhow::Error> {
+    let mut request = Request::new(ListAffiliatesForUserRequest {
+        user_id: Uuid::new_v4().to_string(),
+        page_size: 10,
+        page_token: "".to_string(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(Uuid::new_v4())));
+    let status = affiliate_service(MockDatabaseClient::new())
+        .list_affiliates_for_user(request)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_affiliate_deletes_stripe_account_if_insert_fails() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let stripe = FakeStripe::start(vec![
+        (
+            Method::POST,
+            "/v1/accounts",
+            json!({
+                "id": "acct_123",
+                "object": "account",
+                "business_type": "company",
+                "company": {"name": "Company"},
+                "email": "affiliate@affect.app",
+            }),
+        ),
+        (
+            Method::DELETE,
+            "/v1/accounts/acct_123",
+            json!({"id": "acct_123", "object": "account", "deleted": true}),
+        ),
+    ]);
+    let mut database = MockDatabaseClient::new();
+    database.expect_begin().times(1).return_once(|| {
+        let mut txn = MockStore::new();
+        txn.expect_add_affiliate()
+            .times(1)
+            .return_once(|_| Err(Error::Other(anyhow::anyhow!("database unavailable"))));
+        txn.expect_commit().never();
+        Ok(txn)
+    });
+
+    let idempotency_key = IdempotencyKey::new("key".to_string());
+    let mut request = Request::new(CreateAffiliateRequest {
+        user_id: user_id.to_string(),
+        company_name: "Company".to_string(),
+        contact_email: "affiliate@affect.app".to_string(),
+        business_type: ProtoBusinessType::Company as i32,
+        asserted_nonprofit_id: Uuid::new_v4().to_string(),
+        ein: "".to_string(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+    request.extensions_mut().insert(idempotency_key.clone());
+    let status = affiliate_service_with_stripe(database, stripe.client())
+        .create_affiliate(request)
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::Internal);
+    assert_eq!(
+        stripe.calls(),
+        vec![
+            (Method::POST, "/v1/accounts".to_string()),
+            (Method::DELETE, "/v1/accounts/acct_123".to_string()),
+        ]
+    );
+    assert_eq!(
+        stripe.requests()[0].idempotency_key,
+        Some("key-account".to_string())
+    );
+    // The account the key created is gone, so the key can't be retried.
+    assert!(idempotency_key.is_compensated());
+    Ok(())
+}
+
+/// Affiliate pending verification which passes the verification checks for
+/// its asserted nonprofit.
+fn pending_affiliate_row(charges_enabled: bool) -> FullAffiliateRow {
+    let nonprofit_id = Uuid::new_v4();
+    let affiliate = AffiliateRow {
+        asserted_nonprofit_id: nonprofit_id,
+        charges_enabled,
+        ein: Some("123456789".to_string()),
+        verification_state: AffiliateVerificationState::Pending,
+        verification_time: None,
+        ..affiliate_row("acct_123")
+    };
+    let mut asserted_nonprofit = nonprofit_row(nonprofit_id, None).nonprofit;
+    asserted_nonprofit.email = Some("info@affect.app".to_string());
+    FullAffiliateRow {
+        affiliate,
+        asserted_nonprofit: Some(asserted_nonprofit),
+        affiliate_managers: AffiliateManagerRowVec::new(vec![]),
+    }
+}
+
+fn privileged_request<T>(message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request
+        .extensions_mut()
+        .insert(Peer::Privileged(user_row(Uuid::new_v4())));
+    request
+}
+
+fn approve_request(affiliate_id: Uuid) -> Request<ApproveAffiliateRequest> {
+    privileged_request(ApproveAffiliateRequest {
+        affiliate_id: affiliate_id.to_string(),
+        override_checks: false,
+    })
+}
+
+/// Database whose transaction approves the affiliate and then links it to its
+/// asserted nonprofit, which succeeds if `nonprofit_unaffiliated`.
+fn approve_database(
+    full_affiliate_row: FullAffiliateRow,
+    nonprofit_unaffiliated: bool,
+) -> MockDatabaseClient {
+    let affiliate_id = full_affiliate_row.affiliate.affiliate_id;
+    let nonprofit_id = full_affiliate_row.affiliate.asserted_nonprofit_id;
+    let mut database = MockDatabaseClient::new();
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        let mut seq = Sequence::new();
+        let found_row = full_affiliate_row.clone();
+        txn.expect_find_affiliate_by_id()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(move |_| Ok(Some(found_row)));
+        let approved_row = full_affiliate_row.affiliate.clone();
+        txn.expect_update_affiliate_verification()
+            .withf(move |updated_affiliate_id, state, rejection_reason, _| {
+                *updated_affiliate_id == affiliate_id
+                    && *state == AffiliateVerificationState::Approved
+                    && rejection_reason.is_none()
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(move |_, _, _, _| Ok(Some(approved_row)));
+        txn.expect_link_affiliate_to_nonprofit()
+            .withf(move |linked_nonprofit_id, linked_affiliate_id, _| {
+                *linked_nonprofit_id == nonprofit_id && *linked_affiliate_id == affiliate_id
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(move |_, _, _| Ok(nonprofit_unaffiliated));
+        if nonprofit_unaffiliated {
+            txn.expect_find_affiliate_by_id()
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(move |_| Ok(Some(full_affiliate_row)));
+            txn.expect_commit()
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(|| Ok(()));
+        } else {
+            txn.expect_commit().never();
+        }
+        Ok(txn)
+    });
+    database
+}
+
+#[tokio::test]
+async fn approve_affiliate_links_nonprofit() -> Result<(), anyhow::Error> {
+    let full_affiliate_row = pending_affiliate_row(true);
+    let affiliate_id = full_affiliate_row.affiliate.affiliate_id;
+    let affiliate = affiliate_service(approve_database(full_affiliate_row, true))
+        .approve_affiliate(approve_request(affiliate_id))
+        .await?
+        .into_inner();
+    assert_eq!(affiliate.affiliate_id, affiliate_id.to_string());
+    Ok(())
+}
+
+#[tokio::test]
+async fn approve_affiliate_of_affiliated_nonprofit_fails() {
+    let full_affiliate_row = pending_affiliate_row(true);
+    let affiliate_id = full_affiliate_row.affiliate.affiliate_id;
+    let status = affiliate_service(approve_database(full_affiliate_row, false))
+        .approve_affiliate(approve_request(affiliate_id))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn approve_affiliate_without_charges_enabled_fails() {
+    let full_affiliate_row = pending_affiliate_row(false);
+    let affiliate_id = full_affiliate_row.affiliate.affiliate_id;
+    let mut database = MockDatabaseClient::new();
+    // Nothing is updated or committed.
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        txn.expect_find_affiliate_by_id()
+            .times(1)
+            .return_once(move |_| Ok(Some(full_affiliate_row)));
+        Ok(txn)
+    });
+
+    let mut request = approve_request(affiliate_id);
+    request.get_mut().override_checks = true;
+    let status = affiliate_service(database)
+        .approve_affiliate(request)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn reject_affiliate() -> Result<(), anyhow::Error> {
+    let full_affiliate_row = pending_affiliate_row(true);
+    let affiliate_id = full_affiliate_row.affiliate.affiliate_id;
+    let mut database = MockDatabaseClient::new();
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        let mut seq = Sequence::new();
+        let rejected_row = full_affiliate_row.affiliate.clone();
+        txn.expect_update_affiliate_verification()
+            .withf(move |updated_affiliate_id, state, rejection_reason, _| {
+                *updated_affiliate_id == affiliate_id
+                    && *state == AffiliateVerificationState::Rejected
+                    && rejection_reason.as_deref() == Some("not the nonprofit")
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(move |_, _, _, _| Ok(Some(rejected_row)));
+        txn.expect_find_affiliate_by_id()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(move |_| Ok(Some(full_affiliate_row)));
+        txn.expect_commit()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| Ok(()));
+        Ok(txn)
+    });
+
+    affiliate_service(database)
+        .reject_affiliate(privileged_request(RejectAffiliateRequest {
+            affiliate_id: affiliate_id.to_string(),
+            reason: "not the nonprofit".to_string(),
+        }))
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn reject_affiliate_without_reason_fails() {
+    let status = affiliate_service(MockDatabaseClient::new())
+        .reject_affiliate(privileged_request(RejectAffiliateRequest {
+            affiliate_id: Uuid::new_v4().to_string(),
+            reason: "".to_string(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
@@ -1,9 +1,17 @@
-use crate::protobuf::into::{IntoProto, ProtoInto};
+use crate::{
+    interceptors::authn::Peer,
+    policy::Policy,
+    protobuf::into::{IntoProto, ProtoInto},
+};
 use affect_api::affect::{
-    cause_service_server::CauseService, Cause, CreateCauseRequest, ListCausesRequest,
-    ListCausesResponse,
+    cause_service_server::CauseService, Cause, CauseRecipient, CauseState as ProtoCauseState,
+    CreateCauseRequest, DeleteCauseRequest, GetCauseRequest, ListCausesRequest, ListCausesResponse,
+    UpdateCauseRequest,
+};
+use affect_status::{
+    failed_precondition, invalid_argument,
+    well_known::{entity_not_found, UnwrapField},
 };
-use affect_status::{invalid_argument, well_known::UnwrapField};
 use affect_storage::{
     database::{
         client::DatabaseClient,
@@ -14,6 +22,7 @@ use affect_storage::{
     stores::cause::*,
 };
 use async_trait::async_trait;
+use chrono::Utc;
 use std::{
     cmp::{max, min},
     marker::PhantomData,
@@ -49,26 +58,78 @@ where
         &self,
         request: Request<CreateCauseRequest>,
     ) -> Result<Response<Cause>, Status> {
+        let peer = Peer::from_request(&request);
         let message = request.into_inner();
+        let state = cause_state(message.state());
 
         let user_id = message
             .user_id
             .unwrap_field("user_id")?
             .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
+        let name = cause_name(message.name)?;
+        let recipients = new_cause_recipients(message.recipients)?;
 
-        let mut recipient_nonprofit_ids = Vec::new();
-        for recipient in message.recipients {
-            recipient_nonprofit_ids.push(
-                recipient
-                    .nonprofit_id
-                    .unwrap_field("nonprofit_id")?
-                    .proto_field_into("nonprofit_id")?,
-            );
-        }
+        let txn = self.database.begin().await?;
+        let (cause_row, cause_recipient_rows) = txn
+            .add_cause_and_recipients(user_id, name, state, recipients)
+            .await?;
+        txn.commit().await?;
+
+        Ok(Response::new(
+            (cause_row, cause_recipient_rows).into_proto()?,
+        ))
+    }
+
+    async fn get_cause(
+        &self,
+        request: Request<GetCauseRequest>,
+    ) -> Result<Response<Cause>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let cause_id = message
+            .cause_id
+            .unwrap_field("cause_id")?
+            .proto_field_into("cause_id")?;
+
+        let full_cause_row = self
+            .database
+            .on_demand()
+            .find_cause_by_id(cause_id)
+            .await?
+            .ok_or(entity_not_found("cause"))?;
+        Policy::Owner(full_cause_row.cause.user_id).authorize(&peer)?;
+
+        Ok(Response::new(full_cause_row.into_proto()?))
+    }
+
+    async fn update_cause(
+        &self,
+        request: Request<UpdateCauseRequest>,
+    ) -> Result<Response<Cause>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let state = cause_state(message.state());
+        let cause_id = message
+            .cause_id
+            .unwrap_field("cause_id")?
+            .proto_field_into("cause_id")?;
+        let name = cause_name(message.name)?;
+        let recipients = new_cause_recipients(message.recipients)?;
 
+        // The cause stays locked until committed, so it can't be archived
+        // concurrently.
         let txn = self.database.begin().await?;
+        let cause_row = txn
+            .lock_cause_by_id(cause_id)
+            .await?
+            .ok_or(entity_not_found("cause"))?;
+        Policy::Owner(cause_row.user_id).authorize(&peer)?;
+        if cause_row.archive_time.is_some() {
+            return Err(failed_precondition!("cause is archived"));
+        }
         let (cause_row, cause_recipient_rows) = txn
-            .add_cause_and_recipients(user_id, recipient_nonprofit_ids)
+            .replace_cause_and_recipients(cause_id, name, state, recipients)
             .await?;
         txn.commit().await?;
 
@@ -77,10 +138,41 @@ where
         ))
     }
 
+    async fn delete_cause(
+        &self,
+        request: Request<DeleteCauseRequest>,
+    ) -> Result<Response<()>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let cause_id = message
+            .cause_id
+            .unwrap_field("cause_id")?
+            .proto_field_into("cause_id")?;
+
+        let full_cause_row = self
+            .database
+            .on_demand()
+            .find_cause_by_id(cause_id)
+            .await?
+            .ok_or(entity_not_found("cause"))?;
+        Policy::Owner(full_cause_row.cause.user_id).authorize(&peer)?;
+
+        // Causes are archived rather than deleted since donations reference them.
+        if full_cause_row.cause.archive_time.is_none() {
+            self.database
+                .on_demand()
+                .archive_cause(cause_id, Utc::now())
+                .await?;
+        }
+
+        Ok(Response::new(()))
+    }
+
     async fn list_causes(
         &self,
         request: Request<ListCausesRequest>,
     ) -> Result<Response<ListCausesResponse>, Status> {
+        let peer = Peer::from_request(&request);
         let message = request.into_inner();
 
         let page_size = min(max(message.page_size, 1), 100);
@@ -90,6 +182,7 @@ where
             .user_id
             .unwrap_field("user_id")?
             .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
 
         let (rows_plus_one, total_count) = self
             .database
@@ -119,3 +212,37 @@ where
         }))
     }
 }
+
+/// Causes are active unless explicitly saved as a draft.
+fn cause_state(state: ProtoCauseState) -> CauseState {
+    match state {
+        ProtoCauseState::Unspecified | ProtoCauseState::Active => CauseState::Active,
+        ProtoCauseState::Draft => CauseState::Draft,
+    }
+}
+
+/// Validates the user provided name of a cause.
+fn cause_name(name: String) -> Result<String, Status> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(invalid_argument!("'name' must be specified"));
+    }
+    if name.chars().count() > 255 {
+        return Err(invalid_argument!("'name' must be at most 255 characters"));
+    }
+    Ok(name)
+}
+
+fn new_cause_recipients(recipients: Vec<CauseRecipient>) -> Result<Vec<NewCauseRecipient>, Status> {
+    let mut new_recipients = Vec::new();
+    for recipient in recipients {
+        new_recipients.push(NewCauseRecipient {
+            nonprofit_id: recipient
+                .nonprofit_id
+                .unwrap_field("nonprofit_id")?
+                .proto_field_into("nonprofit_id")?,
+            weight_basis_points: recipient.weight_basis_points,
+        });
+    }
+    Ok(new_recipients)
+}
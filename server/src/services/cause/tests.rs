@@ -1,11 +1,19 @@
-use crate::services::cause::CauseServiceImpl;
+use crate::{
+    interceptors::authn::Peer,
+    services::cause::CauseServiceImpl,
+    testing::{user_row, StaticPeerInterceptor},
+    tonic::async_interceptor::AsyncInterceptorLayer,
+};
 use affect_api::affect::{
-    cause_service_client::CauseServiceClient, cause_service_server::CauseServiceServer,
-    CauseRecipient, CreateCauseRequest,
+    cause_service_client::CauseServiceClient,
+    cause_service_server::{CauseService, CauseServiceServer},
+    CauseRecipient, CauseState as ProtoCauseState, CreateCauseRequest, DeleteCauseRequest,
+    GetCauseRequest, UpdateCauseRequest,
 };
+use affect_status::Code;
 use affect_storage::models::cause::*;
 use affect_storage_mocks::*;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use mockall::Sequence;
 use std::sync::Arc;
 use std::time::Duration;
@@ -14,6 +22,7 @@ use uuid::Uuid;
 
 #[tokio::test]
 async fn create_cause() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
     let mut database = MockDatabaseClient::new();
     let mut txn = MockStore::new();
 
@@ -29,7 +38,9 @@ async fn create_cause() -> Result<(), anyhow::Error> {
                     create_time: Utc::now(),
                     update_time: Utc::now(),
                     user_id: Uuid::new_v4(),
-                    name: "some name".to_string(),
+                    name: "Climate".to_string(),
+                    archive_time: None,
+                    state: CauseState::Active,
                 })
             });
         txn.expect_add_cause_recipient()
@@ -41,6 +52,7 @@ async fn create_cause() -> Result<(), anyhow::Error> {
                     nonprofit_id: Uuid::new_v4(),
                     create_time: Utc::now(),
                     update_time: Utc::now(),
+                    weight_basis_points: 10_000,
                 })
             });
         txn.expect_commit()
@@ -56,6 +68,9 @@ async fn create_cause() -> Result<(), anyhow::Error> {
     let addr = "127.0.0.1:54321";
     let server = tokio::spawn(async move {
         Server::builder()
+            .layer(AsyncInterceptorLayer::new(StaticPeerInterceptor(
+                Peer::User(user_row(user_id)),
+            )))
             .add_service(cause_service)
             .serve(addr.parse().unwrap())
             .await
@@ -66,10 +81,13 @@ async fn create_cause() -> Result<(), anyhow::Error> {
     let mut channel = CauseServiceClient::connect(format!("http://{0}", addr)).await?;
     channel
         .create_cause(Request::new(CreateCauseRequest {
-            user_id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            name: "Climate".to_string(),
+            state: ProtoCauseState::Active as i32,
             recipients: [CauseRecipient {
                 cause_id: "unused".to_string(),
                 nonprofit_id: Uuid::new_v4().to_string(),
+                weight_basis_points: 10_000,
             }]
             .to_vec(),
         }))
@@ -78,3 +96,324 @@ async fn create_cause() -> Result<(), anyhow::Error> {
     server.abort();
     Ok(())
 }
+
+#[tokio::test]
+async fn create_cause_weights_must_sum_to_total() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let mut database = MockDatabaseClient::new();
+    // No expectations, the transaction must not be written to or committed.
+    database
+        .expect_begin()
+        .times(1)
+        .return_once(|| Ok(MockStore::new()));
+    let cause_service: CauseServiceImpl<_, MockStore, MockStore> =
+        CauseServiceImpl::new(Arc::new(database));
+
+    let mut request = Request::new(CreateCauseRequest {
+        user_id: user_id.to_string(),
+        name: "Climate".to_string(),
+        state: ProtoCauseState::Active as i32,
+        recipients: [
+            CauseRecipient {
+                cause_id: "unused".to_string(),
+                nonprofit_id: Uuid::new_v4().to_string(),
+                weight_basis_points: 7_000,
+            },
+            CauseRecipient {
+                cause_id: "unused".to_string(),
+                nonprofit_id: Uuid::new_v4().to_string(),
+                weight_basis_points: 2_000,
+            },
+        ]
+        .to_vec(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+
+    let status = cause_service.create_cause(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_draft_cause_without_recipients() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let mut database = MockDatabaseClient::new();
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        let mut seq = Sequence::new();
+        txn.expect_add_cause()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|new_row| {
+                Ok(CauseRow {
+                    cause_id: Uuid::new_v4(),
+                    create_time: new_row.create_time,
+                    update_time: new_row.update_time,
+                    user_id: new_row.user_id,
+                    name: new_row.name,
+                    archive_time: None,
+                    state: new_row.state,
+                })
+            });
+        txn.expect_commit()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| Ok(()));
+        Ok(txn)
+    });
+    let cause_service: CauseServiceImpl<_, MockStore, MockStore> =
+        CauseServiceImpl::new(Arc::new(database));
+
+    let mut request = Request::new(CreateCauseRequest {
+        user_id: user_id.to_string(),
+        name: "Climate".to_string(),
+        state: ProtoCauseState::Draft as i32,
+        recipients: Vec::new(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+
+    let cause = cause_service.create_cause(request).await?.into_inner();
+    assert_eq!(cause.state(), ProtoCauseState::Draft);
+    assert!(cause.recipients.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_cause_for_other_user_is_denied() -> Result<(), anyhow::Error> {
+    // No expectations, the database must not be accessed.
+    let database = MockDatabaseClient::new();
+    let cause_service: CauseServiceImpl<_, MockStore, MockStore> =
+        CauseServiceImpl::new(Arc::new(database));
+
+    let mut request = Request::new(CreateCauseRequest {
+        user_id: Uuid::new_v4().to_string(),
+        name: "Climate".to_string(),
+        state: ProtoCauseState::Active as i32,
+        recipients: [CauseRecipient {
+            cause_id: "unused".to_string(),
+            nonprofit_id: Uuid::new_v4().to_string(),
+            weight_basis_points: 10_000,
+        }]
+        .to_vec(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(Uuid::new_v4())));
+
+    let status = cause_service.create_cause(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_cause_anonymous_is_unauthenticated() -> Result<(), anyhow::Error> {
+    let database = MockDatabaseClient::new();
+    let cause_service: CauseServiceImpl<_, MockStore, MockStore> =
+        CauseServiceImpl::new(Arc::new(database));
+
+    let status = cause_service
+        .create_cause(Request::new(CreateCauseRequest {
+            user_id: Uuid::new_v4().to_string(),
+            name: "Climate".to_string(),
+            state: ProtoCauseState::Active as i32,
+            recipients: Vec::new(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    Ok(())
+}
+
+fn full_cause_row(user_id: Uuid, archive_time: Option<DateTime<Utc>>) -> FullCauseRow {
+    FullCauseRow {
+        cause: CauseRow {
+            cause_id: Uuid::new_v4(),
+            create_time: Utc::now(),
+            update_time: Utc::now(),
+            user_id,
+            name: "Climate".to_string(),
+            archive_time,
+            state: CauseState::Active,
+        },
+        cause_recipients: CauseRecipientRowVec::new(Vec::new()),
+    }
+}
+
+#[tokio::test]
+async fn get_cause_of_other_user_is_denied() -> Result<(), anyhow::Error> {
+    let mut database = MockDatabaseClient::new();
+    database.expect_on_demand().returning(|| {
+        let mut store = MockStore::new();
+        store
+            .expect_find_cause_by_id()
+            .return_once(|_| Ok(Some(full_cause_row(Uuid::new_v4(), None))));
+        store
+    });
+    let cause_service: CauseServiceImpl<_, MockStore, MockStore> =
+        CauseServiceImpl::new(Arc::new(database));
+
+    let mut request = Request::new(GetCauseRequest {
+        cause_id: Uuid::new_v4().to_string(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(Uuid::new_v4())));
+
+    let status = cause_service.get_cause(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_cause_archives() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let mut database = MockDatabaseClient::new();
+    let mut seq = Sequence::new();
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(move || {
+            let mut store = MockStore::new();
+            store
+                .expect_find_cause_by_id()
+                .return_once(move |_| Ok(Some(full_cause_row(user_id, None))));
+            store
+        });
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(move || {
+            let mut store = MockStore::new();
+            store
+                .expect_archive_cause()
+                .times(1)
+                .return_once(move |_, archive_time| {
+                    Ok(full_cause_row(user_id, Some(archive_time)).cause)
+                });
+            store
+        });
+    let cause_service: CauseServiceImpl<_, MockStore, MockStore> =
+        CauseServiceImpl::new(Arc::new(database));
+
+    let mut request = Request::new(DeleteCauseRequest {
+        cause_id: Uuid::new_v4().to_string(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+
+    cause_service.delete_cause(request).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_cause_locks_cause_before_replacing() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let mut database = MockDatabaseClient::new();
+    let mut txn = MockStore::new();
+
+    // Transaction
+    {
+        let mut seq = Sequence::new();
+        txn.expect_lock_cause_by_id()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(move |_| Ok(Some(full_cause_row(user_id, None).cause)));
+        txn.expect_update_cause()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(move |cause_id, name, state, update_time| {
+                Ok(CauseRow {
+                    cause_id,
+                    create_time: update_time,
+                    update_time,
+                    user_id,
+                    name,
+                    archive_time: None,
+                    state,
+                })
+            });
+        txn.expect_delete_cause_recipients_for_cause()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|_| Ok(()));
+        txn.expect_add_cause_recipient()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|new_row| {
+                Ok(CauseRecipientRow {
+                    cause_id: new_row.cause_id,
+                    nonprofit_id: new_row.nonprofit_id,
+                    create_time: new_row.create_time,
+                    update_time: new_row.update_time,
+                    weight_basis_points: new_row.weight_basis_points,
+                })
+            });
+        txn.expect_commit()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| Ok(()));
+    }
+    database.expect_begin().times(1).return_once(|| Ok(txn));
+    let cause_service: CauseServiceImpl<_, MockStore, MockStore> =
+        CauseServiceImpl::new(Arc::new(database));
+
+    let mut request = Request::new(UpdateCauseRequest {
+        cause_id: Uuid::new_v4().to_string(),
+        name: "Oceans".to_string(),
+        state: ProtoCauseState::Active as i32,
+        recipients: [CauseRecipient {
+            cause_id: "unused".to_string(),
+            nonprofit_id: Uuid::new_v4().to_string(),
+            weight_basis_points: 10_000,
+        }]
+        .to_vec(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+
+    let cause = cause_service.update_cause(request).await?.into_inner();
+    assert_eq!(cause.name, "Oceans");
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_archived_cause_fails() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let mut database = MockDatabaseClient::new();
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        txn.expect_lock_cause_by_id()
+            .return_once(move |_| Ok(Some(full_cause_row(user_id, Some(Utc::now())).cause)));
+        txn.expect_update_cause().never();
+        Ok(txn)
+    });
+    let cause_service: CauseServiceImpl<_, MockStore, MockStore> =
+        CauseServiceImpl::new(Arc::new(database));
+
+    let mut request = Request::new(UpdateCauseRequest {
+        cause_id: Uuid::new_v4().to_string(),
+        name: "Oceans".to_string(),
+        state: ProtoCauseState::Active as i32,
+        recipients: [CauseRecipient {
+            cause_id: "unused".to_string(),
+            nonprofit_id: Uuid::new_v4().to_string(),
+            weight_basis_points: 10_000,
+        }]
+        .to_vec(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+
+    let status = cause_service.update_cause(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    Ok(())
+}
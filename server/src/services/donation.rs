@@ -1,8 +1,9 @@
 use affect_api::affect::{
-    donation_service_server::DonationService, CreateDonationRequest, Donation, *,
+    donation_service_server::DonationService, list_donations_request::Filter,
+    CreateDonationRequest, Donation, *,
 };
 use affect_status::{
-    internal, invalid_argument,
+    failed_precondition, internal, invalid_argument,
     well_known::{entity_not_found, UnwrapField},
 };
 use affect_storage::{
@@ -10,63 +11,645 @@ use affect_storage::{
         client::DatabaseClient,
         store::{OnDemandStore, TransactionalStore},
     },
-    stores::{nonprofit::NonprofitStore, user::UserStore},
+    models::{
+        account::AccountRow,
+        affiliate::AffiliateRow,
+        cause::CauseState,
+        donation::{
+            DonationAllocationRow, DonationPageToken, DonationRow, DonationStatus,
+            NewDonationAllocationRow, NewDonationRow,
+        },
+        user::UserRow,
+    },
+    page_token::{PageToken, PageTokenable},
+    stores::{
+        account::AccountStore,
+        affiliate::AffiliateStore,
+        cause::CauseStore,
+        donation::{DonationAndAllocationStore, DonationStore},
+        nonprofit::NonprofitStore,
+        user::UserStore,
+    },
 };
 use async_trait::async_trait;
-use log::info;
-use std::{marker::PhantomData, sync::Arc};
+use chrono::Utc;
+use iso_currency::Currency;
+use log::{info, warn};
+use std::{
+    cmp::{max, min},
+    marker::PhantomData,
+    sync::Arc,
+};
 use tonic::{Request, Response, Status};
 use uuid::Uuid;
 
-use crate::{money::Money, protobuf::into::ProtoInto};
+use crate::{
+    fees::{FeeBreakdown, FeeCalculator, PaymentMethod},
+    interceptors::authn::Peer,
+    money::Money,
+    policy::Policy,
+    protobuf::into::{IntoProto, ProtoInto},
+    saga::{RefundStripeCharge, ReverseStripeTransfer, Saga},
+    tonic::idempotency::{idempotent_stripe_client, IdempotencyKey},
+};
+
+#[cfg(test)]
+mod tests;
 
 pub struct DonationServiceImpl<Db, Store, TStore> {
     database: Arc<Db>,
     stripe: Arc<stripe::Client>,
+    fees: FeeCalculator,
     _marker: PhantomData<(Store, TStore)>,
 }
 
 impl<Db, Store, TStore> DonationServiceImpl<Db, Store, TStore> {
-    pub fn new(database: Arc<Db>, stripe: Arc<stripe::Client>) -> Self {
+    pub fn new(database: Arc<Db>, stripe: Arc<stripe::Client>, fees: FeeCalculator) -> Self {
         Self {
             database,
             stripe,
+            fees,
             _marker: PhantomData,
         }
     }
 }
 
+impl<Db, Store, TStore> DonationServiceImpl<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore> + 'static,
+    Store: CauseStore + DonationStore + NonprofitStore + OnDemandStore + 'static,
+    TStore: DonationStore + TransactionalStore + 'static,
+{
+    /// Fees of a donation charged from the bank account if provided, otherwise
+    /// from a card. Fails if nothing would be left for the recipients.
+    fn fee_breakdown(
+        &self,
+        amount: &Money,
+        bank_account_id: &Option<stripe::BankAccountId>,
+        cover_fees: bool,
+    ) -> Result<FeeBreakdown, Status> {
+        let payment_method = match bank_account_id {
+            Some(_) => PaymentMethod::Ach,
+            None => PaymentMethod::Card,
+        };
+        let fee_breakdown = self.fees.breakdown(amount, payment_method, cover_fees)?;
+        if !fee_breakdown.net_amount()?.is_positive() {
+            return Err(invalid_argument!("amount is too small to cover the fees"));
+        }
+        Ok(fee_breakdown)
+    }
+
+    /// Donation already recorded for the charge. Stripe replays the charge of
+    /// an earlier attempt for a retry with the same idempotency key, e.g. one
+    /// whose response was lost, and its donation must not be added again.
+    async fn recorded_donation(
+        &self,
+        charge: &stripe::Charge,
+    ) -> Result<Option<DonationRow>, Status> {
+        Ok(self
+            .database
+            .on_demand()
+            .find_donation_by_stripe_charge_id(charge.id.as_str())
+            .await?)
+    }
+
+    /// Passes the result of recording the donation of the charge through. If
+    /// recording failed, the side effects of the saga are undone unless the
+    /// donation exists after all, e.g. because a concurrent attempt of the
+    /// request recorded it. Nothing is undone if that can't be determined, a
+    /// retry with the same idempotency key then records the replayed charge.
+    pub(crate) async fn check_recorded(
+        &self,
+        saga: &mut Saga,
+        stripe_charge_id: &str,
+        result: Result<DonationRow, Status>,
+    ) -> Result<DonationRow, Status> {
+        let status = match result {
+            Ok(donation_row) => return Ok(donation_row),
+            Err(status) => status,
+        };
+        match self
+            .database
+            .on_demand()
+            .find_donation_by_stripe_charge_id(stripe_charge_id)
+            .await
+        {
+            Ok(Some(donation_row)) => Ok(donation_row),
+            Ok(None) => saga.check(Err(status)).await,
+            Err(e) => {
+                warn!(
+                    "Failed to find donation of charge {0}, not undoing it: {1:?}",
+                    stripe_charge_id, e
+                );
+                Err(status)
+            }
+        }
+    }
+
+    /// Donates to the nonprofit, see `add_nonprofit_donation`, and commits the
+    /// donation.
+    pub(crate) async fn donate_to_nonprofit(
+        &self,
+        user: &UserRow,
+        nonprofit_id: Uuid,
+        amount: Money,
+        account: Option<AccountRow>,
+        cover_fees: bool,
+        idempotency_key: &Option<IdempotencyKey>,
+    ) -> Result<DonationRow, Status> {
+        let txn = self.database.begin().await?;
+        let (donation_row, mut saga) = self
+            .add_nonprofit_donation(
+                &txn,
+                user,
+                nonprofit_id,
+                amount,
+                account,
+                cover_fees,
+                idempotency_key,
+            )
+            .await?;
+        let result = txn
+            .commit()
+            .await
+            .map(|()| donation_row.clone())
+            .map_err(Status::from);
+        self.check_recorded(&mut saga, &donation_row.stripe_charge_id, result)
+            .await
+    }
+
+    /// Charges the user on the nonprofit's connected account, with the
+    /// platform fee as application fee. Bank accounts can't be shared with
+    /// connected accounts, so a charge from the provided linked account of the
+    /// user is made on the platform account and transferred to the connected
+    /// account instead. The platform then pays stripe's fee, so it keeps both
+    /// fees.
+    ///
+    /// The donation is added to the transaction, which the caller commits
+    /// together with its own writes. The returned saga undoes the charge, see
+    /// `check_recorded`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn add_nonprofit_donation(
+        &self,
+        txn: &TStore,
+        user: &UserRow,
+        nonprofit_id: Uuid,
+        amount: Money,
+        account: Option<AccountRow>,
+        cover_fees: bool,
+        idempotency_key: &Option<IdempotencyKey>,
+    ) -> Result<(DonationRow, Saga), Status> {
+        let (stripe_currency, currency_code) = currency(&amount)?;
+        let bank_account_id = bank_account_id(&account)?;
+        let fee_breakdown = self.fee_breakdown(&amount, &bank_account_id, cover_fees)?;
+        let customer_id = user
+            .stripe_customer_id
+            .parse()
+            .map_err(|e| internal!("failed to parse stripe customer id: {:?}", e))?;
+
+        let nonprofit = self
+            .database
+            .on_demand()
+            .find_nonprofit_by_id(nonprofit_id)
+            .await?
+            .ok_or(entity_not_found("nonprofit"))?;
+        let affiliate = nonprofit
+            .affiliate
+            .ok_or(invalid_argument!("nonprofit is not affiliated"))?;
+        validate_currency(&affiliate, amount.currency())?;
+        let mut saga = Saga::for_request(idempotency_key);
+        let charge = match bank_account_id {
+            Some(bank_account_id) => {
+                let mut create_charge = stripe::CreateCharge::default();
+                create_charge.amount = Some(fee_breakdown.charge_amount.subunits_truncated());
+                create_charge.application_fee_amount =
+                    application_fee_amount(&fee_breakdown.total_fee()?);
+                create_charge.currency = Some(stripe_currency);
+                create_charge.customer = Some(customer_id);
+                create_charge.source =
+                    Some(stripe::ChargeSourceParams::BankAccount(bank_account_id));
+                create_charge.transfer_data = Some(stripe::TransferDataParams {
+                    amount: None,
+                    destination: affiliate.stripe_account_id.clone(),
+                });
+                let charge = stripe::Charge::create(
+                    &idempotent_stripe_client(&self.stripe, idempotency_key, "charge"),
+                    create_charge,
+                )
+                .await
+                .map_err(|e| internal!("failed to create stripe charge: {:?}", e))?;
+                // The destination charge transfers to the nonprofit, undo both.
+                saga.record(RefundStripeCharge {
+                    client: (*self.stripe).clone(),
+                    charge_id: charge.id.clone(),
+                    refund_application_fee: true,
+                    reverse_transfer: true,
+                });
+                charge
+            }
+            None => {
+                let account_id = affiliate
+                    .stripe_account_id
+                    .parse()
+                    .map_err(|e| internal!("failed to parse stripe account id: {:?}", e))?;
+
+                let mut create_token = stripe::CreateToken::default();
+                create_token.customer = Some(customer_id);
+
+                let nonprofit_stripe_client =
+                    (*self.stripe).clone().with_stripe_account(account_id);
+                let stripe_token = stripe::Token::create(
+                    &idempotent_stripe_client(&nonprofit_stripe_client, idempotency_key, "token"),
+                    create_token,
+                )
+                .await
+                .map_err(|e| internal!("failed to create stripe token: {:?}", e))?;
+
+                let mut create_charge = stripe::CreateCharge::default();
+                create_charge.amount = Some(fee_breakdown.charge_amount.subunits_truncated());
+                create_charge.application_fee_amount =
+                    application_fee_amount(&fee_breakdown.platform_fee);
+                create_charge.currency = Some(stripe_currency);
+                create_charge.source = Some(stripe::ChargeSourceParams::Token(stripe_token.id));
+                let charge = stripe::Charge::create(
+                    &idempotent_stripe_client(&nonprofit_stripe_client, idempotency_key, "charge"),
+                    create_charge,
+                )
+                .await
+                .map_err(|e| internal!("failed to create stripe charge: {:?}", e))?;
+                saga.record(RefundStripeCharge {
+                    client: nonprofit_stripe_client,
+                    charge_id: charge.id.clone(),
+                    refund_application_fee: true,
+                    reverse_transfer: false,
+                });
+                charge
+            }
+        };
+
+        info!("Created charge: {:?}", charge);
+        if let Some(donation_row) = self.recorded_donation(&charge).await? {
+            return Ok((donation_row, Saga::new()));
+        }
+        replayed_refund_check(&charge)?;
+
+        let now = Utc::now();
+        let result = txn
+            .add_donation(NewDonationRow {
+                create_time: now,
+                update_time: now,
+                nonprofit_id: Some(nonprofit_id),
+                cause_id: None,
+                user_id: user.user_id,
+                affiliate_id: Some(affiliate.affiliate_id),
+                currency_code,
+                amount_units: amount.units(),
+                amount_nanos: amount.nanos(),
+                stripe_charge_id: charge.id.to_string(),
+                stripe_transfer_group: None,
+                status: donation_status(&charge),
+                account_id: account.map(|account| account.account_id),
+                cover_fees,
+                charge_amount_units: fee_breakdown.charge_amount.units(),
+                charge_amount_nanos: fee_breakdown.charge_amount.nanos(),
+                processing_fee_units: fee_breakdown.processing_fee.units(),
+                processing_fee_nanos: fee_breakdown.processing_fee.nanos(),
+                platform_fee_units: fee_breakdown.platform_fee.units(),
+                platform_fee_nanos: fee_breakdown.platform_fee.nanos(),
+            })
+            .await
+            .map_err(Status::from);
+        let donation_row = self
+            .check_recorded(&mut saga, charge.id.as_str(), result)
+            .await?;
+        Ok((donation_row, saga))
+    }
+
+    /// Donates to the cause, see `add_cause_donation`, and commits the
+    /// donation.
+    pub(crate) async fn donate_to_cause(
+        &self,
+        user: &UserRow,
+        cause_id: Uuid,
+        amount: Money,
+        account: Option<AccountRow>,
+        cover_fees: bool,
+        idempotency_key: &Option<IdempotencyKey>,
+    ) -> Result<DonationRow, Status> {
+        let txn = self.database.begin().await?;
+        let (donation_row, mut saga) = self
+            .add_cause_donation(
+                &txn,
+                user,
+                cause_id,
+                amount,
+                account,
+                cover_fees,
+                idempotency_key,
+            )
+            .await?;
+        let result = txn
+            .commit()
+            .await
+            .map(|()| donation_row.clone())
+            .map_err(Status::from);
+        self.check_recorded(&mut saga, &donation_row.stripe_charge_id, result)
+            .await
+    }
+
+    /// Charges the user on the platform account, then transfers a share of the
+    /// charge net of fees to the connected account of every recipient of the
+    /// cause. The charge and transfers are linked through a transfer group.
+    /// The charge is made from the provided linked account of the user,
+    /// otherwise from the customer's default source.
+    ///
+    /// The donation is added to the transaction, which the caller commits
+    /// together with its own writes. The returned saga undoes the charge and
+    /// transfers, see `check_recorded`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn add_cause_donation(
+        &self,
+        txn: &TStore,
+        user: &UserRow,
+        cause_id: Uuid,
+        amount: Money,
+        account: Option<AccountRow>,
+        cover_fees: bool,
+        idempotency_key: &Option<IdempotencyKey>,
+    ) -> Result<(DonationRow, Saga), Status> {
+        let (stripe_currency, currency_code) = currency(&amount)?;
+        let bank_account_id = bank_account_id(&account)?;
+        let fee_breakdown = self.fee_breakdown(&amount, &bank_account_id, cover_fees)?;
+        let customer_id = user
+            .stripe_customer_id
+            .parse()
+            .map_err(|e| internal!("failed to parse stripe customer id: {:?}", e))?;
+
+        let cause = self
+            .database
+            .on_demand()
+            .find_cause_by_id(cause_id)
+            .await?
+            .ok_or(entity_not_found("cause"))?;
+        // The donation is made as the user, whichever peer requested it.
+        Policy::Owner(cause.cause.user_id).authorize(&Peer::User(user.clone()))?;
+        if cause.cause.archive_time.is_some() {
+            return Err(failed_precondition!("cause is archived"));
+        }
+        if cause.cause.state != CauseState::Active {
+            return Err(failed_precondition!("cause is a draft"));
+        }
+
+        // Resolve the connected account of every recipient before moving money.
+        let mut recipients = Vec::new();
+        let mut weights = Vec::new();
+        for cause_recipient in cause.cause_recipients.inner() {
+            let nonprofit = self
+                .database
+                .on_demand()
+                .find_nonprofit_by_id(cause_recipient.nonprofit_id)
+                .await?
+                .ok_or(entity_not_found("nonprofit"))?;
+            let affiliate = nonprofit.affiliate.ok_or(invalid_argument!(
+                "nonprofit is not affiliated: {0}",
+                cause_recipient.nonprofit_id
+            ))?;
+            validate_currency(&affiliate, amount.currency())?;
+            recipients.push((cause_recipient.nonprofit_id, affiliate));
+            weights.push(cause_recipient.weight_basis_points);
+        }
+        if recipients.is_empty() {
+            return Err(invalid_argument!("cause has no recipients"));
+        }
+
+        let shares = fee_breakdown.net_amount()?.allocate(&weights)?;
+        if shares.iter().any(|share| share.subunits_truncated() <= 0) {
+            return Err(invalid_argument!(
+                "amount is too small to split across the cause's recipients"
+            ));
+        }
+
+        // Retries of the request must reuse the transfer group, otherwise stripe
+        // rejects the idempotent charge as having different parameters.
+        let transfer_group = match idempotency_key {
+            Some(idempotency_key) => format!("donation-{0}", idempotency_key.as_str()),
+            None => format!("donation-{0}", Uuid::new_v4()),
+        };
+
+        let mut create_charge = stripe::CreateCharge::default();
+        create_charge.amount = Some(fee_breakdown.charge_amount.subunits_truncated());
+        create_charge.currency = Some(stripe_currency);
+        create_charge.customer = Some(customer_id);
+        create_charge.source = bank_account_id.map(stripe::ChargeSourceParams::BankAccount);
+        create_charge.transfer_group = Some(&transfer_group);
+        let charge = stripe::Charge::create(
+            &idempotent_stripe_client(&self.stripe, idempotency_key, "charge"),
+            create_charge,
+        )
+        .await
+        .map_err(|e| internal!("failed to create stripe charge: {:?}", e))?;
+
+        info!("Created charge: {:?}", charge);
+        if let Some(donation_row) = self.recorded_donation(&charge).await? {
+            return Ok((donation_row, Saga::new()));
+        }
+        replayed_refund_check(&charge)?;
+        let mut saga = Saga::for_request(idempotency_key);
+        saga.record(RefundStripeCharge {
+            client: (*self.stripe).clone(),
+            charge_id: charge.id.clone(),
+            refund_application_fee: false,
+            reverse_transfer: false,
+        });
+
+        let mut new_allocation_rows = Vec::new();
+        for ((nonprofit_id, affiliate), share) in recipients.into_iter().zip(shares) {
+            let mut create_transfer =
+                stripe::CreateTransfer::new(stripe_currency, affiliate.stripe_account_id);
+            create_transfer.amount = Some(share.subunits_truncated());
+            create_transfer.source_transaction = Some(charge.id.clone());
+            create_transfer.transfer_group = Some(&transfer_group);
+            let transfer = saga
+                .check(
+                    stripe::Transfer::create(
+                        &idempotent_stripe_client(
+                            &self.stripe,
+                            idempotency_key,
+                            &format!("transfer-{0}", nonprofit_id),
+                        ),
+                        create_transfer,
+                    )
+                    .await
+                    .map_err(|e| internal!("failed to create stripe transfer: {:?}", e)),
+                )
+                .await?;
+            saga.record(ReverseStripeTransfer {
+                client: (*self.stripe).clone(),
+                transfer_id: transfer.id.clone(),
+            });
+
+            new_allocation_rows.push(NewDonationAllocationRow {
+                nonprofit_id,
+                affiliate_id: affiliate.affiliate_id,
+                amount_units: share.units(),
+                amount_nanos: share.nanos(),
+                stripe_transfer_id: transfer.id.to_string(),
+            });
+        }
+
+        let now = Utc::now();
+        let result = txn
+            .add_donation_and_allocations(
+                NewDonationRow {
+                    create_time: now,
+                    update_time: now,
+                    nonprofit_id: None,
+                    cause_id: Some(cause_id),
+                    user_id: user.user_id,
+                    affiliate_id: None,
+                    currency_code,
+                    amount_units: amount.units(),
+                    amount_nanos: amount.nanos(),
+                    stripe_charge_id: charge.id.to_string(),
+                    stripe_transfer_group: Some(transfer_group.clone()),
+                    status: donation_status(&charge),
+                    account_id: account.map(|account| account.account_id),
+                    cover_fees,
+                    charge_amount_units: fee_breakdown.charge_amount.units(),
+                    charge_amount_nanos: fee_breakdown.charge_amount.nanos(),
+                    processing_fee_units: fee_breakdown.processing_fee.units(),
+                    processing_fee_nanos: fee_breakdown.processing_fee.nanos(),
+                    platform_fee_units: fee_breakdown.platform_fee.units(),
+                    platform_fee_nanos: fee_breakdown.platform_fee.nanos(),
+                },
+                new_allocation_rows,
+            )
+            .await
+            .map(|(donation_row, _)| donation_row)
+            .map_err(Status::from);
+        let donation_row = self
+            .check_recorded(&mut saga, charge.id.as_str(), result)
+            .await?;
+        Ok((donation_row, saga))
+    }
+}
+
+/// Stripe currency and stored currency code of the amount.
+pub(crate) fn currency(amount: &Money) -> Result<(stripe::Currency, String), Status> {
+    let stripe_currency = amount.stripe_currency().map_err(|e| {
+        invalid_argument!(
+            "unsupported currency: {0}: {1:?}",
+            amount.currency().code(),
+            e
+        )
+    })?;
+    Ok((stripe_currency, amount.currency().code().to_string()))
+}
+
+/// Validates that the affiliate's connected account settles the currency
+/// without conversion. The account's currencies are those last reported by
+/// stripe, which `AffiliateRefresher` and the account webhooks keep current.
+pub(crate) fn validate_currency(
+    affiliate: &AffiliateRow,
+    currency: Currency,
+) -> Result<(), Status> {
+    let supported = affiliate
+        .currencies
+        .iter()
+        .any(|code| code.eq_ignore_ascii_case(currency.code()));
+    if !supported {
+        return Err(invalid_argument!(
+            "nonprofit does not accept donations in {0}",
+            currency.code()
+        ));
+    }
+    Ok(())
+}
+
+/// Allocations of a cause donation, none for a donation to a nonprofit.
+async fn allocation_rows(
+    store: &impl DonationStore,
+    donation_row: &DonationRow,
+) -> Result<Vec<DonationAllocationRow>, Status> {
+    if donation_row.cause_id.is_none() {
+        return Ok(Vec::new());
+    }
+    Ok(store
+        .list_donation_allocations_for_donation(donation_row.donation_id)
+        .await?)
+}
+
+/// Stripe bank account of the linked account, if any.
+fn bank_account_id(account: &Option<AccountRow>) -> Result<Option<stripe::BankAccountId>, Status> {
+    account
+        .as_ref()
+        .map(|account| account.stripe_bank_account_id.parse())
+        .transpose()
+        .map_err(|e| internal!("failed to parse stripe bank account id: {:?}", e))
+}
+
+/// Application fee of a charge, none if there's no fee.
+fn application_fee_amount(fee: &Money) -> Option<i64> {
+    Some(fee.subunits_truncated()).filter(|fee| *fee > 0)
+}
+
+/// Fails for a charge which stripe replayed from an earlier attempt that
+/// didn't record its donation and refunded the charge, e.g. because storing
+/// the donation failed. Recording it would count a refunded charge as a
+/// donation.
+fn replayed_refund_check(charge: &stripe::Charge) -> Result<(), Status> {
+    if charge.refunded {
+        return Err(failed_precondition!(
+            "charge {0} was refunded by an earlier attempt",
+            charge.id
+        ));
+    }
+    Ok(())
+}
+
+/// Status of the donation for the charge. Bank account (ACH) charges are
+/// pending until the payment settles, which takes several business days.
+fn donation_status(charge: &stripe::Charge) -> DonationStatus {
+    match charge.status.as_str() {
+        "succeeded" => DonationStatus::Succeeded,
+        "failed" => DonationStatus::Failed,
+        _ => DonationStatus::Pending,
+    }
+}
+
 #[async_trait]
 impl<Db, Store, TStore> DonationService for DonationServiceImpl<Db, Store, TStore>
 where
     Db: DatabaseClient<Store, TStore> + 'static,
-    Store: NonprofitStore + UserStore + OnDemandStore + 'static,
-    TStore: TransactionalStore + 'static,
+    Store: AccountStore
+        + AffiliateStore
+        + CauseStore
+        + DonationStore
+        + NonprofitStore
+        + UserStore
+        + OnDemandStore
+        + 'static,
+    TStore: DonationStore + TransactionalStore + 'static,
     Self: Sync + Send,
 {
     async fn create_donation(
         &self,
         request: Request<CreateDonationRequest>,
     ) -> Result<Response<Donation>, Status> {
+        let peer = Peer::from_request(&request);
+        let idempotency_key = request.extensions().get::<IdempotencyKey>().cloned();
         let message = request.into_inner();
-        let nonprofit_id: Uuid = message
-            .nonprofit_id
-            .clone()
-            .unwrap_field("nonprofit_id")?
-            .proto_field_into("nonprofit_id")?;
         let user_id: Uuid = message
             .user_id
             .clone()
             .unwrap_field("user_id")?
             .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
         let amount: Money = message
             .amount
             .clone()
             .unwrap_field("amount")?
             .proto_field_into("amount")?;
-        let stripe_currency = amount
-            .stripe_currency()
-            .map_err(|e| invalid_argument!("failed to parse currency: {:?}", e))?;
 
         let user = self
             .database
@@ -74,60 +657,188 @@ where
             .find_user_by_id(user_id)
             .await?
             .ok_or(entity_not_found("user"))?;
-        let customer_id = user
-            .stripe_customer_id
-            .parse()
-            .map_err(|e| internal!("failed to parse stripe customer id: {:?}", e))?;
+        // Charges the linked account if provided, i.e. through ACH.
+        let account = match message.account_id.is_empty() {
+            true => None,
+            false => Some(
+                self.database
+                    .on_demand()
+                    .find_account_by_id_for_user(
+                        message.account_id.proto_field_into("account_id")?,
+                        user_id,
+                    )
+                    .await?
+                    .ok_or(entity_not_found("account"))?,
+            ),
+        };
 
-        let nonprofit = self
-            .database
-            .on_demand()
-            .find_nonprofit_by_id(nonprofit_id)
+        let donation_row = match (message.nonprofit_id.is_empty(), message.cause_id.is_empty()) {
+            (false, true) => {
+                let nonprofit_id = message.nonprofit_id.proto_field_into("nonprofit_id")?;
+                self.donate_to_nonprofit(
+                    &user,
+                    nonprofit_id,
+                    amount,
+                    account,
+                    message.cover_fees,
+                    &idempotency_key,
+                )
+                .await?
+            }
+            (true, false) => {
+                let cause_id = message.cause_id.proto_field_into("cause_id")?;
+                self.donate_to_cause(
+                    &user,
+                    cause_id,
+                    amount,
+                    account,
+                    message.cover_fees,
+                    &idempotency_key,
+                )
+                .await?
+            }
+            _ => {
+                return Err(invalid_argument!(
+                    "exactly one of 'nonprofit_id' or 'cause_id' must be specified"
+                ))
+            }
+        };
+
+        let allocation_rows = allocation_rows(&self.database.on_demand(), &donation_row).await?;
+        Ok(Response::new((donation_row, allocation_rows).into_proto()?))
+    }
+
+    async fn get_donation(
+        &self,
+        request: Request<GetDonationRequest>,
+    ) -> Result<Response<Donation>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let donation_id = message
+            .donation_id
+            .unwrap_field("donation_id")?
+            .proto_field_into("donation_id")?;
+
+        let store = self.database.on_demand();
+        let donation_row = store
+            .find_donation_by_id(donation_id)
             .await?
-            .ok_or(entity_not_found("nonprofit"))?;
-        let account_id = nonprofit
-            .affiliate
-            .ok_or(invalid_argument!("nonprofit is not affiliated"))?
-            .stripe_account_id
-            .parse()
-            .map_err(|e| internal!("failed to parse stripe account id: {:?}", e))?;
+            .ok_or(entity_not_found("donation"))?;
+        Policy::Owner(donation_row.user_id).authorize(&peer)?;
+
+        let allocation_rows = allocation_rows(&store, &donation_row).await?;
+        Ok(Response::new((donation_row, allocation_rows).into_proto()?))
+    }
 
-        let mut create_token = stripe::CreateToken::default();
-        create_token.customer = Some(customer_id);
+    async fn list_donations(
+        &self,
+        request: Request<ListDonationsRequest>,
+    ) -> Result<Response<ListDonationsResponse>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
 
-        let nonprofit_stripe_client = (*self.stripe).clone().with_stripe_account(account_id);
-        let stripe_token = stripe::Token::create(&nonprofit_stripe_client, create_token)
-            .await
-            .map_err(|e| internal!("failed to create stripe token: {:?}", e))?;
+        let page_size = min(max(message.page_size, 1), 100);
+        let limit: i64 = (page_size + 1).into();
+        let page_token = DonationPageToken::deserialize_page_token(&message.page_token)
+            .map_err(|e| invalid_argument!("'page_token' is invalid: {:?}", e))?;
 
-        let mut create_charge = stripe::CreateCharge::default();
-        create_charge.amount = Some(amount.subunits_truncated());
-        create_charge.currency = Some(stripe_currency);
-        create_charge.source = Some(stripe::ChargeSourceParams::Token(stripe_token.id));
-        let charge = stripe::Charge::create(&nonprofit_stripe_client, create_charge)
-            .await
-            .map_err(|e| internal!("failed to create stripe charge: {:?}", e))?;
+        let store = self.database.on_demand();
+        let (rows_plus_one, total_count) = match message.filter {
+            Some(Filter::UserId(user_id)) => {
+                let user_id = user_id.proto_field_into("user_id")?;
+                Policy::Owner(user_id).authorize(&peer)?;
+                store
+                    .list_and_count_donations_for_user(limit, page_token, user_id)
+                    .await?
+            }
+            Some(Filter::NonprofitId(nonprofit_id)) => {
+                let nonprofit_id = nonprofit_id.proto_field_into("nonprofit_id")?;
+                Policy::Privileged.authorize(&peer)?;
+                store
+                    .list_and_count_donations_for_nonprofit(limit, page_token, nonprofit_id)
+                    .await?
+            }
+            Some(Filter::AffiliateId(affiliate_id)) => {
+                let affiliate_id = affiliate_id.proto_field_into("affiliate_id")?;
+                let full_affiliate_row = store
+                    .find_affiliate_by_id(affiliate_id)
+                    .await?
+                    .ok_or(entity_not_found("affiliate"))?;
+                Policy::affiliate_managers(&full_affiliate_row).authorize(&peer)?;
+                store
+                    .list_and_count_donations_for_affiliate(limit, page_token, affiliate_id)
+                    .await?
+            }
+            None => return Err(invalid_argument!("'filter' must be specified")),
+        };
 
-        info!("Created charge: {:?}", charge);
+        let (page_rows, next_page_rows) =
+            rows_plus_one.split_at(min(rows_plus_one.len(), page_size as usize));
+
+        // Map rows to protos and serialize page token.
+        let mut donations = Vec::new();
+        for row in page_rows {
+            let allocation_rows = allocation_rows(&store, row).await?;
+            donations.push((row.clone(), allocation_rows).into_proto()?);
+        }
+
+        // Next page token or empty string.
+        let next_page_token = next_page_rows
+            .first()
+            .map(|next_row| next_row.page_token().serialize_page_token())
+            .unwrap_or(Ok("".to_string()))?;
 
-        // - Insert DonationRow to donations table.
-        // Ok(Response::new(Donation {
-        //     donation_id: "".to_string(),
-        //     create_time: None,
-        //     update_time: None,
-        //     nonprofit_id: nonprofit_id.into_proto()?,
-        //     user_id: user_id.into_proto()?,
-        //     amount: Some(amount.into_proto()?),
-        //     cause_id: "".to_string(),
-        //     status: None,
-        // }))
-        todo!()
+        Ok(Response::new(ListDonationsResponse {
+            donations,
+            next_page_token,
+            total_count,
+        }))
     }
 
-    async fn get_donation(
+    async fn get_donation_totals(
         &self,
-        _request: Request<GetDonationRequest>,
-    ) -> Result<Response<Donation>, Status> {
-        todo!()
+        request: Request<GetDonationTotalsRequest>,
+    ) -> Result<Response<GetDonationTotalsResponse>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+
+        let store = self.database.on_demand();
+        let total_rows = match message.filter {
+            Some(get_donation_totals_request::Filter::UserId(user_id)) => {
+                let user_id = user_id.proto_field_into("user_id")?;
+                Policy::Owner(user_id).authorize(&peer)?;
+                store.sum_donations_by_currency_for_user(user_id).await?
+            }
+            Some(get_donation_totals_request::Filter::NonprofitId(nonprofit_id)) => {
+                let nonprofit_id = nonprofit_id.proto_field_into("nonprofit_id")?;
+                Policy::Privileged.authorize(&peer)?;
+                store
+                    .sum_donations_by_currency_for_nonprofit(nonprofit_id)
+                    .await?
+            }
+            Some(get_donation_totals_request::Filter::AffiliateId(affiliate_id)) => {
+                let affiliate_id = affiliate_id.proto_field_into("affiliate_id")?;
+                let full_affiliate_row = store
+                    .find_affiliate_by_id(affiliate_id)
+                    .await?
+                    .ok_or(entity_not_found("affiliate"))?;
+                Policy::affiliate_managers(&full_affiliate_row).authorize(&peer)?;
+                store
+                    .sum_donations_by_currency_for_affiliate(affiliate_id)
+                    .await?
+            }
+            None => return Err(invalid_argument!("'filter' must be specified")),
+        };
+
+        // One total per currency, amounts in different currencies are never added.
+        let mut totals = Vec::new();
+        for total_row in total_rows {
+            let currency = Currency::from_code(&total_row.currency_code)
+                .ok_or(internal!("unknown currency: {0}", total_row.currency_code))?;
+            let total = Money::from_sums(currency, total_row.amount_units, total_row.amount_nanos)?;
+            totals.push(total.into_proto()?);
+        }
+
+        Ok(Response::new(GetDonationTotalsResponse { totals }))
     }
 }
@@ -0,0 +1,535 @@
+use crate::{
+    config::FeesConfig,
+    fees::FeeCalculator,
+    interceptors::authn::Peer,
+    money::Money,
+    protobuf::into::IntoProto,
+    services::donation::DonationServiceImpl,
+    testing::{account_row, affiliate_row, donation_row, nonprofit_row, user_row, FakeStripe},
+    tonic::idempotency::IdempotencyKey,
+};
+use affect_api::affect::{donation_service_server::DonationService, GetDonationRequest};
+use affect_status::Code;
+use affect_storage::{
+    models::{
+        cause::{CauseRecipientRowVec, CauseRow, CauseState, FullCauseRow},
+        donation::{DonationAllocationRow, DonationRow, DonationStatus, NewDonationRow},
+    },
+    Error,
+};
+use affect_storage_mocks::*;
+use chrono::Utc;
+use hyper::Method;
+use iso_currency::Currency;
+use mockall::Sequence;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tonic::Request;
+use uuid::Uuid;
+
+fn donation_service(
+    database: MockDatabaseClient,
+    stripe: stripe::Client,
+) -> DonationServiceImpl<MockDatabaseClient, MockStore, MockStore> {
+    DonationServiceImpl::new(
+        Arc::new(database),
+        Arc::new(stripe),
+        FeeCalculator::new(FeesConfig::default()).unwrap(),
+    )
+}
+
+fn charge(charge_id: &str, amount: i64, status: &str) -> Value {
+    json!({
+        "id": charge_id,
+        "object": "charge",
+        "amount": amount,
+        "amount_captured": amount,
+        "amount_refunded": 0,
+        "billing_details": {},
+        "captured": true,
+        "created": 1,
+        "currency": "usd",
+        "disputed": false,
+        "livemode": false,
+        "metadata": {},
+        "paid": true,
+        "refunded": false,
+        "refunds": {"object": "list", "data": [], "has_more": false, "url": "/v1/refunds"},
+        "status": status,
+    })
+}
+
+/// Stripe where charges are created with the status.
+fn stripe(charge_status: &str) -> FakeStripe {
+    FakeStripe::start(vec![
+        (
+            Method::POST,
+            "/v1/tokens",
+            json!({
+                "id": "tok_123",
+                "object": "token",
+                "created": 1,
+                "livemode": false,
+                "type": "card",
+                "used": false,
+            }),
+        ),
+        (
+            Method::POST,
+            "/v1/charges",
+            charge("ch_123", 1061, charge_status),
+        ),
+        (
+            Method::POST,
+            "/v1/refunds",
+            json!({
+                "id": "re_123",
+                "object": "refund",
+                "amount": 1061,
+                "created": 1,
+                "currency": "usd",
+                "metadata": {},
+            }),
+        ),
+    ])
+}
+
+fn new_donation_row(
+    user_id: Uuid,
+    nonprofit_id: Option<Uuid>,
+    cause_id: Option<Uuid>,
+) -> NewDonationRow {
+    NewDonationRow {
+        create_time: Utc::now(),
+        update_time: Utc::now(),
+        nonprofit_id,
+        cause_id,
+        user_id,
+        affiliate_id: None,
+        currency_code: "USD".to_string(),
+        amount_units: 10,
+        amount_nanos: 0,
+        stripe_charge_id: "ch_123".to_string(),
+        stripe_transfer_group: cause_id.map(|_| "group".to_string()),
+        status: DonationStatus::Succeeded,
+        account_id: None,
+        cover_fees: false,
+        charge_amount_units: 10,
+        charge_amount_nanos: 0,
+        processing_fee_units: 0,
+        processing_fee_nanos: 590_000_000,
+        platform_fee_units: 0,
+        platform_fee_nanos: 0,
+    }
+}
+
+/// Store which finds the donation of a charge.
+fn recorded_donation_store(donation_row: Option<DonationRow>) -> MockStore {
+    let mut store = MockStore::new();
+    store
+        .expect_find_donation_by_stripe_charge_id()
+        .withf(|stripe_charge_id| stripe_charge_id == "ch_123")
+        .times(1)
+        .return_once(move |_| Ok(donation_row));
+    store
+}
+
+/// Database with an affiliated nonprofit, adding donations with the result.
+/// If adding fails, the donation of the charge is looked up again and found
+/// if `recorded_after_failure` is some. The transaction is committed unless
+/// the donation is neither added nor found.
+fn database(
+    nonprofit_id: Uuid,
+    add_donation: impl FnOnce(NewDonationRow) -> Result<DonationRow, Error> + Send + 'static,
+    recorded_after_failure: Option<Option<DonationRow>>,
+) -> MockDatabaseClient {
+    let mut database = MockDatabaseClient::new();
+    let committed = !matches!(recorded_after_failure, Some(None));
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        let mut seq = Sequence::new();
+        txn.expect_add_donation()
+            .withf(move |new_row| new_row.nonprofit_id == Some(nonprofit_id))
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(add_donation);
+        txn.expect_commit()
+            .times(committed as usize)
+            .in_sequence(&mut seq)
+            .return_once(|| Ok(()));
+        Ok(txn)
+    });
+    let mut seq = Sequence::new();
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(|| {
+            let mut store = MockStore::new();
+            store
+                .expect_find_nonprofit_by_id()
+                .returning(|nonprofit_id| {
+                    Ok(Some(nonprofit_row(
+                        nonprofit_id,
+                        Some(affiliate_row("acct_123")),
+                    )))
+                });
+            store
+        });
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(|| recorded_donation_store(None));
+    if let Some(donation_row) = recorded_after_failure {
+        database
+            .expect_on_demand()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| recorded_donation_store(donation_row));
+    }
+    database
+}
+
+#[tokio::test]
+async fn donate_to_nonprofit_adds_donation() -> Result<(), anyhow::Error> {
+    let nonprofit_id = Uuid::new_v4();
+    let stripe = stripe("succeeded");
+    let service = donation_service(
+        database(nonprofit_id, |new_row| Ok(donation_row(new_row)), None),
+        stripe.client(),
+    );
+
+    let donation = service
+        .donate_to_nonprofit(
+            &user_row(Uuid::new_v4()),
+            nonprofit_id,
+            Money::from_subunits(Currency::USD, 1000),
+            None,
+            true,
+            &Some(IdempotencyKey::new("key".to_string())),
+        )
+        .await?;
+
+    assert_eq!(donation.stripe_charge_id, "ch_123");
+    assert_eq!(donation.status, DonationStatus::Succeeded);
+    assert!(donation.cover_fees);
+    assert_eq!(
+        (donation.charge_amount_units, donation.charge_amount_nanos),
+        (10, 610_000_000)
+    );
+    assert!(!stripe
+        .calls()
+        .contains(&(Method::POST, "/v1/refunds".to_string())));
+    Ok(())
+}
+
+#[tokio::test]
+async fn donate_to_nonprofit_refunds_charge_if_insert_fails() -> Result<(), anyhow::Error> {
+    let nonprofit_id = Uuid::new_v4();
+    let stripe = stripe("succeeded");
+    let service = donation_service(
+        database(
+            nonprofit_id,
+            |_| Err(Error::Other(anyhow::anyhow!("database unavailable"))),
+            Some(None),
+        ),
+        stripe.client(),
+    );
+
+    let idempotency_key = IdempotencyKey::new("key".to_string());
+    let status = service
+        .donate_to_nonprofit(
+            &user_row(Uuid::new_v4()),
+            nonprofit_id,
+            Money::from_subunits(Currency::USD, 1000),
+            None,
+            false,
+            &Some(idempotency_key.clone()),
+        )
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::Internal);
+    assert_eq!(
+        stripe.calls(),
+        vec![
+            (Method::POST, "/v1/tokens".to_string()),
+            (Method::POST, "/v1/charges".to_string()),
+            (Method::POST, "/v1/refunds".to_string()),
+        ]
+    );
+    assert!(idempotency_key.is_compensated());
+    Ok(())
+}
+
+#[tokio::test]
+async fn donate_to_nonprofit_returns_donation_of_replayed_charge() -> Result<(), anyhow::Error> {
+    let nonprofit_id = Uuid::new_v4();
+    let user = user_row(Uuid::new_v4());
+    let recorded = donation_row(new_donation_row(user.user_id, Some(nonprofit_id), None));
+    let recorded_id = recorded.donation_id;
+    // An earlier attempt with the key recorded the charge stripe replays, the
+    // donation is neither added again nor refunded.
+    let mut database = MockDatabaseClient::new();
+    database.expect_begin().times(1).return_once(|| {
+        let mut txn = MockStore::new();
+        txn.expect_add_donation().never();
+        txn.expect_commit().times(1).return_once(|| Ok(()));
+        Ok(txn)
+    });
+    let mut seq = Sequence::new();
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(|| {
+            let mut store = MockStore::new();
+            store
+                .expect_find_nonprofit_by_id()
+                .returning(|nonprofit_id| {
+                    Ok(Some(nonprofit_row(
+                        nonprofit_id,
+                        Some(affiliate_row("acct_123")),
+                    )))
+                });
+            store
+        });
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(|| recorded_donation_store(Some(recorded)));
+    let stripe = stripe("succeeded");
+    let service = donation_service(database, stripe.client());
+
+    let idempotency_key = IdempotencyKey::new("key".to_string());
+    let donation = service
+        .donate_to_nonprofit(
+            &user,
+            nonprofit_id,
+            Money::from_subunits(Currency::USD, 1000),
+            None,
+            false,
+            &Some(idempotency_key.clone()),
+        )
+        .await?;
+
+    assert_eq!(donation.donation_id, recorded_id);
+    assert!(!stripe
+        .calls()
+        .contains(&(Method::POST, "/v1/refunds".to_string())));
+    assert!(!idempotency_key.is_compensated());
+    Ok(())
+}
+
+#[tokio::test]
+async fn donate_to_nonprofit_keeps_charge_recorded_concurrently() -> Result<(), anyhow::Error> {
+    let nonprofit_id = Uuid::new_v4();
+    let user = user_row(Uuid::new_v4());
+    let recorded = donation_row(new_donation_row(user.user_id, Some(nonprofit_id), None));
+    let recorded_id = recorded.donation_id;
+    // Adding fails because a concurrent attempt added the donation first.
+    let stripe = stripe("succeeded");
+    let service = donation_service(
+        database(
+            nonprofit_id,
+            |_| Err(Error::Other(anyhow::anyhow!("duplicate stripe_charge_id"))),
+            Some(Some(recorded)),
+        ),
+        stripe.client(),
+    );
+
+    let donation = service
+        .donate_to_nonprofit(
+            &user,
+            nonprofit_id,
+            Money::from_subunits(Currency::USD, 1000),
+            None,
+            false,
+            &Some(IdempotencyKey::new("key".to_string())),
+        )
+        .await?;
+
+    assert_eq!(donation.donation_id, recorded_id);
+    assert!(!stripe
+        .calls()
+        .contains(&(Method::POST, "/v1/refunds".to_string())));
+    Ok(())
+}
+
+#[tokio::test]
+async fn donate_to_nonprofit_from_bank_account_is_pending() -> Result<(), anyhow::Error> {
+    let nonprofit_id = Uuid::new_v4();
+    let account = account_row(Uuid::new_v4());
+    let account_id = account.account_id;
+    // ACH charges are pending until the payment settles.
+    let stripe = stripe("pending");
+    let service = donation_service(
+        database(nonprofit_id, |new_row| Ok(donation_row(new_row)), None),
+        stripe.client(),
+    );
+
+    let donation = service
+        .donate_to_nonprofit(
+            &user_row(Uuid::new_v4()),
+            nonprofit_id,
+            Money::from_subunits(Currency::USD, 1000),
+            Some(account),
+            false,
+            &None,
+        )
+        .await?;
+
+    assert_eq!(donation.status, DonationStatus::Pending);
+    assert_eq!(donation.account_id, Some(account_id));
+    assert_eq!(donation.settle_time, None);
+    // Bank accounts are charged on the platform and transferred, without a
+    // token on the connected account.
+    assert_eq!(
+        stripe.calls(),
+        vec![(Method::POST, "/v1/charges".to_string()),]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn donate_to_nonprofit_rejects_currency_of_affiliate() -> Result<(), anyhow::Error> {
+    let mut database = MockDatabaseClient::new();
+    let mut txn = MockStore::new();
+    txn.expect_add_donation().never();
+    txn.expect_commit().never();
+    database.expect_begin().times(1).return_once(|| Ok(txn));
+    database.expect_on_demand().returning(|| {
+        let mut store = MockStore::new();
+        store
+            .expect_find_nonprofit_by_id()
+            .returning(|nonprofit_id| {
+                Ok(Some(nonprofit_row(
+                    nonprofit_id,
+                    Some(affiliate_row("acct_123")),
+                )))
+            });
+        store
+    });
+    let stripe = stripe("succeeded");
+    let service = donation_service(database, stripe.client());
+
+    let status = service
+        .donate_to_nonprofit(
+            &user_row(Uuid::new_v4()),
+            Uuid::new_v4(),
+            Money::from_subunits(Currency::EUR, 1000),
+            None,
+            true,
+            &None,
+        )
+        .await
+        .unwrap_err();
+
+    // The affiliate's stored currencies are checked without asking stripe.
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(stripe.calls().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn donate_to_cause_of_other_user_is_denied() -> Result<(), anyhow::Error> {
+    let mut database = MockDatabaseClient::new();
+    let mut txn = MockStore::new();
+    txn.expect_add_donation().never();
+    txn.expect_commit().never();
+    database.expect_begin().times(1).return_once(|| Ok(txn));
+    database.expect_on_demand().times(1).return_once(|| {
+        let mut store = MockStore::new();
+        store.expect_find_cause_by_id().return_once(|cause_id| {
+            Ok(Some(FullCauseRow {
+                cause: CauseRow {
+                    cause_id,
+                    create_time: Utc::now(),
+                    update_time: Utc::now(),
+                    user_id: Uuid::new_v4(),
+                    name: "Climate".to_string(),
+                    archive_time: None,
+                    state: CauseState::Active,
+                },
+                cause_recipients: CauseRecipientRowVec::new(Vec::new()),
+            }))
+        });
+        store
+    });
+    let stripe = stripe("succeeded");
+    let service = donation_service(database, stripe.client());
+
+    let status = service
+        .donate_to_cause(
+            &user_row(Uuid::new_v4()),
+            Uuid::new_v4(),
+            Money::from_subunits(Currency::USD, 1000),
+            None,
+            true,
+            &None,
+        )
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert!(stripe.calls().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_donation_includes_allocations_of_cause_donation() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let nonprofit_id = Uuid::new_v4();
+    let affiliate_id = Uuid::new_v4();
+    let donation = donation_row(new_donation_row(user_id, None, Some(Uuid::new_v4())));
+    let donation_id = donation.donation_id;
+    let mut database = MockDatabaseClient::new();
+    database.expect_on_demand().times(1).return_once(move || {
+        let mut store = MockStore::new();
+        store
+            .expect_find_donation_by_id()
+            .return_once(move |_| Ok(Some(donation)));
+        store
+            .expect_list_donation_allocations_for_donation()
+            .withf(move |id| *id == donation_id)
+            .return_once(move |donation_id| {
+                Ok(vec![DonationAllocationRow {
+                    donation_id,
+                    nonprofit_id,
+                    create_time: Utc::now(),
+                    update_time: Utc::now(),
+                    affiliate_id,
+                    amount_units: 9,
+                    amount_nanos: 410_000_000,
+                    stripe_transfer_id: "tr_123".to_string(),
+                }])
+            });
+        store
+    });
+    let service = donation_service(database, stripe("succeeded").client());
+
+    let mut request = Request::new(GetDonationRequest {
+        donation_id: donation_id.to_string(),
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+    let donation = service.get_donation(request).await?.into_inner();
+
+    assert_eq!(donation.allocations.len(), 1);
+    assert_eq!(
+        donation.allocations[0].nonprofit_id,
+        nonprofit_id.to_string()
+    );
+    assert_eq!(
+        donation.allocations[0].affiliate_id,
+        affiliate_id.to_string()
+    );
+    assert_eq!(
+        donation.allocations[0].amount,
+        Some(Money::from_sums(Currency::USD, 9, 410_000_000)?.into_proto()?)
+    );
+    Ok(())
+}
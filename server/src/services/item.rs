@@ -1,7 +1,14 @@
+use crate::interceptors::authn::Peer;
+use crate::plaid_api::{PlaidApi, PlaidItemAccounts};
+use crate::policy::Policy;
 use crate::protobuf::into::{IntoProto, ProtoInto};
+use crate::saga::{DetachStripeSource, Saga};
+use crate::tonic::idempotency::{idempotent_stripe_client, IdempotencyKey};
+use crate::web::WebLinks;
 use affect_api::affect::{
     item_service_server::ItemService, CreateItemRequest, DeleteItemRequest,
     GenerateLinkTokenRequest, Item, LinkToken, ListItemsRequest, ListItemsResponse,
+    ReconcileItemRequest,
 };
 use affect_status::well_known::UnwrapField;
 use affect_status::{internal, invalid_argument, not_found};
@@ -26,22 +33,95 @@ use std::{
 use tonic::{Request, Response, Status};
 use uuid::Uuid;
 
+#[cfg(test)]
+mod tests;
+
 pub struct ItemServiceImpl<Db, Store, TStore> {
     database: Arc<Db>,
-    plaid: Arc<plaid::Client>,
+    plaid: Arc<dyn PlaidApi>,
     stripe: Arc<stripe::Client>,
+    plaid_webhook_url: Option<String>,
+    web_links: WebLinks,
     _marker: PhantomData<(Store, TStore)>,
 }
 
 impl<Db, Store, TStore> ItemServiceImpl<Db, Store, TStore> {
-    pub fn new(database: Arc<Db>, plaid: Arc<plaid::Client>, stripe: Arc<stripe::Client>) -> Self {
+    pub fn new(
+        database: Arc<Db>,
+        plaid: Arc<dyn PlaidApi>,
+        stripe: Arc<stripe::Client>,
+        plaid_webhook_url: Option<String>,
+        web_links: WebLinks,
+    ) -> Self {
         Self {
             database,
             plaid,
             stripe,
+            plaid_webhook_url,
+            web_links,
             _marker: PhantomData,
         }
     }
+
+    /// Attaches the plaid account to the stripe customer, returning the id of
+    /// the stripe bank account.
+    async fn attach_bank_account(
+        &self,
+        plaid_access_token: &str,
+        plaid_account_id: &str,
+        customer_id: &stripe::CustomerId,
+        idempotency_key: &Option<IdempotencyKey>,
+    ) -> Result<stripe::BankAccountId, Status> {
+        let plaid_stripe_response = self
+            .plaid
+            .stripe_create_bank_account_token(plaid_access_token, plaid_account_id)
+            .await
+            .map_err(|e| internal!("failed to create stripe bank account token: {:?}", e))?;
+
+        let stripe_payment_source_params =
+            stripe::PaymentSourceParams::Token(stripe::TokenId::Bank(
+                plaid_stripe_response
+                    .parse()
+                    .map_err(|e| internal!("failed to parse stripe bank account token: {:?}", e))?,
+            ));
+        match stripe::Customer::attach_source(
+            &idempotent_stripe_client(
+                &self.stripe,
+                idempotency_key,
+                &format!("attach-source-{0}", plaid_account_id),
+            ),
+            customer_id,
+            stripe_payment_source_params,
+        )
+        .await
+        .map_err(|e| internal!("failed to attach source to stripe customer: {:?}", e))?
+        {
+            stripe::PaymentSource::BankAccount(bank_account) => Ok(bank_account.id),
+            _ => Err(internal!(
+                "expected bank account payment source returned from stripe"
+            )),
+        }
+    }
+
+    /// Detaches the bank accounts from the stripe customer.
+    async fn detach_bank_accounts(
+        &self,
+        customer_id: &stripe::CustomerId,
+        account_rows: &[AccountRow],
+    ) -> Result<(), Status> {
+        for account in account_rows {
+            let source_id = stripe::PaymentSourceId::BankAccount(
+                account
+                    .stripe_bank_account_id
+                    .parse()
+                    .map_err(|e| internal!("failed to parse stripe bank account id: {:?}", e))?,
+            );
+            stripe::Customer::detach_source(&self.stripe, customer_id, &source_id)
+                .await
+                .map_err(|e| internal!("failed to detach source from stripe customer: {:?}", e))?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -55,11 +135,13 @@ where
         &self,
         request: Request<GenerateLinkTokenRequest>,
     ) -> Result<Response<LinkToken>, Status> {
+        let peer = Peer::from_request(&request);
         let message = request.into_inner();
         let user_id = message
             .user_id
             .unwrap_field("user_id")?
             .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
         let user_row = self
             .database
             .on_demand()
@@ -67,6 +149,28 @@ where
             .await?
             .ok_or(not_found!("user not found"))?;
 
+        // Link runs in update mode when repairing an existing item.
+        let access_token = match message.item_id.as_str() {
+            "" => None,
+            item_id => {
+                let item_row = self
+                    .database
+                    .on_demand()
+                    .find_item_by_id(item_id.to_string().proto_field_into("item_id")?)
+                    .await?
+                    .ok_or(not_found!("item not found"))?;
+                Policy::Owner(item_row.user_id).authorize(&peer)?;
+                if item_row.user_id != user_id {
+                    return Err(not_found!("item not found"));
+                }
+                Some(item_row.plaid_access_token)
+            }
+        };
+        let products = match access_token {
+            Some(_) => Vec::new(),
+            None => [plaid::SupportedProduct::Transactions].to_vec(),
+        };
+
         #[allow(deprecated)]
         let plaid_response = self
             .plaid
@@ -77,11 +181,11 @@ where
                 user: plaid::EndUser {
                     client_user_id: user_row.user_id.to_string(),
                 },
-                products: [plaid::SupportedProduct::Transactions].to_vec(),
-                webhook: None,
-                access_token: None,
+                products,
+                webhook: self.plaid_webhook_url.clone(),
+                access_token,
                 link_customization_name: None,
-                redirect_uri: None,
+                redirect_uri: self.web_links.plaid_redirect_url(),
                 android_package_name: None,
                 account_filters: None,
                 institution_id: None,
@@ -103,11 +207,14 @@ where
         &self,
         request: Request<CreateItemRequest>,
     ) -> Result<Response<Item>, Status> {
+        let peer = Peer::from_request(&request);
+        let idempotency_key = request.extensions().get::<IdempotencyKey>().cloned();
         let message = request.into_inner();
         let user_id = message
             .user_id
             .unwrap_field("user_id")?
             .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
         let user_row = self
             .database
             .on_demand()
@@ -121,80 +228,293 @@ where
             .await
             .map_err(|e| invalid_argument!("failed to exchange public token: {:?}", e))?;
 
-        let plaid_accounts_response = self
+        let item_accounts = self
             .plaid
             .accounts(&plaid_item_response.access_token)
             .await
-            .map_err(|e| internal!("failed to fetch accounts: {:?}", e))?;
-        let plaid_account = match plaid_accounts_response.accounts.into_iter().nth(0) {
-            Some(account) => account,
-            None => {
-                return Err(internal!("expected only 1 linked account"));
+            .map_err(|e| internal!("failed to fetch accounts: {:?}", e))?
+            .accounts;
+
+        // Link the accounts selected in plaid link, or every eligible account
+        // if the user did not select any.
+        let link_all = message.plaid_account_ids.is_empty();
+        let mut plaid_accounts = Vec::new();
+        for plaid_account in item_accounts {
+            let eligible = is_eligible_account(&plaid_account.r#type, &plaid_account.subtype);
+            if link_all {
+                if eligible {
+                    plaid_accounts.push(plaid_account);
+                }
+            } else if message
+                .plaid_account_ids
+                .contains(&plaid_account.account_id)
+            {
+                if !eligible {
+                    return Err(invalid_argument!(
+                        "account is not a checking or savings account: {0}",
+                        plaid_account.account_id
+                    ));
+                }
+                plaid_accounts.push(plaid_account);
             }
-        };
+        }
+        for plaid_account_id in &message.plaid_account_ids {
+            if !plaid_accounts
+                .iter()
+                .any(|plaid_account| &plaid_account.account_id == plaid_account_id)
+            {
+                return Err(invalid_argument!(
+                    "'plaid_account_ids' contains unknown account: {0}",
+                    plaid_account_id
+                ));
+            }
+        }
+        if plaid_accounts.is_empty() {
+            return Err(invalid_argument!(
+                "item has no checking or savings accounts"
+            ));
+        }
 
-        let plaid_stripe_response = self
+        let customer_id = user_row
+            .stripe_customer_id
+            .parse()
+            .map_err(|e| internal!("failed to parse stripe customer id: {:?}", e))?;
+        let mut saga = Saga::for_request(&idempotency_key);
+        let mut new_account_rows = Vec::new();
+        for plaid_account in plaid_accounts {
+            let bank_account_id = saga
+                .check(
+                    self.attach_bank_account(
+                        &plaid_item_response.access_token,
+                        &plaid_account.account_id,
+                        &customer_id,
+                        &idempotency_key,
+                    )
+                    .await,
+                )
+                .await?;
+            saga.record(DetachStripeSource {
+                client: (*self.stripe).clone(),
+                customer_id: customer_id.clone(),
+                source_id: stripe::PaymentSourceId::BankAccount(bank_account_id.clone()),
+            });
+            new_account_rows.push(NewItemAccountRow {
+                plaid_account_id: plaid_account.account_id,
+                name: plaid_account.name,
+                mask: plaid_account.mask,
+                stripe_bank_account_id: bank_account_id.to_string(),
+            });
+        }
+
+        let result: Result<(ItemRow, Vec<AccountRow>), Status> = async {
+            let now = Utc::now();
+            let store = self.database.begin().await?;
+            let rows = store
+                .add_item_and_accounts(
+                    NewItemRow {
+                        create_time: now,
+                        update_time: now,
+                        user_id,
+                        plaid_item_id: plaid_item_response.item_id,
+                        plaid_access_token: plaid_item_response.access_token,
+                    },
+                    new_account_rows,
+                )
+                .await?;
+            store.commit().await?;
+            Ok(rows)
+        }
+        .await;
+        let (item_row, account_rows) = saga.check(result).await?;
+
+        Ok(Response::new((item_row, account_rows).into_proto()?))
+    }
+
+    async fn reconcile_item(
+        &self,
+        request: Request<ReconcileItemRequest>,
+    ) -> Result<Response<Item>, Status> {
+        let peer = Peer::from_request(&request);
+        let idempotency_key = request.extensions().get::<IdempotencyKey>().cloned();
+        let message = request.into_inner();
+        let item_id = message
+            .item_id
+            .unwrap_field("item_id")?
+            .proto_field_into("item_id")?;
+
+        let item_row = self
+            .database
+            .on_demand()
+            .find_item_by_id(item_id)
+            .await?
+            .ok_or(not_found!("item not found"))?;
+        Policy::Owner(item_row.user_id).authorize(&peer)?;
+        let user_row = self
+            .database
+            .on_demand()
+            .find_user_by_id(item_row.user_id)
+            .await?
+            .ok_or(internal!("user not found"))?;
+        let customer_id = user_row
+            .stripe_customer_id
+            .parse()
+            .map_err(|e| internal!("failed to parse stripe customer id: {:?}", e))?;
+
+        let PlaidItemAccounts {
+            accounts: item_accounts,
+            item_error_code,
+        } = self
             .plaid
-            .stripe_create_bank_account_token(
-                &plaid_item_response.access_token,
-                &plaid_account.account_id,
-            )
+            .accounts(&item_row.plaid_access_token)
             .await
-            .map_err(|e| internal!("failed to create stripe bank account token: {:?}", e))?;
+            .map_err(|e| internal!("failed to fetch accounts: {:?}", e))?;
+        for plaid_account_id in &message.plaid_account_ids {
+            if !item_accounts
+                .iter()
+                .any(|plaid_account| &plaid_account.account_id == plaid_account_id)
+            {
+                return Err(invalid_argument!(
+                    "'plaid_account_ids' contains unknown account: {0}",
+                    plaid_account_id
+                ));
+            }
+        }
+        let account_rows = self
+            .database
+            .on_demand()
+            .list_accounts_for_item(item_id)
+            .await?;
 
-        let stripe_payment_source_params =
-            stripe::PaymentSourceParams::Token(stripe::TokenId::Bank(
-                plaid_stripe_response
-                    .stripe_bank_account_token
-                    .parse()
-                    .map_err(|e| internal!("failed to parse stripe bank account token: {:?}", e))?,
-            ));
-        let stripe_payment_source = match stripe::Customer::attach_source(
-            &self.stripe,
-            &user_row
-                .stripe_customer_id
-                .parse()
-                .map_err(|e| internal!("failed to parse stripe customer id: {:?}", e))?,
-            stripe_payment_source_params,
-        )
-        .await
-        .map_err(|e| internal!("failed to attach source to stripe customer: {:?}", e))?
-        {
-            stripe::PaymentSource::BankAccount(bank_account) => bank_account,
-            _ => {
-                return Err(internal!(
-                    "expected bank account payment source returned from stripe"
-                ))
+        // Linked accounts which are no longer available at the institution.
+        let removed_account_rows = account_rows
+            .iter()
+            .filter(|account_row| {
+                !item_accounts
+                    .iter()
+                    .any(|plaid_account| plaid_account.account_id == account_row.plaid_account_id)
+            })
+            .cloned()
+            .collect::<Vec<AccountRow>>();
+
+        // Linked accounts whose details changed, and accounts the user selected
+        // in update mode which are not linked yet.
+        let mut saga = Saga::for_request(&idempotency_key);
+        let mut changed_account_rows = Vec::new();
+        let mut new_account_rows = Vec::new();
+        for plaid_account in item_accounts {
+            match account_rows
+                .iter()
+                .find(|account_row| account_row.plaid_account_id == plaid_account.account_id)
+            {
+                Some(account_row) => {
+                    if account_row.name != plaid_account.name
+                        || account_row.mask != plaid_account.mask
+                    {
+                        changed_account_rows.push((
+                            account_row.account_id,
+                            plaid_account.name,
+                            plaid_account.mask,
+                        ));
+                    }
+                }
+                None => {
+                    if !message
+                        .plaid_account_ids
+                        .contains(&plaid_account.account_id)
+                    {
+                        continue;
+                    }
+                    if !is_eligible_account(&plaid_account.r#type, &plaid_account.subtype) {
+                        return saga
+                            .check(Err(invalid_argument!(
+                                "account is not a checking or savings account: {0}",
+                                plaid_account.account_id
+                            )))
+                            .await;
+                    }
+                    let bank_account_id = saga
+                        .check(
+                            self.attach_bank_account(
+                                &item_row.plaid_access_token,
+                                &plaid_account.account_id,
+                                &customer_id,
+                                &idempotency_key,
+                            )
+                            .await,
+                        )
+                        .await?;
+                    saga.record(DetachStripeSource {
+                        client: (*self.stripe).clone(),
+                        customer_id: customer_id.clone(),
+                        source_id: stripe::PaymentSourceId::BankAccount(bank_account_id.clone()),
+                    });
+                    new_account_rows.push(NewAccountRow {
+                        create_time: Utc::now(),
+                        update_time: Utc::now(),
+                        item_id,
+                        plaid_account_id: plaid_account.account_id,
+                        name: plaid_account.name,
+                        mask: plaid_account.mask,
+                        stripe_bank_account_id: bank_account_id.to_string(),
+                    });
+                }
             }
-        };
+        }
+
+        let result: Result<(), Status> = async {
+            let now = Utc::now();
+            let store = self.database.begin().await?;
+            for account_row in &removed_account_rows {
+                store.delete_account(account_row.account_id).await?;
+            }
+            for (account_id, name, mask) in changed_account_rows {
+                store.update_account(account_id, name, mask, now).await?;
+            }
+            for new_account_row in new_account_rows {
+                store.add_account(new_account_row).await?;
+            }
+            // The item is only repaired once plaid no longer reports an error
+            // for it, otherwise it keeps the status set by plaid's webhooks.
+            if item_error_code.is_none() {
+                store
+                    .update_item_status_by_plaid_item_id(
+                        &item_row.plaid_item_id,
+                        ItemStatus::Healthy,
+                        None,
+                        None,
+                        now,
+                    )
+                    .await?;
+            }
+            if !message.plaid_account_ids.is_empty() {
+                store
+                    .update_item_new_accounts_available_by_plaid_item_id(
+                        &item_row.plaid_item_id,
+                        false,
+                        now,
+                    )
+                    .await?;
+            }
+            store.commit().await?;
+            Ok(())
+        }
+        .await;
+        saga.check(result).await?;
+
+        self.detach_bank_accounts(&customer_id, &removed_account_rows)
+            .await?;
 
-        let now = Utc::now();
         let item_row = self
             .database
             .on_demand()
-            .add_item(NewItemRow {
-                create_time: now,
-                update_time: now,
-                user_id,
-                plaid_item_id: plaid_item_response.item_id,
-                plaid_access_token: plaid_item_response.access_token,
-            })
+            .find_item_by_id(item_id)
+            .await?
+            .ok_or(not_found!("item not found"))?;
+        let account_rows = self
+            .database
+            .on_demand()
+            .list_accounts_for_item(item_id)
             .await?;
-
-        let account_rows = vec![
-            self.database
-                .on_demand()
-                .add_account(NewAccountRow {
-                    create_time: now,
-                    update_time: now,
-                    item_id: item_row.item_id.clone(),
-                    plaid_account_id: plaid_account.account_id,
-                    name: plaid_account.name,
-                    mask: plaid_account.mask,
-                    stripe_bank_account_id: stripe_payment_source.id.to_string(),
-                })
-                .await?,
-        ];
         Ok(Response::new((item_row, account_rows).into_proto()?))
     }
 
@@ -202,6 +522,7 @@ where
         &self,
         request: Request<ListItemsRequest>,
     ) -> Result<Response<ListItemsResponse>, Status> {
+        let peer = Peer::from_request(&request);
         let message = request.into_inner();
 
         let page_size = min(max(message.page_size, 1), 100);
@@ -211,6 +532,7 @@ where
             .user_id
             .unwrap_field("user_id")?
             .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
 
         let (rows_plus_one, total_count) = self
             .database
@@ -249,6 +571,7 @@ where
         &self,
         request: Request<DeleteItemRequest>,
     ) -> Result<Response<()>, Status> {
+        let peer = Peer::from_request(&request);
         let message = request.into_inner();
         let item_id = message
             .item_id
@@ -260,6 +583,7 @@ where
             .find_item_by_id(item_id)
             .await?
             .ok_or(not_found!("item not found"))?;
+        Policy::Owner(item.user_id).authorize(&peer)?;
         let accounts = store.list_accounts_for_item(item.item_id).await?;
         let account_ids = accounts
             .iter()
@@ -281,18 +605,21 @@ where
             .stripe_customer_id
             .parse()
             .map_err(|e| internal!("failed to parse stripe customer id: {:?}", e))?;
-        for account in accounts {
-            let source_id = stripe::PaymentSourceId::BankAccount(
-                account
-                    .stripe_bank_account_id
-                    .parse()
-                    .map_err(|e| internal!("failed to parse stripe bank account id: {:?}", e))?,
-            );
-            stripe::Customer::detach_source(&self.stripe, &customer_id, &source_id)
-                .await
-                .map_err(|e| internal!("failed to detach source from stripe customer: {:?}", e))?;
-        }
+        self.detach_bank_accounts(&customer_id, &accounts).await?;
 
         Ok(Response::new(()))
     }
 }
+
+/// Whether a plaid account can be attached to stripe for ach payments, which
+/// requires a checking or savings depository account.
+fn is_eligible_account(
+    account_type: &plaid::AccountType,
+    account_subtype: &Option<plaid::AccountSubtype>,
+) -> bool {
+    matches!(account_type, plaid::AccountType::Depository)
+        && matches!(
+            account_subtype,
+            Some(plaid::AccountSubtype::Checking) | Some(plaid::AccountSubtype::Savings)
+        )
+}
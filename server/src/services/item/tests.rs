@@ -0,0 +1,585 @@
+use crate::{
+    config::WebConfig,
+    interceptors::authn::Peer,
+    plaid_api::{MockPlaidApi, PlaidAccount, PlaidItem, PlaidItemAccounts, PlaidLinkToken},
+    services::item::ItemServiceImpl,
+    testing::{account_row, user_row, FakeStripe},
+    tonic::idempotency::IdempotencyKey,
+    web::WebLinks,
+};
+use affect_api::affect::{
+    item_service_server::ItemService, CreateItemRequest, GenerateLinkTokenRequest,
+    ReconcileItemRequest,
+};
+use affect_status::Code;
+use affect_storage::{
+    models::{
+        account::AccountRow,
+        item::{ItemRow, ItemStatus},
+    },
+    Error,
+};
+use affect_storage_mocks::*;
+use chrono::Utc;
+use hyper::Method;
+use mockall::{predicate::eq, Sequence};
+use serde_json::json;
+use std::sync::Arc;
+use tonic::Request;
+use uuid::Uuid;
+
+fn item_service(
+    database: MockDatabaseClient,
+    plaid: MockPlaidApi,
+    stripe: stripe::Client,
+) -> ItemServiceImpl<MockDatabaseClient, MockStore, MockStore> {
+    ItemServiceImpl::new(
+        Arc::new(database),
+        Arc::new(plaid),
+        Arc::new(stripe),
+        None,
+        WebLinks::new(WebConfig {
+            affiliate_onboarding_url: "https://web.affect.app/{affiliate_id}/onboarding"
+                .to_string(),
+            affiliate_return_url: "https://web.affect.app/{affiliate_id}/return".to_string(),
+            affiliate_invitation_url: "https://web.affect.app/invitation/{token}".to_string(),
+            plaid_redirect_url: None,
+        })
+        .unwrap(),
+    )
+}
+
+fn plaid_account(
+    account_id: &str,
+    account_type: plaid::AccountType,
+    subtype: Option<plaid::AccountSubtype>,
+) -> PlaidAccount {
+    PlaidAccount {
+        account_id: account_id.to_string(),
+        name: format!("Account {0}", account_id),
+        mask: Some("0000".to_string()),
+        r#type: account_type,
+        subtype,
+    }
+}
+
+/// Accounts of the plaid item, a checking account "checking" and a credit card
+/// "credit", with the error plaid reports for the item.
+fn item_accounts(item_error_code: Option<&str>) -> PlaidItemAccounts {
+    PlaidItemAccounts {
+        accounts: vec![
+            plaid_account(
+                "checking",
+                plaid::AccountType::Depository,
+                Some(plaid::AccountSubtype::Checking),
+            ),
+            plaid_account("credit", plaid::AccountType::Credit, None),
+        ],
+        item_error_code: item_error_code.map(|code| code.to_string()),
+    }
+}
+
+/// Plaid item with a checking account "checking" and a credit card "credit".
+fn plaid() -> MockPlaidApi {
+    plaid_with_accounts(item_accounts(None).accounts)
+}
+
+/// Plaid item with the accounts, which are linked to stripe with a bank account
+/// token per account.
+fn plaid_with_accounts(accounts: Vec<PlaidAccount>) -> MockPlaidApi {
+    let mut plaid = MockPlaidApi::new();
+    plaid.expect_exchange_public_token().returning(|_| {
+        Ok(PlaidItem {
+            item_id: "item".to_string(),
+            access_token: "access-token".to_string(),
+        })
+    });
+    plaid.expect_accounts().returning(move |_| {
+        Ok(PlaidItemAccounts {
+            accounts: accounts.clone(),
+            item_error_code: None,
+        })
+    });
+    plaid
+        .expect_stripe_create_bank_account_token()
+        .returning(|_, account_id| Ok(format!("btok_{0}", account_id)));
+    plaid
+}
+
+/// Stripe which attaches bank account "ba_123" to customer "cus_123".
+fn stripe() -> FakeStripe {
+    FakeStripe::start(vec![
+        (
+            Method::POST,
+            "/v1/customers/cus_123/sources",
+            json!({"id": "ba_123", "object": "bank_account", "currency": "usd"}),
+        ),
+        (
+            Method::DELETE,
+            "/v1/customers/cus_123/sources/ba_123",
+            json!({"id": "ba_123", "object": "bank_account", "deleted": true}),
+        ),
+    ])
+}
+
+/// Item of the user which requires the user to log in again.
+fn item_row(user_id: Uuid) -> ItemRow {
+    ItemRow {
+        item_id: Uuid::new_v4(),
+        create_time: Utc::now(),
+        update_time: Utc::now(),
+        user_id,
+        plaid_item_id: "item".to_string(),
+        plaid_access_token: "access-token".to_string(),
+        status: ItemStatus::LoginRequired,
+        plaid_error_code: Some("ITEM_LOGIN_REQUIRED".to_string()),
+        consent_expiration_time: None,
+        new_accounts_available: false,
+        transactions_cursor: None,
+    }
+}
+
+/// Database with the item of the user, without linked accounts.
+fn item_database(item_row: ItemRow) -> MockDatabaseClient {
+    let mut database = MockDatabaseClient::new();
+    database.expect_on_demand().returning(move || {
+        let mut store = MockStore::new();
+        let found_row = item_row.clone();
+        store
+            .expect_find_item_by_id()
+            .returning(move |_| Ok(Some(found_row.clone())));
+        store
+            .expect_find_user_by_id()
+            .returning(|user_id| Ok(Some(user_row(user_id))));
+        store
+            .expect_list_accounts_for_item()
+            .returning(|_| Ok(Vec::new()));
+        store
+    });
+    database
+}
+
+fn request<T>(peer_user_id: Uuid, message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(peer_user_id)));
+    request
+}
+
+fn create_request(user_id: Uuid, plaid_account_ids: Vec<String>) -> Request<CreateItemRequest> {
+    let mut request = Request::new(CreateItemRequest {
+        user_id: user_id.to_string(),
+        plaid_public_token: "public-token".to_string(),
+        plaid_account_ids,
+    });
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+    request
+}
+
+#[tokio::test]
+async fn create_item_detaches_sources_if_insert_fails() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let stripe = stripe();
+    let mut database = MockDatabaseClient::new();
+    database.expect_on_demand().returning(move || {
+        let mut store = MockStore::new();
+        store
+            .expect_find_user_by_id()
+            .returning(|user_id| Ok(Some(user_row(user_id))));
+        store
+    });
+    database.expect_begin().times(1).return_once(|| {
+        let mut txn = MockStore::new();
+        txn.expect_add_item()
+            .times(1)
+            .return_once(|_| Err(Error::Other(anyhow::anyhow!("database unavailable"))));
+        txn.expect_commit().never();
+        Ok(txn)
+    });
+
+    let idempotency_key = IdempotencyKey::new("key".to_string());
+    let mut request = create_request(user_id, Vec::new());
+    request.extensions_mut().insert(idempotency_key.clone());
+    let service = item_service(database, plaid(), stripe.client());
+    let status = service.create_item(request).await.unwrap_err();
+
+    assert_eq!(status.code(), Code::Internal);
+    assert_eq!(
+        stripe.calls(),
+        vec![
+            (Method::POST, "/v1/customers/cus_123/sources".to_string()),
+            (
+                Method::DELETE,
+                "/v1/customers/cus_123/sources/ba_123".to_string()
+            ),
+        ]
+    );
+    assert_eq!(
+        stripe.requests()[0].idempotency_key,
+        Some("key-attach-source-checking".to_string())
+    );
+    assert!(idempotency_key.is_compensated());
+    Ok(())
+}
+
+/// Checking and savings accounts, and a credit card which can't be linked.
+fn depository_accounts() -> Vec<PlaidAccount> {
+    vec![
+        plaid_account(
+            "checking",
+            plaid::AccountType::Depository,
+            Some(plaid::AccountSubtype::Checking),
+        ),
+        plaid_account(
+            "savings",
+            plaid::AccountType::Depository,
+            Some(plaid::AccountSubtype::Savings),
+        ),
+        plaid_account("credit", plaid::AccountType::Credit, None),
+    ]
+}
+
+/// Database of the user which expects the item to be added with accounts for
+/// the plaid accounts, in order, in a single transaction.
+fn add_item_database(plaid_account_ids: &[&str]) -> MockDatabaseClient {
+    let plaid_account_ids: Vec<String> = plaid_account_ids
+        .iter()
+        .map(|plaid_account_id| plaid_account_id.to_string())
+        .collect();
+    let mut database = MockDatabaseClient::new();
+    database.expect_on_demand().returning(move || {
+        let mut store = MockStore::new();
+        store
+            .expect_find_user_by_id()
+            .returning(|user_id| Ok(Some(user_row(user_id))));
+        store
+    });
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        let mut seq = Sequence::new();
+        txn.expect_add_item()
+            .withf(|new_row| new_row.plaid_item_id == "item")
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|new_row| {
+                Ok(ItemRow {
+                    status: ItemStatus::Healthy,
+                    plaid_error_code: None,
+                    ..item_row(new_row.user_id)
+                })
+            });
+        for plaid_account_id in plaid_account_ids {
+            txn.expect_add_account()
+                .withf(move |new_row| new_row.plaid_account_id == plaid_account_id)
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(|new_row| {
+                    Ok(AccountRow {
+                        item_id: new_row.item_id,
+                        plaid_account_id: new_row.plaid_account_id,
+                        stripe_bank_account_id: new_row.stripe_bank_account_id,
+                        ..account_row(Uuid::new_v4())
+                    })
+                });
+        }
+        txn.expect_commit()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| Ok(()));
+        Ok(txn)
+    });
+    database
+}
+
+#[tokio::test]
+async fn create_item_links_every_depository_account() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let stripe = stripe();
+    let service = item_service(
+        add_item_database(&["checking", "savings"]),
+        plaid_with_accounts(depository_accounts()),
+        stripe.client(),
+    );
+
+    let item = service
+        .create_item(create_request(user_id, Vec::new()))
+        .await?
+        .into_inner();
+
+    assert_eq!(item.accounts.len(), 2);
+    assert_eq!(
+        stripe.calls(),
+        vec![
+            (Method::POST, "/v1/customers/cus_123/sources".to_string()),
+            (Method::POST, "/v1/customers/cus_123/sources".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_item_links_selected_accounts() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let stripe = stripe();
+    let service = item_service(
+        add_item_database(&["savings"]),
+        plaid_with_accounts(depository_accounts()),
+        stripe.client(),
+    );
+
+    let item = service
+        .create_item(create_request(user_id, vec!["savings".to_string()]))
+        .await?
+        .into_inner();
+
+    assert_eq!(item.accounts.len(), 1);
+    assert_eq!(
+        stripe.calls(),
+        vec![(Method::POST, "/v1/customers/cus_123/sources".to_string())]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_item_rejects_ineligible_or_unknown_accounts() -> Result<(), anyhow::Error> {
+    for plaid_account_id in ["credit", "unknown"] {
+        let user_id = Uuid::new_v4();
+        let stripe = stripe();
+        let mut database = MockDatabaseClient::new();
+        database.expect_on_demand().returning(move || {
+            let mut store = MockStore::new();
+            store
+                .expect_find_user_by_id()
+                .returning(|user_id| Ok(Some(user_row(user_id))));
+            store
+        });
+        database.expect_begin().never();
+        let service = item_service(
+            database,
+            plaid_with_accounts(depository_accounts()),
+            stripe.client(),
+        );
+
+        let status = service
+            .create_item(create_request(
+                user_id,
+                vec!["checking".to_string(), plaid_account_id.to_string()],
+            ))
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert!(stripe.calls().is_empty());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn generate_link_token_for_item_runs_update_mode() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let item_row = item_row(user_id);
+    let item_id = item_row.item_id;
+    let mut plaid = MockPlaidApi::new();
+    plaid
+        .expect_create_link_token()
+        .withf(|request| {
+            request.access_token == Some("access-token".to_string()) && request.products.is_empty()
+        })
+        .times(1)
+        .returning(|_| {
+            Ok(PlaidLinkToken {
+                link_token: "link-token".to_string(),
+                expiration: Utc::now(),
+            })
+        });
+
+    let service = item_service(
+        item_database(item_row),
+        plaid,
+        stripe::Client::new("sk_test_xyz"),
+    );
+    let link_token = service
+        .generate_link_token(request(
+            user_id,
+            GenerateLinkTokenRequest {
+                user_id: user_id.to_string(),
+                item_id: item_id.to_string(),
+            },
+        ))
+        .await?
+        .into_inner();
+
+    assert_eq!(link_token.plaid_link_token, "link-token");
+    Ok(())
+}
+
+#[tokio::test]
+async fn generate_link_token_for_other_users_item_fails() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let item_row = item_row(Uuid::new_v4());
+    let item_id = item_row.item_id;
+    let mut plaid = MockPlaidApi::new();
+    plaid.expect_create_link_token().never();
+
+    let service = item_service(
+        item_database(item_row),
+        plaid,
+        stripe::Client::new("sk_test_xyz"),
+    );
+    let status = service
+        .generate_link_token(request(
+            user_id,
+            GenerateLinkTokenRequest {
+                user_id: user_id.to_string(),
+                item_id: item_id.to_string(),
+            },
+        ))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::PermissionDenied);
+    Ok(())
+}
+
+#[tokio::test]
+async fn reconcile_other_users_item_fails() -> Result<(), anyhow::Error> {
+    let item_row = item_row(Uuid::new_v4());
+    let item_id = item_row.item_id;
+    let mut plaid = MockPlaidApi::new();
+    plaid.expect_accounts().never();
+
+    let service = item_service(
+        item_database(item_row),
+        plaid,
+        stripe::Client::new("sk_test_xyz"),
+    );
+    let status = service
+        .reconcile_item(request(
+            Uuid::new_v4(),
+            ReconcileItemRequest {
+                item_id: item_id.to_string(),
+                plaid_account_ids: Vec::new(),
+            },
+        ))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::PermissionDenied);
+    Ok(())
+}
+
+#[tokio::test]
+async fn reconcile_item_rejects_unknown_plaid_account_ids() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let item_row = item_row(user_id);
+    let item_id = item_row.item_id;
+    // Stripe fails every request.
+    let stripe = FakeStripe::start(Vec::new());
+    let mut database = item_database(item_row);
+    database.expect_begin().never();
+
+    let service = item_service(database, plaid(), stripe.client());
+    let status = service
+        .reconcile_item(request(
+            user_id,
+            ReconcileItemRequest {
+                item_id: item_id.to_string(),
+                plaid_account_ids: vec!["checking".to_string(), "unknown".to_string()],
+            },
+        ))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(stripe.calls().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn reconcile_item_marks_item_healthy() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let item_row = item_row(user_id);
+    let item_id = item_row.item_id;
+    let stripe = stripe();
+    let mut database = item_database(item_row);
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        txn.expect_add_account()
+            .withf(move |new_row| {
+                new_row.item_id == item_id
+                    && new_row.plaid_account_id == "checking"
+                    && new_row.stripe_bank_account_id == "ba_123"
+            })
+            .times(1)
+            .return_once(|_| Ok(account_row(Uuid::new_v4())));
+        txn.expect_update_item_status_by_plaid_item_id()
+            .withf(|plaid_item_id, status, plaid_error_code, _, _| {
+                plaid_item_id == "item"
+                    && *status == ItemStatus::Healthy
+                    && plaid_error_code.is_none()
+            })
+            .times(1)
+            .returning(|_, _, _, _, _| Ok(None));
+        txn.expect_update_item_new_accounts_available_by_plaid_item_id()
+            .with(eq("item"), eq(false), mockall::predicate::always())
+            .times(1)
+            .returning(|_, _, _| Ok(None));
+        txn.expect_commit().times(1).return_once(|| Ok(()));
+        Ok(txn)
+    });
+
+    let service = item_service(database, plaid(), stripe.client());
+    service
+        .reconcile_item(request(
+            user_id,
+            ReconcileItemRequest {
+                item_id: item_id.to_string(),
+                plaid_account_ids: vec!["checking".to_string()],
+            },
+        ))
+        .await?;
+
+    assert_eq!(
+        stripe.calls(),
+        vec![(Method::POST, "/v1/customers/cus_123/sources".to_string())]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn reconcile_item_keeps_status_while_plaid_reports_error() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let item_row = item_row(user_id);
+    let item_id = item_row.item_id;
+    let mut plaid = MockPlaidApi::new();
+    plaid
+        .expect_accounts()
+        .with(eq("access-token"))
+        .times(1)
+        .returning(|_| Ok(item_accounts(Some("ITEM_LOGIN_REQUIRED"))));
+    let mut database = item_database(item_row);
+    database.expect_begin().times(1).return_once(|| {
+        let mut txn = MockStore::new();
+        txn.expect_update_item_status_by_plaid_item_id().never();
+        txn.expect_commit().times(1).return_once(|| Ok(()));
+        Ok(txn)
+    });
+
+    let service = item_service(database, plaid, stripe::Client::new("sk_test_xyz"));
+    let item = service
+        .reconcile_item(request(
+            user_id,
+            ReconcileItemRequest {
+                item_id: item_id.to_string(),
+                plaid_account_ids: Vec::new(),
+            },
+        ))
+        .await?
+        .into_inner();
+
+    assert_eq!(item.item_id, item_id.to_string());
+    Ok(())
+}
@@ -1,4 +1,8 @@
-use crate::protobuf::into::{IntoProto, ProtoInto};
+use crate::{
+    interceptors::authn::Peer,
+    policy::Policy,
+    protobuf::into::{IntoProto, ProtoInto},
+};
 use affect_api::affect::{
     list_nonprofits_request::Filter, nonprofit_service_server::NonprofitService,
     ListNonprofitsRequest, *,
@@ -47,6 +51,7 @@ where
         &self,
         request: Request<GetNonprofitRequest>,
     ) -> Result<Response<Nonprofit>, Status> {
+        Policy::Anyone.authorize(&Peer::from_request(&request))?;
         let message = request.into_inner();
 
         let nonprofit_id = message
@@ -68,6 +73,7 @@ where
         &self,
         request: Request<ListNonprofitsRequest>,
     ) -> Result<Response<ListNonprofitsResponse>, Status> {
+        Policy::Anyone.authorize(&Peer::from_request(&request))?;
         let message = request.into_inner();
 
         let page_size = min(max(message.page_size, 10), 100);
@@ -0,0 +1,330 @@
+use crate::{
+    fees::FeeCalculator,
+    interceptors::authn::Peer,
+    money::Money,
+    policy::Policy,
+    protobuf::into::{IntoProto, ProtoInto},
+    recurring_donation::next_run_time,
+    services::donation::{currency, validate_currency},
+};
+use affect_api::affect::{
+    recurring_donation_service_server::RecurringDonationService, CancelRecurringDonationRequest,
+    CreateRecurringDonationRequest, PauseRecurringDonationRequest, RecurringDonation,
+    RecurringDonationFrequency as ProtoRecurringDonationFrequency, ResumeRecurringDonationRequest,
+};
+use affect_status::{
+    failed_precondition, invalid_argument,
+    well_known::{entity_not_found, UnwrapField},
+};
+use affect_storage::{
+    database::{
+        client::DatabaseClient,
+        store::{OnDemandStore, TransactionalStore},
+    },
+    models::{cause::CauseState, recurring_donation::*},
+    stores::{
+        account::AccountStore, cause::CauseStore, nonprofit::NonprofitStore,
+        recurring_donation::RecurringDonationStore,
+    },
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use iso_currency::Currency;
+use std::{cmp::max, marker::PhantomData, sync::Arc};
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+#[cfg(test)]
+mod tests;
+
+pub struct RecurringDonationServiceImpl<Db, Store, TStore> {
+    database: Arc<Db>,
+    fees: FeeCalculator,
+    _marker: PhantomData<(Store, TStore)>,
+}
+
+impl<Db, Store, TStore> RecurringDonationServiceImpl<Db, Store, TStore> {
+    pub fn new(database: Arc<Db>, fees: FeeCalculator) -> Self {
+        Self {
+            database,
+            fees,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Db, Store, TStore> RecurringDonationServiceImpl<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore> + 'static,
+    Store: AccountStore
+        + CauseStore
+        + NonprofitStore
+        + RecurringDonationStore
+        + OnDemandStore
+        + 'static,
+    TStore: RecurringDonationStore + TransactionalStore + 'static,
+{
+    /// Validates that the nonprofit can receive donations in the currency.
+    async fn validate_nonprofit(
+        &self,
+        nonprofit_id: Uuid,
+        currency: Currency,
+    ) -> Result<(), Status> {
+        let nonprofit = self
+            .database
+            .on_demand()
+            .find_nonprofit_by_id(nonprofit_id)
+            .await?
+            .ok_or(entity_not_found("nonprofit"))?;
+        let affiliate = nonprofit
+            .affiliate
+            .ok_or(invalid_argument!("nonprofit is not affiliated"))?;
+        validate_currency(&affiliate, currency)
+    }
+
+    /// Validates that the cause belongs to the user and that its recipients can
+    /// receive donations in the currency.
+    async fn validate_cause(
+        &self,
+        user_id: Uuid,
+        cause_id: Uuid,
+        currency: Currency,
+    ) -> Result<(), Status> {
+        let full_cause_row = self
+            .database
+            .on_demand()
+            .find_cause_by_id(cause_id)
+            .await?
+            .ok_or(entity_not_found("cause"))?;
+        if full_cause_row.cause.user_id != user_id {
+            return Err(invalid_argument!("cause does not belong to the user"));
+        }
+        if full_cause_row.cause.archive_time.is_some() {
+            return Err(failed_precondition!("cause is archived"));
+        }
+        if full_cause_row.cause.state != CauseState::Active {
+            return Err(failed_precondition!("cause is a draft"));
+        }
+        for cause_recipient in full_cause_row.cause_recipients.inner() {
+            self.validate_nonprofit(cause_recipient.nonprofit_id, currency)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the recurring donation to the state returned by `transition` for
+    /// its current row.
+    async fn transition<F>(
+        &self,
+        peer: &Peer,
+        recurring_donation_id: Uuid,
+        transition: F,
+    ) -> Result<RecurringDonationRow, Status>
+    where
+        F: FnOnce(&RecurringDonationRow) -> Result<(RecurringDonationState, DateTime<Utc>), Status>
+            + Send,
+    {
+        let txn = self.database.begin().await?;
+        let row = txn
+            .find_recurring_donation_by_id(recurring_donation_id)
+            .await?
+            .ok_or(entity_not_found("recurring_donation"))?;
+        Policy::Owner(row.user_id).authorize(peer)?;
+        let (state, next_run_time) = transition(&row)?;
+        let row = txn
+            .update_recurring_donation_state(
+                recurring_donation_id,
+                state,
+                next_run_time,
+                Utc::now(),
+            )
+            .await?;
+        txn.commit().await?;
+        Ok(row)
+    }
+}
+
+#[async_trait]
+impl<Db, Store, TStore> RecurringDonationService for RecurringDonationServiceImpl<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore> + 'static,
+    Store: AccountStore
+        + CauseStore
+        + NonprofitStore
+        + RecurringDonationStore
+        + OnDemandStore
+        + 'static,
+    TStore: RecurringDonationStore + TransactionalStore + 'static,
+{
+    async fn create_recurring_donation(
+        &self,
+        request: Request<CreateRecurringDonationRequest>,
+    ) -> Result<Response<RecurringDonation>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let user_id: Uuid = message
+            .user_id
+            .clone()
+            .unwrap_field("user_id")?
+            .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
+        let amount: Money = message
+            .amount
+            .clone()
+            .unwrap_field("amount")?
+            .proto_field_into("amount")?;
+        let (_, currency_code) = currency(&amount)?;
+        self.fees.check_currency(amount.currency())?;
+        if amount.subunits_truncated() <= 0 {
+            return Err(invalid_argument!("'amount' must be positive"));
+        }
+        let frequency = frequency(message.frequency())?;
+        let account_id: Uuid = message
+            .account_id
+            .clone()
+            .unwrap_field("account_id")?
+            .proto_field_into("account_id")?;
+        let now = Utc::now();
+        // Schedules can't start in the past.
+        let start_time = match message.start_time.clone() {
+            Some(start_time) => {
+                let start_time: DateTime<Utc> = start_time.proto_field_into("start_time")?;
+                max(start_time, now)
+            }
+            None => now,
+        };
+
+        let (nonprofit_id, cause_id) =
+            match (message.nonprofit_id.is_empty(), message.cause_id.is_empty()) {
+                (false, true) => {
+                    let nonprofit_id = message.nonprofit_id.proto_field_into("nonprofit_id")?;
+                    self.validate_nonprofit(nonprofit_id, amount.currency())
+                        .await?;
+                    (Some(nonprofit_id), None)
+                }
+                (true, false) => {
+                    let cause_id = message.cause_id.proto_field_into("cause_id")?;
+                    self.validate_cause(user_id, cause_id, amount.currency())
+                        .await?;
+                    (None, Some(cause_id))
+                }
+                _ => {
+                    return Err(invalid_argument!(
+                        "exactly one of 'nonprofit_id' or 'cause_id' must be specified"
+                    ))
+                }
+            };
+        self.database
+            .on_demand()
+            .find_account_by_id_for_user(account_id, user_id)
+            .await?
+            .ok_or(entity_not_found("account"))?;
+
+        let row = self
+            .database
+            .on_demand()
+            .add_recurring_donation(NewRecurringDonationRow {
+                create_time: now,
+                update_time: now,
+                user_id,
+                nonprofit_id,
+                cause_id,
+                account_id: Some(account_id),
+                currency_code,
+                amount_units: amount.units(),
+                amount_nanos: amount.nanos(),
+                frequency,
+                start_time,
+            })
+            .await?;
+
+        Ok(Response::new(row.into_proto()?))
+    }
+
+    async fn pause_recurring_donation(
+        &self,
+        request: Request<PauseRecurringDonationRequest>,
+    ) -> Result<Response<RecurringDonation>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let recurring_donation_id = message
+            .recurring_donation_id
+            .unwrap_field("recurring_donation_id")?
+            .proto_field_into("recurring_donation_id")?;
+
+        let row = self
+            .transition(&peer, recurring_donation_id, |row| match row.state {
+                RecurringDonationState::Canceled => {
+                    Err(failed_precondition!("recurring donation is canceled"))
+                }
+                _ => Ok((RecurringDonationState::Paused, row.next_run_time)),
+            })
+            .await?;
+
+        Ok(Response::new(row.into_proto()?))
+    }
+
+    async fn resume_recurring_donation(
+        &self,
+        request: Request<ResumeRecurringDonationRequest>,
+    ) -> Result<Response<RecurringDonation>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let recurring_donation_id = message
+            .recurring_donation_id
+            .unwrap_field("recurring_donation_id")?
+            .proto_field_into("recurring_donation_id")?;
+
+        let row = self
+            .transition(&peer, recurring_donation_id, |row| {
+                if row.state == RecurringDonationState::Canceled {
+                    return Err(failed_precondition!("recurring donation is canceled"));
+                }
+                if row.account_id.is_none() {
+                    return Err(failed_precondition!("recurring donation has no account"));
+                }
+                // Donations missed while paused are skipped.
+                let now = Utc::now();
+                let run_time = match row.next_run_time > now {
+                    true => row.next_run_time,
+                    false => next_run_time(row.start_time, &row.frequency, now),
+                };
+                Ok((RecurringDonationState::Active, run_time))
+            })
+            .await?;
+
+        Ok(Response::new(row.into_proto()?))
+    }
+
+    async fn cancel_recurring_donation(
+        &self,
+        request: Request<CancelRecurringDonationRequest>,
+    ) -> Result<Response<RecurringDonation>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let recurring_donation_id = message
+            .recurring_donation_id
+            .unwrap_field("recurring_donation_id")?
+            .proto_field_into("recurring_donation_id")?;
+
+        let row = self
+            .transition(&peer, recurring_donation_id, |row| {
+                Ok((RecurringDonationState::Canceled, row.next_run_time))
+            })
+            .await?;
+
+        Ok(Response::new(row.into_proto()?))
+    }
+}
+
+fn frequency(
+    frequency: ProtoRecurringDonationFrequency,
+) -> Result<RecurringDonationFrequency, Status> {
+    match frequency {
+        ProtoRecurringDonationFrequency::Unspecified => {
+            Err(invalid_argument!("'frequency' must be specified"))
+        }
+        ProtoRecurringDonationFrequency::Weekly => Ok(RecurringDonationFrequency::Weekly),
+        ProtoRecurringDonationFrequency::Monthly => Ok(RecurringDonationFrequency::Monthly),
+    }
+}
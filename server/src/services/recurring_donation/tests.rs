@@ -0,0 +1,399 @@
+use crate::{
+    config::FeesConfig,
+    fees::FeeCalculator,
+    interceptors::authn::Peer,
+    services::recurring_donation::RecurringDonationServiceImpl,
+    testing::{account_row, affiliate_row, nonprofit_row, user_row},
+};
+use affect_api::{
+    affect::{
+        recurring_donation_service_server::RecurringDonationService,
+        CancelRecurringDonationRequest, CreateRecurringDonationRequest,
+        PauseRecurringDonationRequest, RecurringDonationFrequency as ProtoFrequency,
+        ResumeRecurringDonationRequest,
+    },
+    google::r#type::Money as MoneyProto,
+};
+use affect_status::Code;
+use affect_storage::models::recurring_donation::*;
+use affect_storage_mocks::*;
+use chrono::{DateTime, Duration, Utc};
+use mockall::{predicate::eq, Sequence};
+use std::sync::Arc;
+use tonic::Request;
+use uuid::Uuid;
+
+fn recurring_donation_service(
+    database: MockDatabaseClient,
+) -> RecurringDonationServiceImpl<MockDatabaseClient, MockStore, MockStore> {
+    RecurringDonationServiceImpl::new(
+        Arc::new(database),
+        FeeCalculator::new(FeesConfig::default()).unwrap(),
+    )
+}
+
+fn recurring_donation_row(
+    user_id: Uuid,
+    state: RecurringDonationState,
+    next_run_time: DateTime<Utc>,
+) -> RecurringDonationRow {
+    RecurringDonationRow {
+        recurring_donation_id: Uuid::new_v4(),
+        create_time: Utc::now(),
+        update_time: Utc::now(),
+        user_id,
+        nonprofit_id: Some(Uuid::new_v4()),
+        cause_id: None,
+        account_id: Some(Uuid::new_v4()),
+        currency_code: "USD".to_string(),
+        amount_units: 10,
+        amount_nanos: 0,
+        frequency: RecurringDonationFrequency::Weekly,
+        state,
+        start_time: next_run_time - Duration::weeks(4),
+        next_run_time,
+        retry_time: None,
+        failure_count: 0,
+        last_donation_id: None,
+    }
+}
+
+fn request<T>(peer_user_id: Uuid, message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(peer_user_id)));
+    request
+}
+
+/// Database with the recurring donation, expecting a transition to the state
+/// if any.
+fn transition_database(
+    row: RecurringDonationRow,
+    state: Option<RecurringDonationState>,
+) -> MockDatabaseClient {
+    let mut database = MockDatabaseClient::new();
+    database.expect_begin().times(1).return_once(move || {
+        let mut txn = MockStore::new();
+        let found_row = row.clone();
+        txn.expect_find_recurring_donation_by_id()
+            .with(eq(row.recurring_donation_id))
+            .times(1)
+            .return_once(move |_| Ok(Some(found_row)));
+        match state {
+            Some(state) => {
+                txn.expect_update_recurring_donation_state()
+                    .withf(move |recurring_donation_id, new_state, _, _| {
+                        *recurring_donation_id == row.recurring_donation_id && *new_state == state
+                    })
+                    .times(1)
+                    .return_once(move |_, state, next_run_time, update_time| {
+                        Ok(RecurringDonationRow {
+                            state,
+                            next_run_time,
+                            update_time,
+                            ..row
+                        })
+                    });
+                txn.expect_commit().times(1).return_once(|| Ok(()));
+            }
+            None => {
+                txn.expect_update_recurring_donation_state().never();
+                txn.expect_commit().never();
+            }
+        }
+        Ok(txn)
+    });
+    database
+}
+
+#[tokio::test]
+async fn create_recurring_donation() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let nonprofit_id = Uuid::new_v4();
+    let account_id = Uuid::new_v4();
+    let mut database = MockDatabaseClient::new();
+    let mut seq = Sequence::new();
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(|| {
+            let mut store = MockStore::new();
+            store
+                .expect_find_nonprofit_by_id()
+                .returning(|nonprofit_id| {
+                    Ok(Some(nonprofit_row(
+                        nonprofit_id,
+                        Some(affiliate_row("acct_123")),
+                    )))
+                });
+            store
+        });
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(move || {
+            let mut store = MockStore::new();
+            store
+                .expect_find_account_by_id_for_user()
+                .with(eq(account_id), eq(user_id))
+                .times(1)
+                .return_once(|account_id, _| Ok(Some(account_row(account_id))));
+            store
+        });
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(move || {
+            let mut store = MockStore::new();
+            store
+                .expect_add_recurring_donation()
+                .withf(move |new_row| {
+                    new_row.user_id == user_id
+                        && new_row.nonprofit_id == Some(nonprofit_id)
+                        && new_row.cause_id.is_none()
+                        && new_row.account_id == Some(account_id)
+                        && new_row.currency_code == "USD"
+                        && (new_row.amount_units, new_row.amount_nanos) == (25, 0)
+                        && new_row.frequency == RecurringDonationFrequency::Monthly
+                })
+                .times(1)
+                .return_once(|new_row| {
+                    Ok(RecurringDonationRow {
+                        nonprofit_id: new_row.nonprofit_id,
+                        account_id: new_row.account_id,
+                        amount_units: new_row.amount_units,
+                        frequency: new_row.frequency,
+                        start_time: new_row.start_time,
+                        next_run_time: new_row.start_time,
+                        ..recurring_donation_row(
+                            new_row.user_id,
+                            RecurringDonationState::Active,
+                            new_row.start_time,
+                        )
+                    })
+                });
+            store
+        });
+
+    let recurring_donation = recurring_donation_service(database)
+        .create_recurring_donation(request(
+            user_id,
+            CreateRecurringDonationRequest {
+                user_id: user_id.to_string(),
+                nonprofit_id: nonprofit_id.to_string(),
+                cause_id: "".to_string(),
+                account_id: account_id.to_string(),
+                amount: Some(MoneyProto {
+                    currency_code: "USD".to_string(),
+                    units: 25,
+                    nanos: 0,
+                }),
+                frequency: ProtoFrequency::Monthly as i32,
+                start_time: None,
+            },
+        ))
+        .await?
+        .into_inner();
+
+    assert_eq!(recurring_donation.nonprofit_id, nonprofit_id.to_string());
+    assert_eq!(recurring_donation.account_id, account_id.to_string());
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_recurring_donation_for_other_user_fails() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    // Calls to the database would panic without expectations.
+    let status = recurring_donation_service(MockDatabaseClient::new())
+        .create_recurring_donation(request(
+            Uuid::new_v4(),
+            CreateRecurringDonationRequest {
+                user_id: user_id.to_string(),
+                nonprofit_id: Uuid::new_v4().to_string(),
+                cause_id: "".to_string(),
+                account_id: Uuid::new_v4().to_string(),
+                amount: Some(MoneyProto {
+                    currency_code: "USD".to_string(),
+                    units: 25,
+                    nanos: 0,
+                }),
+                frequency: ProtoFrequency::Monthly as i32,
+                start_time: None,
+            },
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_recurring_donation_without_fee_schedule_fails() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    // Calls to the database would panic without expectations.
+    let status = recurring_donation_service(MockDatabaseClient::new())
+        .create_recurring_donation(request(
+            user_id,
+            CreateRecurringDonationRequest {
+                user_id: user_id.to_string(),
+                nonprofit_id: Uuid::new_v4().to_string(),
+                cause_id: "".to_string(),
+                account_id: Uuid::new_v4().to_string(),
+                amount: Some(MoneyProto {
+                    currency_code: "EUR".to_string(),
+                    units: 25,
+                    nanos: 0,
+                }),
+                frequency: ProtoFrequency::Monthly as i32,
+                start_time: None,
+            },
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pause_recurring_donation() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let next_run_time = Utc::now() + Duration::days(1);
+    let row = recurring_donation_row(user_id, RecurringDonationState::Active, next_run_time);
+    let recurring_donation_id = row.recurring_donation_id;
+
+    let recurring_donation = recurring_donation_service(transition_database(
+        row,
+        Some(RecurringDonationState::Paused),
+    ))
+    .pause_recurring_donation(request(
+        user_id,
+        PauseRecurringDonationRequest {
+            recurring_donation_id: recurring_donation_id.to_string(),
+        },
+    ))
+    .await?
+    .into_inner();
+
+    assert_eq!(
+        recurring_donation.state(),
+        affect_api::affect::RecurringDonationState::Paused
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn pause_canceled_recurring_donation_fails() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let row = recurring_donation_row(user_id, RecurringDonationState::Canceled, Utc::now());
+    let recurring_donation_id = row.recurring_donation_id;
+
+    let status = recurring_donation_service(transition_database(row, None))
+        .pause_recurring_donation(request(
+            user_id,
+            PauseRecurringDonationRequest {
+                recurring_donation_id: recurring_donation_id.to_string(),
+            },
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_recurring_donation_skips_missed_donations() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let row = recurring_donation_row(
+        user_id,
+        RecurringDonationState::Paused,
+        Utc::now() - Duration::days(10),
+    );
+    let recurring_donation_id = row.recurring_donation_id;
+
+    let recurring_donation = recurring_donation_service(transition_database(
+        row,
+        Some(RecurringDonationState::Active),
+    ))
+    .resume_recurring_donation(request(
+        user_id,
+        ResumeRecurringDonationRequest {
+            recurring_donation_id: recurring_donation_id.to_string(),
+        },
+    ))
+    .await?
+    .into_inner();
+
+    assert_eq!(
+        recurring_donation.state(),
+        affect_api::affect::RecurringDonationState::Active
+    );
+    assert!(recurring_donation.next_run_time.unwrap().seconds > Utc::now().timestamp());
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_canceled_recurring_donation_fails() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let row = recurring_donation_row(user_id, RecurringDonationState::Canceled, Utc::now());
+    let recurring_donation_id = row.recurring_donation_id;
+
+    let status = recurring_donation_service(transition_database(row, None))
+        .resume_recurring_donation(request(
+            user_id,
+            ResumeRecurringDonationRequest {
+                recurring_donation_id: recurring_donation_id.to_string(),
+            },
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancel_recurring_donation() -> Result<(), anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let row = recurring_donation_row(user_id, RecurringDonationState::Paused, Utc::now());
+    let recurring_donation_id = row.recurring_donation_id;
+
+    let recurring_donation = recurring_donation_service(transition_database(
+        row,
+        Some(RecurringDonationState::Canceled),
+    ))
+    .cancel_recurring_donation(request(
+        user_id,
+        CancelRecurringDonationRequest {
+            recurring_donation_id: recurring_donation_id.to_string(),
+        },
+    ))
+    .await?
+    .into_inner();
+
+    assert_eq!(
+        recurring_donation.state(),
+        affect_api::affect::RecurringDonationState::Canceled
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancel_other_users_recurring_donation_fails() -> Result<(), anyhow::Error> {
+    let row = recurring_donation_row(Uuid::new_v4(), RecurringDonationState::Active, Utc::now());
+    let recurring_donation_id = row.recurring_donation_id;
+
+    let status = recurring_donation_service(transition_database(row, None))
+        .cancel_recurring_donation(request(
+            Uuid::new_v4(),
+            CancelRecurringDonationRequest {
+                recurring_donation_id: recurring_donation_id.to_string(),
+            },
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    Ok(())
+}
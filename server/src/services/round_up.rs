@@ -0,0 +1,162 @@
+use crate::{
+    interceptors::authn::Peer,
+    policy::Policy,
+    protobuf::into::{IntoProto, ProtoInto},
+    round_up::pending_amount,
+};
+use affect_api::affect::{
+    round_up_service_server::RoundUpService, GetRoundUpSettingsRequest, RoundUpSettings,
+    UpdateRoundUpSettingsRequest,
+};
+use affect_status::{
+    failed_precondition, invalid_argument,
+    well_known::{entity_not_found, UnwrapField},
+};
+use affect_storage::{
+    database::{
+        client::DatabaseClient,
+        store::{OnDemandStore, TransactionalStore},
+    },
+    models::{
+        cause::CauseState,
+        round_up::{NewRoundUpSettingsRow, RoundUpSettingsRow},
+    },
+    stores::{account::AccountStore, cause::CauseStore, round_up::RoundUpStore},
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::{marker::PhantomData, sync::Arc};
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+pub struct RoundUpServiceImpl<Db, Store, TStore> {
+    database: Arc<Db>,
+    _marker: PhantomData<(Store, TStore)>,
+}
+
+impl<Db, Store, TStore> RoundUpServiceImpl<Db, Store, TStore> {
+    pub fn new(database: Arc<Db>) -> Self {
+        Self {
+            database,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Db, Store, TStore> RoundUpServiceImpl<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore> + 'static,
+    Store: AccountStore + CauseStore + RoundUpStore + OnDemandStore + 'static,
+    TStore: TransactionalStore + 'static,
+    Self: Sync + Send,
+{
+    /// Settings proto with the user's pending round-ups.
+    async fn round_up_settings(
+        &self,
+        settings_row: RoundUpSettingsRow,
+    ) -> Result<RoundUpSettings, Status> {
+        let round_up_rows = self
+            .database
+            .on_demand()
+            .list_pending_round_ups_for_user(settings_row.user_id)
+            .await?;
+        let pending_amount = pending_amount(&round_up_rows)?;
+        (settings_row, pending_amount).into_proto()
+    }
+}
+
+#[async_trait]
+impl<Db, Store, TStore> RoundUpService for RoundUpServiceImpl<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore> + 'static,
+    Store: AccountStore + CauseStore + RoundUpStore + OnDemandStore + 'static,
+    TStore: TransactionalStore + 'static,
+    Self: Sync + Send,
+{
+    async fn get_round_up_settings(
+        &self,
+        request: Request<GetRoundUpSettingsRequest>,
+    ) -> Result<Response<RoundUpSettings>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let user_id = message
+            .user_id
+            .unwrap_field("user_id")?
+            .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
+
+        let settings_row = self
+            .database
+            .on_demand()
+            .find_round_up_settings_by_user_id(user_id)
+            .await?
+            .ok_or(entity_not_found("round_up_settings"))?;
+
+        Ok(Response::new(self.round_up_settings(settings_row).await?))
+    }
+
+    async fn update_round_up_settings(
+        &self,
+        request: Request<UpdateRoundUpSettingsRequest>,
+    ) -> Result<Response<RoundUpSettings>, Status> {
+        let peer = Peer::from_request(&request);
+        let message = request.into_inner();
+        let user_id = message
+            .user_id
+            .unwrap_field("user_id")?
+            .proto_field_into("user_id")?;
+        Policy::Owner(user_id).authorize(&peer)?;
+        let cause_id = message
+            .cause_id
+            .unwrap_field("cause_id")?
+            .proto_field_into("cause_id")?;
+        let account_id: Option<Uuid> = match message.account_id.is_empty() {
+            true => None,
+            false => Some(message.account_id.proto_field_into("account_id")?),
+        };
+        if message.enabled && account_id.is_none() {
+            return Err(invalid_argument!(
+                "'account_id' must be specified to enable round-ups"
+            ));
+        }
+
+        let full_cause_row = self
+            .database
+            .on_demand()
+            .find_cause_by_id(cause_id)
+            .await?
+            .ok_or(entity_not_found("cause"))?;
+        if full_cause_row.cause.user_id != user_id {
+            return Err(invalid_argument!("cause does not belong to the user"));
+        }
+        if full_cause_row.cause.archive_time.is_some() {
+            return Err(failed_precondition!("cause is archived"));
+        }
+        if full_cause_row.cause.state != CauseState::Active {
+            return Err(failed_precondition!("cause is a draft"));
+        }
+        if let Some(account_id) = account_id {
+            self.database
+                .on_demand()
+                .find_account_by_id_for_user(account_id, user_id)
+                .await?
+                .ok_or(entity_not_found("account"))?;
+        }
+
+        let now = Utc::now();
+        let settings_row = self
+            .database
+            .on_demand()
+            .upsert_round_up_settings(NewRoundUpSettingsRow {
+                user_id,
+                create_time: now,
+                update_time: now,
+                cause_id,
+                account_id,
+                enabled: message.enabled,
+            })
+            .await?;
+
+        Ok(Response::new(self.round_up_settings(settings_row).await?))
+    }
+}
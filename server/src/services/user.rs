@@ -1,6 +1,9 @@
 use crate::{
-    firebase::FirebaseAuth,
+    firebase::IdTokenVerifier,
+    interceptors::authn::Peer,
+    policy::Policy,
     protobuf::into::{IntoProto, ProtoInto},
+    saga::{DeleteStripeCustomer, Saga},
 };
 use affect_api::affect::{get_user_request::Identifier, user_service_server::UserService, *};
 use affect_status::{internal, invalid_argument, not_found, well_known::UnwrapField};
@@ -17,16 +20,19 @@ use std::{
 };
 use tonic::{Request, Response, Status};
 
+#[cfg(test)]
+mod tests;
+
 pub struct UserServiceImpl {
     user_store: Arc<dyn UserStore>,
-    firebase_auth: Arc<FirebaseAuth>,
+    firebase_auth: Arc<dyn IdTokenVerifier>,
     stripe_client: Arc<stripe::Client>,
 }
 
 impl UserServiceImpl {
     pub fn new(
         user_store: Arc<dyn UserStore>,
-        firebase_auth: Arc<FirebaseAuth>,
+        firebase_auth: Arc<dyn IdTokenVerifier>,
         stripe_client: Arc<stripe::Client>,
     ) -> Self {
         Self {
@@ -40,6 +46,8 @@ impl UserServiceImpl {
 #[async_trait]
 impl UserService for UserServiceImpl {
     async fn create_user(&self, req: Request<CreateUserRequest>) -> Result<Response<User>, Status> {
+        // Callers prove their identity with the firebase id token in the request.
+        Policy::Anyone.authorize(&Peer::from_request(&req))?;
         let message = req.into_inner();
 
         let firebase_id_token = message
@@ -61,21 +69,32 @@ impl UserService for UserServiceImpl {
             .await
             .map_err(|e| internal!("failed to fetch accounts: {:?}", e))?;
 
-        let user_row = self
-            .user_store
-            .add_user(NewUserRow {
-                create_time: now,
-                update_time: now,
-                firebase_uid: decoded_id_token.uid,
-                firebase_email: email,
-                stripe_customer_id: stripe_customer.id.to_string(),
-            })
+        let mut saga = Saga::new();
+        saga.record(DeleteStripeCustomer {
+            client: (*self.stripe_client).clone(),
+            customer_id: stripe_customer.id.clone(),
+        });
+
+        let user_row = saga
+            .check(
+                self.user_store
+                    .add_user(NewUserRow {
+                        create_time: now,
+                        update_time: now,
+                        firebase_uid: decoded_id_token.uid,
+                        firebase_email: email,
+                        stripe_customer_id: stripe_customer.id.to_string(),
+                    })
+                    .await
+                    .map_err(Status::from),
+            )
             .await?;
 
         Ok(Response::new(user_row.into_proto()?))
     }
 
     async fn get_user(&self, req: Request<GetUserRequest>) -> Result<Response<User>, Status> {
+        let peer = Peer::from_request(&req);
         let message = req.into_inner();
         let user_row = match message.identifier {
             Some(Identifier::FirebaseUserId(firebase_user_id)) => {
@@ -91,6 +110,7 @@ impl UserService for UserServiceImpl {
             None => return Err(invalid_argument!("must specify identifier")),
         }
         .ok_or(not_found!("user not found"))?;
+        Policy::Owner(user_row.user_id).authorize(&peer)?;
 
         Ok(Response::new(user_row.into_proto()?))
     }
@@ -99,6 +119,7 @@ impl UserService for UserServiceImpl {
         &self,
         request: Request<ListUsersRequest>,
     ) -> Result<Response<ListUsersResponse>, Status> {
+        Policy::Privileged.authorize(&Peer::from_request(&request))?;
         let message = request.into_inner();
 
         let page_size = min(max(message.page_size, 1), 100);
@@ -0,0 +1,74 @@
+use crate::{
+    firebase::{DecodedIdToken, MockIdTokenVerifier},
+    interceptors::authn::Peer,
+    services::user::UserServiceImpl,
+    testing::FakeStripe,
+};
+use affect_api::affect::{user_service_server::UserService, CreateUserRequest};
+use affect_status::Code;
+use affect_storage::Error;
+use affect_storage_mocks::*;
+use chrono::Utc;
+use hyper::Method;
+use serde_json::json;
+use std::sync::Arc;
+use tonic::Request;
+
+#[tokio::test]
+async fn create_user_deletes_stripe_customer_if_insert_fails() -> Result<(), anyhow::Error> {
+    let stripe = FakeStripe::start(vec![
+        (
+            Method::POST,
+            "/v1/customers",
+            json!({"id": "cus_new", "object": "customer"}),
+        ),
+        (
+            Method::DELETE,
+            "/v1/customers/cus_new",
+            json!({"id": "cus_new", "object": "customer", "deleted": true}),
+        ),
+    ]);
+    let mut firebase_auth = MockIdTokenVerifier::new();
+    firebase_auth
+        .expect_verify_id_token()
+        .times(1)
+        .returning(|_| {
+            Ok(DecodedIdToken {
+                uid: "firebase_uid".to_string(),
+                email: "user@affect.app".to_string(),
+                email_verified: true,
+                auth_time: Utc::now(),
+                expire_time: Utc::now(),
+                privileged: false,
+            })
+        });
+    let mut store = MockStore::new();
+    store
+        .expect_add_user()
+        .withf(|new_row| new_row.stripe_customer_id == "cus_new")
+        .times(1)
+        .return_once(|_| Err(Error::Other(anyhow::anyhow!("database unavailable"))));
+
+    let mut request = Request::new(CreateUserRequest {
+        firebase_id_token: "id_token".to_string(),
+    });
+    request.extensions_mut().insert(Peer::Anonymous);
+    let status = UserServiceImpl::new(
+        Arc::new(store),
+        Arc::new(firebase_auth),
+        Arc::new(stripe.client()),
+    )
+    .create_user(request)
+    .await
+    .unwrap_err();
+
+    assert_eq!(status.code(), Code::Internal);
+    assert_eq!(
+        stripe.calls(),
+        vec![
+            (Method::POST, "/v1/customers".to_string()),
+            (Method::DELETE, "/v1/customers/cus_new".to_string()),
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use crate::{interceptors::authn::Peer, tonic::async_interceptor::AsyncInterceptor};
+use affect_storage::models::{
+    account::AccountRow,
+    affiliate::{AffiliateRow, AffiliateVerificationState, BusinessType},
+    donation::{DonationRow, NewDonationRow},
+    nonprofit::{FullNonprofitRow, NonprofitRow},
+    user::UserRow,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+use tonic::Status;
+use uuid::Uuid;
+
+/// Interceptor which authenticates every request as the provided peer.
+#[derive(Clone)]
+pub struct StaticPeerInterceptor(pub Peer);
+
+#[async_trait]
+impl AsyncInterceptor for StaticPeerInterceptor {
+    async fn intercept(&self, req: &mut Request<Body>) -> Result<(), Status> {
+        req.extensions_mut().insert(self.0.clone());
+        Ok(())
+    }
+}
+
+/// Returns a user row with the provided id.
+pub fn user_row(user_id: Uuid) -> UserRow {
+    UserRow {
+        user_id,
+        create_time: Utc::now(),
+        update_time: Utc::now(),
+        firebase_uid: format!("firebase_uid_{0}", user_id),
+        firebase_email: "user@affect.app".to_string(),
+        stripe_customer_id: "cus_123".to_string(),
+    }
+}
+
+/// Returns a linked bank account with the provided id.
+pub fn account_row(account_id: Uuid) -> AccountRow {
+    AccountRow {
+        account_id,
+        create_time: Utc::now(),
+        update_time: Utc::now(),
+        item_id: Uuid::new_v4(),
+        plaid_account_id: "plaid_account".to_string(),
+        name: "Checking".to_string(),
+        mask: Some("0000".to_string()),
+        stripe_bank_account_id: "ba_123".to_string(),
+    }
+}
+
+/// Returns an approved affiliate with the provided stripe account, which
+/// accepts usd.
+pub fn affiliate_row(stripe_account_id: &str) -> AffiliateRow {
+    AffiliateRow {
+        affiliate_id: Uuid::new_v4(),
+        create_time: Utc::now(),
+        update_time: Utc::now(),
+        stripe_account_id: stripe_account_id.to_string(),
+        company_name: "Food Bank".to_string(),
+        contact_email: "affiliate@affect.app".to_string(),
+        business_type: BusinessType::Nonprofit,
+        asserted_nonprofit_id: Uuid::new_v4(),
+        charges_enabled: true,
+        payouts_enabled: true,
+        country: Some("US".to_string()),
+        business_name: Some("Food Bank".to_string()),
+        requirements_currently_due: Vec::new(),
+        disabled_reason: None,
+        stripe_refresh_time: None,
+        ein: None,
+        verification_state: AffiliateVerificationState::Approved,
+        rejection_reason: None,
+        verification_time: Some(Utc::now()),
+        currencies: vec!["USD".to_string()],
+    }
+}
+
+/// Returns a nonprofit with the provided id and affiliate.
+pub fn nonprofit_row(nonprofit_id: Uuid, affiliate: Option<AffiliateRow>) -> FullNonprofitRow {
+    FullNonprofitRow {
+        nonprofit: NonprofitRow {
+            nonprofit_id,
+            create_time: Utc::now(),
+            update_time: Utc::now(),
+            change_nonprofit_id: None,
+            icon_url: "https://affect.app/icon.png".to_string(),
+            name: "Food Bank".to_string(),
+            ein: "12-3456789".to_string(),
+            mission: "Feed people".to_string(),
+            category: "Food".to_string(),
+            affiliate_id: affiliate.as_ref().map(|affiliate| affiliate.affiliate_id),
+            email: None,
+        },
+        affiliate,
+    }
+}
+
+/// Returns the donation added for the new row.
+pub fn donation_row(new_row: NewDonationRow) -> DonationRow {
+    DonationRow {
+        donation_id: Uuid::new_v4(),
+        create_time: new_row.create_time,
+        update_time: new_row.update_time,
+        nonprofit_id: new_row.nonprofit_id,
+        cause_id: new_row.cause_id,
+        user_id: new_row.user_id,
+        affiliate_id: new_row.affiliate_id,
+        currency_code: new_row.currency_code,
+        amount_units: new_row.amount_units,
+        amount_nanos: new_row.amount_nanos,
+        stripe_charge_id: new_row.stripe_charge_id,
+        stripe_transfer_group: new_row.stripe_transfer_group,
+        status: new_row.status,
+        account_id: new_row.account_id,
+        settle_time: None,
+        cover_fees: new_row.cover_fees,
+        charge_amount_units: new_row.charge_amount_units,
+        charge_amount_nanos: new_row.charge_amount_nanos,
+        processing_fee_units: new_row.processing_fee_units,
+        processing_fee_nanos: new_row.processing_fee_nanos,
+        platform_fee_units: new_row.platform_fee_units,
+        platform_fee_nanos: new_row.platform_fee_nanos,
+    }
+}
+
+/// Request received by a `FakeStripe`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StripeRequest {
+    pub method: Method,
+    /// Path of the request, e.g. `/v1/accounts`.
+    pub path: String,
+    pub idempotency_key: Option<String>,
+}
+
+/// Local http server standing in for the stripe api. Responds to requests with
+/// the canned json body for their method and path, or a stripe error if there
+/// is none, and records every request it receives.
+pub struct FakeStripe {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<StripeRequest>>>,
+}
+
+impl FakeStripe {
+    pub fn start(responses: Vec<(Method, &str, serde_json::Value)>) -> Self {
+        let responses: Arc<HashMap<(Method, String), String>> = Arc::new(
+            responses
+                .into_iter()
+                .map(|(method, path, body)| ((method, path.to_string()), body.to_string()))
+                .collect(),
+        );
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let server_requests = requests.clone();
+        let make_service = make_service_fn(move |_| {
+            let responses = responses.clone();
+            let requests = server_requests.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let path = request.uri().path().to_string();
+                    requests.lock().unwrap().push(StripeRequest {
+                        method: request.method().clone(),
+                        path: path.clone(),
+                        idempotency_key: request
+                            .headers()
+                            .get("Idempotency-Key")
+                            .and_then(|value| value.to_str().ok())
+                            .map(|value| value.to_string()),
+                    });
+                    let response = match responses.get(&(request.method().clone(), path)) {
+                        Some(body) => Response::new(Body::from(body.clone())),
+                        None => Response::builder()
+                            .status(StatusCode::NOT_FOUND)
+                            .body(Body::from(
+                                r#"{"error":{"type":"invalid_request_error","message":"not found"}}"#,
+                            ))
+                            .unwrap(),
+                    };
+                    async { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        Self { addr, requests }
+    }
+
+    /// Returns a stripe client which sends its requests to this server.
+    pub fn client(&self) -> stripe::Client {
+        stripe::Client::from_url(format!("http://{0}/", self.addr).as_str(), "sk_test_xyz")
+    }
+
+    /// Returns the requests received so far, in order.
+    pub fn requests(&self) -> Vec<StripeRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Returns the method and path of the requests received so far, in order.
+    pub fn calls(&self) -> Vec<(Method, String)> {
+        self.requests()
+            .into_iter()
+            .map(|request| (request.method, request.path))
+            .collect()
+    }
+}
@@ -1 +1,2 @@
 pub mod async_interceptor;
+pub mod idempotency;
@@ -0,0 +1,420 @@
+use crate::interceptors::authn::Peer;
+use affect_status::{aborted, failed_precondition, internal, invalid_argument};
+use affect_storage::{
+    models::idempotency_key::NewIdempotencyKeyRow, stores::idempotency_key::IdempotencyKeyStore,
+};
+use bytes::{Bytes, BytesMut};
+use chrono::{Duration, Utc};
+use futures::future::BoxFuture;
+use http_body::Body as HttpBody;
+use hyper::{
+    header::{HeaderValue, CONTENT_TYPE},
+    Body, HeaderMap, Request, Response,
+};
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+use tonic::{body::BoxBody, Status};
+use tower::{Layer, Service};
+use uuid::Uuid;
+
+#[cfg(test)]
+mod tests;
+
+/// Header clients set to make retries of a request safe.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// How long a claim is held for an in-progress request before another request
+/// with the same key may take it over. Longer than the server's request
+/// timeout, so only claims of requests which can no longer complete expire.
+const CLAIM_TIMEOUT_SECS: i64 = 60;
+
+/// How long a key is kept once its request completed or failed, or its claim
+/// expired. Stripe forgets its idempotency keys after 24 hours, so a retry
+/// after that would repeat the stripe side effects of the request anyway.
+const KEY_RETENTION_HOURS: i64 = 24;
+
+/// Idempotency key of the current request. Inserted into the request
+/// extensions by `IdempotencyLayer` for requests which are replay protected.
+#[derive(Clone, Debug)]
+pub struct IdempotencyKey {
+    key: String,
+    /// Set once side effects performed under the key were undone. Shared by
+    /// clones, so the layer sees it after the service handled the request.
+    compensated: Arc<AtomicBool>,
+}
+
+impl IdempotencyKey {
+    pub fn new(key: String) -> Self {
+        Self {
+            key,
+            compensated: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.key
+    }
+
+    /// Records that stripe objects created with this key were undone. Stripe
+    /// would replay them for a retry with the same key, so the key must not be
+    /// released for retries.
+    pub fn mark_compensated(&self) {
+        self.compensated.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_compensated(&self) -> bool {
+        self.compensated.load(Ordering::SeqCst)
+    }
+
+    /// Returns a stripe client which forwards this key as stripe's `Idempotency-Key`.
+    /// Requests which make several stripe calls must use a distinct `operation` for
+    /// each of them.
+    pub fn stripe_client(&self, client: &stripe::Client, operation: &str) -> stripe::Client {
+        client
+            .clone()
+            .with_strategy(stripe::RequestStrategy::Idempotent(format!(
+                "{0}-{1}",
+                self.key, operation
+            )))
+    }
+}
+
+impl PartialEq for IdempotencyKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+/// Returns `client` as-is when the request has no idempotency key, otherwise a
+/// client forwarding the key to stripe.
+pub fn idempotent_stripe_client(
+    client: &stripe::Client,
+    idempotency_key: &Option<IdempotencyKey>,
+    operation: &str,
+) -> stripe::Client {
+    match idempotency_key {
+        Some(idempotency_key) => idempotency_key.stripe_client(client, operation),
+        None => client.clone(),
+    }
+}
+
+/// Layer which makes the configured rpcs idempotent. The first successful
+/// response for an idempotency key is stored and replayed for every later
+/// request of the same user with the same key, path and body, until the key
+/// is purged after its retention. Requests of anonymous peers aren't replay
+/// protected. Must be applied after the authn interceptor, which
+/// authenticates the user keys are scoped to.
+#[derive(Clone)]
+pub struct IdempotencyLayer {
+    store: Arc<dyn IdempotencyKeyStore>,
+    paths: Arc<HashSet<String>>,
+}
+
+impl IdempotencyLayer {
+    pub fn new<'a>(
+        store: Arc<dyn IdempotencyKeyStore>,
+        paths: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        Self {
+            store,
+            paths: Arc::new(paths.into_iter().map(|path| path.to_string()).collect()),
+        }
+    }
+
+    /// Deletes expired idempotency keys every interval, forever.
+    pub async fn purge(&self, interval: std::time::Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.purge_expired().await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} idempotency keys", purged),
+                Err(e) => warn!("Failed to purge idempotency keys: {:?}", e),
+            }
+        }
+    }
+
+    /// Deletes the keys whose retention ended, returning the number of deleted
+    /// keys.
+    pub async fn purge_expired(&self) -> Result<u64, affect_storage::Error> {
+        self.store
+            .delete_expired_idempotency_keys(Utc::now() - Duration::hours(KEY_RETENTION_HOURS))
+            .await
+    }
+}
+
+impl<S> Layer<S> for IdempotencyLayer {
+    type Service = IdempotencyService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        Self::Service {
+            inner: service,
+            store: self.store.clone(),
+            paths: self.paths.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct IdempotencyService<S> {
+    inner: S,
+    store: Arc<dyn IdempotencyKeyStore>,
+    paths: Arc<HashSet<String>>,
+}
+
+impl<S> Service<hyper::Request<Body>> for IdempotencyService<S>
+where
+    S: Service<hyper::Request<Body>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: hyper::Request<Body>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let store = self.store.clone();
+        let paths = self.paths.clone();
+
+        Box::pin(async move {
+            let path = req.uri().path().to_string();
+            let user_id = req
+                .extensions()
+                .get::<Peer>()
+                .and_then(|peer| peer.user())
+                .map(|user| user.user_id);
+            let (idempotency_key, user_id) = match (idempotency_key(&req), user_id) {
+                (Some(key), Some(user_id)) if paths.contains(&path) => (key, user_id),
+                _ => return inner.call(req).await,
+            };
+
+            let (parts, body) = req.into_parts();
+            let body = match hyper::body::to_bytes(body).await {
+                Ok(body) => body,
+                Err(e) => return Ok(internal!("failed to read request: {:?}", e).to_http()),
+            };
+            let request_hash = hex::encode(Sha256::digest(&body));
+            let mut req = Request::from_parts(parts, Body::from(body));
+
+            match claim(&*store, user_id, &idempotency_key, &path, &request_hash).await {
+                Ok(Some(response_body)) => return Ok(replay_response(response_body)),
+                Ok(None) => {}
+                Err(status) => return Ok(status.to_http()),
+            }
+
+            let request_key = IdempotencyKey::new(idempotency_key.clone());
+            req.extensions_mut().insert(request_key.clone());
+            let (parts, body) = inner.call(req).await?.into_parts();
+
+            let (data, trailers) = match collect_body(body).await {
+                Ok(collected) => collected,
+                Err(status) => {
+                    fail(&*store, user_id, &request_key, &path).await;
+                    return Ok(status.to_http());
+                }
+            };
+
+            if is_ok(&parts.headers, &trailers) {
+                if let Err(e) = store
+                    .update_idempotency_key_response(
+                        user_id,
+                        &idempotency_key,
+                        &path,
+                        data.to_vec(),
+                        Utc::now(),
+                    )
+                    .await
+                {
+                    // The side effects of the request happened, so the key is
+                    // kept failed rather than released or taken over once the
+                    // claim expires, which would repeat them for a retry.
+                    warn!("Failed to store response for idempotency key: {:?}", e);
+                    block(&*store, user_id, &request_key, &path).await;
+                    return Ok(internal!(
+                        "request completed but its response could not be stored for the idempotency key"
+                    )
+                    .to_http());
+                }
+            } else {
+                fail(&*store, user_id, &request_key, &path).await;
+            }
+
+            Ok(Response::from_parts(
+                parts,
+                ReplayBody::new(data, trailers).boxed_unsync(),
+            ))
+        })
+    }
+}
+
+fn idempotency_key(req: &Request<Body>) -> Option<String> {
+    req.headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string())
+}
+
+/// Claims the user's idempotency key, returning the stored response body if a
+/// previous request with the same key already completed.
+async fn claim(
+    store: &dyn IdempotencyKeyStore,
+    user_id: Uuid,
+    idempotency_key: &str,
+    path: &str,
+    request_hash: &str,
+) -> Result<Option<Vec<u8>>, Status> {
+    let now = Utc::now();
+    let claimed = store
+        .add_idempotency_key(NewIdempotencyKeyRow {
+            user_id,
+            idempotency_key: idempotency_key.to_string(),
+            request_path: path.to_string(),
+            request_hash: request_hash.to_string(),
+            create_time: now,
+            update_time: now,
+            lock_expire_time: now + Duration::seconds(CLAIM_TIMEOUT_SECS),
+        })
+        .await?;
+    if claimed.is_some() {
+        return Ok(None);
+    }
+
+    let existing = store
+        .find_idempotency_key(user_id, idempotency_key, path)
+        .await?
+        .ok_or(aborted!(
+            "request with the same idempotency key was concurrently released"
+        ))?;
+    if existing.request_hash != request_hash {
+        return Err(invalid_argument!(
+            "idempotency key was already used for a different request"
+        ));
+    }
+    match (existing.response_body, existing.lock_expire_time) {
+        (Some(response_body), _) => Ok(Some(response_body)),
+        (None, None) => Err(failed_precondition!(
+            "request with the same idempotency key failed and can't be retried with it, retry with a new idempotency key"
+        )),
+        (None, Some(_)) => Err(aborted!(
+            "request with the same idempotency key is in progress"
+        )),
+    }
+}
+
+/// Handles a failed request. Failed requests are not replayed, so the key is
+/// released for the client to retry, unless side effects performed under the
+/// key were undone: stripe would replay those for a retry, so the claim is kept
+/// as failed instead.
+async fn fail(
+    store: &dyn IdempotencyKeyStore,
+    user_id: Uuid,
+    idempotency_key: &IdempotencyKey,
+    path: &str,
+) {
+    if idempotency_key.is_compensated() {
+        block(store, user_id, idempotency_key, path).await;
+    } else if let Err(e) = store
+        .delete_idempotency_key(user_id, idempotency_key.as_str(), path)
+        .await
+    {
+        warn!("Failed to release idempotency key: {:?}", e);
+    }
+}
+
+/// Keeps the claim of a request which must not be retried with its key as
+/// failed for good.
+async fn block(
+    store: &dyn IdempotencyKeyStore,
+    user_id: Uuid,
+    idempotency_key: &IdempotencyKey,
+    path: &str,
+) {
+    if let Err(e) = store
+        .update_idempotency_key_failed(user_id, idempotency_key.as_str(), path, Utc::now())
+        .await
+    {
+        warn!("Failed to keep idempotency key failed: {:?}", e);
+    }
+}
+
+async fn collect_body(mut body: BoxBody) -> Result<(Bytes, Option<HeaderMap>), Status> {
+    let mut data = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        data.extend_from_slice(&chunk?);
+    }
+    let trailers = body.trailers().await?;
+    Ok((data.freeze(), trailers))
+}
+
+/// Whether the grpc status of the response, either in the trailers or in the
+/// headers of a trailers-only response, is OK.
+fn is_ok(headers: &HeaderMap, trailers: &Option<HeaderMap>) -> bool {
+    trailers
+        .as_ref()
+        .and_then(|trailers| trailers.get("grpc-status"))
+        .or(headers.get("grpc-status"))
+        .map(|status| status.as_bytes() == b"0")
+        .unwrap_or(false)
+}
+
+fn replay_response(response_body: Vec<u8>) -> Response<BoxBody> {
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+
+    let mut response =
+        Response::new(ReplayBody::new(Bytes::from(response_body), Some(trailers)).boxed_unsync());
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+    response
+}
+
+/// Body which yields already collected data followed by trailers.
+struct ReplayBody {
+    data: Option<Bytes>,
+    trailers: Option<HeaderMap>,
+}
+
+impl ReplayBody {
+    fn new(data: Bytes, trailers: Option<HeaderMap>) -> Self {
+        Self {
+            data: Some(data).filter(|data| !data.is_empty()),
+            trailers,
+        }
+    }
+}
+
+impl HttpBody for ReplayBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Poll::Ready(self.data.take().map(Ok))
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(self.trailers.take()))
+    }
+}
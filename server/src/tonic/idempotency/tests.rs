@@ -0,0 +1,398 @@
+use crate::{
+    interceptors::authn::Peer,
+    testing::user_row,
+    tonic::idempotency::{
+        collect_body, replay_response, IdempotencyKey, IdempotencyLayer, IDEMPOTENCY_KEY_HEADER,
+    },
+};
+use affect_storage::{models::idempotency_key::IdempotencyKeyRow, Error};
+use affect_storage_mocks::*;
+use chrono::{Duration, Utc};
+use hyper::{Body, Request, Response};
+use mockall::predicate::eq;
+use sha2::{Digest, Sha256};
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tonic::{body::BoxBody, Code, Status};
+use tower::{service_fn, Layer, ServiceExt};
+use uuid::Uuid;
+
+const PATH: &str = "/affect.DonationService/CreateDonation";
+const REQUEST_BODY: &[u8] = b"request";
+const RESPONSE_BODY: &[u8] = b"response";
+
+fn request(user_id: Uuid, body: &'static [u8]) -> Request<Body> {
+    let mut request = Request::builder()
+        .uri(PATH)
+        .header(IDEMPOTENCY_KEY_HEADER, "key")
+        .body(Body::from(body))
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(Peer::User(user_row(user_id)));
+    request
+}
+
+fn request_hash(body: &[u8]) -> String {
+    hex::encode(Sha256::digest(body))
+}
+
+fn idempotency_key_row(
+    user_id: Uuid,
+    request_hash: String,
+    response_body: Option<Vec<u8>>,
+) -> IdempotencyKeyRow {
+    IdempotencyKeyRow {
+        user_id,
+        idempotency_key: "key".to_string(),
+        request_path: PATH.to_string(),
+        request_hash,
+        create_time: Utc::now(),
+        update_time: Utc::now(),
+        lock_expire_time: None,
+        response_body,
+    }
+}
+
+/// How the service behind the layer handles the request.
+#[derive(Clone, Copy)]
+enum Outcome {
+    Succeed,
+    Fail(Code),
+    /// Fails after undoing the side effects made under the idempotency key.
+    Compensate(Code),
+}
+
+/// Sends the request through the layer to a service which handles it as given
+/// by the outcome, returning the response and how often the service was
+/// called.
+async fn call(
+    store: MockStore,
+    request: Request<Body>,
+    outcome: Outcome,
+) -> (Response<BoxBody>, usize) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let inner_calls = calls.clone();
+    let inner = service_fn(move |request: Request<Body>| {
+        inner_calls.fetch_add(1, Ordering::SeqCst);
+        async move {
+            let idempotency_key = request.extensions().get::<IdempotencyKey>().cloned();
+            assert_eq!(
+                idempotency_key,
+                Some(IdempotencyKey::new("key".to_string()))
+            );
+            assert_eq!(
+                hyper::body::to_bytes(request.into_body()).await.unwrap(),
+                REQUEST_BODY
+            );
+            Ok::<_, Infallible>(match outcome {
+                Outcome::Succeed => replay_response(RESPONSE_BODY.to_vec()),
+                Outcome::Fail(code) => Status::new(code, "failed").to_http(),
+                Outcome::Compensate(code) => {
+                    idempotency_key.unwrap().mark_compensated();
+                    Status::new(code, "failed").to_http()
+                }
+            })
+        }
+    });
+    let response = IdempotencyLayer::new(Arc::new(store), [PATH])
+        .layer(inner)
+        .oneshot(request)
+        .await
+        .unwrap();
+    (response, calls.load(Ordering::SeqCst))
+}
+
+fn grpc_code(response: &Response<BoxBody>) -> Option<Code> {
+    response
+        .headers()
+        .get("grpc-status")
+        .and_then(|status| status.to_str().ok())
+        .and_then(|status| status.parse::<i32>().ok())
+        .map(Code::from)
+}
+
+#[tokio::test]
+async fn stores_successful_response() {
+    let user_id = Uuid::new_v4();
+    let mut store = MockStore::new();
+    store
+        .expect_add_idempotency_key()
+        .withf(move |new_row| {
+            new_row.user_id == user_id
+                && new_row.idempotency_key == "key"
+                && new_row.request_path == PATH
+                && new_row.request_hash == request_hash(REQUEST_BODY)
+                && new_row.lock_expire_time > new_row.create_time
+        })
+        .times(1)
+        .returning(move |new_row| {
+            Ok(Some(IdempotencyKeyRow {
+                lock_expire_time: Some(new_row.lock_expire_time),
+                ..idempotency_key_row(user_id, new_row.request_hash, None)
+            }))
+        });
+    store
+        .expect_update_idempotency_key_response()
+        .with(
+            eq(user_id),
+            eq("key"),
+            eq(PATH),
+            eq(RESPONSE_BODY.to_vec()),
+            mockall::predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _, _| Ok(()));
+    store.expect_delete_idempotency_key().never();
+
+    let (response, calls) = call(store, request(user_id, REQUEST_BODY), Outcome::Succeed).await;
+    assert_eq!(calls, 1);
+    let (data, trailers) = collect_body(response.into_body()).await.unwrap();
+    assert_eq!(data, RESPONSE_BODY);
+    assert_eq!(trailers.unwrap().get("grpc-status").unwrap(), "0");
+}
+
+#[tokio::test]
+async fn replays_completed_response() {
+    let user_id = Uuid::new_v4();
+    let mut store = MockStore::new();
+    store
+        .expect_add_idempotency_key()
+        .times(1)
+        .returning(|_| Ok(None));
+    store
+        .expect_find_idempotency_key()
+        .with(eq(user_id), eq("key"), eq(PATH))
+        .times(1)
+        .returning(move |_, _, _| {
+            Ok(Some(idempotency_key_row(
+                user_id,
+                request_hash(REQUEST_BODY),
+                Some(b"stored".to_vec()),
+            )))
+        });
+
+    let (response, calls) = call(store, request(user_id, REQUEST_BODY), Outcome::Succeed).await;
+    assert_eq!(calls, 0);
+    let (data, trailers) = collect_body(response.into_body()).await.unwrap();
+    assert_eq!(data, &b"stored"[..]);
+    assert_eq!(trailers.unwrap().get("grpc-status").unwrap(), "0");
+}
+
+#[tokio::test]
+async fn rejects_request_in_progress() {
+    let user_id = Uuid::new_v4();
+    let mut store = MockStore::new();
+    store
+        .expect_add_idempotency_key()
+        .times(1)
+        .returning(|_| Ok(None));
+    store
+        .expect_find_idempotency_key()
+        .times(1)
+        .returning(move |_, _, _| {
+            Ok(Some(IdempotencyKeyRow {
+                lock_expire_time: Some(Utc::now()),
+                ..idempotency_key_row(user_id, request_hash(REQUEST_BODY), None)
+            }))
+        });
+
+    let (response, calls) = call(store, request(user_id, REQUEST_BODY), Outcome::Succeed).await;
+    assert_eq!(calls, 0);
+    assert_eq!(grpc_code(&response), Some(Code::Aborted));
+}
+
+#[tokio::test]
+async fn rejects_retry_of_compensated_request() {
+    let user_id = Uuid::new_v4();
+    let mut store = MockStore::new();
+    store
+        .expect_add_idempotency_key()
+        .times(1)
+        .returning(|_| Ok(None));
+    store
+        .expect_find_idempotency_key()
+        .times(1)
+        .returning(move |_, _, _| {
+            Ok(Some(idempotency_key_row(
+                user_id,
+                request_hash(REQUEST_BODY),
+                None,
+            )))
+        });
+
+    let (response, calls) = call(store, request(user_id, REQUEST_BODY), Outcome::Succeed).await;
+    assert_eq!(calls, 0);
+    assert_eq!(grpc_code(&response), Some(Code::FailedPrecondition));
+}
+
+#[tokio::test]
+async fn rejects_key_reused_for_different_request() {
+    let user_id = Uuid::new_v4();
+    let mut store = MockStore::new();
+    store
+        .expect_add_idempotency_key()
+        .times(1)
+        .returning(|_| Ok(None));
+    store
+        .expect_find_idempotency_key()
+        .times(1)
+        .returning(move |_, _, _| {
+            Ok(Some(idempotency_key_row(
+                user_id,
+                request_hash(b"other request"),
+                Some(b"stored".to_vec()),
+            )))
+        });
+
+    let (response, calls) = call(store, request(user_id, REQUEST_BODY), Outcome::Succeed).await;
+    assert_eq!(calls, 0);
+    assert_eq!(grpc_code(&response), Some(Code::InvalidArgument));
+}
+
+#[tokio::test]
+async fn releases_key_on_error() {
+    let user_id = Uuid::new_v4();
+    let mut store = MockStore::new();
+    store
+        .expect_add_idempotency_key()
+        .times(1)
+        .returning(move |new_row| {
+            Ok(Some(idempotency_key_row(
+                user_id,
+                new_row.request_hash,
+                None,
+            )))
+        });
+    store.expect_update_idempotency_key_response().never();
+    store
+        .expect_delete_idempotency_key()
+        .with(eq(user_id), eq("key"), eq(PATH))
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let (response, calls) = call(
+        store,
+        request(user_id, REQUEST_BODY),
+        Outcome::Fail(Code::Internal),
+    )
+    .await;
+    assert_eq!(calls, 1);
+    assert_eq!(grpc_code(&response), Some(Code::Internal));
+}
+
+#[tokio::test]
+async fn keeps_compensated_key_failed() {
+    let user_id = Uuid::new_v4();
+    let mut store = MockStore::new();
+    store
+        .expect_add_idempotency_key()
+        .times(1)
+        .returning(move |new_row| {
+            Ok(Some(idempotency_key_row(
+                user_id,
+                new_row.request_hash,
+                None,
+            )))
+        });
+    store.expect_update_idempotency_key_response().never();
+    store.expect_delete_idempotency_key().never();
+    store
+        .expect_update_idempotency_key_failed()
+        .with(
+            eq(user_id),
+            eq("key"),
+            eq(PATH),
+            mockall::predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(()));
+
+    let (response, calls) = call(
+        store,
+        request(user_id, REQUEST_BODY),
+        Outcome::Compensate(Code::Internal),
+    )
+    .await;
+    assert_eq!(calls, 1);
+    assert_eq!(grpc_code(&response), Some(Code::Internal));
+}
+
+#[tokio::test]
+async fn keeps_key_failed_if_response_is_not_stored() {
+    let user_id = Uuid::new_v4();
+    let mut store = MockStore::new();
+    store
+        .expect_add_idempotency_key()
+        .times(1)
+        .returning(move |new_row| {
+            Ok(Some(idempotency_key_row(
+                user_id,
+                new_row.request_hash,
+                None,
+            )))
+        });
+    store
+        .expect_update_idempotency_key_response()
+        .times(1)
+        .returning(|_, _, _, _, _| Err(Error::Other(anyhow::anyhow!("database unavailable"))));
+    store.expect_delete_idempotency_key().never();
+    // The request succeeded, so a retry with the key must neither repeat it
+    // nor take the claim over.
+    store
+        .expect_update_idempotency_key_failed()
+        .with(
+            eq(user_id),
+            eq("key"),
+            eq(PATH),
+            mockall::predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(()));
+
+    let (response, calls) = call(store, request(user_id, REQUEST_BODY), Outcome::Succeed).await;
+    assert_eq!(calls, 1);
+    assert_eq!(grpc_code(&response), Some(Code::Internal));
+}
+
+#[tokio::test]
+async fn purges_expired_keys() {
+    let mut store = MockStore::new();
+    store
+        .expect_delete_expired_idempotency_keys()
+        .withf(|expire_time| {
+            let retention = Utc::now() - *expire_time;
+            retention >= Duration::hours(24) && retention < Duration::hours(25)
+        })
+        .times(1)
+        .returning(|_| Ok(3));
+
+    let layer = IdempotencyLayer::new(Arc::new(store), [PATH]);
+    assert_eq!(layer.purge_expired().await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn skips_anonymous_requests() {
+    // Calls to the store would panic without expectations.
+    let store = MockStore::new();
+    let mut request = request(Uuid::new_v4(), REQUEST_BODY);
+    request.extensions_mut().insert(Peer::Anonymous);
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let inner_calls = calls.clone();
+    let inner = service_fn(move |_: Request<Body>| {
+        inner_calls.fetch_add(1, Ordering::SeqCst);
+        async { Ok::<_, Infallible>(replay_response(RESPONSE_BODY.to_vec())) }
+    });
+    IdempotencyLayer::new(Arc::new(store), [PATH])
+        .layer(inner)
+        .oneshot(request)
+        .await
+        .unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
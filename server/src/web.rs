@@ -0,0 +1,181 @@
+use crate::config::WebConfig;
+use reqwest::Url;
+use uuid::Uuid;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("invalid web url template {name} ({template:?}): {reason}")]
+    InvalidTemplate {
+        name: &'static str,
+        template: String,
+        reason: String,
+    },
+}
+
+/// Builds links to pages of the web app, from the url templates in the web
+/// config. Templates are validated on construction, so building a link can't
+/// fail.
+#[derive(Clone, Debug)]
+pub struct WebLinks {
+    config: WebConfig,
+}
+
+impl WebLinks {
+    pub fn new(config: WebConfig) -> Result<Self, Error> {
+        validate(
+            "affiliate-onboarding-url",
+            &config.affiliate_onboarding_url,
+            &["affiliate_id"],
+        )?;
+        validate(
+            "affiliate-return-url",
+            &config.affiliate_return_url,
+            &["affiliate_id"],
+        )?;
+        validate(
+            "affiliate-invitation-url",
+            &config.affiliate_invitation_url,
+            &["token"],
+        )?;
+        if let Some(plaid_redirect_url) = &config.plaid_redirect_url {
+            validate("plaid-redirect-url", plaid_redirect_url, &[])?;
+        }
+        Ok(Self { config })
+    }
+
+    /// Page stripe sends affiliates to when their onboarding link expired.
+    pub fn affiliate_onboarding_url(&self, affiliate_id: Uuid) -> String {
+        fill(
+            &self.config.affiliate_onboarding_url,
+            &[("affiliate_id", &affiliate_id.to_string())],
+        )
+    }
+
+    /// Page stripe sends affiliates to when they leave onboarding or the
+    /// express dashboard.
+    pub fn affiliate_return_url(&self, affiliate_id: Uuid) -> String {
+        fill(
+            &self.config.affiliate_return_url,
+            &[("affiliate_id", &affiliate_id.to_string())],
+        )
+    }
+
+    /// Page an affiliate manager invitation is accepted on.
+    pub fn affiliate_invitation_url(&self, token: &str) -> String {
+        fill(&self.config.affiliate_invitation_url, &[("token", token)])
+    }
+
+    /// Page plaid link redirects to after OAuth, if configured.
+    pub fn plaid_redirect_url(&self) -> Option<String> {
+        self.config.plaid_redirect_url.clone()
+    }
+}
+
+/// Replaces each `{name}` placeholder of the template with its value.
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |url, (name, value)| {
+            url.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+/// Checks the template has every placeholder, no unknown placeholders, and
+/// forms an absolute http(s) url once filled.
+fn validate(name: &'static str, template: &str, placeholders: &[&str]) -> Result<(), Error> {
+    let invalid = |reason: String| Error::InvalidTemplate {
+        name,
+        template: template.to_string(),
+        reason,
+    };
+
+    for placeholder in placeholders {
+        if !template.contains(&format!("{{{}}}", placeholder)) {
+            return Err(invalid(format!("missing placeholder {{{}}}", placeholder)));
+        }
+    }
+
+    let values: Vec<(&str, &str)> = placeholders.iter().map(|p| (*p, "x")).collect();
+    let url = fill(template, &values);
+    if url.contains('{') || url.contains('}') {
+        return Err(invalid(format!(
+            "unknown placeholder, expected only {:?}",
+            placeholders
+        )));
+    }
+
+    let url = Url::parse(&url).map_err(|e| invalid(e.to_string()))?;
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err(invalid(format!("unsupported scheme {}", url.scheme())));
+    }
+    if url.host_str().is_none() {
+        return Err(invalid("missing host".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn web_config() -> WebConfig {
+        WebConfig {
+            affiliate_onboarding_url:
+                "https://web.affect.app/#/affiliate/{affiliate_id}/stripe/onboarding".to_string(),
+            affiliate_return_url: "https://web.affect.app/#/affiliate/{affiliate_id}/stripe/return"
+                .to_string(),
+            affiliate_invitation_url: "https://web.affect.app/#/affiliate-invitation/{token}"
+                .to_string(),
+            plaid_redirect_url: None,
+        }
+    }
+
+    #[test]
+    fn builds_links() -> Result<(), Error> {
+        let links = WebLinks::new(web_config())?;
+        let affiliate_id = Uuid::new_v4();
+        assert_eq!(
+            links.affiliate_onboarding_url(affiliate_id),
+            format!(
+                "https://web.affect.app/#/affiliate/{}/stripe/onboarding",
+                affiliate_id
+            )
+        );
+        assert_eq!(
+            links.affiliate_return_url(affiliate_id),
+            format!(
+                "https://web.affect.app/#/affiliate/{}/stripe/return",
+                affiliate_id
+            )
+        );
+        assert_eq!(
+            links.affiliate_invitation_url("abc"),
+            "https://web.affect.app/#/affiliate-invitation/abc"
+        );
+        assert_eq!(links.plaid_redirect_url(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        // Missing placeholder.
+        let mut config = web_config();
+        config.affiliate_onboarding_url = "https://web.affect.app/#/affiliate".to_string();
+        assert!(WebLinks::new(config).is_err());
+
+        // Unknown placeholder.
+        let mut config = web_config();
+        config.affiliate_invitation_url = "https://web.affect.app/{token}/{user_id}".to_string();
+        assert!(WebLinks::new(config).is_err());
+
+        // Not an absolute url.
+        let mut config = web_config();
+        config.affiliate_return_url = "/affiliate/{affiliate_id}".to_string();
+        assert!(WebLinks::new(config).is_err());
+
+        // Unsupported scheme.
+        let mut config = web_config();
+        config.plaid_redirect_url = Some("ftp://web.affect.app/plaid".to_string());
+        assert!(WebLinks::new(config).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use hyper::{Body, HeaderMap, Method, Request, Response, StatusCode};
+use log::warn;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tonic::body::{empty_body, BoxBody};
+use tower::{Layer, Service};
+
+pub mod plaid;
+pub mod stripe;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    // The request could not be authenticated as coming from the sender.
+    #[error("invalid signature: {0}")]
+    InvalidSignature(String),
+
+    // The body of the request could not be parsed.
+    #[error("invalid payload: {0}")]
+    InvalidPayload(String),
+
+    // Storing or processing the event failed, the sender should retry.
+    #[error("storage error: {0:?}")]
+    Storage(#[from] affect_storage::Error),
+
+    // Fetching data needed to verify the request from the sender failed.
+    #[error("upstream error: {0}")]
+    Upstream(String),
+}
+
+impl Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::InvalidSignature(_) => StatusCode::BAD_REQUEST,
+            Error::InvalidPayload(_) => StatusCode::BAD_REQUEST,
+            Error::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Upstream(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Handles webhook requests sent to a single path.
+#[async_trait]
+pub trait WebhookHandler: Sync + Send {
+    async fn handle(&self, headers: &HeaderMap, body: Bytes) -> Result<(), Error>;
+}
+
+/// Layer which serves plain http webhooks next to the grpc services. Requests
+/// to a registered path are handled by its webhook handler, every other request
+/// is passed to the inner service.
+#[derive(Clone, Default)]
+pub struct WebhookLayer {
+    handlers: HashMap<String, Arc<dyn WebhookHandler>>,
+}
+
+impl WebhookLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles POST requests to `path` with `handler`.
+    pub fn route(mut self, path: &str, handler: Arc<dyn WebhookHandler>) -> Self {
+        self.handlers.insert(path.to_string(), handler);
+        self
+    }
+}
+
+impl<S> Layer<S> for WebhookLayer {
+    type Service = WebhookService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        Self::Service {
+            inner: service,
+            handlers: Arc::new(self.handlers.clone()),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct WebhookService<S> {
+    inner: S,
+    handlers: Arc<HashMap<String, Arc<dyn WebhookHandler>>>,
+}
+
+impl<S> Service<Request<Body>> for WebhookService<S>
+where
+    S: Service<Request<Body>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let path = req.uri().path().to_string();
+        let handler = match self.handlers.get(&path) {
+            Some(handler) => handler.clone(),
+            None => return Box::pin(self.inner.call(req)),
+        };
+
+        Box::pin(async move {
+            if req.method() != Method::POST {
+                return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+            }
+
+            let (parts, body) = req.into_parts();
+            let body = match hyper::body::to_bytes(body).await {
+                Ok(body) => body,
+                Err(e) => {
+                    warn!("Failed to read webhook body for {}: {:?}", path, e);
+                    return Ok(status_response(StatusCode::BAD_REQUEST));
+                }
+            };
+
+            match handler.handle(&parts.headers, body).await {
+                Ok(()) => Ok(status_response(StatusCode::OK)),
+                Err(e) => {
+                    warn!("Failed to handle webhook for {}: {:?}", path, e);
+                    Ok(status_response(e.status_code()))
+                }
+            }
+        })
+    }
+}
+
+fn status_response(status_code: StatusCode) -> Response<BoxBody> {
+    let mut response = Response::new(empty_body());
+    *response.status_mut() = status_code;
+    response
+}
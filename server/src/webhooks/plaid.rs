@@ -0,0 +1,471 @@
+use crate::webhooks::{Error, WebhookHandler};
+use affect_storage::{
+    database::{
+        client::DatabaseClient,
+        store::{OnDemandStore, TransactionalStore},
+    },
+    models::item::ItemStatus,
+    stores::item::ItemStore,
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use hyper::HeaderMap;
+use log::info;
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Header plaid signs webhook requests with.
+pub const VERIFICATION_HEADER: &str = "plaid-verification";
+
+/// Maximum age of a signed request, protecting against replay attacks.
+const MAX_TOKEN_AGE_SECS: i64 = 300;
+
+/// Age after which a cached verification key is fetched again, so that keys
+/// plaid rotated out stop being trusted.
+const KEY_TTL_SECS: i64 = 3600;
+
+/// Age after which an expired or unknown key id is looked up again.
+const UNKNOWN_KEY_TTL_SECS: i64 = 300;
+
+/// Minimum interval between lookups of key ids which are not cached, so that
+/// unauthenticated requests cannot make us flood plaid.
+const MIN_KEY_LOOKUP_INTERVAL_SECS: i64 = 1;
+
+/// Public key plaid signs webhooks with, see
+/// https://plaid.com/docs/api/webhooks/webhook-verification/.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct VerificationKey {
+    pub kid: String,
+    pub alg: String,
+    pub crv: String,
+    pub x: String,
+    pub y: String,
+    pub expired_at: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct GetVerificationKeyRequest<'a> {
+    client_id: &'a str,
+    secret: &'a str,
+    key_id: &'a str,
+}
+
+#[derive(Deserialize)]
+struct GetVerificationKeyResponse {
+    key: VerificationKey,
+}
+
+#[derive(Deserialize)]
+struct TokenHeader {
+    alg: String,
+    kid: String,
+}
+
+#[derive(Deserialize)]
+struct TokenClaims {
+    iat: i64,
+    request_body_sha256: String,
+}
+
+/// Verification key as fetched from plaid, `None` if plaid did not know its id.
+struct CachedKey {
+    key: Option<VerificationKey>,
+    fetch_time: DateTime<Utc>,
+}
+
+impl CachedKey {
+    fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+        let ttl_secs = match &self.key {
+            Some(key) if key.expired_at.is_none() => KEY_TTL_SECS,
+            _ => UNKNOWN_KEY_TTL_SECS,
+        };
+        now - self.fetch_time < Duration::seconds(ttl_secs)
+    }
+}
+
+#[derive(Default)]
+struct KeyCache {
+    keys: HashMap<String, CachedKey>,
+    last_lookup_time: Option<DateTime<Utc>>,
+}
+
+/// Verifies the `plaid-verification` jwt of webhook requests. Verification
+/// keys are fetched from plaid on first use and cached until they go stale.
+pub struct PlaidWebhookVerifier {
+    client: reqwest::Client,
+    url: String,
+    client_id: String,
+    secret: String,
+    cache: Mutex<KeyCache>,
+}
+
+impl PlaidWebhookVerifier {
+    pub fn new(client_id: String, secret: String, env: &str) -> Self {
+        Self::from_url(client_id, secret, &format!("https://{0}.plaid.com", env))
+    }
+
+    /// Creates a verifier which fetches keys from the plaid api at `url`.
+    pub fn from_url(client_id: String, secret: String, url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: format!(
+                "{0}/webhook_verification_key/get",
+                url.trim_end_matches('/')
+            ),
+            client_id,
+            secret,
+            cache: Mutex::new(KeyCache::default()),
+        }
+    }
+
+    /// Verifies that the jwt was issued by plaid for the body.
+    pub async fn verify(&self, jwt: &str, body: &[u8]) -> Result<(), Error> {
+        let now = Utc::now();
+        let header: TokenHeader = decode_token_part(jwt.split('.').next().unwrap_or(""))?;
+        let key = self.verification_key(&header.kid, now).await?;
+        verify_token(jwt, body, &key, now)
+    }
+
+    async fn verification_key(
+        &self,
+        key_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<VerificationKey, Error> {
+        {
+            let mut cache = self.cache.lock().unwrap();
+            match cache.keys.get(key_id) {
+                Some(cached) if cached.is_fresh(now) => return cached_key(cached),
+                // Stale keys are refreshed, they were issued by plaid before.
+                Some(_) => {}
+                None => {
+                    if let Some(last_lookup_time) = cache.last_lookup_time {
+                        if now - last_lookup_time < Duration::seconds(MIN_KEY_LOOKUP_INTERVAL_SECS)
+                        {
+                            return Err(Error::Upstream(
+                                "too many verification key lookups".to_string(),
+                            ));
+                        }
+                    }
+                    cache.last_lookup_time = Some(now);
+                }
+            }
+        }
+
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&GetVerificationKeyRequest {
+                client_id: &self.client_id,
+                secret: &self.secret,
+                key_id,
+            })
+            .send()
+            .await
+            .map_err(|e| Error::Upstream(format!("failed to fetch verification key: {:?}", e)))?;
+        let key = if response.status().is_client_error() {
+            // Plaid answers requests for unknown key ids with an invalid
+            // request error.
+            None
+        } else {
+            let response = response
+                .error_for_status()
+                .map_err(|e| Error::Upstream(format!("failed to fetch verification key: {:?}", e)))?
+                .json::<GetVerificationKeyResponse>()
+                .await
+                .map_err(|e| {
+                    Error::Upstream(format!("failed to parse verification key: {:?}", e))
+                })?;
+            Some(response.key)
+        };
+
+        let cached = CachedKey {
+            key,
+            fetch_time: now,
+        };
+        let result = cached_key(&cached);
+        let mut cache = self.cache.lock().unwrap();
+        cache.keys.retain(|_, cached| cached.is_fresh(now));
+        cache.keys.insert(key_id.to_string(), cached);
+        result
+    }
+}
+
+fn cached_key(cached: &CachedKey) -> Result<VerificationKey, Error> {
+    cached
+        .key
+        .clone()
+        .ok_or_else(|| Error::InvalidSignature("unknown key id".to_string()))
+}
+
+/// Verifies the signature, age and body hash of a plaid webhook jwt.
+pub fn verify_token(
+    jwt: &str,
+    body: &[u8],
+    key: &VerificationKey,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    let parts: Vec<&str> = jwt.split('.').collect();
+    let (header_part, claims_part, signature_part) = match parts[..] {
+        [header_part, claims_part, signature_part] => (header_part, claims_part, signature_part),
+        _ => return Err(Error::InvalidSignature("malformed jwt".to_string())),
+    };
+
+    let header: TokenHeader = decode_token_part(header_part)?;
+    if header.alg != "ES256" || key.alg != "ES256" || key.crv != "P-256" {
+        return Err(Error::InvalidSignature(format!(
+            "unsupported algorithm: {0}",
+            header.alg
+        )));
+    }
+    if header.kid != key.kid {
+        return Err(Error::InvalidSignature("key id mismatch".to_string()));
+    }
+    if key.expired_at.is_some() {
+        return Err(Error::InvalidSignature("key is expired".to_string()));
+    }
+
+    // Uncompressed P-256 point.
+    let mut public_key = vec![0x04];
+    public_key.extend(decode_base64(&key.x)?);
+    public_key.extend(decode_base64(&key.y)?);
+    UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, public_key)
+        .verify(
+            format!("{0}.{1}", header_part, claims_part).as_bytes(),
+            &decode_base64(signature_part)?,
+        )
+        .map_err(|_| Error::InvalidSignature("signature does not match".to_string()))?;
+
+    let claims: TokenClaims = decode_token_part(claims_part)?;
+    if now.timestamp() - claims.iat > MAX_TOKEN_AGE_SECS {
+        return Err(Error::InvalidSignature("jwt is too old".to_string()));
+    }
+    let body_sha256 = hex::encode(Sha256::digest(body));
+    if ring::constant_time::verify_slices_are_equal(
+        body_sha256.as_bytes(),
+        claims.request_body_sha256.as_bytes(),
+    )
+    .is_err()
+    {
+        return Err(Error::InvalidSignature(
+            "body does not match signed hash".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>, Error> {
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+        .map_err(|e| Error::InvalidSignature(format!("invalid base64: {:?}", e)))
+}
+
+fn decode_token_part<T: DeserializeOwned>(part: &str) -> Result<T, Error> {
+    serde_json::from_slice(&decode_base64(part)?)
+        .map_err(|e| Error::InvalidSignature(format!("invalid jwt: {:?}", e)))
+}
+
+#[derive(Deserialize)]
+struct Webhook {
+    webhook_type: String,
+    webhook_code: String,
+    item_id: Option<String>,
+    error: Option<WebhookError>,
+    consent_expiration_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct WebhookError {
+    error_code: String,
+}
+
+/// Plaid webhooks which are acted upon.
+#[derive(Debug, PartialEq)]
+pub enum PlaidWebhook {
+    /// The item entered an error state, e.g. because its login expired.
+    ItemError {
+        plaid_item_id: String,
+        error_code: String,
+    },
+    /// The user's consent for the item is about to expire.
+    PendingExpiration {
+        plaid_item_id: String,
+        consent_expiration_time: Option<DateTime<Utc>>,
+    },
+    /// The user revoked access to the item at their bank.
+    PermissionRevoked {
+        plaid_item_id: String,
+        error_code: Option<String>,
+    },
+    /// The item left its error state without relinking.
+    LoginRepaired { plaid_item_id: String },
+    /// The institution has accounts which were not linked yet.
+    NewAccountsAvailable { plaid_item_id: String },
+    /// Webhooks of other types, which are ignored.
+    Unhandled,
+}
+
+impl PlaidWebhook {
+    /// Parses the json body of a plaid webhook.
+    pub fn parse(payload: &[u8]) -> Result<Self, Error> {
+        let webhook: Webhook = serde_json::from_slice(payload)
+            .map_err(|e| Error::InvalidPayload(format!("failed to parse webhook: {:?}", e)))?;
+        if webhook.webhook_type != "ITEM" {
+            return Ok(PlaidWebhook::Unhandled);
+        }
+        let plaid_item_id = webhook
+            .item_id
+            .ok_or_else(|| Error::InvalidPayload("missing item_id".to_string()))?;
+
+        Ok(match webhook.webhook_code.as_str() {
+            "ERROR" => PlaidWebhook::ItemError {
+                plaid_item_id,
+                error_code: webhook
+                    .error
+                    .ok_or_else(|| Error::InvalidPayload("missing error".to_string()))?
+                    .error_code,
+            },
+            "PENDING_EXPIRATION" => PlaidWebhook::PendingExpiration {
+                plaid_item_id,
+                consent_expiration_time: webhook.consent_expiration_time,
+            },
+            "USER_PERMISSION_REVOKED" => PlaidWebhook::PermissionRevoked {
+                plaid_item_id,
+                error_code: webhook.error.map(|error| error.error_code),
+            },
+            "LOGIN_REPAIRED" => PlaidWebhook::LoginRepaired { plaid_item_id },
+            "NEW_ACCOUNTS_AVAILABLE" => PlaidWebhook::NewAccountsAvailable { plaid_item_id },
+            _ => PlaidWebhook::Unhandled,
+        })
+    }
+}
+
+/// Receives plaid item webhooks and records the item's status, so that items
+/// needing to be relinked can be surfaced to their user.
+pub struct PlaidWebhookHandler<Db, Store, TStore> {
+    database: Arc<Db>,
+    verifier: PlaidWebhookVerifier,
+    _marker: PhantomData<(Store, TStore)>,
+}
+
+impl<Db, Store, TStore> PlaidWebhookHandler<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore>,
+    Store: ItemStore + OnDemandStore,
+    TStore: TransactionalStore,
+{
+    pub fn new(database: Arc<Db>, verifier: PlaidWebhookVerifier) -> Self {
+        Self {
+            database,
+            verifier,
+            _marker: PhantomData,
+        }
+    }
+
+    async fn process(&self, webhook: PlaidWebhook) -> Result<(), Error> {
+        let now = Utc::now();
+        let store = self.database.on_demand();
+        let (plaid_item_id, item_row) = match webhook {
+            PlaidWebhook::ItemError {
+                plaid_item_id,
+                error_code,
+            } => {
+                let status = match error_code.as_str() {
+                    "ITEM_LOGIN_REQUIRED" => ItemStatus::LoginRequired,
+                    _ => ItemStatus::Error,
+                };
+                let item_row = store
+                    .update_item_status_by_plaid_item_id(
+                        &plaid_item_id,
+                        status,
+                        Some(error_code),
+                        None,
+                        now,
+                    )
+                    .await?;
+                (plaid_item_id, item_row)
+            }
+            PlaidWebhook::PendingExpiration {
+                plaid_item_id,
+                consent_expiration_time,
+            } => {
+                let item_row = store
+                    .update_item_status_by_plaid_item_id(
+                        &plaid_item_id,
+                        ItemStatus::PendingExpiration,
+                        None,
+                        consent_expiration_time,
+                        now,
+                    )
+                    .await?;
+                (plaid_item_id, item_row)
+            }
+            PlaidWebhook::PermissionRevoked {
+                plaid_item_id,
+                error_code,
+            } => {
+                let item_row = store
+                    .update_item_status_by_plaid_item_id(
+                        &plaid_item_id,
+                        ItemStatus::PermissionRevoked,
+                        error_code,
+                        None,
+                        now,
+                    )
+                    .await?;
+                (plaid_item_id, item_row)
+            }
+            PlaidWebhook::LoginRepaired { plaid_item_id } => {
+                let item_row = store
+                    .update_item_status_by_plaid_item_id(
+                        &plaid_item_id,
+                        ItemStatus::Healthy,
+                        None,
+                        None,
+                        now,
+                    )
+                    .await?;
+                (plaid_item_id, item_row)
+            }
+            PlaidWebhook::NewAccountsAvailable { plaid_item_id } => {
+                let item_row = store
+                    .update_item_new_accounts_available_by_plaid_item_id(&plaid_item_id, true, now)
+                    .await?;
+                (plaid_item_id, item_row)
+            }
+            PlaidWebhook::Unhandled => return Ok(()),
+        };
+
+        if item_row.is_none() {
+            info!("Ignoring plaid webhook for unknown item: {}", plaid_item_id);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<Db, Store, TStore> WebhookHandler for PlaidWebhookHandler<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore> + 'static,
+    Store: ItemStore + OnDemandStore + 'static,
+    TStore: TransactionalStore + 'static,
+    Self: Sync + Send,
+{
+    async fn handle(&self, headers: &HeaderMap, body: Bytes) -> Result<(), Error> {
+        let jwt = headers
+            .get(VERIFICATION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| Error::InvalidSignature("missing verification header".to_string()))?;
+        self.verifier.verify(jwt, &body).await?;
+
+        self.process(PlaidWebhook::parse(&body)?).await
+    }
+}
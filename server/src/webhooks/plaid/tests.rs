@@ -0,0 +1,361 @@
+use crate::webhooks::{
+    plaid::{
+        verify_token, PlaidWebhook, PlaidWebhookHandler, PlaidWebhookVerifier, VerificationKey,
+    },
+    Error,
+};
+use affect_storage::models::item::ItemStatus;
+use affect_storage_mocks::*;
+use chrono::{Duration, TimeZone, Utc};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use ring::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+const ITEM_ERROR: &str = include_str!("fixtures/item_error.json");
+const PENDING_EXPIRATION: &str = include_str!("fixtures/pending_expiration.json");
+const NEW_ACCOUNTS_AVAILABLE: &str = include_str!("fixtures/new_accounts_available.json");
+const PLAID_ITEM_ID: &str = "wz666MBjYWTp2PDzzggYhM6oWWmBb";
+
+fn encode(value: &[u8]) -> String {
+    base64::encode_config(value, base64::URL_SAFE_NO_PAD)
+}
+
+/// Generates a signing key and the verification key plaid would return for it.
+fn signing_key() -> (EcdsaKeyPair, VerificationKey) {
+    let rng = SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+    let key_pair =
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref()).unwrap();
+    let public_key = key_pair.public_key().as_ref().to_vec();
+    let verification_key = VerificationKey {
+        kid: "6c5516e1-92dc-479e-a8ff-5a51992e0001".to_string(),
+        alg: "ES256".to_string(),
+        crv: "P-256".to_string(),
+        x: encode(&public_key[1..33]),
+        y: encode(&public_key[33..65]),
+        expired_at: None,
+    };
+    (key_pair, verification_key)
+}
+
+/// Signs the body the way plaid does.
+fn sign(key_pair: &EcdsaKeyPair, kid: &str, body: &[u8], iat: i64) -> String {
+    let header = encode(format!(r#"{{"alg":"ES256","kid":"{0}","typ":"JWT"}}"#, kid).as_bytes());
+    let claims = encode(
+        format!(
+            r#"{{"iat":{0},"request_body_sha256":"{1}"}}"#,
+            iat,
+            hex::encode(Sha256::digest(body))
+        )
+        .as_bytes(),
+    );
+    let message = format!("{0}.{1}", header, claims);
+    let signature = key_pair
+        .sign(&SystemRandom::new(), message.as_bytes())
+        .unwrap();
+    format!("{0}.{1}", message, encode(signature.as_ref()))
+}
+
+#[test]
+fn verify_token_accepts_signed_body() {
+    let (key_pair, key) = signing_key();
+    let now = Utc::now();
+    let jwt = sign(&key_pair, &key.kid, ITEM_ERROR.as_bytes(), now.timestamp());
+    assert!(verify_token(&jwt, ITEM_ERROR.as_bytes(), &key, now).is_ok());
+}
+
+#[test]
+fn verify_token_rejects_tampered_body() {
+    let (key_pair, key) = signing_key();
+    let now = Utc::now();
+    let jwt = sign(&key_pair, &key.kid, ITEM_ERROR.as_bytes(), now.timestamp());
+    assert!(matches!(
+        verify_token(&jwt, PENDING_EXPIRATION.as_bytes(), &key, now),
+        Err(Error::InvalidSignature(_))
+    ));
+}
+
+#[test]
+fn verify_token_rejects_other_key() {
+    let (key_pair, key) = signing_key();
+    let (_, other_key) = signing_key();
+    let now = Utc::now();
+    let jwt = sign(&key_pair, &key.kid, ITEM_ERROR.as_bytes(), now.timestamp());
+    assert!(matches!(
+        verify_token(&jwt, ITEM_ERROR.as_bytes(), &other_key, now),
+        Err(Error::InvalidSignature(_))
+    ));
+}
+
+#[test]
+fn verify_token_rejects_old_token() {
+    let (key_pair, key) = signing_key();
+    let now = Utc::now();
+    let issued_at = now - Duration::minutes(10);
+    let jwt = sign(
+        &key_pair,
+        &key.kid,
+        ITEM_ERROR.as_bytes(),
+        issued_at.timestamp(),
+    );
+    assert!(matches!(
+        verify_token(&jwt, ITEM_ERROR.as_bytes(), &key, now),
+        Err(Error::InvalidSignature(_))
+    ));
+}
+
+#[test]
+fn verify_token_rejects_expired_key() {
+    let (key_pair, mut key) = signing_key();
+    key.expired_at = Some(Utc::now().timestamp());
+    let now = Utc::now();
+    let jwt = sign(&key_pair, &key.kid, ITEM_ERROR.as_bytes(), now.timestamp());
+    assert!(matches!(
+        verify_token(&jwt, ITEM_ERROR.as_bytes(), &key, now),
+        Err(Error::InvalidSignature(_))
+    ));
+}
+
+/// Local http server standing in for plaid's verification key endpoint.
+/// Responds with the key for the requested id, or an invalid request error if
+/// there is none, and counts the requests it receives.
+struct FakePlaidKeys {
+    url: String,
+    keys: Arc<Mutex<HashMap<String, VerificationKey>>>,
+    lookups: Arc<Mutex<usize>>,
+}
+
+impl FakePlaidKeys {
+    fn start() -> Self {
+        let keys: Arc<Mutex<HashMap<String, VerificationKey>>> = Arc::default();
+        let lookups = Arc::new(Mutex::new(0));
+
+        let (server_keys, server_lookups) = (keys.clone(), lookups.clone());
+        let make_service = make_service_fn(move |_| {
+            let (keys, lookups) = (server_keys.clone(), server_lookups.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let (keys, lookups) = (keys.clone(), lookups.clone());
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                        *lookups.lock().unwrap() += 1;
+                        let key = keys
+                            .lock()
+                            .unwrap()
+                            .get(body["key_id"].as_str().unwrap())
+                            .cloned();
+                        let response = match key {
+                            Some(key) => Response::new(Body::from(
+                                serde_json::json!({ "key": key }).to_string(),
+                            )),
+                            None => Response::builder()
+                                .status(StatusCode::BAD_REQUEST)
+                                .body(Body::from(
+                                    r#"{"error_type":"INVALID_REQUEST","error_code":"INVALID_FIELD"}"#,
+                                ))
+                                .unwrap(),
+                        };
+                        Ok::<_, Infallible>(response)
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{0}", server.local_addr());
+        tokio::spawn(server);
+        Self { url, keys, lookups }
+    }
+
+    fn verifier(&self) -> PlaidWebhookVerifier {
+        PlaidWebhookVerifier::from_url("client_id".to_string(), "secret".to_string(), &self.url)
+    }
+
+    fn publish(&self, key: &VerificationKey) {
+        self.keys
+            .lock()
+            .unwrap()
+            .insert(key.kid.clone(), key.clone());
+    }
+
+    fn lookups(&self) -> usize {
+        *self.lookups.lock().unwrap()
+    }
+}
+
+#[tokio::test]
+async fn verification_key_is_cached() -> Result<(), anyhow::Error> {
+    let plaid = FakePlaidKeys::start();
+    let (_, key) = signing_key();
+    plaid.publish(&key);
+    let verifier = plaid.verifier();
+
+    let now = Utc::now();
+    verifier.verification_key(&key.kid, now).await?;
+    verifier
+        .verification_key(&key.kid, now + Duration::minutes(30))
+        .await?;
+    assert_eq!(plaid.lookups(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn stale_verification_key_is_refreshed() -> Result<(), anyhow::Error> {
+    let plaid = FakePlaidKeys::start();
+    let (_, mut key) = signing_key();
+    plaid.publish(&key);
+    let verifier = plaid.verifier();
+
+    let now = Utc::now();
+    verifier.verification_key(&key.kid, now).await?;
+    // Plaid rotates the key out.
+    key.expired_at = Some(now.timestamp());
+    plaid.publish(&key);
+    let refreshed = verifier
+        .verification_key(&key.kid, now + Duration::hours(2))
+        .await?;
+    assert_eq!(refreshed.expired_at, key.expired_at);
+    assert_eq!(plaid.lookups(), 2);
+
+    // Expired keys are looked up again once the shorter ttl passed.
+    verifier
+        .verification_key(&key.kid, now + Duration::hours(2) + Duration::minutes(1))
+        .await?;
+    assert_eq!(plaid.lookups(), 2);
+    verifier
+        .verification_key(&key.kid, now + Duration::hours(3))
+        .await?;
+    assert_eq!(plaid.lookups(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_key_id_is_cached() {
+    let plaid = FakePlaidKeys::start();
+    let verifier = plaid.verifier();
+
+    let now = Utc::now();
+    for minutes in [0, 1, 2] {
+        assert!(matches!(
+            verifier
+                .verification_key("unknown", now + Duration::minutes(minutes))
+                .await,
+            Err(Error::InvalidSignature(_))
+        ));
+    }
+    assert_eq!(plaid.lookups(), 1);
+}
+
+#[tokio::test]
+async fn lookups_of_uncached_key_ids_are_rate_limited() -> Result<(), anyhow::Error> {
+    let plaid = FakePlaidKeys::start();
+    let (_, key) = signing_key();
+    plaid.publish(&key);
+    let verifier = plaid.verifier();
+
+    let now = Utc::now();
+    assert!(matches!(
+        verifier.verification_key("unknown", now).await,
+        Err(Error::InvalidSignature(_))
+    ));
+    assert!(matches!(
+        verifier.verification_key(&key.kid, now).await,
+        Err(Error::Upstream(_))
+    ));
+    assert_eq!(plaid.lookups(), 1);
+
+    verifier
+        .verification_key(&key.kid, now + Duration::seconds(1))
+        .await?;
+    assert_eq!(plaid.lookups(), 2);
+    Ok(())
+}
+
+#[test]
+fn parse_webhooks() {
+    assert_eq!(
+        PlaidWebhook::parse(ITEM_ERROR.as_bytes()).unwrap(),
+        PlaidWebhook::ItemError {
+            plaid_item_id: PLAID_ITEM_ID.to_string(),
+            error_code: "ITEM_LOGIN_REQUIRED".to_string(),
+        }
+    );
+    assert_eq!(
+        PlaidWebhook::parse(PENDING_EXPIRATION.as_bytes()).unwrap(),
+        PlaidWebhook::PendingExpiration {
+            plaid_item_id: PLAID_ITEM_ID.to_string(),
+            consent_expiration_time: Some(Utc.ymd(2026, 11, 1).and_hms_milli(13, 25, 17, 766)),
+        }
+    );
+    assert_eq!(
+        PlaidWebhook::parse(NEW_ACCOUNTS_AVAILABLE.as_bytes()).unwrap(),
+        PlaidWebhook::NewAccountsAvailable {
+            plaid_item_id: PLAID_ITEM_ID.to_string(),
+        }
+    );
+    assert_eq!(
+        PlaidWebhook::parse(br#"{"webhook_type":"TRANSACTIONS","webhook_code":"DEFAULT_UPDATE"}"#)
+            .unwrap(),
+        PlaidWebhook::Unhandled
+    );
+}
+
+fn handler(
+    database: MockDatabaseClient,
+) -> PlaidWebhookHandler<MockDatabaseClient, MockStore, MockStore> {
+    PlaidWebhookHandler::new(
+        Arc::new(database),
+        PlaidWebhookVerifier::new("client_id".to_string(), "secret".to_string(), "sandbox"),
+    )
+}
+
+#[tokio::test]
+async fn process_login_required() -> Result<(), anyhow::Error> {
+    let mut database = MockDatabaseClient::new();
+    let mut store = MockStore::new();
+    store
+        .expect_update_item_status_by_plaid_item_id()
+        .withf(|plaid_item_id, status, plaid_error_code, _, _| {
+            plaid_item_id == PLAID_ITEM_ID
+                && *status == ItemStatus::LoginRequired
+                && plaid_error_code.as_deref() == Some("ITEM_LOGIN_REQUIRED")
+        })
+        .times(1)
+        .return_once(|_, _, _, _, _| Ok(None));
+    database.expect_on_demand().times(1).return_once(|| store);
+
+    handler(database)
+        .process(PlaidWebhook::parse(ITEM_ERROR.as_bytes())?)
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn process_new_accounts_available() -> Result<(), anyhow::Error> {
+    let mut database = MockDatabaseClient::new();
+    let mut store = MockStore::new();
+    store
+        .expect_update_item_new_accounts_available_by_plaid_item_id()
+        .withf(|plaid_item_id, new_accounts_available, _| {
+            plaid_item_id == PLAID_ITEM_ID && *new_accounts_available
+        })
+        .times(1)
+        .return_once(|_, _, _| Ok(None));
+    database.expect_on_demand().times(1).return_once(|| store);
+
+    handler(database)
+        .process(PlaidWebhook::parse(NEW_ACCOUNTS_AVAILABLE.as_bytes())?)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,471 @@
+use crate::{
+    money::Money,
+    webhooks::{Error, WebhookHandler},
+};
+use affect_storage::{
+    database::{
+        client::DatabaseClient,
+        store::{OnDemandStore, TransactionalStore},
+    },
+    models::{
+        affiliate::AffiliateStripeState,
+        donation::DonationStatus,
+        stripe_event::{NewStripeEventRow, StripeEventRow},
+    },
+    stores::{affiliate::AffiliateStore, donation::DonationStore, stripe_event::StripeEventStore},
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use hyper::HeaderMap;
+use iso_currency::Currency;
+use log::{info, warn};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::{marker::PhantomData, sync::Arc};
+
+#[cfg(test)]
+mod tests;
+
+/// Header stripe signs webhook requests with.
+pub const SIGNATURE_HEADER: &str = "stripe-signature";
+
+/// Maximum age of a signed request, protecting against replay attacks.
+const SIGNATURE_TOLERANCE_SECS: i64 = 300;
+
+/// Maximum number of stored events processed by a single replay.
+const REPLAY_LIMIT: i64 = 100;
+
+/// Verifies the `stripe-signature` header of a webhook request, as described in
+/// https://stripe.com/docs/webhooks/signatures.
+pub fn verify_signature(
+    payload: &[u8],
+    header: &str,
+    secret: &str,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for pair in header.split(',') {
+        match pair.trim().split_once('=') {
+            Some(("t", value)) => {
+                timestamp = Some(value.parse::<i64>().map_err(|e| {
+                    Error::InvalidSignature(format!("failed to parse timestamp: {:?}", e))
+                })?)
+            }
+            Some(("v1", value)) => signatures.push(value),
+            _ => {}
+        }
+    }
+    let timestamp =
+        timestamp.ok_or_else(|| Error::InvalidSignature("missing timestamp".to_string()))?;
+    if signatures.is_empty() {
+        return Err(Error::InvalidSignature("missing v1 signature".to_string()));
+    }
+    if (now.timestamp() - timestamp).abs() > SIGNATURE_TOLERANCE_SECS {
+        return Err(Error::InvalidSignature(
+            "timestamp outside of tolerance".to_string(),
+        ));
+    }
+
+    let mac = signed_payload_mac(payload, timestamp, secret)?;
+    let verified = signatures
+        .into_iter()
+        .any(|signature| match hex::decode(signature) {
+            Ok(signature) => mac.clone().verify_slice(&signature).is_ok(),
+            Err(_) => false,
+        });
+    if !verified {
+        return Err(Error::InvalidSignature(
+            "no signature matches the payload".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the `stripe-signature` header stripe would send for the payload.
+/// Useful to send locally signed events to the webhook endpoint.
+pub fn signature_header(payload: &[u8], secret: &str, timestamp: i64) -> Result<String, Error> {
+    let signature = signed_payload_mac(payload, timestamp, secret)?
+        .finalize()
+        .into_bytes();
+    Ok(format!("t={0},v1={1}", timestamp, hex::encode(signature)))
+}
+
+fn signed_payload_mac(payload: &[u8], timestamp: i64, secret: &str) -> Result<Hmac<Sha256>, Error> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| Error::InvalidSignature(format!("invalid secret: {:?}", e)))?;
+    mac.update(format!("{0}.", timestamp).as_bytes());
+    mac.update(payload);
+    Ok(mac)
+}
+
+#[derive(Deserialize)]
+struct Event {
+    id: String,
+    #[serde(rename = "type")]
+    event_type: String,
+    /// Time the event was created, in seconds since the epoch.
+    #[serde(default)]
+    created: i64,
+    data: EventData,
+}
+
+#[derive(Deserialize)]
+struct EventData {
+    object: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ChargeObject {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct RefundedChargeObject {
+    id: String,
+    /// Whether the charge was refunded in full.
+    refunded: bool,
+    /// Amount refunded so far, in subunits of the currency.
+    amount_refunded: i64,
+    currency: String,
+}
+
+#[derive(Deserialize)]
+struct AccountObject {
+    id: String,
+    #[serde(default)]
+    charges_enabled: bool,
+    #[serde(default)]
+    payouts_enabled: bool,
+    country: Option<String>,
+    business_profile: Option<BusinessProfileObject>,
+    requirements: Option<RequirementsObject>,
+    default_currency: Option<String>,
+    external_accounts: Option<ExternalAccountsObject>,
+}
+
+#[derive(Deserialize)]
+struct BusinessProfileObject {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RequirementsObject {
+    #[serde(default)]
+    currently_due: Vec<String>,
+    disabled_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ExternalAccountsObject {
+    #[serde(default)]
+    data: Vec<ExternalAccountObject>,
+}
+
+#[derive(Deserialize)]
+struct ExternalAccountObject {
+    currency: Option<String>,
+}
+
+impl From<AccountObject> for AffiliateStripeState {
+    fn from(account: AccountObject) -> Self {
+        let (requirements_currently_due, disabled_reason) = match account.requirements {
+            Some(requirements) => (requirements.currently_due, requirements.disabled_reason),
+            None => (Vec::new(), None),
+        };
+        let external_currencies = account
+            .external_accounts
+            .into_iter()
+            .flat_map(|external_accounts| external_accounts.data)
+            .filter_map(|external_account| external_account.currency);
+        let mut currencies: Vec<String> = account
+            .default_currency
+            .into_iter()
+            .chain(external_currencies)
+            .map(|currency| currency.to_uppercase())
+            .collect();
+        currencies.sort();
+        currencies.dedup();
+        AffiliateStripeState {
+            charges_enabled: account.charges_enabled,
+            payouts_enabled: account.payouts_enabled,
+            country: account.country,
+            business_name: account.business_profile.and_then(|p| p.name),
+            requirements_currently_due,
+            disabled_reason,
+            currencies,
+        }
+    }
+}
+
+/// Stripe events which are acted upon.
+#[derive(Debug, PartialEq)]
+pub enum StripeEvent {
+    ChargeSucceeded {
+        charge_id: String,
+    },
+    ChargeFailed {
+        charge_id: String,
+    },
+    /// Sent for partial refunds too, until the charge is refunded in full.
+    ChargeRefunded {
+        charge_id: String,
+        refunded: bool,
+        amount_refunded: Money,
+    },
+    AccountUpdated {
+        account_id: String,
+        state: AffiliateStripeState,
+        /// Time the event was created, i.e. when the account had the state.
+        create_time: DateTime<Utc>,
+    },
+    /// Events of other types, which are stored but otherwise ignored.
+    Unhandled,
+}
+
+impl StripeEvent {
+    /// Parses the raw json body of a stripe event.
+    pub fn parse(payload: &str) -> Result<Self, Error> {
+        let event: Event = parse_json(serde_json::from_str(payload))?;
+        let object = event.data.object;
+        Ok(match event.event_type.as_str() {
+            "charge.succeeded" => StripeEvent::ChargeSucceeded {
+                charge_id: parse_json::<ChargeObject>(serde_json::from_value(object))?.id,
+            },
+            "charge.failed" => StripeEvent::ChargeFailed {
+                charge_id: parse_json::<ChargeObject>(serde_json::from_value(object))?.id,
+            },
+            "charge.refunded" => {
+                let charge: RefundedChargeObject = parse_json(serde_json::from_value(object))?;
+                let currency =
+                    Currency::from_code(&charge.currency.to_uppercase()).ok_or_else(|| {
+                        Error::InvalidPayload(format!("unknown currency: {}", charge.currency))
+                    })?;
+                StripeEvent::ChargeRefunded {
+                    charge_id: charge.id,
+                    refunded: charge.refunded,
+                    amount_refunded: Money::from_subunits(currency, charge.amount_refunded),
+                }
+            }
+            "account.updated" => {
+                let account: AccountObject = parse_json(serde_json::from_value(object))?;
+                let create_time =
+                    Utc.timestamp_opt(event.created, 0)
+                        .single()
+                        .ok_or_else(|| {
+                            Error::InvalidPayload(format!("invalid event time: {}", event.created))
+                        })?;
+                StripeEvent::AccountUpdated {
+                    account_id: account.id.clone(),
+                    state: account.into(),
+                    create_time,
+                }
+            }
+            _ => StripeEvent::Unhandled,
+        })
+    }
+}
+
+fn parse_json<T>(result: Result<T, serde_json::Error>) -> Result<T, Error> {
+    result.map_err(|e| Error::InvalidPayload(format!("failed to parse event: {:?}", e)))
+}
+
+/// Receives stripe webhooks. Every event is stored before being processed so
+/// that redeliveries are deduplicated and unprocessed events can be replayed.
+pub struct StripeWebhookHandler<Db, Store, TStore> {
+    database: Arc<Db>,
+    webhook_secret: String,
+    _marker: PhantomData<(Store, TStore)>,
+}
+
+impl<Db, Store, TStore> StripeWebhookHandler<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore>,
+    Store: StripeEventStore + OnDemandStore,
+    TStore: AffiliateStore + DonationStore + StripeEventStore + TransactionalStore,
+{
+    pub fn new(database: Arc<Db>, webhook_secret: String) -> Self {
+        Self {
+            database,
+            webhook_secret,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Processes stored events which were not processed yet, e.g. because
+    /// processing failed when they were received. Returns the number of
+    /// processed events.
+    pub async fn replay_unprocessed(&self) -> Result<usize, Error> {
+        let event_rows = self
+            .database
+            .on_demand()
+            .list_unprocessed_stripe_events(REPLAY_LIMIT)
+            .await?;
+
+        let mut processed = 0;
+        for event_row in event_rows {
+            match self.process(&event_row).await {
+                Ok(()) => processed += 1,
+                Err(e) => warn!(
+                    "Failed to replay stripe event {}: {:?}",
+                    event_row.stripe_event_id, e
+                ),
+            }
+        }
+        Ok(processed)
+    }
+
+    /// Applies the event and marks it processed in a single transaction.
+    async fn process(&self, event_row: &StripeEventRow) -> Result<(), Error> {
+        let event = StripeEvent::parse(&event_row.payload)?;
+        let now = Utc::now();
+
+        let txn = self.database.begin().await?;
+        match event {
+            StripeEvent::ChargeSucceeded { charge_id } => {
+                update_donation_status(&txn, &charge_id, DonationStatus::Succeeded, now).await?
+            }
+            StripeEvent::ChargeFailed { charge_id } => {
+                update_donation_status(&txn, &charge_id, DonationStatus::Failed, now).await?
+            }
+            StripeEvent::ChargeRefunded {
+                charge_id,
+                refunded,
+                amount_refunded,
+            } => {
+                record_refund(&txn, &charge_id, &amount_refunded, now).await?;
+                // The rest of a partially refunded donation stays with the
+                // recipients, so only full refunds change its status.
+                if refunded {
+                    update_donation_status(&txn, &charge_id, DonationStatus::Refunded, now).await?
+                }
+            }
+            StripeEvent::AccountUpdated {
+                account_id,
+                state,
+                create_time,
+            } => {
+                // Stripe doesn't order deliveries, so an event may arrive after
+                // a later event or refresh already stored a newer state.
+                if !txn
+                    .update_affiliate_stripe_state(&account_id, state, create_time, now)
+                    .await?
+                {
+                    info!(
+                        "Ignoring stripe account update for unknown account or out of order: {}",
+                        account_id
+                    );
+                }
+            }
+            StripeEvent::Unhandled => {}
+        }
+        txn.mark_stripe_event_processed(&event_row.stripe_event_id, now)
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+}
+
+async fn update_donation_status<TStore>(
+    txn: &TStore,
+    charge_id: &str,
+    status: DonationStatus,
+    update_time: DateTime<Utc>,
+) -> Result<(), Error>
+where
+    TStore: DonationStore,
+{
+    let donation_row = txn
+        .update_donation_status_by_stripe_charge_id(charge_id, status, update_time)
+        .await?;
+    if donation_row.is_none() {
+        info!(
+            "Ignoring stripe event for charge without donation or out of order: {}",
+            charge_id
+        );
+    }
+    Ok(())
+}
+
+async fn record_refund<TStore>(
+    txn: &TStore,
+    charge_id: &str,
+    amount_refunded: &Money,
+    update_time: DateTime<Utc>,
+) -> Result<(), Error>
+where
+    TStore: DonationStore,
+{
+    let refund_row = txn
+        .upsert_donation_refund_by_stripe_charge_id(
+            charge_id,
+            amount_refunded.units(),
+            amount_refunded.nanos(),
+            update_time,
+        )
+        .await?;
+    if refund_row.is_none() {
+        info!(
+            "Ignoring stripe refund for charge without donation or out of order: {}",
+            charge_id
+        );
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl<Db, Store, TStore> WebhookHandler for StripeWebhookHandler<Db, Store, TStore>
+where
+    Db: DatabaseClient<Store, TStore> + 'static,
+    Store: StripeEventStore + OnDemandStore + 'static,
+    TStore: AffiliateStore + DonationStore + StripeEventStore + TransactionalStore + 'static,
+    Self: Sync + Send,
+{
+    async fn handle(&self, headers: &HeaderMap, body: Bytes) -> Result<(), Error> {
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| Error::InvalidSignature("missing signature header".to_string()))?;
+        verify_signature(&body, signature, &self.webhook_secret, Utc::now())?;
+
+        let payload = String::from_utf8(body.to_vec())
+            .map_err(|e| Error::InvalidPayload(format!("payload is not utf-8: {:?}", e)))?;
+        let event: Event = parse_json(serde_json::from_str(&payload))?;
+
+        let now = Utc::now();
+        let new_row = NewStripeEventRow {
+            stripe_event_id: event.id.clone(),
+            create_time: now,
+            update_time: now,
+            event_type: event.event_type,
+            payload,
+        };
+        let event_row = match self.database.on_demand().add_stripe_event(new_row).await? {
+            Some(event_row) => event_row,
+            None => {
+                // Redelivery of a stored event, only process it if a previous
+                // attempt failed.
+                let event_row = self
+                    .database
+                    .on_demand()
+                    .find_stripe_event_by_id(&event.id)
+                    .await?
+                    .ok_or_else(|| {
+                        affect_storage::Error::Other(anyhow::anyhow!(
+                            "stripe event was concurrently removed: {}",
+                            event.id
+                        ))
+                    })?;
+                if event_row.process_time.is_some() {
+                    info!("Ignoring already processed stripe event: {}", event.id);
+                    return Ok(());
+                }
+                event_row
+            }
+        };
+
+        self.process(&event_row).await
+    }
+}
@@ -0,0 +1,399 @@
+use crate::money::Money;
+use crate::webhooks::{
+    stripe::{signature_header, verify_signature, StripeEvent, StripeWebhookHandler},
+    Error, WebhookHandler,
+};
+use affect_storage::models::{
+    affiliate::AffiliateStripeState,
+    donation::{DonationRefundRow, DonationStatus},
+    stripe_event::StripeEventRow,
+};
+use affect_storage_mocks::*;
+use bytes::Bytes;
+use chrono::{Duration, TimeZone, Utc};
+use hyper::{header::HeaderValue, HeaderMap};
+use iso_currency::Currency;
+use mockall::Sequence;
+use std::sync::Arc;
+
+const SECRET: &str = "whsec_test";
+const CHARGE_SUCCEEDED: &str = include_str!("fixtures/charge_succeeded.json");
+const ACCOUNT_UPDATED: &str = include_str!("fixtures/account_updated.json");
+const CHARGE_REFUNDED: &str = include_str!("fixtures/charge_refunded.json");
+
+fn signed_headers(payload: &str) -> HeaderMap {
+    let header = signature_header(payload.as_bytes(), SECRET, Utc::now().timestamp()).unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert("stripe-signature", HeaderValue::from_str(&header).unwrap());
+    headers
+}
+
+fn stripe_event_row(id: &str, payload: &str, processed: bool) -> StripeEventRow {
+    StripeEventRow {
+        stripe_event_id: id.to_string(),
+        create_time: Utc::now(),
+        update_time: Utc::now(),
+        event_type: "charge.succeeded".to_string(),
+        payload: payload.to_string(),
+        process_time: if processed { Some(Utc::now()) } else { None },
+    }
+}
+
+#[test]
+fn verify_signature_accepts_signed_payload() {
+    let now = Utc::now();
+    let header = signature_header(b"{}", SECRET, now.timestamp()).unwrap();
+    assert!(verify_signature(b"{}", &header, SECRET, now).is_ok());
+}
+
+#[test]
+fn verify_signature_accepts_any_matching_signature() {
+    let now = Utc::now();
+    let header = signature_header(b"{}", SECRET, now.timestamp()).unwrap();
+    let header = format!("{0},v1={1}", header, "00".repeat(32));
+    assert!(verify_signature(b"{}", &header, SECRET, now).is_ok());
+}
+
+#[test]
+fn verify_signature_rejects_tampered_payload() {
+    let now = Utc::now();
+    let header = signature_header(b"{}", SECRET, now.timestamp()).unwrap();
+    assert!(matches!(
+        verify_signature(b"{\"id\":1}", &header, SECRET, now),
+        Err(Error::InvalidSignature(_))
+    ));
+}
+
+#[test]
+fn verify_signature_rejects_other_secret() {
+    let now = Utc::now();
+    let header = signature_header(b"{}", "whsec_other", now.timestamp()).unwrap();
+    assert!(matches!(
+        verify_signature(b"{}", &header, SECRET, now),
+        Err(Error::InvalidSignature(_))
+    ));
+}
+
+#[test]
+fn verify_signature_rejects_stale_timestamp() {
+    let now = Utc::now();
+    let signed_at = now - Duration::minutes(10);
+    let header = signature_header(b"{}", SECRET, signed_at.timestamp()).unwrap();
+    assert!(matches!(
+        verify_signature(b"{}", &header, SECRET, now),
+        Err(Error::InvalidSignature(_))
+    ));
+}
+
+#[test]
+fn verify_signature_rejects_malformed_header() {
+    let now = Utc::now();
+    for header in [
+        "",
+        "t=abc,v1=00",
+        "v1=00",
+        &format!("t={0}", now.timestamp()),
+    ] {
+        assert!(matches!(
+            verify_signature(b"{}", header, SECRET, now),
+            Err(Error::InvalidSignature(_))
+        ));
+    }
+}
+
+#[test]
+fn parse_events() {
+    assert_eq!(
+        StripeEvent::parse(CHARGE_SUCCEEDED).unwrap(),
+        StripeEvent::ChargeSucceeded {
+            charge_id: "ch_3KpDq92eZvKYlo2C0wQHLx1L".to_string(),
+        }
+    );
+    assert_eq!(
+        StripeEvent::parse(ACCOUNT_UPDATED).unwrap(),
+        StripeEvent::AccountUpdated {
+            account_id: "acct_1KpDnZ2fQvXyR8aB".to_string(),
+            state: AffiliateStripeState {
+                charges_enabled: true,
+                payouts_enabled: false,
+                country: Some("US".to_string()),
+                business_name: Some("Example Food Bank".to_string()),
+                requirements_currently_due: vec!["external_account".to_string()],
+                disabled_reason: Some("requirements.past_due".to_string()),
+                currencies: vec!["CAD".to_string(), "USD".to_string()],
+            },
+            create_time: Utc.timestamp(1650000100, 0),
+        }
+    );
+    assert_eq!(
+        StripeEvent::parse(CHARGE_REFUNDED).unwrap(),
+        StripeEvent::ChargeRefunded {
+            charge_id: "ch_3KpDq92eZvKYlo2C0wQHLx1L".to_string(),
+            refunded: false,
+            amount_refunded: Money::from_subunits(Currency::USD, 1000),
+        }
+    );
+    assert_eq!(
+        StripeEvent::parse(r#"{"id":"evt_1","type":"customer.created","data":{"object":{}}}"#)
+            .unwrap(),
+        StripeEvent::Unhandled
+    );
+}
+
+#[tokio::test]
+async fn handle_charge_succeeded() -> Result<(), anyhow::Error> {
+    let mut database = MockDatabaseClient::new();
+    let mut store = MockStore::new();
+    let mut txn = MockStore::new();
+
+    store
+        .expect_add_stripe_event()
+        .withf(|new_row| new_row.stripe_event_id == "evt_1KpDqA2eZvKYlo2CvJxZl4Rk")
+        .times(1)
+        .return_once(|new_row| {
+            Ok(Some(stripe_event_row(
+                &new_row.stripe_event_id,
+                &new_row.payload,
+                false,
+            )))
+        });
+
+    // Transaction
+    {
+        let mut seq = Sequence::new();
+        txn.expect_update_donation_status_by_stripe_charge_id()
+            .withf(|charge_id, status, _| {
+                charge_id == "ch_3KpDq92eZvKYlo2C0wQHLx1L" && *status == DonationStatus::Succeeded
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|_, _, _| Ok(None));
+        txn.expect_mark_stripe_event_processed()
+            .withf(|stripe_event_id, _| stripe_event_id == "evt_1KpDqA2eZvKYlo2CvJxZl4Rk")
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|_, _| Ok(()));
+        txn.expect_commit()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| Ok(()));
+    }
+
+    database.expect_on_demand().times(1).return_once(|| store);
+    database.expect_begin().times(1).return_once(|| Ok(txn));
+
+    let handler = StripeWebhookHandler::new(Arc::new(database), SECRET.to_string());
+    handler
+        .handle(
+            &signed_headers(CHARGE_SUCCEEDED),
+            Bytes::from_static(CHARGE_SUCCEEDED.as_bytes()),
+        )
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn handle_account_updated() -> Result<(), anyhow::Error> {
+    let mut database = MockDatabaseClient::new();
+    let mut store = MockStore::new();
+    let mut txn = MockStore::new();
+
+    store
+        .expect_add_stripe_event()
+        .times(1)
+        .return_once(|new_row| {
+            Ok(Some(stripe_event_row(
+                &new_row.stripe_event_id,
+                &new_row.payload,
+                false,
+            )))
+        });
+
+    // Transaction
+    {
+        let mut seq = Sequence::new();
+        // The state is as of the event's creation, so older events can't
+        // overwrite newer states.
+        txn.expect_update_affiliate_stripe_state()
+            .withf(|stripe_account_id, state, refresh_time, _| {
+                stripe_account_id == "acct_1KpDnZ2fQvXyR8aB"
+                    && state.charges_enabled
+                    && !state.payouts_enabled
+                    && state.requirements_currently_due == vec!["external_account".to_string()]
+                    && *refresh_time == Utc.timestamp(1650000100, 0)
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|_, _, _, _| Ok(false));
+        txn.expect_mark_stripe_event_processed()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|_, _| Ok(()));
+        txn.expect_commit()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| Ok(()));
+    }
+
+    database.expect_on_demand().times(1).return_once(|| store);
+    database.expect_begin().times(1).return_once(|| Ok(txn));
+
+    let handler = StripeWebhookHandler::new(Arc::new(database), SECRET.to_string());
+    handler
+        .handle(
+            &signed_headers(ACCOUNT_UPDATED),
+            Bytes::from_static(ACCOUNT_UPDATED.as_bytes()),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Database which processes the refund of the charge, recording the refunded
+/// amount and marking the donation refunded if `refunded`.
+fn refund_database(refunded: bool, amount_units: i64) -> MockDatabaseClient {
+    let mut database = MockDatabaseClient::new();
+    let mut store = MockStore::new();
+    let mut txn = MockStore::new();
+
+    store
+        .expect_add_stripe_event()
+        .times(1)
+        .return_once(|new_row| {
+            Ok(Some(stripe_event_row(
+                &new_row.stripe_event_id,
+                &new_row.payload,
+                false,
+            )))
+        });
+
+    // Transaction
+    {
+        let mut seq = Sequence::new();
+        txn.expect_upsert_donation_refund_by_stripe_charge_id()
+            .withf(move |charge_id, units, nanos, _| {
+                charge_id == "ch_3KpDq92eZvKYlo2C0wQHLx1L" && *units == amount_units && *nanos == 0
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|_, amount_units, amount_nanos, update_time| {
+                Ok(Some(DonationRefundRow {
+                    donation_id: uuid::Uuid::new_v4(),
+                    create_time: update_time,
+                    update_time,
+                    amount_units,
+                    amount_nanos,
+                }))
+            });
+        if refunded {
+            txn.expect_update_donation_status_by_stripe_charge_id()
+                .withf(|charge_id, status, _| {
+                    charge_id == "ch_3KpDq92eZvKYlo2C0wQHLx1L"
+                        && *status == DonationStatus::Refunded
+                })
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_once(|_, _, _| Ok(None));
+        } else {
+            txn.expect_update_donation_status_by_stripe_charge_id()
+                .never();
+        }
+        txn.expect_mark_stripe_event_processed()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|_, _| Ok(()));
+        txn.expect_commit()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| Ok(()));
+    }
+
+    database.expect_on_demand().times(1).return_once(|| store);
+    database.expect_begin().times(1).return_once(|| Ok(txn));
+    database
+}
+
+#[tokio::test]
+async fn handle_partial_refund_keeps_donation_status() -> Result<(), anyhow::Error> {
+    let handler =
+        StripeWebhookHandler::new(Arc::new(refund_database(false, 10)), SECRET.to_string());
+    handler
+        .handle(
+            &signed_headers(CHARGE_REFUNDED),
+            Bytes::from_static(CHARGE_REFUNDED.as_bytes()),
+        )
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn handle_full_refund_marks_donation_refunded() -> Result<(), anyhow::Error> {
+    let payload = CHARGE_REFUNDED
+        .replace("\"amount_refunded\": 1000", "\"amount_refunded\": 2500")
+        .replace("\"refunded\": false", "\"refunded\": true");
+    let handler =
+        StripeWebhookHandler::new(Arc::new(refund_database(true, 25)), SECRET.to_string());
+    handler
+        .handle(&signed_headers(&payload), Bytes::from(payload))
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn handle_processed_event_is_deduplicated() -> Result<(), anyhow::Error> {
+    let mut database = MockDatabaseClient::new();
+    let mut add_store = MockStore::new();
+    let mut find_store = MockStore::new();
+
+    add_store
+        .expect_add_stripe_event()
+        .times(1)
+        .return_once(|_| Ok(None));
+    find_store
+        .expect_find_stripe_event_by_id()
+        .times(1)
+        .return_once(|stripe_event_id| {
+            Ok(Some(stripe_event_row(
+                stripe_event_id,
+                CHARGE_SUCCEEDED,
+                true,
+            )))
+        });
+
+    let mut seq = Sequence::new();
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(|| add_store);
+    database
+        .expect_on_demand()
+        .times(1)
+        .in_sequence(&mut seq)
+        .return_once(|| find_store);
+    database.expect_begin().times(0);
+
+    let handler = StripeWebhookHandler::new(Arc::new(database), SECRET.to_string());
+    handler
+        .handle(
+            &signed_headers(CHARGE_SUCCEEDED),
+            Bytes::from_static(CHARGE_SUCCEEDED.as_bytes()),
+        )
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn handle_rejects_unsigned_request() {
+    let mut database = MockDatabaseClient::new();
+    database.expect_on_demand().times(0);
+    database.expect_begin().times(0);
+
+    let handler = StripeWebhookHandler::new(Arc::new(database), SECRET.to_string());
+    let result = handler
+        .handle(
+            &HeaderMap::new(),
+            Bytes::from_static(CHARGE_SUCCEEDED.as_bytes()),
+        )
+        .await;
+    assert!(matches!(result, Err(Error::InvalidSignature(_))));
+}
@@ -1,35 +1,50 @@
-#[macro_export]
-macro_rules! status {
-    ($name:ident,$($arg:tt)*) => ({
-        $crate::Status::$name(format!($($arg)*))
-    })
-}
-
-#[macro_export]
-macro_rules! invalid_argument {
-    ($($arg:tt)*) => ($crate::status!(invalid_argument, $($arg)*))
-}
-
-#[macro_export]
-macro_rules! not_found {
-    ($($arg:tt)*) => ($crate::status!(not_found, $($arg)*))
-}
-
-#[macro_export]
-macro_rules! internal {
-    ($($arg:tt)*) => ($crate::status!(internal, $($arg)*))
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::Code;
-
-    #[test]
-    pub fn macros_use_correct_code() {
-        assert_eq!(invalid_argument!("message").code(), Code::InvalidArgument);
-        assert_eq!(
-            invalid_argument!("bad input: {0}", "some issue").message(),
-            "bad input: some issue"
-        );
-    }
-}
+#[macro_export]
+macro_rules! status {
+    ($name:ident,$($arg:tt)*) => ({
+        $crate::Status::$name(format!($($arg)*))
+    })
+}
+
+#[macro_export]
+macro_rules! invalid_argument {
+    ($($arg:tt)*) => ($crate::status!(invalid_argument, $($arg)*))
+}
+
+#[macro_export]
+macro_rules! not_found {
+    ($($arg:tt)*) => ($crate::status!(not_found, $($arg)*))
+}
+
+#[macro_export]
+macro_rules! failed_precondition {
+    ($($arg:tt)*) => ($crate::status!(failed_precondition, $($arg)*))
+}
+
+#[macro_export]
+macro_rules! aborted {
+    ($($arg:tt)*) => ($crate::status!(aborted, $($arg)*))
+}
+
+#[macro_export]
+macro_rules! internal {
+    ($($arg:tt)*) => ($crate::status!(internal, $($arg)*))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Code;
+
+    #[test]
+    pub fn macros_use_correct_code() {
+        assert_eq!(invalid_argument!("message").code(), Code::InvalidArgument);
+        assert_eq!(
+            invalid_argument!("bad input: {0}", "some issue").message(),
+            "bad input: some issue"
+        );
+        assert_eq!(
+            failed_precondition!("message").code(),
+            Code::FailedPrecondition
+        );
+        assert_eq!(aborted!("message").code(), Code::Aborted);
+    }
+}
@@ -0,0 +1,2 @@
+DROP TABLE donations;
+DROP TYPE currency_code;
\ No newline at end of file
@@ -0,0 +1,17 @@
+CREATE TYPE currency_code AS ENUM ('usd');
+CREATE TABLE donations (
+  donation_id uuid NOT NULL DEFAULT uuid_generate_v4(),
+  create_time TIMESTAMPTZ NOT NULL,
+  update_time TIMESTAMPTZ NOT NULL,
+  nonprofit_id uuid NOT NULL,
+  user_id uuid NOT NULL,
+  affiliate_id uuid,
+  currency_code currency_code NOT NULL,
+  amount_units BIGINT NOT NULL,
+  amount_nanos INTEGER NOT NULL,
+  stripe_charge_id VARCHAR(255) NOT NULL UNIQUE,
+  PRIMARY KEY (donation_id),
+  CONSTRAINT fk_donation_to_nonprofit FOREIGN KEY (nonprofit_id) REFERENCES nonprofits(nonprofit_id),
+  CONSTRAINT fk_donation_to_user FOREIGN KEY (user_id) REFERENCES users(user_id),
+  CONSTRAINT fk_donation_to_affiliate FOREIGN KEY (affiliate_id) REFERENCES affiliates(affiliate_id)
+)
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TABLE idempotency_keys (
+  user_id UUID NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  idempotency_key VARCHAR(255) NOT NULL,
+  request_path VARCHAR(255) NOT NULL,
+  request_hash VARCHAR(64) NOT NULL,
+  create_time TIMESTAMPTZ NOT NULL,
+  update_time TIMESTAMPTZ NOT NULL,
+  lock_expire_time TIMESTAMPTZ,
+  response_body BYTEA,
+  PRIMARY KEY (user_id, idempotency_key, request_path)
+);
+CREATE INDEX idempotency_keys_expire_idx ON idempotency_keys (update_time)
\ No newline at end of file
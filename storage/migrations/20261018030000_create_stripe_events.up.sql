@@ -0,0 +1,9 @@
+CREATE TABLE stripe_events (
+  stripe_event_id VARCHAR(255) NOT NULL,
+  create_time TIMESTAMPTZ NOT NULL,
+  update_time TIMESTAMPTZ NOT NULL,
+  event_type VARCHAR(255) NOT NULL,
+  payload TEXT NOT NULL,
+  process_time TIMESTAMPTZ,
+  PRIMARY KEY (stripe_event_id)
+)
\ No newline at end of file
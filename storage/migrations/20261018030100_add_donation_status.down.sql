@@ -0,0 +1,2 @@
+ALTER TABLE donations DROP COLUMN status;
+DROP TYPE donation_status;
\ No newline at end of file
@@ -0,0 +1,8 @@
+CREATE TYPE donation_status AS ENUM (
+  'pending',
+  'succeeded',
+  'failed',
+  'refunded'
+);
+ALTER TABLE donations
+ADD COLUMN status donation_status NOT NULL DEFAULT 'pending';
\ No newline at end of file
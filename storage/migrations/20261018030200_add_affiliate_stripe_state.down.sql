@@ -0,0 +1,2 @@
+ALTER TABLE affiliates DROP COLUMN charges_enabled,
+DROP COLUMN payouts_enabled;
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE affiliates
+ADD COLUMN charges_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+ADD COLUMN payouts_enabled BOOLEAN NOT NULL DEFAULT FALSE;
\ No newline at end of file
@@ -0,0 +1,5 @@
+ALTER TABLE items DROP COLUMN status,
+  DROP COLUMN plaid_error_code,
+  DROP COLUMN consent_expiration_time,
+  DROP COLUMN new_accounts_available;
+DROP TYPE item_status;
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TYPE item_status AS ENUM (
+  'healthy',
+  'login_required',
+  'pending_expiration',
+  'permission_revoked',
+  'error'
+);
+ALTER TABLE items
+ADD COLUMN status item_status NOT NULL DEFAULT 'healthy',
+  ADD COLUMN plaid_error_code VARCHAR(255),
+  ADD COLUMN consent_expiration_time TIMESTAMPTZ,
+  ADD COLUMN new_accounts_available BOOLEAN NOT NULL DEFAULT FALSE;
\ No newline at end of file
@@ -0,0 +1,7 @@
+DROP TABLE donation_allocations;
+ALTER TABLE donations DROP CONSTRAINT donation_nonprofit_or_cause,
+  DROP CONSTRAINT fk_donation_to_cause,
+  DROP COLUMN cause_id,
+  DROP COLUMN stripe_transfer_group,
+  ALTER COLUMN nonprofit_id
+SET NOT NULL;
\ No newline at end of file
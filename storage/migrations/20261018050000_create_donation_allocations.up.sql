@@ -0,0 +1,20 @@
+ALTER TABLE donations
+ALTER COLUMN nonprofit_id DROP NOT NULL,
+  ADD COLUMN cause_id uuid,
+  ADD COLUMN stripe_transfer_group VARCHAR(255),
+  ADD CONSTRAINT fk_donation_to_cause FOREIGN KEY (cause_id) REFERENCES causes(cause_id),
+  ADD CONSTRAINT donation_nonprofit_or_cause CHECK ((nonprofit_id IS NULL) <> (cause_id IS NULL));
+CREATE TABLE donation_allocations (
+  donation_id uuid NOT NULL,
+  nonprofit_id uuid NOT NULL,
+  create_time TIMESTAMPTZ NOT NULL,
+  update_time TIMESTAMPTZ NOT NULL,
+  affiliate_id uuid NOT NULL,
+  amount_units BIGINT NOT NULL,
+  amount_nanos INTEGER NOT NULL,
+  stripe_transfer_id VARCHAR(255) NOT NULL UNIQUE,
+  PRIMARY KEY (donation_id, nonprofit_id),
+  CONSTRAINT fk_donation_allocation_to_donation FOREIGN KEY (donation_id) REFERENCES donations(donation_id),
+  CONSTRAINT fk_donation_allocation_to_nonprofit FOREIGN KEY (nonprofit_id) REFERENCES nonprofits(nonprofit_id),
+  CONSTRAINT fk_donation_allocation_to_affiliate FOREIGN KEY (affiliate_id) REFERENCES affiliates(affiliate_id)
+)
\ No newline at end of file
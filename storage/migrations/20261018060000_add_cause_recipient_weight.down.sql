@@ -0,0 +1 @@
+ALTER TABLE cause_recipients DROP COLUMN weight_basis_points;
\ No newline at end of file
@@ -0,0 +1,28 @@
+ALTER TABLE cause_recipients
+ADD COLUMN weight_basis_points INTEGER;
+-- Existing causes split their donations evenly.
+UPDATE cause_recipients AS cause_recipient
+SET weight_basis_points = 10000 / weights.count + CASE
+    WHEN weights.position <= 10000 % weights.count THEN 1
+    ELSE 0
+  END
+FROM (
+    SELECT cause_id,
+      nonprofit_id,
+      COUNT(*) OVER (PARTITION BY cause_id) AS count,
+      ROW_NUMBER() OVER (
+        PARTITION BY cause_id
+        ORDER BY create_time ASC,
+          nonprofit_id ASC
+      ) AS position
+    FROM cause_recipients
+  ) AS weights
+WHERE cause_recipient.cause_id = weights.cause_id
+  AND cause_recipient.nonprofit_id = weights.nonprofit_id;
+ALTER TABLE cause_recipients
+ALTER COLUMN weight_basis_points
+SET NOT NULL,
+  ADD CONSTRAINT cause_recipient_weight CHECK (
+    weight_basis_points > 0
+    AND weight_basis_points <= 10000
+  );
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE causes DROP COLUMN archive_time;
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE causes
+ADD COLUMN archive_time TIMESTAMPTZ;
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE causes DROP COLUMN state;
+DROP TYPE cause_state;
\ No newline at end of file
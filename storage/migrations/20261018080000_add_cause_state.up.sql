@@ -0,0 +1,3 @@
+CREATE TYPE cause_state AS ENUM ('draft', 'active');
+ALTER TABLE causes
+ADD COLUMN state cause_state NOT NULL DEFAULT 'active';
\ No newline at end of file
@@ -0,0 +1,7 @@
+CREATE OR REPLACE VIEW full_causes AS (
+  SELECT cause,
+    ARRAY_AGG(cause_recipients) AS cause_recipients
+  FROM causes AS cause
+    JOIN cause_recipients USING (cause_id)
+  GROUP BY 1
+);
\ No newline at end of file
@@ -0,0 +1,13 @@
+-- Causes without recipients (e.g. drafts) have an empty recipient array.
+CREATE OR REPLACE VIEW full_causes AS (
+  SELECT cause,
+    COALESCE(
+      ARRAY_AGG(cause_recipients) FILTER (
+        WHERE cause_recipients.cause_id IS NOT NULL
+      ),
+      '{}'::cause_recipients []
+    ) AS cause_recipients
+  FROM causes AS cause
+    LEFT OUTER JOIN cause_recipients USING (cause_id)
+  GROUP BY 1
+);
\ No newline at end of file
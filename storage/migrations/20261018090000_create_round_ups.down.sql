@@ -0,0 +1,3 @@
+DROP TABLE round_ups;
+DROP TABLE round_up_settings;
+ALTER TABLE items DROP COLUMN transactions_cursor;
\ No newline at end of file
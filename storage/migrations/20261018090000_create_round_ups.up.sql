@@ -0,0 +1,32 @@
+ALTER TABLE items
+ADD COLUMN transactions_cursor TEXT;
+CREATE TABLE round_up_settings (
+  user_id uuid NOT NULL,
+  create_time TIMESTAMPTZ NOT NULL,
+  update_time TIMESTAMPTZ NOT NULL,
+  cause_id uuid NOT NULL,
+  account_id uuid,
+  enabled BOOLEAN NOT NULL,
+  PRIMARY KEY (user_id),
+  CONSTRAINT fk_round_up_settings_to_user FOREIGN KEY (user_id) REFERENCES users(user_id),
+  CONSTRAINT fk_round_up_settings_to_cause FOREIGN KEY (cause_id) REFERENCES causes(cause_id),
+  CONSTRAINT fk_round_up_settings_to_account FOREIGN KEY (account_id) REFERENCES accounts(account_id) ON DELETE
+  SET NULL
+);
+CREATE TABLE round_ups (
+  round_up_id uuid NOT NULL DEFAULT uuid_generate_v4(),
+  create_time TIMESTAMPTZ NOT NULL,
+  update_time TIMESTAMPTZ NOT NULL,
+  user_id uuid NOT NULL,
+  plaid_transaction_id VARCHAR(255) NOT NULL UNIQUE,
+  plaid_account_id VARCHAR(255) NOT NULL,
+  currency_code currency_code NOT NULL,
+  amount_units BIGINT NOT NULL,
+  amount_nanos INTEGER NOT NULL,
+  donation_id uuid,
+  PRIMARY KEY (round_up_id),
+  CONSTRAINT fk_round_up_to_user FOREIGN KEY (user_id) REFERENCES users(user_id),
+  CONSTRAINT fk_round_up_to_donation FOREIGN KEY (donation_id) REFERENCES donations(donation_id)
+);
+CREATE INDEX round_ups_pending_idx ON round_ups (user_id)
+WHERE donation_id IS NULL;
\ No newline at end of file
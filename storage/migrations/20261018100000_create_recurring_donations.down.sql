@@ -0,0 +1,3 @@
+DROP TABLE recurring_donations;
+DROP TYPE recurring_donation_state;
+DROP TYPE recurring_donation_frequency;
\ No newline at end of file
@@ -0,0 +1,31 @@
+CREATE TYPE recurring_donation_frequency AS ENUM ('weekly', 'monthly');
+CREATE TYPE recurring_donation_state AS ENUM ('active', 'paused', 'canceled');
+CREATE TABLE recurring_donations (
+  recurring_donation_id uuid NOT NULL DEFAULT uuid_generate_v4(),
+  create_time TIMESTAMPTZ NOT NULL,
+  update_time TIMESTAMPTZ NOT NULL,
+  user_id uuid NOT NULL,
+  nonprofit_id uuid,
+  cause_id uuid,
+  account_id uuid,
+  currency_code currency_code NOT NULL,
+  amount_units BIGINT NOT NULL,
+  amount_nanos INTEGER NOT NULL,
+  frequency recurring_donation_frequency NOT NULL,
+  state recurring_donation_state NOT NULL,
+  start_time TIMESTAMPTZ NOT NULL,
+  next_run_time TIMESTAMPTZ NOT NULL,
+  retry_time TIMESTAMPTZ,
+  failure_count INTEGER NOT NULL,
+  last_donation_id uuid,
+  PRIMARY KEY (recurring_donation_id),
+  CONSTRAINT fk_recurring_donation_to_user FOREIGN KEY (user_id) REFERENCES users(user_id),
+  CONSTRAINT fk_recurring_donation_to_nonprofit FOREIGN KEY (nonprofit_id) REFERENCES nonprofits(nonprofit_id),
+  CONSTRAINT fk_recurring_donation_to_cause FOREIGN KEY (cause_id) REFERENCES causes(cause_id),
+  CONSTRAINT fk_recurring_donation_to_account FOREIGN KEY (account_id) REFERENCES accounts(account_id) ON DELETE
+  SET NULL,
+  CONSTRAINT fk_recurring_donation_to_last_donation FOREIGN KEY (last_donation_id) REFERENCES donations(donation_id),
+  CONSTRAINT recurring_donation_nonprofit_or_cause CHECK ((nonprofit_id IS NULL) <> (cause_id IS NULL))
+);
+CREATE INDEX recurring_donations_due_idx ON recurring_donations (COALESCE(retry_time, next_run_time))
+WHERE state = 'active';
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE donations DROP COLUMN settle_time,
+  DROP COLUMN account_id;
\ No newline at end of file
@@ -0,0 +1,5 @@
+ALTER TABLE donations
+ADD COLUMN account_id uuid,
+  ADD COLUMN settle_time TIMESTAMPTZ,
+  ADD CONSTRAINT fk_donation_to_account FOREIGN KEY (account_id) REFERENCES accounts(account_id) ON DELETE
+SET NULL;
\ No newline at end of file
@@ -0,0 +1,7 @@
+CREATE TYPE currency_code AS ENUM ('usd');
+ALTER TABLE recurring_donations DROP CONSTRAINT recurring_donation_currency_code_iso,
+  ALTER COLUMN currency_code TYPE currency_code USING lower(currency_code)::currency_code;
+ALTER TABLE round_ups DROP CONSTRAINT round_up_currency_code_iso,
+  ALTER COLUMN currency_code TYPE currency_code USING lower(currency_code)::currency_code;
+ALTER TABLE donations DROP CONSTRAINT donation_currency_code_iso,
+  ALTER COLUMN currency_code TYPE currency_code USING lower(currency_code)::currency_code;
\ No newline at end of file
@@ -0,0 +1,10 @@
+ALTER TABLE donations
+ALTER COLUMN currency_code TYPE CHAR(3) USING upper(currency_code::text),
+  ADD CONSTRAINT donation_currency_code_iso CHECK (currency_code ~ '^[A-Z]{3}$');
+ALTER TABLE round_ups
+ALTER COLUMN currency_code TYPE CHAR(3) USING upper(currency_code::text),
+  ADD CONSTRAINT round_up_currency_code_iso CHECK (currency_code ~ '^[A-Z]{3}$');
+ALTER TABLE recurring_donations
+ALTER COLUMN currency_code TYPE CHAR(3) USING upper(currency_code::text),
+  ADD CONSTRAINT recurring_donation_currency_code_iso CHECK (currency_code ~ '^[A-Z]{3}$');
+DROP TYPE currency_code;
\ No newline at end of file
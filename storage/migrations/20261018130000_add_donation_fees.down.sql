@@ -0,0 +1,7 @@
+ALTER TABLE donations DROP COLUMN platform_fee_nanos,
+  DROP COLUMN platform_fee_units,
+  DROP COLUMN processing_fee_nanos,
+  DROP COLUMN processing_fee_units,
+  DROP COLUMN charge_amount_nanos,
+  DROP COLUMN charge_amount_units,
+  DROP COLUMN cover_fees;
\ No newline at end of file
@@ -0,0 +1,16 @@
+ALTER TABLE donations
+ADD COLUMN cover_fees BOOLEAN NOT NULL DEFAULT FALSE,
+  ADD COLUMN charge_amount_units BIGINT,
+  ADD COLUMN charge_amount_nanos INTEGER,
+  ADD COLUMN processing_fee_units BIGINT NOT NULL DEFAULT 0,
+  ADD COLUMN processing_fee_nanos INTEGER NOT NULL DEFAULT 0,
+  ADD COLUMN platform_fee_units BIGINT NOT NULL DEFAULT 0,
+  ADD COLUMN platform_fee_nanos INTEGER NOT NULL DEFAULT 0;
+UPDATE donations
+SET charge_amount_units = amount_units,
+  charge_amount_nanos = amount_nanos;
+ALTER TABLE donations
+ALTER COLUMN charge_amount_units
+SET NOT NULL,
+  ALTER COLUMN charge_amount_nanos
+SET NOT NULL;
\ No newline at end of file
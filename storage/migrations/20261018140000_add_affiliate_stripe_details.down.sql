@@ -0,0 +1,6 @@
+DROP INDEX affiliates_stripe_refresh_idx;
+ALTER TABLE affiliates DROP COLUMN stripe_refresh_time,
+  DROP COLUMN disabled_reason,
+  DROP COLUMN requirements_currently_due,
+  DROP COLUMN business_name,
+  DROP COLUMN country;
\ No newline at end of file
@@ -0,0 +1,7 @@
+ALTER TABLE affiliates
+ADD COLUMN country VARCHAR(2),
+  ADD COLUMN business_name VARCHAR(255),
+  ADD COLUMN requirements_currently_due TEXT [] NOT NULL DEFAULT '{}',
+  ADD COLUMN disabled_reason VARCHAR(255),
+  ADD COLUMN stripe_refresh_time TIMESTAMPTZ;
+CREATE INDEX affiliates_stripe_refresh_idx ON affiliates (stripe_refresh_time NULLS FIRST)
\ No newline at end of file
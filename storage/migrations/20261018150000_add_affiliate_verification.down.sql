@@ -0,0 +1,6 @@
+ALTER TABLE nonprofits DROP COLUMN email;
+ALTER TABLE affiliates DROP COLUMN verification_time,
+  DROP COLUMN rejection_reason,
+  DROP COLUMN verification_state,
+  DROP COLUMN ein;
+DROP TYPE affiliate_verification_state;
\ No newline at end of file
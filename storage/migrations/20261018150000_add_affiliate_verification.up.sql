@@ -0,0 +1,8 @@
+CREATE TYPE affiliate_verification_state AS ENUM ('pending', 'approved', 'rejected');
+ALTER TABLE affiliates
+ADD COLUMN ein VARCHAR(255),
+  ADD COLUMN verification_state affiliate_verification_state NOT NULL DEFAULT 'pending',
+  ADD COLUMN rejection_reason TEXT,
+  ADD COLUMN verification_time TIMESTAMPTZ;
+ALTER TABLE nonprofits
+ADD COLUMN email VARCHAR(255)
\ No newline at end of file
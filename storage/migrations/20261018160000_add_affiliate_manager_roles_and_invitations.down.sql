@@ -0,0 +1,3 @@
+DROP TABLE affiliate_manager_invitations;
+ALTER TABLE affiliate_managers DROP COLUMN role;
+DROP TYPE affiliate_manager_role;
\ No newline at end of file
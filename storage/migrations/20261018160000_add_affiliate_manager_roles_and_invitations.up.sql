@@ -0,0 +1,20 @@
+CREATE TYPE affiliate_manager_role AS ENUM ('owner', 'admin', 'viewer');
+ALTER TABLE affiliate_managers
+ADD COLUMN role affiliate_manager_role NOT NULL DEFAULT 'owner';
+CREATE TABLE affiliate_manager_invitations (
+  affiliate_manager_invitation_id uuid NOT NULL DEFAULT uuid_generate_v4(),
+  create_time TIMESTAMPTZ NOT NULL,
+  update_time TIMESTAMPTZ NOT NULL,
+  affiliate_id uuid NOT NULL,
+  inviter_user_id uuid NOT NULL,
+  email VARCHAR(255) NOT NULL,
+  role affiliate_manager_role NOT NULL,
+  token_hash VARCHAR(64) NOT NULL UNIQUE,
+  expire_time TIMESTAMPTZ NOT NULL,
+  accept_time TIMESTAMPTZ,
+  accepted_user_id uuid,
+  PRIMARY KEY (affiliate_manager_invitation_id),
+  CONSTRAINT fk_affiliate_manager_invitation_to_affiliate FOREIGN KEY (affiliate_id) REFERENCES affiliates(affiliate_id),
+  CONSTRAINT fk_affiliate_manager_invitation_to_inviter FOREIGN KEY (inviter_user_id) REFERENCES users(user_id),
+  CONSTRAINT fk_affiliate_manager_invitation_to_accepted_user FOREIGN KEY (accepted_user_id) REFERENCES users(user_id)
+);
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE donation_refunds
\ No newline at end of file
@@ -0,0 +1,8 @@
+CREATE TABLE donation_refunds (
+  donation_id uuid PRIMARY KEY,
+  create_time TIMESTAMPTZ NOT NULL,
+  update_time TIMESTAMPTZ NOT NULL,
+  amount_units BIGINT NOT NULL,
+  amount_nanos INTEGER NOT NULL,
+  CONSTRAINT fk_donation_refund_to_donation FOREIGN KEY (donation_id) REFERENCES donations(donation_id)
+)
\ No newline at end of file
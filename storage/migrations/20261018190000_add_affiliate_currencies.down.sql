@@ -0,0 +1 @@
+ALTER TABLE affiliates DROP COLUMN currencies
\ No newline at end of file
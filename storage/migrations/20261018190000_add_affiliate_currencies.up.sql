@@ -0,0 +1,2 @@
+ALTER TABLE affiliates
+ADD COLUMN currencies VARCHAR(3) [] NOT NULL DEFAULT '{}'
\ No newline at end of file
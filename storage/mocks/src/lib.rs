@@ -3,11 +3,18 @@ use affect_storage::{
         client::DatabaseClient,
         store::{OnDemandStore, TransactionalStore},
     },
-    models::cause::*,
-    stores::cause::*,
+    models::{
+        account::*, affiliate::*, cause::*, donation::*, idempotency_key::*, item::*, nonprofit::*,
+        recurring_donation::*, round_up::*, stripe_event::*, user::*,
+    },
+    stores::{
+        account::*, affiliate::*, cause::*, donation::*, idempotency_key::*, item::*, nonprofit::*,
+        recurring_donation::*, round_up::*, stripe_event::*, user::*,
+    },
     Error,
 };
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use mockall::mock;
 use uuid::Uuid;
 
@@ -29,6 +36,24 @@ mock! {
   impl CauseStore for Store {
       async fn add_cause(&self, new_row: NewCauseRow) -> Result<CauseRow, Error>;
 
+      async fn find_cause_by_id(&self, cause_id: Uuid) -> Result<Option<FullCauseRow>, Error>;
+
+      async fn lock_cause_by_id(&self, cause_id: Uuid) -> Result<Option<CauseRow>, Error>;
+
+      async fn update_cause(
+          &self,
+          cause_id: Uuid,
+          name: String,
+          state: CauseState,
+          update_time: DateTime<Utc>,
+      ) -> Result<CauseRow, Error>;
+
+      async fn archive_cause(
+          &self,
+          cause_id: Uuid,
+          archive_time: DateTime<Utc>,
+      ) -> Result<CauseRow, Error>;
+
       async fn list_causes_for_user(
           &self,
           page_size: i64,
@@ -47,6 +72,445 @@ mock! {
           &self,
           cause_id: Uuid,
       ) -> Result<Vec<CauseRecipientRow>, Error>;
+
+      async fn delete_cause_recipients_for_cause(&self, cause_id: Uuid) -> Result<(), Error>;
+  }
+
+  #[async_trait]
+  impl AccountStore for Store {
+      async fn add_account(&self, new_row: NewAccountRow) -> Result<AccountRow, Error>;
+
+      async fn find_account_by_id(&self, account_id: Uuid) -> Result<Option<AccountRow>, Error>;
+
+      async fn find_account_by_id_for_user(
+          &self,
+          account_id: Uuid,
+          user_id: Uuid,
+      ) -> Result<Option<AccountRow>, Error>;
+
+      async fn list_accounts_for_item(&self, item_id: Uuid) -> Result<Vec<AccountRow>, Error>;
+
+      async fn update_account(
+          &self,
+          account_id: Uuid,
+          name: String,
+          mask: Option<String>,
+          update_time: DateTime<Utc>,
+      ) -> Result<AccountRow, Error>;
+
+      async fn delete_account(&self, account_id: Uuid) -> Result<(), Error>;
+  }
+
+  #[async_trait]
+  impl AffiliateStore for Store {
+      async fn add_affiliate(&self, new_row: NewAffiliateRow) -> Result<AffiliateRow, Error>;
+
+      async fn find_affiliate_by_id(
+          &self,
+          affiliate_id: Uuid,
+      ) -> Result<Option<FullAffiliateRow>, Error>;
+
+      async fn list_affiliates(
+          &self,
+          page_size: i64,
+          page_token: Option<AffiliatePageToken>,
+      ) -> Result<Vec<FullAffiliateRow>, Error>;
+
+      async fn count_affiliates(&self) -> Result<i64, Error>;
+
+      async fn list_affiliates_for_user(
+          &self,
+          page_size: i64,
+          page_token: Option<AffiliatePageToken>,
+          user_id: Uuid,
+      ) -> Result<Vec<FullAffiliateRow>, Error>;
+
+      async fn count_affiliates_for_user(&self, user_id: Uuid) -> Result<i64, Error>;
+
+      async fn add_affiliate_manager(
+          &self,
+          new_row: NewAffiliateManagerRow,
+      ) -> Result<AffiliateManagerRow, Error>;
+
+      async fn list_affiliate_managers_for_affilate(
+          &self,
+          affiliate_id: Uuid,
+      ) -> Result<Vec<AffiliateManagerRow>, Error>;
+
+      async fn list_affiliate_managers_for_user(
+          &self,
+          user_id: Uuid,
+      ) -> Result<Vec<AffiliateManagerRow>, Error>;
+
+      async fn lock_affiliate_managers_for_affiliate(
+          &self,
+          affiliate_id: Uuid,
+      ) -> Result<Vec<AffiliateManagerRow>, Error>;
+
+      async fn delete_affiliate_manager(
+          &self,
+          affiliate_id: Uuid,
+          user_id: Uuid,
+      ) -> Result<bool, Error>;
+
+      async fn add_affiliate_manager_invitation(
+          &self,
+          new_row: NewAffiliateManagerInvitationRow,
+      ) -> Result<AffiliateManagerInvitationRow, Error>;
+
+      async fn find_affiliate_manager_invitation_by_token_hash(
+          &self,
+          token_hash: &str,
+      ) -> Result<Option<AffiliateManagerInvitationRow>, Error>;
+
+      async fn accept_affiliate_manager_invitation(
+          &self,
+          affiliate_manager_invitation_id: Uuid,
+          user_id: Uuid,
+          accept_time: DateTime<Utc>,
+      ) -> Result<Option<AffiliateManagerInvitationRow>, Error>;
+
+      async fn update_affiliate_stripe_state(
+          &self,
+          stripe_account_id: &str,
+          state: AffiliateStripeState,
+          refresh_time: DateTime<Utc>,
+          update_time: DateTime<Utc>,
+      ) -> Result<bool, Error>;
+
+      async fn update_affiliate_stripe_refresh_time(
+          &self,
+          stripe_account_id: &str,
+          refresh_time: DateTime<Utc>,
+      ) -> Result<(), Error>;
+
+      async fn update_affiliate_verification(
+          &self,
+          affiliate_id: Uuid,
+          verification_state: AffiliateVerificationState,
+          rejection_reason: Option<String>,
+          verification_time: DateTime<Utc>,
+      ) -> Result<Option<AffiliateRow>, Error>;
+
+      async fn link_affiliate_to_nonprofit(
+          &self,
+          nonprofit_id: Uuid,
+          affiliate_id: Uuid,
+          update_time: DateTime<Utc>,
+      ) -> Result<bool, Error>;
+
+      async fn list_affiliates_for_stripe_refresh(
+          &self,
+          refreshed_before: DateTime<Utc>,
+          limit: i64,
+      ) -> Result<Vec<AffiliateRow>, Error>;
+  }
+
+  #[async_trait]
+  impl DonationStore for Store {
+      async fn add_donation(&self, new_row: NewDonationRow) -> Result<DonationRow, Error>;
+
+      async fn find_donation_by_id(&self, donation_id: Uuid) -> Result<Option<DonationRow>, Error>;
+
+      async fn find_donation_by_stripe_charge_id(
+          &self,
+          stripe_charge_id: &str,
+      ) -> Result<Option<DonationRow>, Error>;
+
+      async fn add_donation_allocation(
+          &self,
+          donation_id: Uuid,
+          new_row: NewDonationAllocationRow,
+          create_time: DateTime<Utc>,
+      ) -> Result<DonationAllocationRow, Error>;
+
+      async fn list_donation_allocations_for_donation(
+          &self,
+          donation_id: Uuid,
+      ) -> Result<Vec<DonationAllocationRow>, Error>;
+
+      async fn update_donation_status_by_stripe_charge_id(
+          &self,
+          stripe_charge_id: &str,
+          status: DonationStatus,
+          update_time: DateTime<Utc>,
+      ) -> Result<Option<DonationRow>, Error>;
+
+      async fn upsert_donation_refund_by_stripe_charge_id(
+          &self,
+          stripe_charge_id: &str,
+          amount_units: i64,
+          amount_nanos: i32,
+          update_time: DateTime<Utc>,
+      ) -> Result<Option<DonationRefundRow>, Error>;
+
+      async fn list_donations_for_user(
+          &self,
+          page_size: i64,
+          page_token: Option<DonationPageToken>,
+          user_id: Uuid,
+      ) -> Result<Vec<DonationRow>, Error>;
+
+      async fn count_donations_for_user(&self, user_id: Uuid) -> Result<i64, Error>;
+
+      async fn list_donations_for_nonprofit(
+          &self,
+          page_size: i64,
+          page_token: Option<DonationPageToken>,
+          nonprofit_id: Uuid,
+      ) -> Result<Vec<DonationRow>, Error>;
+
+      async fn count_donations_for_nonprofit(&self, nonprofit_id: Uuid) -> Result<i64, Error>;
+
+      async fn list_donations_for_affiliate(
+          &self,
+          page_size: i64,
+          page_token: Option<DonationPageToken>,
+          affiliate_id: Uuid,
+      ) -> Result<Vec<DonationRow>, Error>;
+
+      async fn count_donations_for_affiliate(&self, affiliate_id: Uuid) -> Result<i64, Error>;
+
+      async fn sum_donations_by_currency_for_user(
+          &self,
+          user_id: Uuid,
+      ) -> Result<Vec<DonationTotalRow>, Error>;
+
+      async fn sum_donations_by_currency_for_nonprofit(
+          &self,
+          nonprofit_id: Uuid,
+      ) -> Result<Vec<DonationTotalRow>, Error>;
+
+      async fn sum_donations_by_currency_for_affiliate(
+          &self,
+          affiliate_id: Uuid,
+      ) -> Result<Vec<DonationTotalRow>, Error>;
+  }
+
+  #[async_trait]
+  impl IdempotencyKeyStore for Store {
+      async fn add_idempotency_key(
+          &self,
+          new_row: NewIdempotencyKeyRow,
+      ) -> Result<Option<IdempotencyKeyRow>, Error>;
+
+      async fn find_idempotency_key(
+          &self,
+          user_id: Uuid,
+          idempotency_key: &str,
+          request_path: &str,
+      ) -> Result<Option<IdempotencyKeyRow>, Error>;
+
+      async fn update_idempotency_key_response(
+          &self,
+          user_id: Uuid,
+          idempotency_key: &str,
+          request_path: &str,
+          response_body: Vec<u8>,
+          update_time: DateTime<Utc>,
+      ) -> Result<(), Error>;
+
+      async fn update_idempotency_key_failed(
+          &self,
+          user_id: Uuid,
+          idempotency_key: &str,
+          request_path: &str,
+          update_time: DateTime<Utc>,
+      ) -> Result<(), Error>;
+
+      async fn delete_idempotency_key(
+          &self,
+          user_id: Uuid,
+          idempotency_key: &str,
+          request_path: &str,
+      ) -> Result<(), Error>;
+
+      async fn delete_expired_idempotency_keys(
+          &self,
+          expire_time: DateTime<Utc>,
+      ) -> Result<u64, Error>;
+  }
+
+  #[async_trait]
+  impl ItemStore for Store {
+      async fn add_item(&self, new_row: NewItemRow) -> Result<ItemRow, Error>;
+
+      async fn find_item_by_id(&self, item_id: Uuid) -> Result<Option<ItemRow>, Error>;
+
+      async fn list_items_for_user(
+          &self,
+          page_size: i64,
+          page_token: Option<ItemPageToken>,
+          user_id: Uuid,
+      ) -> Result<Vec<ItemRow>, Error>;
+
+      async fn count_items_for_user(&self, user_id: Uuid) -> Result<i64, Error>;
+
+      async fn delete_item(&self, item_id: Uuid) -> Result<(), Error>;
+
+      async fn update_item_status_by_plaid_item_id(
+          &self,
+          plaid_item_id: &str,
+          status: ItemStatus,
+          plaid_error_code: Option<String>,
+          consent_expiration_time: Option<DateTime<Utc>>,
+          update_time: DateTime<Utc>,
+      ) -> Result<Option<ItemRow>, Error>;
+
+      async fn update_item_new_accounts_available_by_plaid_item_id(
+          &self,
+          plaid_item_id: &str,
+          new_accounts_available: bool,
+          update_time: DateTime<Utc>,
+      ) -> Result<Option<ItemRow>, Error>;
+
+      async fn update_item_transactions_cursor(
+          &self,
+          item_id: Uuid,
+          transactions_cursor: String,
+          update_time: DateTime<Utc>,
+      ) -> Result<(), Error>;
+  }
+
+  #[async_trait]
+  impl NonprofitStore for Store {
+      async fn add_nonprofit(&self, new_nonprofit: NewNonprofitRow) -> Result<NonprofitRow, Error>;
+
+      async fn find_nonprofit_by_id(
+          &self,
+          nonprofit_id: Uuid,
+      ) -> Result<Option<FullNonprofitRow>, Error>;
+
+      async fn list_nonprofits(
+          &self,
+          page_size: i64,
+          page_token: Option<NonprofitPageToken>,
+      ) -> Result<Vec<FullNonprofitRow>, Error>;
+
+      async fn count_nonprofits(&self) -> Result<i64, Error>;
+
+      async fn list_nonprofits_by_search(
+          &self,
+          page_size: i64,
+          page_token: Option<NonprofitPageToken>,
+          query: &str,
+      ) -> Result<Vec<FullNonprofitRow>, Error>;
+
+      async fn count_nonprofits_by_search(&self, query: &str) -> Result<i64, Error>;
+  }
+
+  #[async_trait]
+  impl RecurringDonationStore for Store {
+      async fn add_recurring_donation(
+          &self,
+          new_row: NewRecurringDonationRow,
+      ) -> Result<RecurringDonationRow, Error>;
+
+      async fn find_recurring_donation_by_id(
+          &self,
+          recurring_donation_id: Uuid,
+      ) -> Result<Option<RecurringDonationRow>, Error>;
+
+      async fn update_recurring_donation_state(
+          &self,
+          recurring_donation_id: Uuid,
+          state: RecurringDonationState,
+          next_run_time: DateTime<Utc>,
+          update_time: DateTime<Utc>,
+      ) -> Result<RecurringDonationRow, Error>;
+
+      async fn claim_due_recurring_donation(
+          &self,
+          now: DateTime<Utc>,
+      ) -> Result<Option<RecurringDonationRow>, Error>;
+
+      async fn update_recurring_donation_schedule(
+          &self,
+          recurring_donation_id: Uuid,
+          next_run_time: DateTime<Utc>,
+          retry_time: Option<DateTime<Utc>>,
+          failure_count: i32,
+          last_donation_id: Option<Uuid>,
+          update_time: DateTime<Utc>,
+      ) -> Result<(), Error>;
+  }
+
+  #[async_trait]
+  impl RoundUpStore for Store {
+      async fn add_round_up(&self, new_row: NewRoundUpRow) -> Result<Option<RoundUpRow>, Error>;
+
+      async fn list_pending_round_ups_for_user(
+          &self,
+          user_id: Uuid,
+      ) -> Result<Vec<RoundUpRow>, Error>;
+
+      async fn mark_round_ups_donated(
+          &self,
+          round_up_ids: Vec<Uuid>,
+          donation_id: Uuid,
+          update_time: DateTime<Utc>,
+      ) -> Result<(), Error>;
+
+      async fn upsert_round_up_settings(
+          &self,
+          new_row: NewRoundUpSettingsRow,
+      ) -> Result<RoundUpSettingsRow, Error>;
+
+      async fn find_round_up_settings_by_user_id(
+          &self,
+          user_id: Uuid,
+      ) -> Result<Option<RoundUpSettingsRow>, Error>;
+
+      async fn list_enabled_round_up_settings(&self) -> Result<Vec<RoundUpSettingsRow>, Error>;
+
+      async fn lock_enabled_round_up_settings_by_user_id(
+          &self,
+          user_id: Uuid,
+      ) -> Result<Option<RoundUpSettingsRow>, Error>;
+  }
+
+  #[async_trait]
+  impl StripeEventStore for Store {
+      async fn add_stripe_event(
+          &self,
+          new_row: NewStripeEventRow,
+      ) -> Result<Option<StripeEventRow>, Error>;
+
+      async fn find_stripe_event_by_id(
+          &self,
+          stripe_event_id: &str,
+      ) -> Result<Option<StripeEventRow>, Error>;
+
+      async fn list_unprocessed_stripe_events(
+          &self,
+          limit: i64,
+      ) -> Result<Vec<StripeEventRow>, Error>;
+
+      async fn mark_stripe_event_processed(
+          &self,
+          stripe_event_id: &str,
+          process_time: DateTime<Utc>,
+      ) -> Result<(), Error>;
+  }
+
+  #[async_trait]
+  impl UserStore for Store {
+      async fn add_user(&self, new_user: NewUserRow) -> Result<UserRow, Error>;
+
+      async fn find_user_by_id(&self, user_id: Uuid) -> Result<Option<UserRow>, Error>;
+
+      async fn find_user_by_firebase_uid(
+          &self,
+          firebase_uid: String,
+      ) -> Result<Option<UserRow>, Error>;
+
+      async fn list_users(
+          &self,
+          page_size: i64,
+          page_token: Option<UserPageToken>,
+      ) -> Result<Vec<UserRow>, Error>;
+
+      async fn count_users(&self) -> Result<i64, Error>;
   }
 
   #[async_trait]
@@ -0,0 +1,3 @@
+SELECT *
+FROM accounts
+WHERE account_id = $1
\ No newline at end of file
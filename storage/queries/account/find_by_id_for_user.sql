@@ -0,0 +1,5 @@
+SELECT accounts.*
+FROM accounts
+  INNER JOIN items ON items.item_id = accounts.item_id
+WHERE accounts.account_id = $1
+  AND items.user_id = $2
\ No newline at end of file
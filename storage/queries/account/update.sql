@@ -0,0 +1,6 @@
+UPDATE accounts
+SET name = $2,
+  mask = $3,
+  update_time = $4
+WHERE account_id = $1
+RETURNING *
\ No newline at end of file
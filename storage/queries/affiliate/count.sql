@@ -0,0 +1,2 @@
+SELECT COUNT(*) AS "count!"
+FROM affiliates
\ No newline at end of file
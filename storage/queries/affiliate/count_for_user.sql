@@ -0,0 +1,3 @@
+SELECT COUNT(*) AS "count!"
+FROM affiliate_managers
+WHERE user_id = $1
\ No newline at end of file
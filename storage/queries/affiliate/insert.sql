@@ -6,9 +6,10 @@ INSERT INTO affiliates (
                 company_name,
                 contact_email,
                 business_type,
-                asserted_nonprofit_id
+                asserted_nonprofit_id,
+                ein
         )
-VALUES (DEFAULT, $1, $2, $3, $4, $5, $6, $7)
+VALUES (DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8)
 RETURNING affiliate_id,
         create_time,
         update_time,
@@ -16,4 +17,16 @@ RETURNING affiliate_id,
         company_name,
         contact_email,
         business_type as "business_type: _",
-        asserted_nonprofit_id
\ No newline at end of file
+        asserted_nonprofit_id,
+        charges_enabled,
+        payouts_enabled,
+        country,
+        business_name,
+        requirements_currently_due,
+        disabled_reason,
+        stripe_refresh_time,
+        ein,
+        verification_state as "verification_state: _",
+        rejection_reason,
+        verification_time,
+        currencies
\ No newline at end of file
@@ -0,0 +1,6 @@
+UPDATE nonprofits
+SET affiliate_id = $2,
+  update_time = $3
+WHERE nonprofit_id = $1
+  AND affiliate_id IS NULL
+RETURNING nonprofit_id
\ No newline at end of file
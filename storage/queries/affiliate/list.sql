@@ -0,0 +1,7 @@
+SELECT affiliate AS "affiliate!: _",
+  asserted_nonprofit AS "asserted_nonprofit: _",
+  affiliate_managers AS "affiliate_managers!: _"
+FROM full_affiliates
+ORDER BY (affiliate).create_time ASC,
+  (affiliate).affiliate_id ASC
+LIMIT $1
\ No newline at end of file
@@ -0,0 +1,8 @@
+SELECT affiliate AS "affiliate!: _",
+  asserted_nonprofit AS "asserted_nonprofit: _",
+  affiliate_managers AS "affiliate_managers!: _"
+FROM full_affiliates
+WHERE ((affiliate).create_time, (affiliate).affiliate_id) >= ($1, $2)
+ORDER BY (affiliate).create_time ASC,
+  (affiliate).affiliate_id ASC
+LIMIT $3
\ No newline at end of file
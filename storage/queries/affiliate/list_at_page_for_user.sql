@@ -0,0 +1,13 @@
+SELECT affiliate AS "affiliate!: _",
+  asserted_nonprofit AS "asserted_nonprofit: _",
+  affiliate_managers AS "affiliate_managers!: _"
+FROM full_affiliates
+WHERE ((affiliate).create_time, (affiliate).affiliate_id) >= ($1, $2)
+  AND (affiliate).affiliate_id IN (
+    SELECT affiliate_id
+    FROM affiliate_managers
+    WHERE user_id = $3
+  )
+ORDER BY (affiliate).create_time ASC,
+  (affiliate).affiliate_id ASC
+LIMIT $4
\ No newline at end of file
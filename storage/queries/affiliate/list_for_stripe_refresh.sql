@@ -0,0 +1,25 @@
+SELECT affiliate_id,
+  create_time,
+  update_time,
+  stripe_account_id,
+  company_name,
+  contact_email,
+  business_type AS "business_type: _",
+  asserted_nonprofit_id,
+  charges_enabled,
+  payouts_enabled,
+  country,
+  business_name,
+  requirements_currently_due,
+  disabled_reason,
+  stripe_refresh_time,
+  ein,
+  verification_state AS "verification_state: _",
+  rejection_reason,
+  verification_time,
+  currencies
+FROM affiliates
+WHERE stripe_refresh_time IS NULL
+  OR stripe_refresh_time < $1
+ORDER BY stripe_refresh_time ASC NULLS FIRST
+LIMIT $2
\ No newline at end of file
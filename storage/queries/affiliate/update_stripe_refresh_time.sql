@@ -0,0 +1,3 @@
+UPDATE affiliates
+SET stripe_refresh_time = $2
+WHERE stripe_account_id = $1
\ No newline at end of file
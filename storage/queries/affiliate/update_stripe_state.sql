@@ -0,0 +1,15 @@
+UPDATE affiliates
+SET charges_enabled = $2,
+  payouts_enabled = $3,
+  country = $4,
+  business_name = $5,
+  requirements_currently_due = $6,
+  disabled_reason = $7,
+  currencies = $8,
+  stripe_refresh_time = $9,
+  update_time = $10
+WHERE stripe_account_id = $1
+  AND (
+    stripe_refresh_time IS NULL
+    OR stripe_refresh_time <= $9
+  )
\ No newline at end of file
@@ -0,0 +1,27 @@
+UPDATE affiliates
+SET verification_state = $2,
+  rejection_reason = $3,
+  verification_time = $4,
+  update_time = $4
+WHERE affiliate_id = $1
+  AND verification_state = 'pending'
+RETURNING affiliate_id,
+  create_time,
+  update_time,
+  stripe_account_id,
+  company_name,
+  contact_email,
+  business_type AS "business_type: _",
+  asserted_nonprofit_id,
+  charges_enabled,
+  payouts_enabled,
+  country,
+  business_name,
+  requirements_currently_due,
+  disabled_reason,
+  stripe_refresh_time,
+  ein,
+  verification_state AS "verification_state: _",
+  rejection_reason,
+  verification_time,
+  currencies
\ No newline at end of file
@@ -0,0 +1,4 @@
+DELETE FROM affiliate_managers
+WHERE affiliate_id = $1
+  AND user_id = $2
+RETURNING user_id
\ No newline at end of file
@@ -2,7 +2,12 @@ INSERT INTO affiliate_managers (
     affiliate_id,
     user_id,
     create_time,
-    update_time
+    update_time,
+    role
   )
-VALUES ($1, $2, $3, $4)
-RETURNING *
\ No newline at end of file
+VALUES ($1, $2, $3, $4, $5)
+RETURNING affiliate_id,
+  user_id,
+  create_time,
+  update_time,
+  role AS "role: _"
\ No newline at end of file
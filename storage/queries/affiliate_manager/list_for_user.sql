@@ -1,3 +1,7 @@
-SELECT *
+SELECT affiliate_id,
+  user_id,
+  create_time,
+  update_time,
+  role AS "role: _"
 FROM affiliate_managers
 WHERE user_id = $1
\ No newline at end of file
@@ -0,0 +1,9 @@
+SELECT affiliate_id,
+  user_id,
+  create_time,
+  update_time,
+  role AS "role: _"
+FROM affiliate_managers
+WHERE affiliate_id = $1
+ORDER BY user_id
+FOR UPDATE
\ No newline at end of file
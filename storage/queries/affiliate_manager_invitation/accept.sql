@@ -0,0 +1,17 @@
+UPDATE affiliate_manager_invitations
+SET accept_time = $3,
+  accepted_user_id = $2,
+  update_time = $3
+WHERE affiliate_manager_invitation_id = $1
+  AND accept_time IS NULL
+RETURNING affiliate_manager_invitation_id,
+  create_time,
+  update_time,
+  affiliate_id,
+  inviter_user_id,
+  email,
+  role AS "role: _",
+  token_hash,
+  expire_time,
+  accept_time,
+  accepted_user_id
\ No newline at end of file
@@ -0,0 +1,14 @@
+SELECT affiliate_manager_invitation_id,
+  create_time,
+  update_time,
+  affiliate_id,
+  inviter_user_id,
+  email,
+  role AS "role: _",
+  token_hash,
+  expire_time,
+  accept_time,
+  accepted_user_id
+FROM affiliate_manager_invitations
+WHERE token_hash = $1
+FOR UPDATE
\ No newline at end of file
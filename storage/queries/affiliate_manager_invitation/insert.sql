@@ -0,0 +1,25 @@
+INSERT INTO affiliate_manager_invitations (
+    affiliate_manager_invitation_id,
+    create_time,
+    update_time,
+    affiliate_id,
+    inviter_user_id,
+    email,
+    role,
+    token_hash,
+    expire_time,
+    accept_time,
+    accepted_user_id
+  )
+VALUES (DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, NULL, NULL)
+RETURNING affiliate_manager_invitation_id,
+  create_time,
+  update_time,
+  affiliate_id,
+  inviter_user_id,
+  email,
+  role AS "role: _",
+  token_hash,
+  expire_time,
+  accept_time,
+  accepted_user_id
\ No newline at end of file
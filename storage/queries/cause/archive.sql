@@ -0,0 +1,11 @@
+UPDATE causes
+SET archive_time = $2,
+  update_time = $2
+WHERE cause_id = $1
+RETURNING cause_id,
+  create_time,
+  update_time,
+  user_id,
+  name,
+  archive_time,
+  state AS "state: _"
\ No newline at end of file
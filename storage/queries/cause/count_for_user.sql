@@ -1,3 +1,4 @@
 SELECT COUNT(*) AS count
 FROM full_causes
-WHERE (cause).user_id = $1
\ No newline at end of file
+WHERE (cause).user_id = $1
+  AND (cause).archive_time IS NULL
\ No newline at end of file
@@ -0,0 +1,4 @@
+SELECT cause AS "cause!: _",
+  cause_recipients AS "cause_recipients!: _"
+FROM full_causes
+WHERE (cause).cause_id = $1
\ No newline at end of file
@@ -3,7 +3,14 @@ INSERT INTO causes (
     create_time,
     update_time,
     user_id,
-    name
+    name,
+    state
   )
-VALUES (DEFAULT, $1, $2, $3, $4)
-RETURNING *
\ No newline at end of file
+VALUES (DEFAULT, $1, $2, $3, $4, $5)
+RETURNING cause_id,
+  create_time,
+  update_time,
+  user_id,
+  name,
+  archive_time,
+  state AS "state: _"
\ No newline at end of file
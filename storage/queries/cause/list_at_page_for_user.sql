@@ -3,6 +3,7 @@ SELECT cause AS "cause!: _",
 FROM full_causes
 WHERE ((cause).create_time, (cause).cause_id) >= ($1, $2)
   AND (cause).user_id = $3
+  AND (cause).archive_time IS NULL
 ORDER BY (cause).create_time ASC,
   (cause).cause_id ASC
 LIMIT $4
\ No newline at end of file
@@ -2,6 +2,7 @@ SELECT cause AS "cause!: _",
   cause_recipients AS "cause_recipients!: _"
 FROM full_causes
 WHERE (cause).user_id = $2
+  AND (cause).archive_time IS NULL
 ORDER BY (cause).create_time ASC,
   (cause).cause_id ASC
 LIMIT $1
\ No newline at end of file
@@ -0,0 +1,10 @@
+SELECT cause_id,
+  create_time,
+  update_time,
+  user_id,
+  name,
+  archive_time,
+  state AS "state: _"
+FROM causes
+WHERE cause_id = $1
+FOR UPDATE
\ No newline at end of file
@@ -0,0 +1,12 @@
+UPDATE causes
+SET name = $2,
+  state = $3,
+  update_time = $4
+WHERE cause_id = $1
+RETURNING cause_id,
+  create_time,
+  update_time,
+  user_id,
+  name,
+  archive_time,
+  state AS "state: _"
\ No newline at end of file
@@ -0,0 +1,2 @@
+DELETE FROM cause_recipients
+WHERE cause_id = $1
\ No newline at end of file
@@ -2,7 +2,8 @@ INSERT INTO cause_recipients (
     cause_id,
     nonprofit_id,
     create_time,
-    update_time
+    update_time,
+    weight_basis_points
   )
-VALUES ($1, $2, $3, $4)
+VALUES ($1, $2, $3, $4, $5)
 RETURNING *
\ No newline at end of file
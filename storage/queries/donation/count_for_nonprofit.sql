@@ -0,0 +1,9 @@
+SELECT COUNT(*) AS "count!"
+FROM donations
+WHERE nonprofit_id = $1
+  OR EXISTS (
+    SELECT 1
+    FROM donation_allocations
+    WHERE donation_allocations.donation_id = donations.donation_id
+      AND donation_allocations.nonprofit_id = $1
+  )
\ No newline at end of file
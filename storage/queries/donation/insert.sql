@@ -0,0 +1,47 @@
+INSERT INTO donations (
+    donation_id,
+    create_time,
+    update_time,
+    nonprofit_id,
+    cause_id,
+    user_id,
+    affiliate_id,
+    currency_code,
+    amount_units,
+    amount_nanos,
+    stripe_charge_id,
+    stripe_transfer_group,
+    status,
+    account_id,
+    settle_time,
+    cover_fees,
+    charge_amount_units,
+    charge_amount_nanos,
+    processing_fee_units,
+    processing_fee_nanos,
+    platform_fee_units,
+    platform_fee_nanos
+  )
+VALUES (DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, NULL, $14, $15, $16, $17, $18, $19, $20)
+RETURNING donation_id,
+  create_time,
+  update_time,
+  nonprofit_id,
+  cause_id,
+  user_id,
+  affiliate_id,
+  currency_code,
+  amount_units,
+  amount_nanos,
+  stripe_charge_id,
+  stripe_transfer_group,
+  status AS "status: _",
+  account_id,
+  settle_time,
+  cover_fees,
+  charge_amount_units,
+  charge_amount_nanos,
+  processing_fee_units,
+  processing_fee_nanos,
+  platform_fee_units,
+  platform_fee_nanos
\ No newline at end of file
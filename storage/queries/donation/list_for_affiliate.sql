@@ -0,0 +1,33 @@
+SELECT donation_id,
+  create_time,
+  update_time,
+  nonprofit_id,
+  cause_id,
+  user_id,
+  affiliate_id,
+  currency_code,
+  amount_units,
+  amount_nanos,
+  stripe_charge_id,
+  stripe_transfer_group,
+  status AS "status: _",
+  account_id,
+  settle_time,
+  cover_fees,
+  charge_amount_units,
+  charge_amount_nanos,
+  processing_fee_units,
+  processing_fee_nanos,
+  platform_fee_units,
+  platform_fee_nanos
+FROM donations
+WHERE affiliate_id = $2
+  OR EXISTS (
+    SELECT 1
+    FROM donation_allocations
+    WHERE donation_allocations.donation_id = donations.donation_id
+      AND donation_allocations.affiliate_id = $2
+  )
+ORDER BY create_time ASC,
+  donation_id ASC
+LIMIT $1
\ No newline at end of file
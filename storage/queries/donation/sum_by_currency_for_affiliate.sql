@@ -0,0 +1,21 @@
+SELECT currency_code AS "currency_code!",
+  SUM(amount_units)::BIGINT AS "amount_units!",
+  SUM(amount_nanos)::BIGINT AS "amount_nanos!"
+FROM (
+    SELECT currency_code,
+      charge_amount_units - processing_fee_units - platform_fee_units AS amount_units,
+      charge_amount_nanos - processing_fee_nanos - platform_fee_nanos AS amount_nanos
+    FROM donations
+    WHERE affiliate_id = $1
+      AND status = 'succeeded'
+    UNION ALL
+    SELECT donations.currency_code,
+      donation_allocations.amount_units,
+      donation_allocations.amount_nanos
+    FROM donation_allocations
+      INNER JOIN donations ON donations.donation_id = donation_allocations.donation_id
+    WHERE donation_allocations.affiliate_id = $1
+      AND donations.status = 'succeeded'
+  ) AS affiliate_donations
+GROUP BY currency_code
+ORDER BY currency_code ASC
\ No newline at end of file
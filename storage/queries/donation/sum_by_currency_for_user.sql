@@ -0,0 +1,8 @@
+SELECT currency_code,
+  SUM(amount_units)::BIGINT AS "amount_units!",
+  SUM(amount_nanos)::BIGINT AS "amount_nanos!"
+FROM donations
+WHERE user_id = $1
+  AND status = 'succeeded'
+GROUP BY currency_code
+ORDER BY currency_code ASC
\ No newline at end of file
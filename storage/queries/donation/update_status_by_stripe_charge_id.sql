@@ -0,0 +1,41 @@
+UPDATE donations
+SET status = $2,
+  settle_time = CASE
+    WHEN $2::donation_status = 'succeeded'
+    AND account_id IS NOT NULL THEN COALESCE(settle_time, $3)
+    ELSE settle_time
+  END,
+  update_time = $3
+WHERE stripe_charge_id = $1
+  AND (
+    (
+      status = 'pending'
+      AND $2::donation_status IN ('succeeded', 'failed')
+    )
+    OR (
+      status = 'succeeded'
+      AND $2::donation_status = 'refunded'
+    )
+  )
+RETURNING donation_id,
+  create_time,
+  update_time,
+  nonprofit_id,
+  cause_id,
+  user_id,
+  affiliate_id,
+  currency_code,
+  amount_units,
+  amount_nanos,
+  stripe_charge_id,
+  stripe_transfer_group,
+  status AS "status: _",
+  account_id,
+  settle_time,
+  cover_fees,
+  charge_amount_units,
+  charge_amount_nanos,
+  processing_fee_units,
+  processing_fee_nanos,
+  platform_fee_units,
+  platform_fee_nanos
\ No newline at end of file
@@ -0,0 +1,23 @@
+INSERT INTO donation_refunds (
+    donation_id,
+    create_time,
+    update_time,
+    amount_units,
+    amount_nanos
+  )
+SELECT donation_id,
+  $2,
+  $2,
+  $3,
+  $4
+FROM donations
+WHERE stripe_charge_id = $1 ON CONFLICT (donation_id) DO
+UPDATE
+SET update_time = EXCLUDED.update_time,
+  amount_units = EXCLUDED.amount_units,
+  amount_nanos = EXCLUDED.amount_nanos
+WHERE (
+    donation_refunds.amount_units,
+    donation_refunds.amount_nanos
+  ) < (EXCLUDED.amount_units, EXCLUDED.amount_nanos)
+RETURNING *
\ No newline at end of file
@@ -0,0 +1,12 @@
+INSERT INTO donation_allocations (
+    donation_id,
+    nonprofit_id,
+    create_time,
+    update_time,
+    affiliate_id,
+    amount_units,
+    amount_nanos,
+    stripe_transfer_id
+  )
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+RETURNING *
\ No newline at end of file
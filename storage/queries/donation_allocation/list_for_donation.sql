@@ -0,0 +1,4 @@
+SELECT *
+FROM donation_allocations
+WHERE donation_id = $1
+ORDER BY nonprofit_id ASC
\ No newline at end of file
@@ -0,0 +1,6 @@
+DELETE FROM idempotency_keys
+WHERE user_id = $1
+  AND idempotency_key = $2
+  AND request_path = $3
+  AND response_body IS NULL
+  AND lock_expire_time IS NOT NULL
\ No newline at end of file
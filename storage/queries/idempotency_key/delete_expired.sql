@@ -0,0 +1,6 @@
+DELETE FROM idempotency_keys
+WHERE update_time < $1
+  AND (
+    lock_expire_time IS NULL
+    OR lock_expire_time < $1
+  )
\ No newline at end of file
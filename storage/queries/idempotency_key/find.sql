@@ -0,0 +1,5 @@
+SELECT *
+FROM idempotency_keys
+WHERE user_id = $1
+  AND idempotency_key = $2
+  AND request_path = $3
\ No newline at end of file
@@ -0,0 +1,18 @@
+INSERT INTO idempotency_keys (
+    user_id,
+    idempotency_key,
+    request_path,
+    request_hash,
+    create_time,
+    update_time,
+    lock_expire_time,
+    response_body
+  )
+VALUES ($1, $2, $3, $4, $5, $6, $7, NULL) ON CONFLICT (user_id, idempotency_key, request_path) DO
+UPDATE
+SET update_time = EXCLUDED.update_time,
+  lock_expire_time = EXCLUDED.lock_expire_time
+WHERE idempotency_keys.response_body IS NULL
+  AND idempotency_keys.request_hash = EXCLUDED.request_hash
+  AND idempotency_keys.lock_expire_time <= EXCLUDED.update_time
+RETURNING *
\ No newline at end of file
@@ -0,0 +1,7 @@
+UPDATE idempotency_keys
+SET update_time = $4,
+  lock_expire_time = NULL
+WHERE user_id = $1
+  AND idempotency_key = $2
+  AND request_path = $3
+  AND response_body IS NULL
\ No newline at end of file
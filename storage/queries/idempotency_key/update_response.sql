@@ -0,0 +1,7 @@
+UPDATE idempotency_keys
+SET response_body = $4,
+  update_time = $5,
+  lock_expire_time = NULL
+WHERE user_id = $1
+  AND idempotency_key = $2
+  AND request_path = $3
\ No newline at end of file
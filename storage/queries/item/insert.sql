@@ -7,4 +7,14 @@ INSERT INTO items (
     plaid_access_token
   )
 VALUES (DEFAULT, $1, $2, $3, $4, $5)
-RETURNING *
\ No newline at end of file
+RETURNING item_id,
+  create_time,
+  update_time,
+  user_id,
+  plaid_item_id,
+  plaid_access_token,
+  status AS "status: _",
+  plaid_error_code,
+  consent_expiration_time,
+  new_accounts_available,
+  transactions_cursor
\ No newline at end of file
@@ -1,4 +1,14 @@
-SELECT *
+SELECT item_id,
+  create_time,
+  update_time,
+  user_id,
+  plaid_item_id,
+  plaid_access_token,
+  status AS "status: _",
+  plaid_error_code,
+  consent_expiration_time,
+  new_accounts_available,
+  transactions_cursor
 FROM items
 WHERE user_id = $2
 ORDER BY create_time ASC,
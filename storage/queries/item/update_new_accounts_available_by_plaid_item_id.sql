@@ -0,0 +1,15 @@
+UPDATE items
+SET new_accounts_available = $2,
+  update_time = $3
+WHERE plaid_item_id = $1
+RETURNING item_id,
+  create_time,
+  update_time,
+  user_id,
+  plaid_item_id,
+  plaid_access_token,
+  status AS "status: _",
+  plaid_error_code,
+  consent_expiration_time,
+  new_accounts_available,
+  transactions_cursor
\ No newline at end of file
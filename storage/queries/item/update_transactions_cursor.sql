@@ -0,0 +1,4 @@
+UPDATE items
+SET transactions_cursor = $2,
+  update_time = $3
+WHERE item_id = $1
\ No newline at end of file
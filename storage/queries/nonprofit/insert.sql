@@ -8,7 +8,8 @@ INSERT INTO nonprofits (
     ein,
     mission,
     category,
-    affiliate_id
+    affiliate_id,
+    email
   )
-VALUES (DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9)
+VALUES (DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
 RETURNING *
\ No newline at end of file
@@ -0,0 +1,23 @@
+SELECT recurring_donation_id,
+  create_time,
+  update_time,
+  user_id,
+  nonprofit_id,
+  cause_id,
+  account_id,
+  currency_code,
+  amount_units,
+  amount_nanos,
+  frequency AS "frequency: _",
+  state AS "state: _",
+  start_time,
+  next_run_time,
+  retry_time,
+  failure_count,
+  last_donation_id
+FROM recurring_donations
+WHERE state = 'active'
+  AND COALESCE(retry_time, next_run_time) <= $1
+ORDER BY COALESCE(retry_time, next_run_time) ASC
+LIMIT 1 FOR
+UPDATE SKIP LOCKED
\ No newline at end of file
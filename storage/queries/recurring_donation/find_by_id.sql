@@ -0,0 +1,19 @@
+SELECT recurring_donation_id,
+  create_time,
+  update_time,
+  user_id,
+  nonprofit_id,
+  cause_id,
+  account_id,
+  currency_code,
+  amount_units,
+  amount_nanos,
+  frequency AS "frequency: _",
+  state AS "state: _",
+  start_time,
+  next_run_time,
+  retry_time,
+  failure_count,
+  last_donation_id
+FROM recurring_donations
+WHERE recurring_donation_id = $1
\ No newline at end of file
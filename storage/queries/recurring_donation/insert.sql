@@ -0,0 +1,37 @@
+INSERT INTO recurring_donations (
+    recurring_donation_id,
+    create_time,
+    update_time,
+    user_id,
+    nonprofit_id,
+    cause_id,
+    account_id,
+    currency_code,
+    amount_units,
+    amount_nanos,
+    frequency,
+    state,
+    start_time,
+    next_run_time,
+    retry_time,
+    failure_count,
+    last_donation_id
+  )
+VALUES (DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, 'active', $11, $11, NULL, 0, NULL)
+RETURNING recurring_donation_id,
+  create_time,
+  update_time,
+  user_id,
+  nonprofit_id,
+  cause_id,
+  account_id,
+  currency_code,
+  amount_units,
+  amount_nanos,
+  frequency AS "frequency: _",
+  state AS "state: _",
+  start_time,
+  next_run_time,
+  retry_time,
+  failure_count,
+  last_donation_id
\ No newline at end of file
@@ -0,0 +1,7 @@
+UPDATE recurring_donations
+SET next_run_time = $2,
+  retry_time = $3,
+  failure_count = $4,
+  last_donation_id = COALESCE($5, last_donation_id),
+  update_time = $6
+WHERE recurring_donation_id = $1
\ No newline at end of file
@@ -0,0 +1,24 @@
+UPDATE recurring_donations
+SET state = $2,
+  next_run_time = $3,
+  retry_time = NULL,
+  failure_count = 0,
+  update_time = $4
+WHERE recurring_donation_id = $1
+RETURNING recurring_donation_id,
+  create_time,
+  update_time,
+  user_id,
+  nonprofit_id,
+  cause_id,
+  account_id,
+  currency_code,
+  amount_units,
+  amount_nanos,
+  frequency AS "frequency: _",
+  state AS "state: _",
+  start_time,
+  next_run_time,
+  retry_time,
+  failure_count,
+  last_donation_id
\ No newline at end of file
@@ -0,0 +1,23 @@
+INSERT INTO round_ups (
+    round_up_id,
+    create_time,
+    update_time,
+    user_id,
+    plaid_transaction_id,
+    plaid_account_id,
+    currency_code,
+    amount_units,
+    amount_nanos,
+    donation_id
+  )
+VALUES (DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, NULL) ON CONFLICT DO NOTHING
+RETURNING round_up_id,
+  create_time,
+  update_time,
+  user_id,
+  plaid_transaction_id,
+  plaid_account_id,
+  currency_code,
+  amount_units,
+  amount_nanos,
+  donation_id
\ No newline at end of file
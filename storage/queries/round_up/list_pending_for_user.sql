@@ -0,0 +1,15 @@
+SELECT round_up_id,
+  create_time,
+  update_time,
+  user_id,
+  plaid_transaction_id,
+  plaid_account_id,
+  currency_code,
+  amount_units,
+  amount_nanos,
+  donation_id
+FROM round_ups
+WHERE user_id = $1
+  AND donation_id IS NULL
+ORDER BY create_time ASC,
+  round_up_id ASC
\ No newline at end of file
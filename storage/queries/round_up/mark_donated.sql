@@ -0,0 +1,5 @@
+UPDATE round_ups
+SET donation_id = $2,
+  update_time = $3
+WHERE round_up_id = ANY($1)
+  AND donation_id IS NULL
\ No newline at end of file
@@ -0,0 +1,3 @@
+SELECT *
+FROM round_up_settings
+WHERE user_id = $1
\ No newline at end of file
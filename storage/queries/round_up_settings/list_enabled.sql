@@ -0,0 +1,4 @@
+SELECT *
+FROM round_up_settings
+WHERE enabled
+ORDER BY user_id ASC
\ No newline at end of file
@@ -0,0 +1,5 @@
+SELECT *
+FROM round_up_settings
+WHERE user_id = $1
+  AND enabled FOR
+UPDATE SKIP LOCKED
\ No newline at end of file
@@ -0,0 +1,20 @@
+INSERT INTO round_up_settings (
+    user_id,
+    create_time,
+    update_time,
+    cause_id,
+    account_id,
+    enabled
+  )
+VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (user_id) DO
+UPDATE
+SET update_time = EXCLUDED.update_time,
+  cause_id = EXCLUDED.cause_id,
+  account_id = EXCLUDED.account_id,
+  enabled = EXCLUDED.enabled
+RETURNING user_id,
+  create_time,
+  update_time,
+  cause_id,
+  account_id,
+  enabled
\ No newline at end of file
@@ -0,0 +1,3 @@
+SELECT *
+FROM stripe_events
+WHERE stripe_event_id = $1
\ No newline at end of file
@@ -0,0 +1,10 @@
+INSERT INTO stripe_events (
+    stripe_event_id,
+    create_time,
+    update_time,
+    event_type,
+    payload,
+    process_time
+  )
+VALUES ($1, $2, $3, $4, $5, NULL) ON CONFLICT DO NOTHING
+RETURNING *
\ No newline at end of file
@@ -0,0 +1,6 @@
+SELECT *
+FROM stripe_events
+WHERE process_time IS NULL
+ORDER BY create_time ASC,
+  stripe_event_id ASC
+LIMIT $1
\ No newline at end of file
@@ -0,0 +1,4 @@
+UPDATE stripe_events
+SET process_time = $2,
+  update_time = $2
+WHERE stripe_event_id = $1
\ No newline at end of file
@@ -24,6 +24,10 @@ pub enum Error {
     #[error(transparent)]
     PageToken(anyhow::Error),
 
+    // Arguments of a store operation were rejected.
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
     // Some other/unexpected error occurred.
     #[error(transparent)]
     Other(#[from] anyhow::Error),
@@ -32,6 +36,9 @@ pub enum Error {
 impl From<Error> for affect_status::Status {
     fn from(error: Error) -> Self {
         // TODO: Maybe map sqlx "duplicate entry" errors to "already exists", etc.
+        if let Error::InvalidArgument(message) = error {
+            return affect_status::invalid_argument!("{0}", message);
+        }
         return affect_status::internal!("storage error: {:?}", error);
     }
 }
@@ -2,6 +2,10 @@ pub mod account;
 pub mod affiliate;
 pub mod cause;
 pub mod donation;
+pub mod idempotency_key;
 pub mod item;
 pub mod nonprofit;
+pub mod recurring_donation;
+pub mod round_up;
+pub mod stripe_event;
 pub mod user;
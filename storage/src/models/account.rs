@@ -50,3 +50,12 @@ pub struct NewAccountRow {
     pub mask: Option<String>,
     pub stripe_bank_account_id: String,
 }
+
+/// Account added together with its item, see `ItemAndAccountStore`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewItemAccountRow {
+    pub plaid_account_id: String,
+    pub name: String,
+    pub mask: Option<String>,
+    pub stripe_bank_account_id: String,
+}
@@ -1,9 +1,10 @@
-use crate::models::nonprofit::NonprofitRow;
-use chrono::{DateTime, Utc};
+use crate::{models::nonprofit::NonprofitRow, page_token::PageTokenable};
+use chrono::{serde::ts_nanoseconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgTypeInfo, FromRow, Postgres};
 use uuid::Uuid;
 
-#[derive(Clone, Debug, PartialEq, FromRow, sqlx::Decode)]
+#[derive(Clone, Debug, PartialEq, FromRow)]
 pub struct AffiliateRow {
     pub affiliate_id: Uuid,
     pub create_time: DateTime<Utc>,
@@ -13,6 +14,18 @@ pub struct AffiliateRow {
     pub contact_email: String,
     pub business_type: BusinessType,
     pub asserted_nonprofit_id: Uuid,
+    pub charges_enabled: bool,
+    pub payouts_enabled: bool,
+    pub country: Option<String>,
+    pub business_name: Option<String>,
+    pub requirements_currently_due: Vec<String>,
+    pub disabled_reason: Option<String>,
+    pub stripe_refresh_time: Option<DateTime<Utc>>,
+    pub ein: Option<String>,
+    pub verification_state: AffiliateVerificationState,
+    pub rejection_reason: Option<String>,
+    pub verification_time: Option<DateTime<Utc>>,
+    pub currencies: Vec<String>,
 }
 
 impl sqlx::Type<Postgres> for AffiliateRow {
@@ -21,6 +34,56 @@ impl sqlx::Type<Postgres> for AffiliateRow {
     }
 }
 
+impl<'a> sqlx::decode::Decode<'a, sqlx::Postgres> for AffiliateRow {
+    fn decode(
+        value: sqlx::postgres::PgValueRef<'a>,
+    ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
+        let mut decoder = sqlx::postgres::types::PgRecordDecoder::new(value)?;
+        let affiliate_id = decoder.try_decode::<Uuid>()?;
+        let create_time = decoder.try_decode::<DateTime<Utc>>()?;
+        let update_time = decoder.try_decode::<DateTime<Utc>>()?;
+        let stripe_account_id = decoder.try_decode::<String>()?;
+        let company_name = decoder.try_decode::<String>()?;
+        let contact_email = decoder.try_decode::<String>()?;
+        let business_type = decoder.try_decode::<BusinessType>()?;
+        let asserted_nonprofit_id = decoder.try_decode::<Uuid>()?;
+        let charges_enabled = decoder.try_decode::<bool>()?;
+        let payouts_enabled = decoder.try_decode::<bool>()?;
+        let country = decoder.try_decode::<Option<String>>()?;
+        let business_name = decoder.try_decode::<Option<String>>()?;
+        let requirements_currently_due = decoder.try_decode::<Vec<String>>()?;
+        let disabled_reason = decoder.try_decode::<Option<String>>()?;
+        let stripe_refresh_time = decoder.try_decode::<Option<DateTime<Utc>>>()?;
+        let ein = decoder.try_decode::<Option<String>>()?;
+        let verification_state = decoder.try_decode::<AffiliateVerificationState>()?;
+        let rejection_reason = decoder.try_decode::<Option<String>>()?;
+        let verification_time = decoder.try_decode::<Option<DateTime<Utc>>>()?;
+        let currencies = decoder.try_decode::<Vec<String>>()?;
+        Ok(AffiliateRow {
+            affiliate_id,
+            create_time,
+            update_time,
+            stripe_account_id,
+            company_name,
+            contact_email,
+            business_type,
+            asserted_nonprofit_id,
+            charges_enabled,
+            payouts_enabled,
+            country,
+            business_name,
+            requirements_currently_due,
+            disabled_reason,
+            stripe_refresh_time,
+            ein,
+            verification_state,
+            rejection_reason,
+            verification_time,
+            currencies,
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, FromRow, sqlx::Decode)]
 pub struct FullAffiliateRow {
     pub affiliate: AffiliateRow,
@@ -34,6 +97,28 @@ impl sqlx::Type<Postgres> for FullAffiliateRow {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct AffiliatePageToken {
+    #[serde(with = "ts_nanoseconds")]
+    pub create_time: DateTime<Utc>,
+    pub affiliate_id: Uuid,
+}
+
+impl PageTokenable<AffiliatePageToken> for AffiliateRow {
+    fn page_token(&self) -> AffiliatePageToken {
+        AffiliatePageToken {
+            create_time: self.create_time.clone(),
+            affiliate_id: self.affiliate_id.clone(),
+        }
+    }
+}
+
+impl PageTokenable<AffiliatePageToken> for FullAffiliateRow {
+    fn page_token(&self) -> AffiliatePageToken {
+        self.affiliate.page_token()
+    }
+}
+
 #[derive(Clone, Debug, FromRow)]
 pub struct NewAffiliateRow {
     pub create_time: DateTime<Utc>,
@@ -43,6 +128,21 @@ pub struct NewAffiliateRow {
     pub contact_email: String,
     pub business_type: BusinessType,
     pub asserted_nonprofit_id: Uuid,
+    pub ein: Option<String>,
+}
+
+/// State of an affiliate's stripe express account, as last reported by stripe.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AffiliateStripeState {
+    pub charges_enabled: bool,
+    pub payouts_enabled: bool,
+    pub country: Option<String>,
+    pub business_name: Option<String>,
+    pub requirements_currently_due: Vec<String>,
+    pub disabled_reason: Option<String>,
+    /// ISO codes of the currencies the account settles without conversion,
+    /// i.e. its default currency and the currencies of its external accounts.
+    pub currencies: Vec<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, FromRow, sqlx::Type)]
@@ -51,6 +151,7 @@ pub struct AffiliateManagerRow {
     pub user_id: Uuid,
     pub create_time: DateTime<Utc>,
     pub update_time: DateTime<Utc>,
+    pub role: AffiliateManagerRole,
 }
 
 #[derive(Clone, Debug, FromRow)]
@@ -59,12 +160,56 @@ pub struct NewAffiliateManagerRow {
     pub user_id: Uuid,
     pub create_time: DateTime<Utc>,
     pub update_time: DateTime<Utc>,
+    pub role: AffiliateManagerRole,
+}
+
+/// Role of an affiliate manager. Owners and admins administer the affiliate,
+/// viewers only see it.
+#[derive(Clone, Copy, Debug, PartialEq, sqlx::Type)]
+#[sqlx(type_name = "affiliate_manager_role", rename_all = "snake_case")]
+pub enum AffiliateManagerRole {
+    Owner,
+    Admin,
+    Viewer,
+}
+
+/// Invitation to become a manager of an affiliate. Only a hash of the
+/// invitation's token is stored.
+#[derive(Clone, Debug, PartialEq, FromRow)]
+pub struct AffiliateManagerInvitationRow {
+    pub affiliate_manager_invitation_id: Uuid,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub affiliate_id: Uuid,
+    pub inviter_user_id: Uuid,
+    pub email: String,
+    pub role: AffiliateManagerRole,
+    pub token_hash: String,
+    pub expire_time: DateTime<Utc>,
+    pub accept_time: Option<DateTime<Utc>>,
+    pub accepted_user_id: Option<Uuid>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewAffiliateManagerInvitationRow {
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub affiliate_id: Uuid,
+    pub inviter_user_id: Uuid,
+    pub email: String,
+    pub role: AffiliateManagerRole,
+    pub token_hash: String,
+    pub expire_time: DateTime<Utc>,
 }
 
 #[derive(Clone, Debug, PartialEq, sqlx::Decode)]
 pub struct AffiliateManagerRowVec(Vec<AffiliateManagerRow>);
 
 impl AffiliateManagerRowVec {
+    pub fn new(affiliate_managers: Vec<AffiliateManagerRow>) -> Self {
+        Self(affiliate_managers)
+    }
+
     pub fn inner(self) -> Vec<AffiliateManagerRow> {
         self.0
     }
@@ -84,3 +229,13 @@ pub enum BusinessType {
     Nonprofit,
     GovernmentEntity,
 }
+
+/// Review state of an affiliate. Approved affiliates are linked to their
+/// asserted nonprofit, which can then receive donations.
+#[derive(Clone, Debug, PartialEq, sqlx::Type)]
+#[sqlx(type_name = "affiliate_verification_state", rename_all = "snake_case")]
+pub enum AffiliateVerificationState {
+    Pending,
+    Approved,
+    Rejected,
+}
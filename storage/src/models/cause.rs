@@ -1,16 +1,22 @@
 use crate::page_token::PageTokenable;
 use chrono::{serde::ts_nanoseconds, DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{postgres::PgTypeInfo, FromRow, Postgres};
+use sqlx::{postgres::PgTypeInfo, FromRow, Postgres, Type};
 use uuid::Uuid;
 
-#[derive(Clone, Debug, FromRow, sqlx::Decode)]
+/// Sum of the weights of a cause's recipients, weights are in basis points
+/// (1/100th of a percent) of the cause's donations.
+pub const TOTAL_WEIGHT_BASIS_POINTS: i32 = 10_000;
+
+#[derive(Clone, Debug, FromRow, PartialEq)]
 pub struct CauseRow {
     pub cause_id: Uuid,
     pub create_time: DateTime<Utc>,
     pub update_time: DateTime<Utc>,
     pub user_id: Uuid,
     pub name: String,
+    pub archive_time: Option<DateTime<Utc>>,
+    pub state: CauseState,
 }
 
 impl sqlx::Type<Postgres> for CauseRow {
@@ -19,7 +25,31 @@ impl sqlx::Type<Postgres> for CauseRow {
     }
 }
 
-#[derive(Clone, Debug, FromRow)]
+impl<'a> sqlx::decode::Decode<'a, sqlx::Postgres> for CauseRow {
+    fn decode(
+        value: sqlx::postgres::PgValueRef<'a>,
+    ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
+        let mut decoder = sqlx::postgres::types::PgRecordDecoder::new(value)?;
+        let cause_id = decoder.try_decode::<Uuid>()?;
+        let create_time = decoder.try_decode::<DateTime<Utc>>()?;
+        let update_time = decoder.try_decode::<DateTime<Utc>>()?;
+        let user_id = decoder.try_decode::<Uuid>()?;
+        let name = decoder.try_decode::<String>()?;
+        let archive_time = decoder.try_decode::<Option<DateTime<Utc>>>()?;
+        let state = decoder.try_decode::<CauseState>()?;
+        Ok(CauseRow {
+            cause_id,
+            create_time,
+            update_time,
+            user_id,
+            name,
+            archive_time,
+            state,
+        })
+    }
+}
+
+#[derive(Clone, Debug, FromRow, PartialEq)]
 pub struct FullCauseRow {
     pub cause: CauseRow,
     pub cause_recipients: CauseRecipientRowVec,
@@ -31,14 +61,16 @@ pub struct NewCauseRow {
     pub update_time: DateTime<Utc>,
     pub user_id: Uuid,
     pub name: String,
+    pub state: CauseState,
 }
 
-#[derive(Clone, Debug, FromRow, sqlx::Decode)]
+#[derive(Clone, Debug, FromRow, PartialEq, sqlx::Decode)]
 pub struct CauseRecipientRow {
     pub cause_id: Uuid,
     pub nonprofit_id: Uuid,
     pub create_time: DateTime<Utc>,
     pub update_time: DateTime<Utc>,
+    pub weight_basis_points: i32,
 }
 
 impl sqlx::Type<Postgres> for CauseRecipientRow {
@@ -53,12 +85,24 @@ pub struct NewCauseRecipientRow {
     pub nonprofit_id: Uuid,
     pub create_time: DateTime<Utc>,
     pub update_time: DateTime<Utc>,
+    pub weight_basis_points: i32,
+}
+
+/// Recipient of a cause which is being created.
+#[derive(Clone, Debug)]
+pub struct NewCauseRecipient {
+    pub nonprofit_id: Uuid,
+    pub weight_basis_points: i32,
 }
 
-#[derive(Clone, Debug, sqlx::Decode)]
+#[derive(Clone, Debug, PartialEq, sqlx::Decode)]
 pub struct CauseRecipientRowVec(Vec<CauseRecipientRow>);
 
 impl CauseRecipientRowVec {
+    pub fn new(rows: Vec<CauseRecipientRow>) -> Self {
+        Self(rows)
+    }
+
     pub fn inner(self) -> Vec<CauseRecipientRow> {
         self.0
     }
@@ -70,6 +114,15 @@ impl sqlx::Type<Postgres> for CauseRecipientRowVec {
     }
 }
 
+/// Draft causes may be saved before their recipients are picked, but do not
+/// accept donations.
+#[derive(Clone, Debug, Type, PartialEq)]
+#[sqlx(type_name = "cause_state", rename_all = "snake_case")]
+pub enum CauseState {
+    Draft,
+    Active,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CausePageToken {
     #[serde(with = "ts_nanoseconds")]
@@ -1,4 +1,6 @@
-use chrono::{DateTime, Utc};
+use crate::page_token::PageTokenable;
+use chrono::{serde::ts_nanoseconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use sqlx::Type;
 use uuid::Uuid;
@@ -8,16 +10,123 @@ pub struct DonationRow {
     pub donation_id: Uuid,
     pub create_time: DateTime<Utc>,
     pub update_time: DateTime<Utc>,
-    pub nonprofit_id: Uuid,
+    pub nonprofit_id: Option<Uuid>,
+    pub cause_id: Option<Uuid>,
     pub user_id: Uuid,
     pub affiliate_id: Option<Uuid>,
-    pub currency_code: CurrencyCode,
+    /// ISO 4217 code of the currency, e.g. "USD".
+    pub currency_code: String,
     pub amount_units: i64,
     pub amount_nanos: i32,
+    pub stripe_charge_id: String,
+    pub stripe_transfer_group: Option<String>,
+    pub status: DonationStatus,
+    /// Linked account the donation was charged from through ACH, none if the
+    /// customer's default source was charged.
+    pub account_id: Option<Uuid>,
+    /// Time the ACH payment of the donation settled.
+    pub settle_time: Option<DateTime<Utc>>,
+    /// Whether the donor chose to cover the fees, adding them to the charge.
+    pub cover_fees: bool,
+    /// Amount charged to the donor.
+    pub charge_amount_units: i64,
+    pub charge_amount_nanos: i32,
+    /// Stripe's estimated processing fee of the charge.
+    pub processing_fee_units: i64,
+    pub processing_fee_nanos: i32,
+    /// Fee kept by the platform.
+    pub platform_fee_units: i64,
+    pub platform_fee_nanos: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewDonationRow {
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub nonprofit_id: Option<Uuid>,
+    pub cause_id: Option<Uuid>,
+    pub user_id: Uuid,
+    pub affiliate_id: Option<Uuid>,
+    pub currency_code: String,
+    pub amount_units: i64,
+    pub amount_nanos: i32,
+    pub stripe_charge_id: String,
+    pub stripe_transfer_group: Option<String>,
+    pub status: DonationStatus,
+    pub account_id: Option<Uuid>,
+    pub cover_fees: bool,
+    pub charge_amount_units: i64,
+    pub charge_amount_nanos: i32,
+    pub processing_fee_units: i64,
+    pub processing_fee_nanos: i32,
+    pub platform_fee_units: i64,
+    pub platform_fee_nanos: i32,
+}
+
+/// Share of a cause donation transferred to one of the cause's recipients.
+#[derive(Clone, Debug, FromRow, PartialEq)]
+pub struct DonationAllocationRow {
+    pub donation_id: Uuid,
+    pub nonprofit_id: Uuid,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub affiliate_id: Uuid,
+    pub amount_units: i64,
+    pub amount_nanos: i32,
+    pub stripe_transfer_id: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewDonationAllocationRow {
+    pub nonprofit_id: Uuid,
+    pub affiliate_id: Uuid,
+    pub amount_units: i64,
+    pub amount_nanos: i32,
+    pub stripe_transfer_id: String,
+}
+
+/// Amount refunded from the charge of a donation so far, in the donation's
+/// currency. Fully refunded donations are also marked refunded, while the
+/// rest of partially refunded donations stays with the recipients.
+#[derive(Clone, Debug, FromRow, PartialEq)]
+pub struct DonationRefundRow {
+    pub donation_id: Uuid,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub amount_units: i64,
+    pub amount_nanos: i32,
+}
+
+/// Sum of the succeeded donations in a currency. Units and nanos are summed
+/// separately, so nanos may exceed a unit.
+#[derive(Clone, Debug, FromRow, PartialEq)]
+pub struct DonationTotalRow {
+    pub currency_code: String,
+    pub amount_units: i64,
+    pub amount_nanos: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DonationPageToken {
+    #[serde(with = "ts_nanoseconds")]
+    pub create_time: DateTime<Utc>,
+    pub donation_id: Uuid,
+}
+
+impl PageTokenable<DonationPageToken> for DonationRow {
+    fn page_token(&self) -> DonationPageToken {
+        DonationPageToken {
+            create_time: self.create_time.clone(),
+            donation_id: self.donation_id.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Type, PartialEq)]
-#[sqlx(type_name = "currency_code", rename_all = "lowercase")]
-pub enum CurrencyCode {
-    USD,
+#[sqlx(type_name = "donation_status", rename_all = "snake_case")]
+pub enum DonationStatus {
+    Pending,
+    Succeeded,
+    Failed,
+    Refunded,
 }
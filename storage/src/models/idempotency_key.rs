@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, FromRow, PartialEq)]
+pub struct IdempotencyKeyRow {
+    pub user_id: Uuid,
+    pub idempotency_key: String,
+    pub request_path: String,
+    /// Hex encoded sha256 of the request body the key was claimed for.
+    pub request_hash: String,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    /// Time after which an in-progress claim is considered abandoned and may
+    /// be taken over, none once the request completed or failed for good.
+    pub lock_expire_time: Option<DateTime<Utc>>,
+    /// Encoded response of the completed request, or none if the request
+    /// is still in progress or failed.
+    pub response_body: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewIdempotencyKeyRow {
+    pub user_id: Uuid,
+    pub idempotency_key: String,
+    pub request_path: String,
+    pub request_hash: String,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub lock_expire_time: DateTime<Utc>,
+}
@@ -1,10 +1,10 @@
 use crate::page_token::PageTokenable;
 use chrono::{serde::ts_nanoseconds, DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, Type};
 use uuid::Uuid;
 
-#[derive(Clone, Debug, FromRow, sqlx::Decode)]
+#[derive(Clone, Debug, FromRow)]
 pub struct ItemRow {
     pub item_id: Uuid,
     pub create_time: DateTime<Utc>,
@@ -12,6 +12,49 @@ pub struct ItemRow {
     pub user_id: Uuid,
     pub plaid_item_id: String,
     pub plaid_access_token: String,
+    pub status: ItemStatus,
+    /// Plaid error code of the item, set while the item is in an error state.
+    pub plaid_error_code: Option<String>,
+    /// Time the user's consent expires, set while the item's consent is
+    /// pending expiration.
+    pub consent_expiration_time: Option<DateTime<Utc>>,
+    /// Whether the institution has accounts which were not linked yet.
+    pub new_accounts_available: bool,
+    /// Cursor of the item's plaid transactions sync, unset until the first
+    /// sync.
+    pub transactions_cursor: Option<String>,
+}
+
+impl<'a> sqlx::decode::Decode<'a, sqlx::Postgres> for ItemRow {
+    fn decode(
+        value: sqlx::postgres::PgValueRef<'a>,
+    ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
+        let mut decoder = sqlx::postgres::types::PgRecordDecoder::new(value)?;
+        let item_id = decoder.try_decode::<Uuid>()?;
+        let create_time = decoder.try_decode::<DateTime<Utc>>()?;
+        let update_time = decoder.try_decode::<DateTime<Utc>>()?;
+        let user_id = decoder.try_decode::<Uuid>()?;
+        let plaid_item_id = decoder.try_decode::<String>()?;
+        let plaid_access_token = decoder.try_decode::<String>()?;
+        let status = decoder.try_decode::<ItemStatus>()?;
+        let plaid_error_code = decoder.try_decode::<Option<String>>()?;
+        let consent_expiration_time = decoder.try_decode::<Option<DateTime<Utc>>>()?;
+        let new_accounts_available = decoder.try_decode::<bool>()?;
+        let transactions_cursor = decoder.try_decode::<Option<String>>()?;
+        Ok(ItemRow {
+            item_id,
+            create_time,
+            update_time,
+            user_id,
+            plaid_item_id,
+            plaid_access_token,
+            status,
+            plaid_error_code,
+            consent_expiration_time,
+            new_accounts_available,
+            transactions_cursor,
+        })
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -39,3 +82,26 @@ impl PageTokenable<ItemPageToken> for ItemRow {
         }
     }
 }
+
+#[derive(Clone, Debug, Type, PartialEq)]
+#[sqlx(type_name = "item_status", rename_all = "snake_case")]
+pub enum ItemStatus {
+    Healthy,
+    LoginRequired,
+    PendingExpiration,
+    PermissionRevoked,
+    Error,
+}
+
+impl ItemStatus {
+    /// Whether the user must go through plaid link again to repair the item.
+    pub fn needs_relink(&self) -> bool {
+        match self {
+            ItemStatus::Healthy => false,
+            ItemStatus::LoginRequired => true,
+            ItemStatus::PendingExpiration => true,
+            ItemStatus::PermissionRevoked => true,
+            ItemStatus::Error => false,
+        }
+    }
+}
@@ -16,6 +16,7 @@ pub struct NonprofitRow {
     pub mission: String,
     pub category: String,
     pub affiliate_id: Option<Uuid>,
+    pub email: Option<String>,
 }
 
 impl sqlx::Type<Postgres> for NonprofitRow {
@@ -39,6 +40,7 @@ impl<'a> sqlx::decode::Decode<'a, sqlx::Postgres> for NonprofitRow {
         let mission = decoder.try_decode::<String>()?;
         let category = decoder.try_decode::<String>()?;
         let affiliate_id = decoder.try_decode::<Option<Uuid>>()?;
+        let email = decoder.try_decode::<Option<String>>()?;
         Ok(NonprofitRow {
             nonprofit_id,
             create_time,
@@ -50,6 +52,7 @@ impl<'a> sqlx::decode::Decode<'a, sqlx::Postgres> for NonprofitRow {
             mission,
             category,
             affiliate_id,
+            email,
         })
     }
 }
@@ -71,6 +74,7 @@ pub struct NewNonprofitRow {
     pub mission: String,
     pub category: String,
     pub affiliate_id: Option<Uuid>,
+    pub email: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
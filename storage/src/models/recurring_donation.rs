@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, Type};
+use uuid::Uuid;
+
+/// Donation which is made on a schedule, to either a nonprofit or a cause.
+#[derive(Clone, Debug, FromRow, PartialEq)]
+pub struct RecurringDonationRow {
+    pub recurring_donation_id: Uuid,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub user_id: Uuid,
+    pub nonprofit_id: Option<Uuid>,
+    pub cause_id: Option<Uuid>,
+    /// Linked bank account donations are charged from, none if the account
+    /// was unlinked.
+    pub account_id: Option<Uuid>,
+    pub currency_code: String,
+    pub amount_units: i64,
+    pub amount_nanos: i32,
+    pub frequency: RecurringDonationFrequency,
+    pub state: RecurringDonationState,
+    /// Time of the first donation, later donations are scheduled relative to it.
+    pub start_time: DateTime<Utc>,
+    /// Time the next donation is scheduled at.
+    pub next_run_time: DateTime<Utc>,
+    /// Time the next donation is retried at after a failed charge.
+    pub retry_time: Option<DateTime<Utc>>,
+    /// Number of consecutive failed charges of the next donation.
+    pub failure_count: i32,
+    pub last_donation_id: Option<Uuid>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewRecurringDonationRow {
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub user_id: Uuid,
+    pub nonprofit_id: Option<Uuid>,
+    pub cause_id: Option<Uuid>,
+    pub account_id: Option<Uuid>,
+    pub currency_code: String,
+    pub amount_units: i64,
+    pub amount_nanos: i32,
+    pub frequency: RecurringDonationFrequency,
+    pub start_time: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Type, PartialEq)]
+#[sqlx(type_name = "recurring_donation_frequency", rename_all = "snake_case")]
+pub enum RecurringDonationFrequency {
+    Weekly,
+    Monthly,
+}
+
+#[derive(Clone, Debug, Type, PartialEq)]
+#[sqlx(type_name = "recurring_donation_state", rename_all = "snake_case")]
+pub enum RecurringDonationState {
+    Active,
+    Paused,
+    /// Canceled schedules are never resumed.
+    Canceled,
+}
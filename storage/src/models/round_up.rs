@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Spare change of a plaid transaction, pending until it's donated.
+#[derive(Clone, Debug, FromRow, PartialEq)]
+pub struct RoundUpRow {
+    pub round_up_id: Uuid,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub user_id: Uuid,
+    pub plaid_transaction_id: String,
+    pub plaid_account_id: String,
+    pub currency_code: String,
+    pub amount_units: i64,
+    pub amount_nanos: i32,
+    /// Donation the round-up was charged with, none while pending.
+    pub donation_id: Option<Uuid>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewRoundUpRow {
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub user_id: Uuid,
+    pub plaid_transaction_id: String,
+    pub plaid_account_id: String,
+    pub currency_code: String,
+    pub amount_units: i64,
+    pub amount_nanos: i32,
+}
+
+/// Where a user's round-ups are charged from and donated to.
+#[derive(Clone, Debug, FromRow, PartialEq)]
+pub struct RoundUpSettingsRow {
+    pub user_id: Uuid,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub cause_id: Uuid,
+    /// Linked bank account round-ups are charged from, none if the account
+    /// was unlinked.
+    pub account_id: Option<Uuid>,
+    pub enabled: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewRoundUpSettingsRow {
+    pub user_id: Uuid,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub cause_id: Uuid,
+    pub account_id: Option<Uuid>,
+    pub enabled: bool,
+}
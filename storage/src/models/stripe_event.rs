@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+#[derive(Clone, Debug, FromRow, PartialEq)]
+pub struct StripeEventRow {
+    pub stripe_event_id: String,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub event_type: String,
+    /// Raw json body of the event as delivered by stripe.
+    pub payload: String,
+    /// Time the event was processed, or none if it still needs processing.
+    pub process_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewStripeEventRow {
+    pub stripe_event_id: String,
+    pub create_time: DateTime<Utc>,
+    pub update_time: DateTime<Utc>,
+    pub event_type: String,
+    pub payload: String,
+}
@@ -1,7 +1,12 @@
 pub mod account;
 pub mod affiliate;
 pub mod cause;
+pub mod donation;
+pub mod idempotency_key;
 pub mod item;
 pub mod item_and_account;
 pub mod nonprofit;
+pub mod recurring_donation;
+pub mod round_up;
+pub mod stripe_event;
 pub mod user;
@@ -4,6 +4,7 @@ use crate::{
     Error,
 };
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::PgExecutor;
 use uuid::Uuid;
 
@@ -12,9 +13,28 @@ pub trait AccountStore: Sync + Send {
     /// Adds an account.
     async fn add_account(&self, new_row: NewAccountRow) -> Result<AccountRow, Error>;
 
+    /// Finds an account by id.
+    async fn find_account_by_id(&self, account_id: Uuid) -> Result<Option<AccountRow>, Error>;
+
+    /// Finds an account by id, if it belongs to an item of the user.
+    async fn find_account_by_id_for_user(
+        &self,
+        account_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<AccountRow>, Error>;
+
     /// Lists all accounts for the provided item.
     async fn list_accounts_for_item(&self, item_id: Uuid) -> Result<Vec<AccountRow>, Error>;
 
+    /// Updates the name and mask of an account.
+    async fn update_account(
+        &self,
+        account_id: Uuid,
+        name: String,
+        mask: Option<String>,
+        update_time: DateTime<Utc>,
+    ) -> Result<AccountRow, Error>;
+
     /// Deletes an account.
     async fn delete_account(&self, account_id: Uuid) -> Result<(), Error>;
 }
@@ -25,10 +45,32 @@ impl AccountStore for PgOnDemandStore {
         Ok(add_account(&*self.pool, new_row).await?)
     }
 
+    async fn find_account_by_id(&self, account_id: Uuid) -> Result<Option<AccountRow>, Error> {
+        Ok(find_account_by_id(&*self.pool, account_id).await?)
+    }
+
+    async fn find_account_by_id_for_user(
+        &self,
+        account_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<AccountRow>, Error> {
+        Ok(find_account_by_id_for_user(&*self.pool, account_id, user_id).await?)
+    }
+
     async fn list_accounts_for_item(&self, item_id: Uuid) -> Result<Vec<AccountRow>, Error> {
         Ok(list_accounts_for_item(&*self.pool, item_id).await?)
     }
 
+    async fn update_account(
+        &self,
+        account_id: Uuid,
+        name: String,
+        mask: Option<String>,
+        update_time: DateTime<Utc>,
+    ) -> Result<AccountRow, Error> {
+        Ok(update_account(&*self.pool, account_id, name, mask, update_time).await?)
+    }
+
     async fn delete_account(&self, account_id: Uuid) -> Result<(), Error> {
         Ok(delete_account(&*self.pool, account_id).await?)
     }
@@ -41,11 +83,36 @@ impl<'a> AccountStore for PgTransactionalStore<'a> {
         Ok(add_account(&mut *lock, new_row).await?)
     }
 
+    async fn find_account_by_id(&self, account_id: Uuid) -> Result<Option<AccountRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(find_account_by_id(&mut *lock, account_id).await?)
+    }
+
+    async fn find_account_by_id_for_user(
+        &self,
+        account_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<AccountRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(find_account_by_id_for_user(&mut *lock, account_id, user_id).await?)
+    }
+
     async fn list_accounts_for_item(&self, item_id: Uuid) -> Result<Vec<AccountRow>, Error> {
         let mut lock = self.txn.lock().await;
         Ok(list_accounts_for_item(&mut *lock, item_id).await?)
     }
 
+    async fn update_account(
+        &self,
+        account_id: Uuid,
+        name: String,
+        mask: Option<String>,
+        update_time: DateTime<Utc>,
+    ) -> Result<AccountRow, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_account(&mut *lock, account_id, name, mask, update_time).await?)
+    }
+
     async fn delete_account(&self, account_id: Uuid) -> Result<(), Error> {
         let mut lock = self.txn.lock().await;
         Ok(delete_account(&mut *lock, account_id).await?)
@@ -71,6 +138,38 @@ where
     .await?)
 }
 
+async fn find_account_by_id<'a, E>(
+    executor: E,
+    account_id: Uuid,
+) -> Result<Option<AccountRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(
+        sqlx::query_file_as!(AccountRow, "queries/account/find_by_id.sql", account_id)
+            .fetch_optional(executor)
+            .await?,
+    )
+}
+
+async fn find_account_by_id_for_user<'a, E>(
+    executor: E,
+    account_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<AccountRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        AccountRow,
+        "queries/account/find_by_id_for_user.sql",
+        account_id,
+        user_id,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
 async fn list_accounts_for_item<'a, E>(executor: E, item_id: Uuid) -> Result<Vec<AccountRow>, Error>
 where
     E: PgExecutor<'a>,
@@ -82,6 +181,28 @@ where
     )
 }
 
+async fn update_account<'a, E>(
+    executor: E,
+    account_id: Uuid,
+    name: String,
+    mask: Option<String>,
+    update_time: DateTime<Utc>,
+) -> Result<AccountRow, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        AccountRow,
+        "queries/account/update.sql",
+        account_id,
+        name,
+        mask,
+        update_time,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
 async fn delete_account<'a, E>(executor: E, account_id: Uuid) -> Result<(), Error>
 where
     E: PgExecutor<'a>,
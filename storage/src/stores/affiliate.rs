@@ -2,6 +2,7 @@ use crate::{
     models::affiliate::*, sqlx::store::PgOnDemandStore, sqlx::store::PgTransactionalStore, Error,
 };
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::PgExecutor;
 use uuid::Uuid;
 
@@ -14,6 +15,45 @@ pub trait AffiliateStore: Sync + Send {
         affiliate_id: Uuid,
     ) -> Result<Option<FullAffiliateRow>, Error>;
 
+    async fn list_affiliates(
+        &self,
+        page_size: i64,
+        page_token: Option<AffiliatePageToken>,
+    ) -> Result<Vec<FullAffiliateRow>, Error>;
+
+    async fn count_affiliates(&self) -> Result<i64, Error>;
+
+    async fn list_and_count_affiliates(
+        &self,
+        page_size: i64,
+        page_token: Option<AffiliatePageToken>,
+    ) -> Result<(Vec<FullAffiliateRow>, i64), Error> {
+        let list_fut = self.list_affiliates(page_size, page_token);
+        let count_fut = self.count_affiliates();
+        futures::try_join!(list_fut, count_fut)
+    }
+
+    /// Lists the affiliates the user manages.
+    async fn list_affiliates_for_user(
+        &self,
+        page_size: i64,
+        page_token: Option<AffiliatePageToken>,
+        user_id: Uuid,
+    ) -> Result<Vec<FullAffiliateRow>, Error>;
+
+    async fn count_affiliates_for_user(&self, user_id: Uuid) -> Result<i64, Error>;
+
+    async fn list_and_count_affiliates_for_user(
+        &self,
+        page_size: i64,
+        page_token: Option<AffiliatePageToken>,
+        user_id: Uuid,
+    ) -> Result<(Vec<FullAffiliateRow>, i64), Error> {
+        let list_fut = self.list_affiliates_for_user(page_size, page_token, user_id);
+        let count_fut = self.count_affiliates_for_user(user_id);
+        futures::try_join!(list_fut, count_fut)
+    }
+
     async fn add_affiliate_manager(
         &self,
         new_row: NewAffiliateManagerRow,
@@ -28,6 +68,92 @@ pub trait AffiliateStore: Sync + Send {
         &self,
         user_id: Uuid,
     ) -> Result<Vec<AffiliateManagerRow>, Error>;
+
+    /// Lists the managers of the affiliate, locking them until the end of the
+    /// transaction.
+    async fn lock_affiliate_managers_for_affiliate(
+        &self,
+        affiliate_id: Uuid,
+    ) -> Result<Vec<AffiliateManagerRow>, Error>;
+
+    /// Deletes a manager of the affiliate. Returns false if the user is not a
+    /// manager of the affiliate.
+    async fn delete_affiliate_manager(
+        &self,
+        affiliate_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, Error>;
+
+    async fn add_affiliate_manager_invitation(
+        &self,
+        new_row: NewAffiliateManagerInvitationRow,
+    ) -> Result<AffiliateManagerInvitationRow, Error>;
+
+    /// Finds an invitation by the hash of its token, locking it until the end
+    /// of the transaction.
+    async fn find_affiliate_manager_invitation_by_token_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<AffiliateManagerInvitationRow>, Error>;
+
+    /// Marks an invitation accepted by the user. Returns none if the invitation
+    /// doesn't exist or was already accepted.
+    async fn accept_affiliate_manager_invitation(
+        &self,
+        affiliate_manager_invitation_id: Uuid,
+        user_id: Uuid,
+        accept_time: DateTime<Utc>,
+    ) -> Result<Option<AffiliateManagerInvitationRow>, Error>;
+
+    /// Updates the stripe account state of the affiliate owning the stripe
+    /// account, marking it refreshed at the refresh time, i.e. the time the
+    /// state was retrieved from stripe. States older than the affiliate's last
+    /// refresh are ignored, so events delivered out of order can't revert it.
+    /// Returns false if the state was ignored or no affiliate owns the account.
+    async fn update_affiliate_stripe_state(
+        &self,
+        stripe_account_id: &str,
+        state: AffiliateStripeState,
+        refresh_time: DateTime<Utc>,
+        update_time: DateTime<Utc>,
+    ) -> Result<bool, Error>;
+
+    /// Marks the affiliate owning the stripe account refreshed at the refresh
+    /// time without changing its state, e.g. after a failed refresh so that it
+    /// doesn't hold up other affiliates.
+    async fn update_affiliate_stripe_refresh_time(
+        &self,
+        stripe_account_id: &str,
+        refresh_time: DateTime<Utc>,
+    ) -> Result<(), Error>;
+
+    /// Moves a pending affiliate to the provided verification state. Returns
+    /// none if the affiliate doesn't exist or is not pending.
+    async fn update_affiliate_verification(
+        &self,
+        affiliate_id: Uuid,
+        verification_state: AffiliateVerificationState,
+        rejection_reason: Option<String>,
+        verification_time: DateTime<Utc>,
+    ) -> Result<Option<AffiliateRow>, Error>;
+
+    /// Sets the affiliate of a nonprofit which has none. Returns false if the
+    /// nonprofit doesn't exist or already has an affiliate.
+    async fn link_affiliate_to_nonprofit(
+        &self,
+        nonprofit_id: Uuid,
+        affiliate_id: Uuid,
+        update_time: DateTime<Utc>,
+    ) -> Result<bool, Error>;
+
+    /// Lists affiliates whose stripe account state was not refreshed, or
+    /// attempted to be, since `refreshed_before`, least recently refreshed
+    /// first.
+    async fn list_affiliates_for_stripe_refresh(
+        &self,
+        refreshed_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<AffiliateRow>, Error>;
 }
 
 #[async_trait]
@@ -43,6 +169,31 @@ impl AffiliateStore for PgOnDemandStore {
         Ok(find_affiliate_by_id(&*self.pool, affiliate_id).await?)
     }
 
+    async fn list_affiliates(
+        &self,
+        page_size: i64,
+        page_token: Option<AffiliatePageToken>,
+    ) -> Result<Vec<FullAffiliateRow>, Error> {
+        Ok(list_affiliates(&*self.pool, page_size, page_token).await?)
+    }
+
+    async fn count_affiliates(&self) -> Result<i64, Error> {
+        Ok(count_affiliates(&*self.pool).await?)
+    }
+
+    async fn list_affiliates_for_user(
+        &self,
+        page_size: i64,
+        page_token: Option<AffiliatePageToken>,
+        user_id: Uuid,
+    ) -> Result<Vec<FullAffiliateRow>, Error> {
+        Ok(list_affiliates_for_user(&*self.pool, page_size, page_token, user_id).await?)
+    }
+
+    async fn count_affiliates_for_user(&self, user_id: Uuid) -> Result<i64, Error> {
+        Ok(count_affiliates_for_user(&*self.pool, user_id).await?)
+    }
+
     async fn add_affiliate_manager(
         &self,
         new_row: NewAffiliateManagerRow,
@@ -63,6 +214,115 @@ impl AffiliateStore for PgOnDemandStore {
     ) -> Result<Vec<AffiliateManagerRow>, Error> {
         Ok(list_affiliate_managers_for_user(&*self.pool, user_id).await?)
     }
+
+    async fn lock_affiliate_managers_for_affiliate(
+        &self,
+        affiliate_id: Uuid,
+    ) -> Result<Vec<AffiliateManagerRow>, Error> {
+        Ok(lock_affiliate_managers_for_affiliate(&*self.pool, affiliate_id).await?)
+    }
+
+    async fn delete_affiliate_manager(
+        &self,
+        affiliate_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, Error> {
+        Ok(delete_affiliate_manager(&*self.pool, affiliate_id, user_id).await?)
+    }
+
+    async fn add_affiliate_manager_invitation(
+        &self,
+        new_row: NewAffiliateManagerInvitationRow,
+    ) -> Result<AffiliateManagerInvitationRow, Error> {
+        Ok(add_affiliate_manager_invitation(&*self.pool, new_row).await?)
+    }
+
+    async fn find_affiliate_manager_invitation_by_token_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<AffiliateManagerInvitationRow>, Error> {
+        Ok(find_affiliate_manager_invitation_by_token_hash(&*self.pool, token_hash).await?)
+    }
+
+    async fn accept_affiliate_manager_invitation(
+        &self,
+        affiliate_manager_invitation_id: Uuid,
+        user_id: Uuid,
+        accept_time: DateTime<Utc>,
+    ) -> Result<Option<AffiliateManagerInvitationRow>, Error> {
+        Ok(accept_affiliate_manager_invitation(
+            &*self.pool,
+            affiliate_manager_invitation_id,
+            user_id,
+            accept_time,
+        )
+        .await?)
+    }
+
+    async fn update_affiliate_stripe_state(
+        &self,
+        stripe_account_id: &str,
+        state: AffiliateStripeState,
+        refresh_time: DateTime<Utc>,
+        update_time: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        Ok(update_affiliate_stripe_state(
+            &*self.pool,
+            stripe_account_id,
+            state,
+            refresh_time,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn update_affiliate_stripe_refresh_time(
+        &self,
+        stripe_account_id: &str,
+        refresh_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        Ok(
+            update_affiliate_stripe_refresh_time(&*self.pool, stripe_account_id, refresh_time)
+                .await?,
+        )
+    }
+
+    async fn update_affiliate_verification(
+        &self,
+        affiliate_id: Uuid,
+        verification_state: AffiliateVerificationState,
+        rejection_reason: Option<String>,
+        verification_time: DateTime<Utc>,
+    ) -> Result<Option<AffiliateRow>, Error> {
+        Ok(update_affiliate_verification(
+            &*self.pool,
+            affiliate_id,
+            verification_state,
+            rejection_reason,
+            verification_time,
+        )
+        .await?)
+    }
+
+    async fn link_affiliate_to_nonprofit(
+        &self,
+        nonprofit_id: Uuid,
+        affiliate_id: Uuid,
+        update_time: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        Ok(
+            link_affiliate_to_nonprofit(&*self.pool, nonprofit_id, affiliate_id, update_time)
+                .await?,
+        )
+    }
+
+    async fn list_affiliates_for_stripe_refresh(
+        &self,
+        refreshed_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<AffiliateRow>, Error> {
+        Ok(list_affiliates_for_stripe_refresh(&*self.pool, refreshed_before, limit).await?)
+    }
 }
 
 #[async_trait]
@@ -80,6 +340,35 @@ impl<'a> AffiliateStore for PgTransactionalStore<'a> {
         Ok(find_affiliate_by_id(&mut *lock, affiliate_id).await?)
     }
 
+    async fn list_affiliates(
+        &self,
+        page_size: i64,
+        page_token: Option<AffiliatePageToken>,
+    ) -> Result<Vec<FullAffiliateRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(list_affiliates(&mut *lock, page_size, page_token).await?)
+    }
+
+    async fn count_affiliates(&self) -> Result<i64, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(count_affiliates(&mut *lock).await?)
+    }
+
+    async fn list_affiliates_for_user(
+        &self,
+        page_size: i64,
+        page_token: Option<AffiliatePageToken>,
+        user_id: Uuid,
+    ) -> Result<Vec<FullAffiliateRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(list_affiliates_for_user(&mut *lock, page_size, page_token, user_id).await?)
+    }
+
+    async fn count_affiliates_for_user(&self, user_id: Uuid) -> Result<i64, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(count_affiliates_for_user(&mut *lock, user_id).await?)
+    }
+
     async fn add_affiliate_manager(
         &self,
         new_row: NewAffiliateManagerRow,
@@ -103,6 +392,125 @@ impl<'a> AffiliateStore for PgTransactionalStore<'a> {
         let mut lock = self.txn.lock().await;
         Ok(list_affiliate_managers_for_user(&mut *lock, user_id).await?)
     }
+
+    async fn lock_affiliate_managers_for_affiliate(
+        &self,
+        affiliate_id: Uuid,
+    ) -> Result<Vec<AffiliateManagerRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(lock_affiliate_managers_for_affiliate(&mut *lock, affiliate_id).await?)
+    }
+
+    async fn delete_affiliate_manager(
+        &self,
+        affiliate_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(delete_affiliate_manager(&mut *lock, affiliate_id, user_id).await?)
+    }
+
+    async fn add_affiliate_manager_invitation(
+        &self,
+        new_row: NewAffiliateManagerInvitationRow,
+    ) -> Result<AffiliateManagerInvitationRow, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(add_affiliate_manager_invitation(&mut *lock, new_row).await?)
+    }
+
+    async fn find_affiliate_manager_invitation_by_token_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<AffiliateManagerInvitationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(find_affiliate_manager_invitation_by_token_hash(&mut *lock, token_hash).await?)
+    }
+
+    async fn accept_affiliate_manager_invitation(
+        &self,
+        affiliate_manager_invitation_id: Uuid,
+        user_id: Uuid,
+        accept_time: DateTime<Utc>,
+    ) -> Result<Option<AffiliateManagerInvitationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(accept_affiliate_manager_invitation(
+            &mut *lock,
+            affiliate_manager_invitation_id,
+            user_id,
+            accept_time,
+        )
+        .await?)
+    }
+
+    async fn update_affiliate_stripe_state(
+        &self,
+        stripe_account_id: &str,
+        state: AffiliateStripeState,
+        refresh_time: DateTime<Utc>,
+        update_time: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_affiliate_stripe_state(
+            &mut *lock,
+            stripe_account_id,
+            state,
+            refresh_time,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn update_affiliate_stripe_refresh_time(
+        &self,
+        stripe_account_id: &str,
+        refresh_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(
+            update_affiliate_stripe_refresh_time(&mut *lock, stripe_account_id, refresh_time)
+                .await?,
+        )
+    }
+
+    async fn update_affiliate_verification(
+        &self,
+        affiliate_id: Uuid,
+        verification_state: AffiliateVerificationState,
+        rejection_reason: Option<String>,
+        verification_time: DateTime<Utc>,
+    ) -> Result<Option<AffiliateRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_affiliate_verification(
+            &mut *lock,
+            affiliate_id,
+            verification_state,
+            rejection_reason,
+            verification_time,
+        )
+        .await?)
+    }
+
+    async fn link_affiliate_to_nonprofit(
+        &self,
+        nonprofit_id: Uuid,
+        affiliate_id: Uuid,
+        update_time: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(
+            link_affiliate_to_nonprofit(&mut *lock, nonprofit_id, affiliate_id, update_time)
+                .await?,
+        )
+    }
+
+    async fn list_affiliates_for_stripe_refresh(
+        &self,
+        refreshed_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<AffiliateRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(list_affiliates_for_stripe_refresh(&mut *lock, refreshed_before, limit).await?)
+    }
 }
 
 async fn add_affiliate<'a, E>(executor: E, new_row: NewAffiliateRow) -> Result<AffiliateRow, Error>
@@ -119,6 +527,7 @@ where
         new_row.contact_email,
         new_row.business_type as BusinessType,
         new_row.asserted_nonprofit_id,
+        new_row.ein,
     )
     .fetch_one(executor)
     .await?)
@@ -140,6 +549,97 @@ where
     .await?)
 }
 
+async fn list_affiliates<'a, E>(
+    executor: E,
+    page_size: i64,
+    page_token: Option<AffiliatePageToken>,
+) -> Result<Vec<FullAffiliateRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let rows = match page_token {
+        Some(page_token) => {
+            // Query by page token:
+            sqlx::query_file_as!(
+                FullAffiliateRow,
+                "queries/affiliate/list_at_page.sql",
+                page_token.create_time,
+                page_token.affiliate_id,
+                page_size,
+            )
+            .fetch_all(executor)
+            .await?
+        }
+        None => {
+            // Query first page:
+            sqlx::query_file_as!(FullAffiliateRow, "queries/affiliate/list.sql", page_size)
+                .fetch_all(executor)
+                .await?
+        }
+    };
+    Ok(rows)
+}
+
+async fn count_affiliates<'a, E>(executor: E) -> Result<i64, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file!("queries/affiliate/count.sql")
+        .fetch_one(executor)
+        .await?
+        .count)
+}
+
+async fn list_affiliates_for_user<'a, E>(
+    executor: E,
+    page_size: i64,
+    page_token: Option<AffiliatePageToken>,
+    user_id: Uuid,
+) -> Result<Vec<FullAffiliateRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let rows = match page_token {
+        Some(page_token) => {
+            // Query by page token:
+            sqlx::query_file_as!(
+                FullAffiliateRow,
+                "queries/affiliate/list_at_page_for_user.sql",
+                page_token.create_time,
+                page_token.affiliate_id,
+                user_id,
+                page_size,
+            )
+            .fetch_all(executor)
+            .await?
+        }
+        None => {
+            // Query first page:
+            sqlx::query_file_as!(
+                FullAffiliateRow,
+                "queries/affiliate/list_for_user.sql",
+                page_size,
+                user_id
+            )
+            .fetch_all(executor)
+            .await?
+        }
+    };
+    Ok(rows)
+}
+
+async fn count_affiliates_for_user<'a, E>(executor: E, user_id: Uuid) -> Result<i64, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(
+        sqlx::query_file!("queries/affiliate/count_for_user.sql", user_id)
+            .fetch_one(executor)
+            .await?
+            .count,
+    )
+}
+
 async fn add_affiliate_manager<'a, E>(
     executor: E,
     new_row: NewAffiliateManagerRow,
@@ -154,6 +654,7 @@ where
         new_row.user_id,
         new_row.create_time,
         new_row.update_time,
+        new_row.role as AffiliateManagerRole,
     )
     .fetch_one(executor)
     .await?)
@@ -190,3 +691,202 @@ where
     .fetch_all(executor)
     .await?)
 }
+
+async fn lock_affiliate_managers_for_affiliate<'a, E>(
+    executor: E,
+    affiliate_id: Uuid,
+) -> Result<Vec<AffiliateManagerRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        AffiliateManagerRow,
+        "queries/affiliate_manager/lock_for_affiliate.sql",
+        affiliate_id
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+async fn delete_affiliate_manager<'a, E>(
+    executor: E,
+    affiliate_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let row = sqlx::query_file!(
+        "queries/affiliate_manager/delete.sql",
+        affiliate_id,
+        user_id
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(row.is_some())
+}
+
+async fn add_affiliate_manager_invitation<'a, E>(
+    executor: E,
+    new_row: NewAffiliateManagerInvitationRow,
+) -> Result<AffiliateManagerInvitationRow, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        AffiliateManagerInvitationRow,
+        "queries/affiliate_manager_invitation/insert.sql",
+        new_row.create_time,
+        new_row.update_time,
+        new_row.affiliate_id,
+        new_row.inviter_user_id,
+        new_row.email,
+        new_row.role as AffiliateManagerRole,
+        new_row.token_hash,
+        new_row.expire_time,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+async fn find_affiliate_manager_invitation_by_token_hash<'a, E>(
+    executor: E,
+    token_hash: &str,
+) -> Result<Option<AffiliateManagerInvitationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        AffiliateManagerInvitationRow,
+        "queries/affiliate_manager_invitation/find_by_token_hash.sql",
+        token_hash
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn accept_affiliate_manager_invitation<'a, E>(
+    executor: E,
+    affiliate_manager_invitation_id: Uuid,
+    user_id: Uuid,
+    accept_time: DateTime<Utc>,
+) -> Result<Option<AffiliateManagerInvitationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        AffiliateManagerInvitationRow,
+        "queries/affiliate_manager_invitation/accept.sql",
+        affiliate_manager_invitation_id,
+        user_id,
+        accept_time,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn update_affiliate_stripe_state<'a, E>(
+    executor: E,
+    stripe_account_id: &str,
+    state: AffiliateStripeState,
+    refresh_time: DateTime<Utc>,
+    update_time: DateTime<Utc>,
+) -> Result<bool, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let result = sqlx::query_file!(
+        "queries/affiliate/update_stripe_state.sql",
+        stripe_account_id,
+        state.charges_enabled,
+        state.payouts_enabled,
+        state.country,
+        state.business_name,
+        &state.requirements_currently_due,
+        state.disabled_reason,
+        &state.currencies,
+        refresh_time,
+        update_time,
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+async fn update_affiliate_stripe_refresh_time<'a, E>(
+    executor: E,
+    stripe_account_id: &str,
+    refresh_time: DateTime<Utc>,
+) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_file!(
+        "queries/affiliate/update_stripe_refresh_time.sql",
+        stripe_account_id,
+        refresh_time,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+async fn update_affiliate_verification<'a, E>(
+    executor: E,
+    affiliate_id: Uuid,
+    verification_state: AffiliateVerificationState,
+    rejection_reason: Option<String>,
+    verification_time: DateTime<Utc>,
+) -> Result<Option<AffiliateRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        AffiliateRow,
+        "queries/affiliate/update_verification.sql",
+        affiliate_id,
+        verification_state as AffiliateVerificationState,
+        rejection_reason,
+        verification_time,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn link_affiliate_to_nonprofit<'a, E>(
+    executor: E,
+    nonprofit_id: Uuid,
+    affiliate_id: Uuid,
+    update_time: DateTime<Utc>,
+) -> Result<bool, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let row = sqlx::query_file!(
+        "queries/affiliate/link_nonprofit.sql",
+        nonprofit_id,
+        affiliate_id,
+        update_time,
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(row.is_some())
+}
+
+async fn list_affiliates_for_stripe_refresh<'a, E>(
+    executor: E,
+    refreshed_before: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<AffiliateRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        AffiliateRow,
+        "queries/affiliate/list_for_stripe_refresh.sql",
+        refreshed_before,
+        limit
+    )
+    .fetch_all(executor)
+    .await?)
+}
@@ -5,14 +5,36 @@ use crate::{
     Error,
 };
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use sqlx::PgExecutor;
+use std::collections::HashSet;
 use uuid::Uuid;
 
 #[async_trait]
 pub trait CauseStore: Send + Sync {
     async fn add_cause(&self, new_row: NewCauseRow) -> Result<CauseRow, Error>;
 
+    async fn find_cause_by_id(&self, cause_id: Uuid) -> Result<Option<FullCauseRow>, Error>;
+
+    /// Finds the cause, locking it until the end of the transaction.
+    async fn lock_cause_by_id(&self, cause_id: Uuid) -> Result<Option<CauseRow>, Error>;
+
+    async fn update_cause(
+        &self,
+        cause_id: Uuid,
+        name: String,
+        state: CauseState,
+        update_time: DateTime<Utc>,
+    ) -> Result<CauseRow, Error>;
+
+    /// Archives a cause. Archived causes are not listed and do not accept
+    /// donations, but remain referenced by past donations.
+    async fn archive_cause(
+        &self,
+        cause_id: Uuid,
+        archive_time: DateTime<Utc>,
+    ) -> Result<CauseRow, Error>;
+
     async fn list_causes_for_user(
         &self,
         page_size: i64,
@@ -42,6 +64,8 @@ pub trait CauseStore: Send + Sync {
         &self,
         cause_id: Uuid,
     ) -> Result<Vec<CauseRecipientRow>, Error>;
+
+    async fn delete_cause_recipients_for_cause(&self, cause_id: Uuid) -> Result<(), Error>;
 }
 
 #[async_trait]
@@ -50,6 +74,32 @@ impl CauseStore for PgOnDemandStore {
         Ok(add_cause(&*self.pool, new_row).await?)
     }
 
+    async fn find_cause_by_id(&self, cause_id: Uuid) -> Result<Option<FullCauseRow>, Error> {
+        Ok(find_cause_by_id(&*self.pool, cause_id).await?)
+    }
+
+    async fn lock_cause_by_id(&self, cause_id: Uuid) -> Result<Option<CauseRow>, Error> {
+        Ok(lock_cause_by_id(&*self.pool, cause_id).await?)
+    }
+
+    async fn update_cause(
+        &self,
+        cause_id: Uuid,
+        name: String,
+        state: CauseState,
+        update_time: DateTime<Utc>,
+    ) -> Result<CauseRow, Error> {
+        Ok(update_cause(&*self.pool, cause_id, name, state, update_time).await?)
+    }
+
+    async fn archive_cause(
+        &self,
+        cause_id: Uuid,
+        archive_time: DateTime<Utc>,
+    ) -> Result<CauseRow, Error> {
+        Ok(archive_cause(&*self.pool, cause_id, archive_time).await?)
+    }
+
     async fn list_causes_for_user(
         &self,
         page_size: i64,
@@ -76,6 +126,10 @@ impl CauseStore for PgOnDemandStore {
     ) -> Result<Vec<CauseRecipientRow>, Error> {
         Ok(list_cause_recipients_for_cause(&*self.pool, cause_id).await?)
     }
+
+    async fn delete_cause_recipients_for_cause(&self, cause_id: Uuid) -> Result<(), Error> {
+        Ok(delete_cause_recipients_for_cause(&*self.pool, cause_id).await?)
+    }
 }
 
 #[async_trait]
@@ -85,6 +139,36 @@ impl<'a> CauseStore for PgTransactionalStore<'a> {
         Ok(add_cause(&mut *lock, new_row).await?)
     }
 
+    async fn find_cause_by_id(&self, cause_id: Uuid) -> Result<Option<FullCauseRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(find_cause_by_id(&mut *lock, cause_id).await?)
+    }
+
+    async fn lock_cause_by_id(&self, cause_id: Uuid) -> Result<Option<CauseRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(lock_cause_by_id(&mut *lock, cause_id).await?)
+    }
+
+    async fn update_cause(
+        &self,
+        cause_id: Uuid,
+        name: String,
+        state: CauseState,
+        update_time: DateTime<Utc>,
+    ) -> Result<CauseRow, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_cause(&mut *lock, cause_id, name, state, update_time).await?)
+    }
+
+    async fn archive_cause(
+        &self,
+        cause_id: Uuid,
+        archive_time: DateTime<Utc>,
+    ) -> Result<CauseRow, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(archive_cause(&mut *lock, cause_id, archive_time).await?)
+    }
+
     async fn list_causes_for_user(
         &self,
         page_size: i64,
@@ -115,15 +199,33 @@ impl<'a> CauseStore for PgTransactionalStore<'a> {
         let mut lock = self.txn.lock().await;
         Ok(list_cause_recipients_for_cause(&mut *lock, cause_id).await?)
     }
+
+    async fn delete_cause_recipients_for_cause(&self, cause_id: Uuid) -> Result<(), Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(delete_cause_recipients_for_cause(&mut *lock, cause_id).await?)
+    }
 }
 
 #[async_trait]
 pub trait CauseAndRecipientStore {
-    /// Adds a cause and recipients of the cause.
+    /// Adds a cause and recipients of the cause. The weights of the recipients
+    /// of an active cause must sum to `TOTAL_WEIGHT_BASIS_POINTS`.
     async fn add_cause_and_recipients(
         &self,
         user_id: Uuid,
-        recipient_nonprofit_ids: Vec<Uuid>,
+        name: String,
+        state: CauseState,
+        recipients: Vec<NewCauseRecipient>,
+    ) -> Result<(CauseRow, Vec<CauseRecipientRow>), Error>;
+
+    /// Replaces the name, state and recipients of a cause. The weights of the
+    /// recipients of an active cause must sum to `TOTAL_WEIGHT_BASIS_POINTS`.
+    async fn replace_cause_and_recipients(
+        &self,
+        cause_id: Uuid,
+        name: String,
+        state: CauseState,
+        recipients: Vec<NewCauseRecipient>,
     ) -> Result<(CauseRow, Vec<CauseRecipientRow>), Error>;
 }
 
@@ -136,26 +238,61 @@ where
     async fn add_cause_and_recipients(
         &self,
         user_id: Uuid,
-        recipient_nonprofit_ids: Vec<Uuid>,
+        name: String,
+        state: CauseState,
+        recipients: Vec<NewCauseRecipient>,
     ) -> Result<(CauseRow, Vec<CauseRecipientRow>), Error> {
+        validate_cause_recipients(&state, &recipients)?;
+
         let now = Utc::now();
         let cause_row = self
             .add_cause(NewCauseRow {
                 create_time: now,
                 update_time: now,
                 user_id,
-                name: "some name".to_string(),
+                name,
+                state,
             })
             .await?;
 
         let mut recipient_rows = Vec::new();
-        for recipient_nonprofit_id in recipient_nonprofit_ids {
+        for recipient in recipients {
             recipient_rows.push(
                 self.add_cause_recipient(NewCauseRecipientRow {
                     cause_id: cause_row.cause_id.clone(),
-                    nonprofit_id: recipient_nonprofit_id.clone(),
+                    nonprofit_id: recipient.nonprofit_id,
+                    create_time: now,
+                    update_time: now,
+                    weight_basis_points: recipient.weight_basis_points,
+                })
+                .await?,
+            );
+        }
+        Ok((cause_row, recipient_rows))
+    }
+
+    async fn replace_cause_and_recipients(
+        &self,
+        cause_id: Uuid,
+        name: String,
+        state: CauseState,
+        recipients: Vec<NewCauseRecipient>,
+    ) -> Result<(CauseRow, Vec<CauseRecipientRow>), Error> {
+        validate_cause_recipients(&state, &recipients)?;
+
+        let now = Utc::now();
+        let cause_row = self.update_cause(cause_id, name, state, now).await?;
+        self.delete_cause_recipients_for_cause(cause_id).await?;
+
+        let mut recipient_rows = Vec::new();
+        for recipient in recipients {
+            recipient_rows.push(
+                self.add_cause_recipient(NewCauseRecipientRow {
+                    cause_id,
+                    nonprofit_id: recipient.nonprofit_id,
                     create_time: now,
                     update_time: now,
+                    weight_basis_points: recipient.weight_basis_points,
                 })
                 .await?,
             );
@@ -164,6 +301,50 @@ where
     }
 }
 
+/// Validates that the recipients are distinct and have positive weights. The
+/// weights of an active cause must sum to `TOTAL_WEIGHT_BASIS_POINTS`, a draft
+/// cause may have fewer (or no) recipients but must not exceed the total.
+fn validate_cause_recipients(
+    state: &CauseState,
+    recipients: &[NewCauseRecipient],
+) -> Result<(), Error> {
+    let mut nonprofit_ids = HashSet::new();
+    let mut total_weight_basis_points: i64 = 0;
+    for recipient in recipients {
+        if !nonprofit_ids.insert(recipient.nonprofit_id) {
+            return Err(Error::InvalidArgument(format!(
+                "duplicate recipient: {0}",
+                recipient.nonprofit_id
+            )));
+        }
+        if recipient.weight_basis_points <= 0 {
+            return Err(Error::InvalidArgument(format!(
+                "weight of recipient must be positive: {0}",
+                recipient.nonprofit_id
+            )));
+        }
+        total_weight_basis_points += recipient.weight_basis_points as i64;
+    }
+    match state {
+        CauseState::Active if recipients.is_empty() => Err(Error::InvalidArgument(
+            "cause must have at least one recipient".to_string(),
+        )),
+        CauseState::Active if total_weight_basis_points != TOTAL_WEIGHT_BASIS_POINTS as i64 => {
+            Err(Error::InvalidArgument(format!(
+                "weights of recipients must sum to {0} basis points, got {1}",
+                TOTAL_WEIGHT_BASIS_POINTS, total_weight_basis_points
+            )))
+        }
+        CauseState::Draft if total_weight_basis_points > TOTAL_WEIGHT_BASIS_POINTS as i64 => {
+            Err(Error::InvalidArgument(format!(
+                "weights of recipients must not exceed {0} basis points, got {1}",
+                TOTAL_WEIGHT_BASIS_POINTS, total_weight_basis_points
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
 async fn add_cause<'a, E>(executor: E, new_row: NewCauseRow) -> Result<CauseRow, Error>
 where
     E: PgExecutor<'a>,
@@ -175,6 +356,69 @@ where
         new_row.update_time,
         new_row.user_id,
         new_row.name,
+        new_row.state as CauseState,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+async fn find_cause_by_id<'a, E>(executor: E, cause_id: Uuid) -> Result<Option<FullCauseRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(
+        sqlx::query_file_as!(FullCauseRow, "queries/cause/find_by_id.sql", &cause_id)
+            .fetch_optional(executor)
+            .await?,
+    )
+}
+
+async fn lock_cause_by_id<'a, E>(executor: E, cause_id: Uuid) -> Result<Option<CauseRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(
+        sqlx::query_file_as!(CauseRow, "queries/cause/lock_by_id.sql", cause_id)
+            .fetch_optional(executor)
+            .await?,
+    )
+}
+
+async fn update_cause<'a, E>(
+    executor: E,
+    cause_id: Uuid,
+    name: String,
+    state: CauseState,
+    update_time: DateTime<Utc>,
+) -> Result<CauseRow, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        CauseRow,
+        "queries/cause/update.sql",
+        cause_id,
+        name,
+        state as CauseState,
+        update_time,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+async fn archive_cause<'a, E>(
+    executor: E,
+    cause_id: Uuid,
+    archive_time: DateTime<Utc>,
+) -> Result<CauseRow, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        CauseRow,
+        "queries/cause/archive.sql",
+        cause_id,
+        archive_time,
     )
     .fetch_one(executor)
     .await?)
@@ -245,6 +489,7 @@ where
         new_row.nonprofit_id,
         new_row.create_time,
         new_row.update_time,
+        new_row.weight_basis_points,
     )
     .fetch_one(executor)
     .await?)
@@ -266,3 +511,13 @@ where
     .await?;
     Ok(rows)
 }
+
+async fn delete_cause_recipients_for_cause<'a, E>(executor: E, cause_id: Uuid) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_file!("queries/cause_recipient/delete_for_cause.sql", &cause_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
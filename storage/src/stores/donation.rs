@@ -0,0 +1,806 @@
+use crate::{
+    database::store::TransactionalStore,
+    models::donation::*,
+    sqlx::store::{PgOnDemandStore, PgTransactionalStore},
+    Error,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+#[async_trait]
+pub trait DonationStore: Sync + Send {
+    /// Adds a donation.
+    async fn add_donation(&self, new_row: NewDonationRow) -> Result<DonationRow, Error>;
+
+    /// Finds a donation by id.
+    async fn find_donation_by_id(&self, donation_id: Uuid) -> Result<Option<DonationRow>, Error>;
+
+    /// Finds the donation created by the provided stripe charge.
+    async fn find_donation_by_stripe_charge_id(
+        &self,
+        stripe_charge_id: &str,
+    ) -> Result<Option<DonationRow>, Error>;
+
+    /// Adds an allocation of a cause donation.
+    async fn add_donation_allocation(
+        &self,
+        donation_id: Uuid,
+        new_row: NewDonationAllocationRow,
+        create_time: DateTime<Utc>,
+    ) -> Result<DonationAllocationRow, Error>;
+
+    /// Lists the allocations of a cause donation.
+    async fn list_donation_allocations_for_donation(
+        &self,
+        donation_id: Uuid,
+    ) -> Result<Vec<DonationAllocationRow>, Error>;
+
+    /// Updates the status of the donation created by the provided stripe charge.
+    /// Only moves the status forward, from pending to succeeded or failed and
+    /// from succeeded to refunded, so events delivered out of order can't
+    /// revert it. An ACH donation which succeeded is settled at the update
+    /// time. Returns none if no donation references the charge or the
+    /// transition isn't allowed.
+    async fn update_donation_status_by_stripe_charge_id(
+        &self,
+        stripe_charge_id: &str,
+        status: DonationStatus,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<DonationRow>, Error>;
+
+    /// Records the amount refunded so far from the provided stripe charge for
+    /// its donation. Only increases the recorded amount, so events delivered
+    /// out of order can't decrease it. Returns none if no donation references
+    /// the charge or a larger amount was already recorded.
+    async fn upsert_donation_refund_by_stripe_charge_id(
+        &self,
+        stripe_charge_id: &str,
+        amount_units: i64,
+        amount_nanos: i32,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<DonationRefundRow>, Error>;
+
+    /// Lists donations made by the provided user.
+    async fn list_donations_for_user(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        user_id: Uuid,
+    ) -> Result<Vec<DonationRow>, Error>;
+
+    /// Counts donations made by the provided user.
+    async fn count_donations_for_user(&self, user_id: Uuid) -> Result<i64, Error>;
+
+    async fn list_and_count_donations_for_user(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        user_id: Uuid,
+    ) -> Result<(Vec<DonationRow>, i64), Error> {
+        let list_fut = self.list_donations_for_user(page_size, page_token, user_id);
+        let count_fut = self.count_donations_for_user(user_id);
+        futures::try_join!(list_fut, count_fut)
+    }
+
+    /// Lists donations made to the provided nonprofit, including cause
+    /// donations allocated to it.
+    async fn list_donations_for_nonprofit(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        nonprofit_id: Uuid,
+    ) -> Result<Vec<DonationRow>, Error>;
+
+    /// Counts donations made to the provided nonprofit, including cause
+    /// donations allocated to it.
+    async fn count_donations_for_nonprofit(&self, nonprofit_id: Uuid) -> Result<i64, Error>;
+
+    async fn list_and_count_donations_for_nonprofit(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        nonprofit_id: Uuid,
+    ) -> Result<(Vec<DonationRow>, i64), Error> {
+        let list_fut = self.list_donations_for_nonprofit(page_size, page_token, nonprofit_id);
+        let count_fut = self.count_donations_for_nonprofit(nonprofit_id);
+        futures::try_join!(list_fut, count_fut)
+    }
+
+    /// Lists donations made through the provided affiliate, including cause
+    /// donations allocated to its nonprofits.
+    async fn list_donations_for_affiliate(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        affiliate_id: Uuid,
+    ) -> Result<Vec<DonationRow>, Error>;
+
+    /// Counts donations made through the provided affiliate, including cause
+    /// donations allocated to its nonprofits.
+    async fn count_donations_for_affiliate(&self, affiliate_id: Uuid) -> Result<i64, Error>;
+
+    async fn list_and_count_donations_for_affiliate(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        affiliate_id: Uuid,
+    ) -> Result<(Vec<DonationRow>, i64), Error> {
+        let list_fut = self.list_donations_for_affiliate(page_size, page_token, affiliate_id);
+        let count_fut = self.count_donations_for_affiliate(affiliate_id);
+        futures::try_join!(list_fut, count_fut)
+    }
+
+    /// Sums the succeeded donations made by the provided user, per currency.
+    async fn sum_donations_by_currency_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<DonationTotalRow>, Error>;
+
+    /// Sums the succeeded donations made to the provided nonprofit, including
+    /// its allocations of cause donations, per currency. Sums the net amounts
+    /// the nonprofit receives after fees, which allocations are shares of.
+    async fn sum_donations_by_currency_for_nonprofit(
+        &self,
+        nonprofit_id: Uuid,
+    ) -> Result<Vec<DonationTotalRow>, Error>;
+
+    /// Sums the succeeded donations made through the provided affiliate,
+    /// including its allocations of cause donations, per currency. Sums the
+    /// net amounts its nonprofits receive after fees, which allocations are
+    /// shares of.
+    async fn sum_donations_by_currency_for_affiliate(
+        &self,
+        affiliate_id: Uuid,
+    ) -> Result<Vec<DonationTotalRow>, Error>;
+}
+
+#[async_trait]
+impl DonationStore for PgOnDemandStore {
+    async fn add_donation(&self, new_row: NewDonationRow) -> Result<DonationRow, Error> {
+        Ok(add_donation(&*self.pool, new_row).await?)
+    }
+
+    async fn find_donation_by_id(&self, donation_id: Uuid) -> Result<Option<DonationRow>, Error> {
+        Ok(find_donation_by_id(&*self.pool, donation_id).await?)
+    }
+
+    async fn find_donation_by_stripe_charge_id(
+        &self,
+        stripe_charge_id: &str,
+    ) -> Result<Option<DonationRow>, Error> {
+        Ok(find_donation_by_stripe_charge_id(&*self.pool, stripe_charge_id).await?)
+    }
+
+    async fn add_donation_allocation(
+        &self,
+        donation_id: Uuid,
+        new_row: NewDonationAllocationRow,
+        create_time: DateTime<Utc>,
+    ) -> Result<DonationAllocationRow, Error> {
+        Ok(add_donation_allocation(&*self.pool, donation_id, new_row, create_time).await?)
+    }
+
+    async fn list_donation_allocations_for_donation(
+        &self,
+        donation_id: Uuid,
+    ) -> Result<Vec<DonationAllocationRow>, Error> {
+        Ok(list_donation_allocations_for_donation(&*self.pool, donation_id).await?)
+    }
+
+    async fn update_donation_status_by_stripe_charge_id(
+        &self,
+        stripe_charge_id: &str,
+        status: DonationStatus,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<DonationRow>, Error> {
+        Ok(update_donation_status_by_stripe_charge_id(
+            &*self.pool,
+            stripe_charge_id,
+            status,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn upsert_donation_refund_by_stripe_charge_id(
+        &self,
+        stripe_charge_id: &str,
+        amount_units: i64,
+        amount_nanos: i32,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<DonationRefundRow>, Error> {
+        Ok(upsert_donation_refund_by_stripe_charge_id(
+            &*self.pool,
+            stripe_charge_id,
+            amount_units,
+            amount_nanos,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn list_donations_for_user(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        user_id: Uuid,
+    ) -> Result<Vec<DonationRow>, Error> {
+        Ok(list_donations_for_user(&*self.pool, page_size, page_token, user_id).await?)
+    }
+
+    async fn count_donations_for_user(&self, user_id: Uuid) -> Result<i64, Error> {
+        Ok(count_donations_for_user(&*self.pool, user_id).await?)
+    }
+
+    async fn list_donations_for_nonprofit(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        nonprofit_id: Uuid,
+    ) -> Result<Vec<DonationRow>, Error> {
+        Ok(list_donations_for_nonprofit(&*self.pool, page_size, page_token, nonprofit_id).await?)
+    }
+
+    async fn count_donations_for_nonprofit(&self, nonprofit_id: Uuid) -> Result<i64, Error> {
+        Ok(count_donations_for_nonprofit(&*self.pool, nonprofit_id).await?)
+    }
+
+    async fn list_donations_for_affiliate(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        affiliate_id: Uuid,
+    ) -> Result<Vec<DonationRow>, Error> {
+        Ok(list_donations_for_affiliate(&*self.pool, page_size, page_token, affiliate_id).await?)
+    }
+
+    async fn count_donations_for_affiliate(&self, affiliate_id: Uuid) -> Result<i64, Error> {
+        Ok(count_donations_for_affiliate(&*self.pool, affiliate_id).await?)
+    }
+
+    async fn sum_donations_by_currency_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<DonationTotalRow>, Error> {
+        Ok(sum_donations_by_currency_for_user(&*self.pool, user_id).await?)
+    }
+
+    async fn sum_donations_by_currency_for_nonprofit(
+        &self,
+        nonprofit_id: Uuid,
+    ) -> Result<Vec<DonationTotalRow>, Error> {
+        Ok(sum_donations_by_currency_for_nonprofit(&*self.pool, nonprofit_id).await?)
+    }
+
+    async fn sum_donations_by_currency_for_affiliate(
+        &self,
+        affiliate_id: Uuid,
+    ) -> Result<Vec<DonationTotalRow>, Error> {
+        Ok(sum_donations_by_currency_for_affiliate(&*self.pool, affiliate_id).await?)
+    }
+}
+
+#[async_trait]
+impl<'a> DonationStore for PgTransactionalStore<'a> {
+    async fn add_donation(&self, new_row: NewDonationRow) -> Result<DonationRow, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(add_donation(&mut *lock, new_row).await?)
+    }
+
+    async fn find_donation_by_id(&self, donation_id: Uuid) -> Result<Option<DonationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(find_donation_by_id(&mut *lock, donation_id).await?)
+    }
+
+    async fn find_donation_by_stripe_charge_id(
+        &self,
+        stripe_charge_id: &str,
+    ) -> Result<Option<DonationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(find_donation_by_stripe_charge_id(&mut *lock, stripe_charge_id).await?)
+    }
+
+    async fn add_donation_allocation(
+        &self,
+        donation_id: Uuid,
+        new_row: NewDonationAllocationRow,
+        create_time: DateTime<Utc>,
+    ) -> Result<DonationAllocationRow, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(add_donation_allocation(&mut *lock, donation_id, new_row, create_time).await?)
+    }
+
+    async fn list_donation_allocations_for_donation(
+        &self,
+        donation_id: Uuid,
+    ) -> Result<Vec<DonationAllocationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(list_donation_allocations_for_donation(&mut *lock, donation_id).await?)
+    }
+
+    async fn update_donation_status_by_stripe_charge_id(
+        &self,
+        stripe_charge_id: &str,
+        status: DonationStatus,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<DonationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_donation_status_by_stripe_charge_id(
+            &mut *lock,
+            stripe_charge_id,
+            status,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn upsert_donation_refund_by_stripe_charge_id(
+        &self,
+        stripe_charge_id: &str,
+        amount_units: i64,
+        amount_nanos: i32,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<DonationRefundRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(upsert_donation_refund_by_stripe_charge_id(
+            &mut *lock,
+            stripe_charge_id,
+            amount_units,
+            amount_nanos,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn list_donations_for_user(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        user_id: Uuid,
+    ) -> Result<Vec<DonationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(list_donations_for_user(&mut *lock, page_size, page_token, user_id).await?)
+    }
+
+    async fn count_donations_for_user(&self, user_id: Uuid) -> Result<i64, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(count_donations_for_user(&mut *lock, user_id).await?)
+    }
+
+    async fn list_donations_for_nonprofit(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        nonprofit_id: Uuid,
+    ) -> Result<Vec<DonationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(list_donations_for_nonprofit(&mut *lock, page_size, page_token, nonprofit_id).await?)
+    }
+
+    async fn count_donations_for_nonprofit(&self, nonprofit_id: Uuid) -> Result<i64, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(count_donations_for_nonprofit(&mut *lock, nonprofit_id).await?)
+    }
+
+    async fn list_donations_for_affiliate(
+        &self,
+        page_size: i64,
+        page_token: Option<DonationPageToken>,
+        affiliate_id: Uuid,
+    ) -> Result<Vec<DonationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(list_donations_for_affiliate(&mut *lock, page_size, page_token, affiliate_id).await?)
+    }
+
+    async fn count_donations_for_affiliate(&self, affiliate_id: Uuid) -> Result<i64, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(count_donations_for_affiliate(&mut *lock, affiliate_id).await?)
+    }
+
+    async fn sum_donations_by_currency_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<DonationTotalRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(sum_donations_by_currency_for_user(&mut *lock, user_id).await?)
+    }
+
+    async fn sum_donations_by_currency_for_nonprofit(
+        &self,
+        nonprofit_id: Uuid,
+    ) -> Result<Vec<DonationTotalRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(sum_donations_by_currency_for_nonprofit(&mut *lock, nonprofit_id).await?)
+    }
+
+    async fn sum_donations_by_currency_for_affiliate(
+        &self,
+        affiliate_id: Uuid,
+    ) -> Result<Vec<DonationTotalRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(sum_donations_by_currency_for_affiliate(&mut *lock, affiliate_id).await?)
+    }
+}
+
+#[async_trait]
+pub trait DonationAndAllocationStore {
+    /// Adds a cause donation and its allocations.
+    async fn add_donation_and_allocations(
+        &self,
+        new_row: NewDonationRow,
+        new_allocation_rows: Vec<NewDonationAllocationRow>,
+    ) -> Result<(DonationRow, Vec<DonationAllocationRow>), Error>;
+}
+
+/// Implementation of the store for transactions.
+#[async_trait]
+impl<S> DonationAndAllocationStore for S
+where
+    S: DonationStore + TransactionalStore,
+{
+    async fn add_donation_and_allocations(
+        &self,
+        new_row: NewDonationRow,
+        new_allocation_rows: Vec<NewDonationAllocationRow>,
+    ) -> Result<(DonationRow, Vec<DonationAllocationRow>), Error> {
+        let donation_row = self.add_donation(new_row).await?;
+        let mut allocation_rows = Vec::new();
+        for new_allocation_row in new_allocation_rows {
+            allocation_rows.push(
+                self.add_donation_allocation(
+                    donation_row.donation_id,
+                    new_allocation_row,
+                    donation_row.create_time,
+                )
+                .await?,
+            );
+        }
+        Ok((donation_row, allocation_rows))
+    }
+}
+
+async fn add_donation<'a, E>(executor: E, new_row: NewDonationRow) -> Result<DonationRow, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        DonationRow,
+        "queries/donation/insert.sql",
+        new_row.create_time,
+        new_row.update_time,
+        new_row.nonprofit_id,
+        new_row.cause_id,
+        new_row.user_id,
+        new_row.affiliate_id,
+        new_row.currency_code,
+        new_row.amount_units,
+        new_row.amount_nanos,
+        new_row.stripe_charge_id,
+        new_row.stripe_transfer_group,
+        new_row.status as DonationStatus,
+        new_row.account_id,
+        new_row.cover_fees,
+        new_row.charge_amount_units,
+        new_row.charge_amount_nanos,
+        new_row.processing_fee_units,
+        new_row.processing_fee_nanos,
+        new_row.platform_fee_units,
+        new_row.platform_fee_nanos,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+async fn find_donation_by_id<'a, E>(
+    executor: E,
+    donation_id: Uuid,
+) -> Result<Option<DonationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(
+        sqlx::query_file_as!(DonationRow, "queries/donation/find_by_id.sql", donation_id)
+            .fetch_optional(executor)
+            .await?,
+    )
+}
+
+async fn find_donation_by_stripe_charge_id<'a, E>(
+    executor: E,
+    stripe_charge_id: &str,
+) -> Result<Option<DonationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        DonationRow,
+        "queries/donation/find_by_stripe_charge_id.sql",
+        stripe_charge_id
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn update_donation_status_by_stripe_charge_id<'a, E>(
+    executor: E,
+    stripe_charge_id: &str,
+    status: DonationStatus,
+    update_time: DateTime<Utc>,
+) -> Result<Option<DonationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        DonationRow,
+        "queries/donation/update_status_by_stripe_charge_id.sql",
+        stripe_charge_id,
+        status as DonationStatus,
+        update_time,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn upsert_donation_refund_by_stripe_charge_id<'a, E>(
+    executor: E,
+    stripe_charge_id: &str,
+    amount_units: i64,
+    amount_nanos: i32,
+    update_time: DateTime<Utc>,
+) -> Result<Option<DonationRefundRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        DonationRefundRow,
+        "queries/donation/upsert_refund_by_stripe_charge_id.sql",
+        stripe_charge_id,
+        update_time,
+        amount_units,
+        amount_nanos,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn list_donations_for_user<'a, E>(
+    executor: E,
+    page_size: i64,
+    page_token: Option<DonationPageToken>,
+    user_id: Uuid,
+) -> Result<Vec<DonationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let rows = match page_token {
+        Some(page_token) => {
+            // Query by page token:
+            sqlx::query_file_as!(
+                DonationRow,
+                "queries/donation/list_at_page_for_user.sql",
+                page_token.create_time,
+                page_token.donation_id,
+                &user_id,
+                page_size,
+            )
+            .fetch_all(executor)
+            .await?
+        }
+        None => {
+            // Query first page:
+            sqlx::query_file_as!(
+                DonationRow,
+                "queries/donation/list_for_user.sql",
+                page_size,
+                &user_id
+            )
+            .fetch_all(executor)
+            .await?
+        }
+    };
+    Ok(rows)
+}
+
+async fn count_donations_for_user<'a, E>(executor: E, user_id: Uuid) -> Result<i64, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(
+        sqlx::query_file!("queries/donation/count_for_user.sql", &user_id)
+            .fetch_one(executor)
+            .await?
+            .count,
+    )
+}
+
+async fn list_donations_for_nonprofit<'a, E>(
+    executor: E,
+    page_size: i64,
+    page_token: Option<DonationPageToken>,
+    nonprofit_id: Uuid,
+) -> Result<Vec<DonationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let rows = match page_token {
+        Some(page_token) => {
+            // Query by page token:
+            sqlx::query_file_as!(
+                DonationRow,
+                "queries/donation/list_at_page_for_nonprofit.sql",
+                page_token.create_time,
+                page_token.donation_id,
+                &nonprofit_id,
+                page_size,
+            )
+            .fetch_all(executor)
+            .await?
+        }
+        None => {
+            // Query first page:
+            sqlx::query_file_as!(
+                DonationRow,
+                "queries/donation/list_for_nonprofit.sql",
+                page_size,
+                &nonprofit_id
+            )
+            .fetch_all(executor)
+            .await?
+        }
+    };
+    Ok(rows)
+}
+
+async fn count_donations_for_nonprofit<'a, E>(executor: E, nonprofit_id: Uuid) -> Result<i64, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(
+        sqlx::query_file!("queries/donation/count_for_nonprofit.sql", &nonprofit_id)
+            .fetch_one(executor)
+            .await?
+            .count,
+    )
+}
+
+async fn list_donations_for_affiliate<'a, E>(
+    executor: E,
+    page_size: i64,
+    page_token: Option<DonationPageToken>,
+    affiliate_id: Uuid,
+) -> Result<Vec<DonationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let rows = match page_token {
+        Some(page_token) => {
+            // Query by page token:
+            sqlx::query_file_as!(
+                DonationRow,
+                "queries/donation/list_at_page_for_affiliate.sql",
+                page_token.create_time,
+                page_token.donation_id,
+                &affiliate_id,
+                page_size,
+            )
+            .fetch_all(executor)
+            .await?
+        }
+        None => {
+            // Query first page:
+            sqlx::query_file_as!(
+                DonationRow,
+                "queries/donation/list_for_affiliate.sql",
+                page_size,
+                &affiliate_id
+            )
+            .fetch_all(executor)
+            .await?
+        }
+    };
+    Ok(rows)
+}
+
+async fn count_donations_for_affiliate<'a, E>(executor: E, affiliate_id: Uuid) -> Result<i64, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(
+        sqlx::query_file!("queries/donation/count_for_affiliate.sql", &affiliate_id)
+            .fetch_one(executor)
+            .await?
+            .count,
+    )
+}
+
+async fn sum_donations_by_currency_for_user<'a, E>(
+    executor: E,
+    user_id: Uuid,
+) -> Result<Vec<DonationTotalRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        DonationTotalRow,
+        "queries/donation/sum_by_currency_for_user.sql",
+        &user_id,
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+async fn sum_donations_by_currency_for_nonprofit<'a, E>(
+    executor: E,
+    nonprofit_id: Uuid,
+) -> Result<Vec<DonationTotalRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        DonationTotalRow,
+        "queries/donation/sum_by_currency_for_nonprofit.sql",
+        &nonprofit_id,
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+async fn sum_donations_by_currency_for_affiliate<'a, E>(
+    executor: E,
+    affiliate_id: Uuid,
+) -> Result<Vec<DonationTotalRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        DonationTotalRow,
+        "queries/donation/sum_by_currency_for_affiliate.sql",
+        &affiliate_id,
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+async fn add_donation_allocation<'a, E>(
+    executor: E,
+    donation_id: Uuid,
+    new_row: NewDonationAllocationRow,
+    create_time: DateTime<Utc>,
+) -> Result<DonationAllocationRow, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        DonationAllocationRow,
+        "queries/donation_allocation/insert.sql",
+        donation_id,
+        new_row.nonprofit_id,
+        create_time,
+        create_time,
+        new_row.affiliate_id,
+        new_row.amount_units,
+        new_row.amount_nanos,
+        new_row.stripe_transfer_id,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+async fn list_donation_allocations_for_donation<'a, E>(
+    executor: E,
+    donation_id: Uuid,
+) -> Result<Vec<DonationAllocationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let rows = sqlx::query_file_as!(
+        DonationAllocationRow,
+        "queries/donation_allocation/list_for_donation.sql",
+        &donation_id,
+    )
+    .fetch_all(executor)
+    .await?;
+    Ok(rows)
+}
@@ -0,0 +1,333 @@
+use crate::{
+    models::idempotency_key::*,
+    sqlx::store::{PgOnDemandStore, PgTransactionalStore},
+    Error,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+#[async_trait]
+pub trait IdempotencyKeyStore: Sync + Send {
+    /// Claims a user's idempotency key for a request, taking over claims of
+    /// the same request whose lock expired. Returns none if the key is held by
+    /// an in-progress or completed request, or was claimed for another body.
+    async fn add_idempotency_key(
+        &self,
+        new_row: NewIdempotencyKeyRow,
+    ) -> Result<Option<IdempotencyKeyRow>, Error>;
+
+    /// Finds a user's claimed idempotency key.
+    async fn find_idempotency_key(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+    ) -> Result<Option<IdempotencyKeyRow>, Error>;
+
+    /// Stores the response of the request which claimed the idempotency key.
+    async fn update_idempotency_key_response(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+        response_body: Vec<u8>,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error>;
+
+    /// Marks the request which claimed the idempotency key as failed for
+    /// good, so the key is neither replayed, released nor taken over.
+    async fn update_idempotency_key_failed(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error>;
+
+    /// Releases an in-progress idempotency key so the request may be retried.
+    async fn delete_idempotency_key(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+    ) -> Result<(), Error>;
+
+    /// Deletes the idempotency keys last updated before the expire time,
+    /// unless their request is still in progress. Returns the number of
+    /// deleted keys.
+    async fn delete_expired_idempotency_keys(
+        &self,
+        expire_time: DateTime<Utc>,
+    ) -> Result<u64, Error>;
+}
+
+#[async_trait]
+impl IdempotencyKeyStore for PgOnDemandStore {
+    async fn add_idempotency_key(
+        &self,
+        new_row: NewIdempotencyKeyRow,
+    ) -> Result<Option<IdempotencyKeyRow>, Error> {
+        Ok(add_idempotency_key(&*self.pool, new_row).await?)
+    }
+
+    async fn find_idempotency_key(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+    ) -> Result<Option<IdempotencyKeyRow>, Error> {
+        Ok(find_idempotency_key(&*self.pool, user_id, idempotency_key, request_path).await?)
+    }
+
+    async fn update_idempotency_key_response(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+        response_body: Vec<u8>,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        Ok(update_idempotency_key_response(
+            &*self.pool,
+            user_id,
+            idempotency_key,
+            request_path,
+            response_body,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn update_idempotency_key_failed(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        Ok(update_idempotency_key_failed(
+            &*self.pool,
+            user_id,
+            idempotency_key,
+            request_path,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn delete_idempotency_key(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+    ) -> Result<(), Error> {
+        Ok(delete_idempotency_key(&*self.pool, user_id, idempotency_key, request_path).await?)
+    }
+
+    async fn delete_expired_idempotency_keys(
+        &self,
+        expire_time: DateTime<Utc>,
+    ) -> Result<u64, Error> {
+        Ok(delete_expired_idempotency_keys(&*self.pool, expire_time).await?)
+    }
+}
+
+#[async_trait]
+impl<'a> IdempotencyKeyStore for PgTransactionalStore<'a> {
+    async fn add_idempotency_key(
+        &self,
+        new_row: NewIdempotencyKeyRow,
+    ) -> Result<Option<IdempotencyKeyRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(add_idempotency_key(&mut *lock, new_row).await?)
+    }
+
+    async fn find_idempotency_key(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+    ) -> Result<Option<IdempotencyKeyRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(find_idempotency_key(&mut *lock, user_id, idempotency_key, request_path).await?)
+    }
+
+    async fn update_idempotency_key_response(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+        response_body: Vec<u8>,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_idempotency_key_response(
+            &mut *lock,
+            user_id,
+            idempotency_key,
+            request_path,
+            response_body,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn update_idempotency_key_failed(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_idempotency_key_failed(
+            &mut *lock,
+            user_id,
+            idempotency_key,
+            request_path,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn delete_idempotency_key(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+    ) -> Result<(), Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(delete_idempotency_key(&mut *lock, user_id, idempotency_key, request_path).await?)
+    }
+
+    async fn delete_expired_idempotency_keys(
+        &self,
+        expire_time: DateTime<Utc>,
+    ) -> Result<u64, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(delete_expired_idempotency_keys(&mut *lock, expire_time).await?)
+    }
+}
+
+async fn add_idempotency_key<'a, E>(
+    executor: E,
+    new_row: NewIdempotencyKeyRow,
+) -> Result<Option<IdempotencyKeyRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        IdempotencyKeyRow,
+        "queries/idempotency_key/insert.sql",
+        new_row.user_id,
+        new_row.idempotency_key,
+        new_row.request_path,
+        new_row.request_hash,
+        new_row.create_time,
+        new_row.update_time,
+        new_row.lock_expire_time,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn find_idempotency_key<'a, E>(
+    executor: E,
+    user_id: Uuid,
+    idempotency_key: &str,
+    request_path: &str,
+) -> Result<Option<IdempotencyKeyRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        IdempotencyKeyRow,
+        "queries/idempotency_key/find.sql",
+        user_id,
+        idempotency_key,
+        request_path,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn update_idempotency_key_response<'a, E>(
+    executor: E,
+    user_id: Uuid,
+    idempotency_key: &str,
+    request_path: &str,
+    response_body: Vec<u8>,
+    update_time: DateTime<Utc>,
+) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_file!(
+        "queries/idempotency_key/update_response.sql",
+        user_id,
+        idempotency_key,
+        request_path,
+        response_body,
+        update_time,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+async fn update_idempotency_key_failed<'a, E>(
+    executor: E,
+    user_id: Uuid,
+    idempotency_key: &str,
+    request_path: &str,
+    update_time: DateTime<Utc>,
+) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_file!(
+        "queries/idempotency_key/update_failed.sql",
+        user_id,
+        idempotency_key,
+        request_path,
+        update_time,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+async fn delete_idempotency_key<'a, E>(
+    executor: E,
+    user_id: Uuid,
+    idempotency_key: &str,
+    request_path: &str,
+) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_file!(
+        "queries/idempotency_key/delete.sql",
+        user_id,
+        idempotency_key,
+        request_path
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+async fn delete_expired_idempotency_keys<'a, E>(
+    executor: E,
+    expire_time: DateTime<Utc>,
+) -> Result<u64, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let result = sqlx::query_file!("queries/idempotency_key/delete_expired.sql", expire_time)
+        .execute(executor)
+        .await?;
+    Ok(result.rows_affected())
+}
@@ -4,6 +4,7 @@ use crate::{
     Error,
 };
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::PgExecutor;
 use uuid::Uuid;
 
@@ -34,6 +35,35 @@ pub trait ItemStore: Sync + Send {
     }
 
     async fn delete_item(&self, item_id: Uuid) -> Result<(), Error>;
+
+    /// Updates the status of the item with the provided plaid item id. Returns
+    /// none if no such item exists.
+    async fn update_item_status_by_plaid_item_id(
+        &self,
+        plaid_item_id: &str,
+        status: ItemStatus,
+        plaid_error_code: Option<String>,
+        consent_expiration_time: Option<DateTime<Utc>>,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<ItemRow>, Error>;
+
+    /// Updates whether the item with the provided plaid item id has accounts
+    /// which were not linked yet. Returns none if no such item exists.
+    async fn update_item_new_accounts_available_by_plaid_item_id(
+        &self,
+        plaid_item_id: &str,
+        new_accounts_available: bool,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<ItemRow>, Error>;
+
+    /// Updates the cursor of the item's transactions sync, i.e. the position
+    /// after the last transaction which was processed.
+    async fn update_item_transactions_cursor(
+        &self,
+        item_id: Uuid,
+        transactions_cursor: String,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error>;
 }
 
 #[async_trait]
@@ -62,6 +92,52 @@ impl ItemStore for PgOnDemandStore {
     async fn delete_item(&self, item_id: Uuid) -> Result<(), Error> {
         Ok(delete_item(&*self.pool, item_id).await?)
     }
+
+    async fn update_item_status_by_plaid_item_id(
+        &self,
+        plaid_item_id: &str,
+        status: ItemStatus,
+        plaid_error_code: Option<String>,
+        consent_expiration_time: Option<DateTime<Utc>>,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<ItemRow>, Error> {
+        Ok(update_item_status_by_plaid_item_id(
+            &*self.pool,
+            plaid_item_id,
+            status,
+            plaid_error_code,
+            consent_expiration_time,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn update_item_new_accounts_available_by_plaid_item_id(
+        &self,
+        plaid_item_id: &str,
+        new_accounts_available: bool,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<ItemRow>, Error> {
+        Ok(update_item_new_accounts_available_by_plaid_item_id(
+            &*self.pool,
+            plaid_item_id,
+            new_accounts_available,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn update_item_transactions_cursor(
+        &self,
+        item_id: Uuid,
+        transactions_cursor: String,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        Ok(
+            update_item_transactions_cursor(&*self.pool, item_id, transactions_cursor, update_time)
+                .await?,
+        )
+    }
 }
 
 #[async_trait]
@@ -95,6 +171,55 @@ impl<'a> ItemStore for PgTransactionalStore<'a> {
         let mut lock = self.txn.lock().await;
         Ok(delete_item(&mut *lock, item_id).await?)
     }
+
+    async fn update_item_status_by_plaid_item_id(
+        &self,
+        plaid_item_id: &str,
+        status: ItemStatus,
+        plaid_error_code: Option<String>,
+        consent_expiration_time: Option<DateTime<Utc>>,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<ItemRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_item_status_by_plaid_item_id(
+            &mut *lock,
+            plaid_item_id,
+            status,
+            plaid_error_code,
+            consent_expiration_time,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn update_item_new_accounts_available_by_plaid_item_id(
+        &self,
+        plaid_item_id: &str,
+        new_accounts_available: bool,
+        update_time: DateTime<Utc>,
+    ) -> Result<Option<ItemRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_item_new_accounts_available_by_plaid_item_id(
+            &mut *lock,
+            plaid_item_id,
+            new_accounts_available,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn update_item_transactions_cursor(
+        &self,
+        item_id: Uuid,
+        transactions_cursor: String,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(
+            update_item_transactions_cursor(&mut *lock, item_id, transactions_cursor, update_time)
+                .await?,
+        )
+    }
 }
 
 async fn add_item<'a, E>(executor: E, new_row: NewItemRow) -> Result<ItemRow, Error>
@@ -185,3 +310,67 @@ where
         .await?;
     Ok(())
 }
+
+async fn update_item_status_by_plaid_item_id<'a, E>(
+    executor: E,
+    plaid_item_id: &str,
+    status: ItemStatus,
+    plaid_error_code: Option<String>,
+    consent_expiration_time: Option<DateTime<Utc>>,
+    update_time: DateTime<Utc>,
+) -> Result<Option<ItemRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        ItemRow,
+        "queries/item/update_status_by_plaid_item_id.sql",
+        plaid_item_id,
+        status as ItemStatus,
+        plaid_error_code,
+        consent_expiration_time,
+        update_time,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn update_item_new_accounts_available_by_plaid_item_id<'a, E>(
+    executor: E,
+    plaid_item_id: &str,
+    new_accounts_available: bool,
+    update_time: DateTime<Utc>,
+) -> Result<Option<ItemRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        ItemRow,
+        "queries/item/update_new_accounts_available_by_plaid_item_id.sql",
+        plaid_item_id,
+        new_accounts_available,
+        update_time,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn update_item_transactions_cursor<'a, E>(
+    executor: E,
+    item_id: Uuid,
+    transactions_cursor: String,
+    update_time: DateTime<Utc>,
+) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_file!(
+        "queries/item/update_transactions_cursor.sql",
+        item_id,
+        transactions_cursor,
+        update_time,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
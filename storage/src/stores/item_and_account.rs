@@ -1,5 +1,6 @@
 use crate::{
     database::store::TransactionalStore,
+    models::{account::*, item::*},
     stores::{account::AccountStore, item::ItemStore},
     Error,
 };
@@ -8,6 +9,13 @@ use uuid::Uuid;
 
 #[async_trait]
 pub trait ItemAndAccountStore {
+    /// Adds an item and its accounts.
+    async fn add_item_and_accounts(
+        &self,
+        new_item_row: NewItemRow,
+        new_account_rows: Vec<NewItemAccountRow>,
+    ) -> Result<(ItemRow, Vec<AccountRow>), Error>;
+
     /// Deletes an item and accounts.
     async fn delete_item_and_accounts(
         &self,
@@ -23,6 +31,30 @@ impl<S> ItemAndAccountStore for S
 where
     S: ItemStore + AccountStore + TransactionalStore,
 {
+    async fn add_item_and_accounts(
+        &self,
+        new_item_row: NewItemRow,
+        new_account_rows: Vec<NewItemAccountRow>,
+    ) -> Result<(ItemRow, Vec<AccountRow>), Error> {
+        let item_row = self.add_item(new_item_row).await?;
+        let mut account_rows = Vec::new();
+        for new_account_row in new_account_rows {
+            account_rows.push(
+                self.add_account(NewAccountRow {
+                    create_time: item_row.create_time,
+                    update_time: item_row.update_time,
+                    item_id: item_row.item_id,
+                    plaid_account_id: new_account_row.plaid_account_id,
+                    name: new_account_row.name,
+                    mask: new_account_row.mask,
+                    stripe_bank_account_id: new_account_row.stripe_bank_account_id,
+                })
+                .await?,
+            );
+        }
+        Ok((item_row, account_rows))
+    }
+
     async fn delete_item_and_accounts(
         &self,
         item_id: Uuid,
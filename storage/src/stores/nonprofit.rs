@@ -65,6 +65,7 @@ impl NonprofitStore for PgOnDemandStore {
             new_nonprofit.mission,
             new_nonprofit.category,
             new_nonprofit.affiliate_id,
+            new_nonprofit.email,
         )
         .fetch_one(&*self.pool)
         .await?)
@@ -0,0 +1,288 @@
+use crate::{
+    models::recurring_donation::*,
+    sqlx::store::{PgOnDemandStore, PgTransactionalStore},
+    Error,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+#[async_trait]
+pub trait RecurringDonationStore: Sync + Send {
+    /// Adds an active recurring donation, first run at its start time.
+    async fn add_recurring_donation(
+        &self,
+        new_row: NewRecurringDonationRow,
+    ) -> Result<RecurringDonationRow, Error>;
+
+    /// Finds a recurring donation by id.
+    async fn find_recurring_donation_by_id(
+        &self,
+        recurring_donation_id: Uuid,
+    ) -> Result<Option<RecurringDonationRow>, Error>;
+
+    /// Updates the state and next run time of a recurring donation, resetting
+    /// its retries.
+    async fn update_recurring_donation_state(
+        &self,
+        recurring_donation_id: Uuid,
+        state: RecurringDonationState,
+        next_run_time: DateTime<Utc>,
+        update_time: DateTime<Utc>,
+    ) -> Result<RecurringDonationRow, Error>;
+
+    /// Locks the active recurring donation which is due the longest, until the
+    /// end of the transaction. Donations locked by other transactions are
+    /// skipped, so concurrent schedulers never claim the same donation.
+    async fn claim_due_recurring_donation(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Option<RecurringDonationRow>, Error>;
+
+    /// Records the outcome of a run of a recurring donation.
+    async fn update_recurring_donation_schedule(
+        &self,
+        recurring_donation_id: Uuid,
+        next_run_time: DateTime<Utc>,
+        retry_time: Option<DateTime<Utc>>,
+        failure_count: i32,
+        last_donation_id: Option<Uuid>,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl RecurringDonationStore for PgOnDemandStore {
+    async fn add_recurring_donation(
+        &self,
+        new_row: NewRecurringDonationRow,
+    ) -> Result<RecurringDonationRow, Error> {
+        Ok(add_recurring_donation(&*self.pool, new_row).await?)
+    }
+
+    async fn find_recurring_donation_by_id(
+        &self,
+        recurring_donation_id: Uuid,
+    ) -> Result<Option<RecurringDonationRow>, Error> {
+        Ok(find_recurring_donation_by_id(&*self.pool, recurring_donation_id).await?)
+    }
+
+    async fn update_recurring_donation_state(
+        &self,
+        recurring_donation_id: Uuid,
+        state: RecurringDonationState,
+        next_run_time: DateTime<Utc>,
+        update_time: DateTime<Utc>,
+    ) -> Result<RecurringDonationRow, Error> {
+        Ok(update_recurring_donation_state(
+            &*self.pool,
+            recurring_donation_id,
+            state,
+            next_run_time,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn claim_due_recurring_donation(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Option<RecurringDonationRow>, Error> {
+        Ok(claim_due_recurring_donation(&*self.pool, now).await?)
+    }
+
+    async fn update_recurring_donation_schedule(
+        &self,
+        recurring_donation_id: Uuid,
+        next_run_time: DateTime<Utc>,
+        retry_time: Option<DateTime<Utc>>,
+        failure_count: i32,
+        last_donation_id: Option<Uuid>,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        Ok(update_recurring_donation_schedule(
+            &*self.pool,
+            recurring_donation_id,
+            next_run_time,
+            retry_time,
+            failure_count,
+            last_donation_id,
+            update_time,
+        )
+        .await?)
+    }
+}
+
+#[async_trait]
+impl<'a> RecurringDonationStore for PgTransactionalStore<'a> {
+    async fn add_recurring_donation(
+        &self,
+        new_row: NewRecurringDonationRow,
+    ) -> Result<RecurringDonationRow, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(add_recurring_donation(&mut *lock, new_row).await?)
+    }
+
+    async fn find_recurring_donation_by_id(
+        &self,
+        recurring_donation_id: Uuid,
+    ) -> Result<Option<RecurringDonationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(find_recurring_donation_by_id(&mut *lock, recurring_donation_id).await?)
+    }
+
+    async fn update_recurring_donation_state(
+        &self,
+        recurring_donation_id: Uuid,
+        state: RecurringDonationState,
+        next_run_time: DateTime<Utc>,
+        update_time: DateTime<Utc>,
+    ) -> Result<RecurringDonationRow, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_recurring_donation_state(
+            &mut *lock,
+            recurring_donation_id,
+            state,
+            next_run_time,
+            update_time,
+        )
+        .await?)
+    }
+
+    async fn claim_due_recurring_donation(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Option<RecurringDonationRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(claim_due_recurring_donation(&mut *lock, now).await?)
+    }
+
+    async fn update_recurring_donation_schedule(
+        &self,
+        recurring_donation_id: Uuid,
+        next_run_time: DateTime<Utc>,
+        retry_time: Option<DateTime<Utc>>,
+        failure_count: i32,
+        last_donation_id: Option<Uuid>,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(update_recurring_donation_schedule(
+            &mut *lock,
+            recurring_donation_id,
+            next_run_time,
+            retry_time,
+            failure_count,
+            last_donation_id,
+            update_time,
+        )
+        .await?)
+    }
+}
+
+async fn add_recurring_donation<'a, E>(
+    executor: E,
+    new_row: NewRecurringDonationRow,
+) -> Result<RecurringDonationRow, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        RecurringDonationRow,
+        "queries/recurring_donation/insert.sql",
+        new_row.create_time,
+        new_row.update_time,
+        new_row.user_id,
+        new_row.nonprofit_id,
+        new_row.cause_id,
+        new_row.account_id,
+        new_row.currency_code,
+        new_row.amount_units,
+        new_row.amount_nanos,
+        new_row.frequency as RecurringDonationFrequency,
+        new_row.start_time,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+async fn find_recurring_donation_by_id<'a, E>(
+    executor: E,
+    recurring_donation_id: Uuid,
+) -> Result<Option<RecurringDonationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        RecurringDonationRow,
+        "queries/recurring_donation/find_by_id.sql",
+        recurring_donation_id,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn update_recurring_donation_state<'a, E>(
+    executor: E,
+    recurring_donation_id: Uuid,
+    state: RecurringDonationState,
+    next_run_time: DateTime<Utc>,
+    update_time: DateTime<Utc>,
+) -> Result<RecurringDonationRow, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        RecurringDonationRow,
+        "queries/recurring_donation/update_state.sql",
+        recurring_donation_id,
+        state as RecurringDonationState,
+        next_run_time,
+        update_time,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+async fn claim_due_recurring_donation<'a, E>(
+    executor: E,
+    now: DateTime<Utc>,
+) -> Result<Option<RecurringDonationRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        RecurringDonationRow,
+        "queries/recurring_donation/claim_due.sql",
+        now,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn update_recurring_donation_schedule<'a, E>(
+    executor: E,
+    recurring_donation_id: Uuid,
+    next_run_time: DateTime<Utc>,
+    retry_time: Option<DateTime<Utc>>,
+    failure_count: i32,
+    last_donation_id: Option<Uuid>,
+    update_time: DateTime<Utc>,
+) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_file!(
+        "queries/recurring_donation/update_schedule.sql",
+        recurring_donation_id,
+        next_run_time,
+        retry_time,
+        failure_count,
+        last_donation_id,
+        update_time,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
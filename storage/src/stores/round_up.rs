@@ -0,0 +1,281 @@
+use crate::{
+    models::round_up::*,
+    sqlx::store::{PgOnDemandStore, PgTransactionalStore},
+    Error,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+#[async_trait]
+pub trait RoundUpStore: Sync + Send {
+    /// Adds a round-up. Returns none if the transaction was already rounded up.
+    async fn add_round_up(&self, new_row: NewRoundUpRow) -> Result<Option<RoundUpRow>, Error>;
+
+    /// Lists the round-ups of the user which were not donated yet.
+    async fn list_pending_round_ups_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<RoundUpRow>, Error>;
+
+    /// Marks pending round-ups as donated with the provided donation.
+    async fn mark_round_ups_donated(
+        &self,
+        round_up_ids: Vec<Uuid>,
+        donation_id: Uuid,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error>;
+
+    /// Adds or replaces the round-up settings of a user.
+    async fn upsert_round_up_settings(
+        &self,
+        new_row: NewRoundUpSettingsRow,
+    ) -> Result<RoundUpSettingsRow, Error>;
+
+    /// Finds the round-up settings of a user.
+    async fn find_round_up_settings_by_user_id(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<RoundUpSettingsRow>, Error>;
+
+    /// Lists the round-up settings of all users with round-ups enabled.
+    async fn list_enabled_round_up_settings(&self) -> Result<Vec<RoundUpSettingsRow>, Error>;
+
+    /// Locks the enabled round-up settings of a user until the end of the
+    /// transaction. Returns none if round-ups are disabled or the settings
+    /// are locked by another transaction.
+    async fn lock_enabled_round_up_settings_by_user_id(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<RoundUpSettingsRow>, Error>;
+}
+
+#[async_trait]
+impl RoundUpStore for PgOnDemandStore {
+    async fn add_round_up(&self, new_row: NewRoundUpRow) -> Result<Option<RoundUpRow>, Error> {
+        Ok(add_round_up(&*self.pool, new_row).await?)
+    }
+
+    async fn list_pending_round_ups_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<RoundUpRow>, Error> {
+        Ok(list_pending_round_ups_for_user(&*self.pool, user_id).await?)
+    }
+
+    async fn mark_round_ups_donated(
+        &self,
+        round_up_ids: Vec<Uuid>,
+        donation_id: Uuid,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        Ok(mark_round_ups_donated(&*self.pool, round_up_ids, donation_id, update_time).await?)
+    }
+
+    async fn upsert_round_up_settings(
+        &self,
+        new_row: NewRoundUpSettingsRow,
+    ) -> Result<RoundUpSettingsRow, Error> {
+        Ok(upsert_round_up_settings(&*self.pool, new_row).await?)
+    }
+
+    async fn find_round_up_settings_by_user_id(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<RoundUpSettingsRow>, Error> {
+        Ok(find_round_up_settings_by_user_id(&*self.pool, user_id).await?)
+    }
+
+    async fn list_enabled_round_up_settings(&self) -> Result<Vec<RoundUpSettingsRow>, Error> {
+        Ok(list_enabled_round_up_settings(&*self.pool).await?)
+    }
+
+    async fn lock_enabled_round_up_settings_by_user_id(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<RoundUpSettingsRow>, Error> {
+        Ok(lock_enabled_round_up_settings_by_user_id(&*self.pool, user_id).await?)
+    }
+}
+
+#[async_trait]
+impl<'a> RoundUpStore for PgTransactionalStore<'a> {
+    async fn add_round_up(&self, new_row: NewRoundUpRow) -> Result<Option<RoundUpRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(add_round_up(&mut *lock, new_row).await?)
+    }
+
+    async fn list_pending_round_ups_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<RoundUpRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(list_pending_round_ups_for_user(&mut *lock, user_id).await?)
+    }
+
+    async fn mark_round_ups_donated(
+        &self,
+        round_up_ids: Vec<Uuid>,
+        donation_id: Uuid,
+        update_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(mark_round_ups_donated(&mut *lock, round_up_ids, donation_id, update_time).await?)
+    }
+
+    async fn upsert_round_up_settings(
+        &self,
+        new_row: NewRoundUpSettingsRow,
+    ) -> Result<RoundUpSettingsRow, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(upsert_round_up_settings(&mut *lock, new_row).await?)
+    }
+
+    async fn find_round_up_settings_by_user_id(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<RoundUpSettingsRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(find_round_up_settings_by_user_id(&mut *lock, user_id).await?)
+    }
+
+    async fn list_enabled_round_up_settings(&self) -> Result<Vec<RoundUpSettingsRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(list_enabled_round_up_settings(&mut *lock).await?)
+    }
+
+    async fn lock_enabled_round_up_settings_by_user_id(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<RoundUpSettingsRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(lock_enabled_round_up_settings_by_user_id(&mut *lock, user_id).await?)
+    }
+}
+
+async fn add_round_up<'a, E>(
+    executor: E,
+    new_row: NewRoundUpRow,
+) -> Result<Option<RoundUpRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        RoundUpRow,
+        "queries/round_up/insert.sql",
+        new_row.create_time,
+        new_row.update_time,
+        new_row.user_id,
+        new_row.plaid_transaction_id,
+        new_row.plaid_account_id,
+        new_row.currency_code,
+        new_row.amount_units,
+        new_row.amount_nanos,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn list_pending_round_ups_for_user<'a, E>(
+    executor: E,
+    user_id: Uuid,
+) -> Result<Vec<RoundUpRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        RoundUpRow,
+        "queries/round_up/list_pending_for_user.sql",
+        user_id,
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+async fn mark_round_ups_donated<'a, E>(
+    executor: E,
+    round_up_ids: Vec<Uuid>,
+    donation_id: Uuid,
+    update_time: DateTime<Utc>,
+) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_file!(
+        "queries/round_up/mark_donated.sql",
+        &round_up_ids,
+        donation_id,
+        update_time,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+async fn upsert_round_up_settings<'a, E>(
+    executor: E,
+    new_row: NewRoundUpSettingsRow,
+) -> Result<RoundUpSettingsRow, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        RoundUpSettingsRow,
+        "queries/round_up_settings/upsert.sql",
+        new_row.user_id,
+        new_row.create_time,
+        new_row.update_time,
+        new_row.cause_id,
+        new_row.account_id,
+        new_row.enabled,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+async fn find_round_up_settings_by_user_id<'a, E>(
+    executor: E,
+    user_id: Uuid,
+) -> Result<Option<RoundUpSettingsRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        RoundUpSettingsRow,
+        "queries/round_up_settings/find_by_user_id.sql",
+        user_id,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn list_enabled_round_up_settings<'a, E>(
+    executor: E,
+) -> Result<Vec<RoundUpSettingsRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        RoundUpSettingsRow,
+        "queries/round_up_settings/list_enabled.sql",
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+async fn lock_enabled_round_up_settings_by_user_id<'a, E>(
+    executor: E,
+    user_id: Uuid,
+) -> Result<Option<RoundUpSettingsRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        RoundUpSettingsRow,
+        "queries/round_up_settings/lock_enabled_by_user_id.sql",
+        user_id,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
@@ -0,0 +1,175 @@
+use crate::{
+    models::stripe_event::*,
+    sqlx::store::{PgOnDemandStore, PgTransactionalStore},
+    Error,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+
+#[async_trait]
+pub trait StripeEventStore: Sync + Send {
+    /// Adds a stripe event. Returns none if an event with the same id was
+    /// already stored.
+    async fn add_stripe_event(
+        &self,
+        new_row: NewStripeEventRow,
+    ) -> Result<Option<StripeEventRow>, Error>;
+
+    /// Finds a stripe event by id.
+    async fn find_stripe_event_by_id(
+        &self,
+        stripe_event_id: &str,
+    ) -> Result<Option<StripeEventRow>, Error>;
+
+    /// Lists the oldest stripe events which were not processed yet.
+    async fn list_unprocessed_stripe_events(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<StripeEventRow>, Error>;
+
+    /// Marks a stripe event as processed.
+    async fn mark_stripe_event_processed(
+        &self,
+        stripe_event_id: &str,
+        process_time: DateTime<Utc>,
+    ) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl StripeEventStore for PgOnDemandStore {
+    async fn add_stripe_event(
+        &self,
+        new_row: NewStripeEventRow,
+    ) -> Result<Option<StripeEventRow>, Error> {
+        Ok(add_stripe_event(&*self.pool, new_row).await?)
+    }
+
+    async fn find_stripe_event_by_id(
+        &self,
+        stripe_event_id: &str,
+    ) -> Result<Option<StripeEventRow>, Error> {
+        Ok(find_stripe_event_by_id(&*self.pool, stripe_event_id).await?)
+    }
+
+    async fn list_unprocessed_stripe_events(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<StripeEventRow>, Error> {
+        Ok(list_unprocessed_stripe_events(&*self.pool, limit).await?)
+    }
+
+    async fn mark_stripe_event_processed(
+        &self,
+        stripe_event_id: &str,
+        process_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        Ok(mark_stripe_event_processed(&*self.pool, stripe_event_id, process_time).await?)
+    }
+}
+
+#[async_trait]
+impl<'a> StripeEventStore for PgTransactionalStore<'a> {
+    async fn add_stripe_event(
+        &self,
+        new_row: NewStripeEventRow,
+    ) -> Result<Option<StripeEventRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(add_stripe_event(&mut *lock, new_row).await?)
+    }
+
+    async fn find_stripe_event_by_id(
+        &self,
+        stripe_event_id: &str,
+    ) -> Result<Option<StripeEventRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(find_stripe_event_by_id(&mut *lock, stripe_event_id).await?)
+    }
+
+    async fn list_unprocessed_stripe_events(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<StripeEventRow>, Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(list_unprocessed_stripe_events(&mut *lock, limit).await?)
+    }
+
+    async fn mark_stripe_event_processed(
+        &self,
+        stripe_event_id: &str,
+        process_time: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut lock = self.txn.lock().await;
+        Ok(mark_stripe_event_processed(&mut *lock, stripe_event_id, process_time).await?)
+    }
+}
+
+async fn add_stripe_event<'a, E>(
+    executor: E,
+    new_row: NewStripeEventRow,
+) -> Result<Option<StripeEventRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        StripeEventRow,
+        "queries/stripe_event/insert.sql",
+        new_row.stripe_event_id,
+        new_row.create_time,
+        new_row.update_time,
+        new_row.event_type,
+        new_row.payload,
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn find_stripe_event_by_id<'a, E>(
+    executor: E,
+    stripe_event_id: &str,
+) -> Result<Option<StripeEventRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        StripeEventRow,
+        "queries/stripe_event/find_by_id.sql",
+        stripe_event_id
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+async fn list_unprocessed_stripe_events<'a, E>(
+    executor: E,
+    limit: i64,
+) -> Result<Vec<StripeEventRow>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    Ok(sqlx::query_file_as!(
+        StripeEventRow,
+        "queries/stripe_event/list_unprocessed.sql",
+        limit
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+async fn mark_stripe_event_processed<'a, E>(
+    executor: E,
+    stripe_event_id: &str,
+    process_time: DateTime<Utc>,
+) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_file!(
+        "queries/stripe_event/mark_processed.sql",
+        stripe_event_id,
+        process_time
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
@@ -1,3 +1,6 @@
+pub mod cause_tests;
 pub mod containers;
+pub mod donation_tests;
+pub mod idempotency_key_tests;
 pub mod nonprofit_tests;
 pub mod pg_pool_tests;
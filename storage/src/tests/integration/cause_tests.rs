@@ -0,0 +1,211 @@
+use crate::{
+    database::{client::DatabaseClient, store::TransactionalStore},
+    models::{cause::*, nonprofit::*, user::*},
+    stores::{cause::*, nonprofit::*, user::*},
+    tests::integration::containers::PgContainer,
+};
+use chrono::{TimeZone, Utc};
+use uuid::Uuid;
+
+async fn add_user(container: &PgContainer<'_>) -> Result<UserRow, anyhow::Error> {
+    Ok(container
+        .pool
+        .on_demand()
+        .add_user(NewUserRow {
+            create_time: Utc.timestamp(500, 0),
+            update_time: Utc.timestamp(500, 0),
+            firebase_uid: Uuid::new_v4().to_string(),
+            firebase_email: "user@example.com".to_string(),
+            stripe_customer_id: "cus_test".to_string(),
+        })
+        .await?)
+}
+
+async fn add_nonprofit(container: &PgContainer<'_>) -> Result<NonprofitRow, anyhow::Error> {
+    Ok(container
+        .pool
+        .on_demand()
+        .add_nonprofit(NewNonprofitRow {
+            create_time: Utc.timestamp(500, 0),
+            update_time: Utc.timestamp(500, 0),
+            change_nonprofit_id: None,
+            icon_url: "icon_url".to_string(),
+            name: "name".to_string(),
+            ein: "ein".to_string(),
+            mission: "mission".to_string(),
+            category: "category".to_string(),
+            affiliate_id: None,
+            email: None,
+        })
+        .await?)
+}
+
+#[tokio::test]
+async fn draft_cause_without_recipients_is_listed() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+
+    let txn = container.pool.begin().await?;
+    let (cause, recipients) = txn
+        .add_cause_and_recipients(
+            user.user_id,
+            "Climate".to_string(),
+            CauseState::Draft,
+            Vec::new(),
+        )
+        .await?;
+    txn.commit().await?;
+    assert!(recipients.is_empty());
+
+    let store = container.pool.on_demand();
+    let (causes, count) = store
+        .list_and_count_causes_for_user(10, None, user.user_id)
+        .await?;
+    assert_eq!(count, 1);
+    assert_eq!(
+        causes,
+        vec![FullCauseRow {
+            cause: cause.clone(),
+            cause_recipients: CauseRecipientRowVec::new(Vec::new()),
+        }]
+    );
+    assert_eq!(
+        store.find_cause_by_id(cause.cause_id).await?,
+        Some(causes[0].clone())
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn cause_with_removed_recipients_is_listed() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let nonprofit = add_nonprofit(&container).await?;
+
+    let txn = container.pool.begin().await?;
+    let (cause, recipients) = txn
+        .add_cause_and_recipients(
+            user.user_id,
+            "Climate".to_string(),
+            CauseState::Active,
+            vec![NewCauseRecipient {
+                nonprofit_id: nonprofit.nonprofit_id,
+                weight_basis_points: TOTAL_WEIGHT_BASIS_POINTS,
+            }],
+        )
+        .await?;
+    txn.commit().await?;
+    assert_eq!(recipients.len(), 1);
+
+    let store = container.pool.on_demand();
+    store
+        .delete_cause_recipients_for_cause(cause.cause_id)
+        .await?;
+
+    let (causes, count) = store
+        .list_and_count_causes_for_user(10, None, user.user_id)
+        .await?;
+    assert_eq!(count, 1);
+    assert_eq!(causes.len(), 1);
+    assert_eq!(causes[0].cause, cause);
+    assert!(causes[0].cause_recipients.clone().inner().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn active_cause_requires_recipients() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+
+    let txn = container.pool.begin().await?;
+    let result = txn
+        .add_cause_and_recipients(
+            user.user_id,
+            "Climate".to_string(),
+            CauseState::Active,
+            Vec::new(),
+        )
+        .await;
+    txn.rollback().await?;
+
+    assert!(matches!(result, Err(crate::Error::InvalidArgument(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn replace_recipients_of_draft_cause() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let nonprofit = add_nonprofit(&container).await?;
+
+    let txn = container.pool.begin().await?;
+    let (cause, _) = txn
+        .add_cause_and_recipients(
+            user.user_id,
+            "Climate".to_string(),
+            CauseState::Draft,
+            Vec::new(),
+        )
+        .await?;
+    let (cause, recipients) = txn
+        .replace_cause_and_recipients(
+            cause.cause_id,
+            "Oceans".to_string(),
+            CauseState::Active,
+            vec![NewCauseRecipient {
+                nonprofit_id: nonprofit.nonprofit_id,
+                weight_basis_points: TOTAL_WEIGHT_BASIS_POINTS,
+            }],
+        )
+        .await?;
+    txn.commit().await?;
+
+    assert_eq!(cause.name, "Oceans");
+    assert_eq!(cause.state, CauseState::Active);
+    let full_cause = container
+        .pool
+        .on_demand()
+        .find_cause_by_id(cause.cause_id)
+        .await?
+        .expect("cause not found");
+    assert_eq!(full_cause.cause_recipients.inner(), recipients);
+    Ok(())
+}
+
+#[tokio::test]
+async fn locked_cause_is_archived_after_commit() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+
+    let txn = container.pool.begin().await?;
+    let (cause, _) = txn
+        .add_cause_and_recipients(
+            user.user_id,
+            "Climate".to_string(),
+            CauseState::Draft,
+            Vec::new(),
+        )
+        .await?;
+    txn.commit().await?;
+
+    let txn = container.pool.begin().await?;
+    let locked_cause = txn
+        .lock_cause_by_id(cause.cause_id)
+        .await?
+        .expect("cause not found");
+    assert_eq!(locked_cause, cause);
+
+    // Archiving waits for the transaction holding the lock.
+    let on_demand = container.pool.on_demand();
+    let archive = on_demand.archive_cause(cause.cause_id, Utc::now());
+    tokio::pin!(archive);
+    assert!(
+        tokio::time::timeout(std::time::Duration::from_millis(200), &mut archive)
+            .await
+            .is_err()
+    );
+    txn.commit().await?;
+    let archived_cause = archive.await?;
+    assert!(archived_cause.archive_time.is_some());
+    Ok(())
+}
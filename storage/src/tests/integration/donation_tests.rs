@@ -0,0 +1,397 @@
+use crate::{
+    database::{client::DatabaseClient, store::TransactionalStore},
+    models::{account::*, affiliate::*, cause::*, donation::*, item::*, nonprofit::*, user::*},
+    page_token::PageTokenable,
+    stores::{account::*, affiliate::*, cause::*, donation::*, item::*, nonprofit::*, user::*},
+    tests::integration::containers::PgContainer,
+};
+use chrono::{TimeZone, Utc};
+use uuid::Uuid;
+
+async fn add_user(container: &PgContainer<'_>) -> Result<UserRow, anyhow::Error> {
+    Ok(container
+        .pool
+        .on_demand()
+        .add_user(NewUserRow {
+            create_time: Utc.timestamp(500, 0),
+            update_time: Utc.timestamp(500, 0),
+            firebase_uid: Uuid::new_v4().to_string(),
+            firebase_email: "user@example.com".to_string(),
+            stripe_customer_id: "cus_test".to_string(),
+        })
+        .await?)
+}
+
+async fn add_nonprofit(container: &PgContainer<'_>) -> Result<NonprofitRow, anyhow::Error> {
+    Ok(container
+        .pool
+        .on_demand()
+        .add_nonprofit(NewNonprofitRow {
+            create_time: Utc.timestamp(500, 0),
+            update_time: Utc.timestamp(500, 0),
+            change_nonprofit_id: None,
+            icon_url: "icon_url".to_string(),
+            name: "name".to_string(),
+            ein: "ein".to_string(),
+            mission: "mission".to_string(),
+            category: "category".to_string(),
+            affiliate_id: None,
+            email: None,
+        })
+        .await?)
+}
+
+async fn add_account(
+    container: &PgContainer<'_>,
+    user_id: Uuid,
+) -> Result<AccountRow, anyhow::Error> {
+    let store = container.pool.on_demand();
+    let item = store
+        .add_item(NewItemRow {
+            create_time: Utc.timestamp(500, 0),
+            update_time: Utc.timestamp(500, 0),
+            user_id,
+            plaid_item_id: Uuid::new_v4().to_string(),
+            plaid_access_token: "access-token".to_string(),
+        })
+        .await?;
+    Ok(store
+        .add_account(NewAccountRow {
+            create_time: Utc.timestamp(500, 0),
+            update_time: Utc.timestamp(500, 0),
+            item_id: item.item_id,
+            plaid_account_id: "plaid_account".to_string(),
+            name: "Checking".to_string(),
+            mask: Some("0000".to_string()),
+            stripe_bank_account_id: "ba_test".to_string(),
+        })
+        .await?)
+}
+
+async fn add_affiliate(
+    container: &PgContainer<'_>,
+    nonprofit_id: Uuid,
+) -> Result<AffiliateRow, anyhow::Error> {
+    Ok(container
+        .pool
+        .on_demand()
+        .add_affiliate(NewAffiliateRow {
+            create_time: Utc.timestamp(500, 0),
+            update_time: Utc.timestamp(500, 0),
+            stripe_account_id: "acct_test".to_string(),
+            company_name: "company".to_string(),
+            contact_email: "affiliate@example.com".to_string(),
+            business_type: BusinessType::Nonprofit,
+            asserted_nonprofit_id: nonprofit_id,
+            ein: None,
+        })
+        .await?)
+}
+
+fn new_donation_row(user_id: Uuid, nonprofit_id: Uuid, stripe_charge_id: &str) -> NewDonationRow {
+    NewDonationRow {
+        create_time: Utc.timestamp(1000, 0),
+        update_time: Utc.timestamp(1000, 0),
+        nonprofit_id: Some(nonprofit_id),
+        cause_id: None,
+        user_id,
+        affiliate_id: None,
+        currency_code: "USD".to_string(),
+        amount_units: 10,
+        amount_nanos: 0,
+        stripe_charge_id: stripe_charge_id.to_string(),
+        stripe_transfer_group: None,
+        status: DonationStatus::Pending,
+        account_id: None,
+        cover_fees: false,
+        charge_amount_units: 10,
+        charge_amount_nanos: 0,
+        processing_fee_units: 0,
+        processing_fee_nanos: 590_000_000,
+        platform_fee_units: 0,
+        platform_fee_nanos: 0,
+    }
+}
+
+#[tokio::test]
+async fn donation_status_only_moves_forward() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let nonprofit = add_nonprofit(&container).await?;
+    let store = container.pool.on_demand();
+    store
+        .add_donation(new_donation_row(
+            user.user_id,
+            nonprofit.nonprofit_id,
+            "ch_test",
+        ))
+        .await?;
+
+    let donation = store
+        .update_donation_status_by_stripe_charge_id(
+            "ch_test",
+            DonationStatus::Succeeded,
+            Utc.timestamp(2000, 0),
+        )
+        .await?
+        .expect("donation not updated");
+    assert_eq!(donation.status, DonationStatus::Succeeded);
+
+    // A pending event delivered late doesn't revert the donation.
+    assert_eq!(
+        store
+            .update_donation_status_by_stripe_charge_id(
+                "ch_test",
+                DonationStatus::Pending,
+                Utc.timestamp(3000, 0),
+            )
+            .await?,
+        None
+    );
+
+    let donation = store
+        .update_donation_status_by_stripe_charge_id(
+            "ch_test",
+            DonationStatus::Refunded,
+            Utc.timestamp(4000, 0),
+        )
+        .await?
+        .expect("donation not updated");
+    assert_eq!(donation.status, DonationStatus::Refunded);
+
+    // Nor does a succeeded event delivered after the refund.
+    assert_eq!(
+        store
+            .update_donation_status_by_stripe_charge_id(
+                "ch_test",
+                DonationStatus::Succeeded,
+                Utc.timestamp(5000, 0),
+            )
+            .await?,
+        None
+    );
+    let donation = store
+        .find_donation_by_id(donation.donation_id)
+        .await?
+        .expect("donation not found");
+    assert_eq!(donation.status, DonationStatus::Refunded);
+    assert_eq!(donation.update_time, Utc.timestamp(4000, 0));
+    Ok(())
+}
+
+#[tokio::test]
+async fn donation_is_added_with_fees() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let nonprofit = add_nonprofit(&container).await?;
+    let store = container.pool.on_demand();
+    let new_row = NewDonationRow {
+        cover_fees: true,
+        charge_amount_units: 10,
+        charge_amount_nanos: 610_000_000,
+        processing_fee_units: 0,
+        processing_fee_nanos: 610_000_000,
+        ..new_donation_row(user.user_id, nonprofit.nonprofit_id, "ch_test")
+    };
+    let donation = store.add_donation(new_row).await?;
+
+    assert_eq!(
+        store.find_donation_by_id(donation.donation_id).await?,
+        Some(donation.clone())
+    );
+    assert_eq!(donation.user_id, user.user_id);
+    assert_eq!(donation.nonprofit_id, Some(nonprofit.nonprofit_id));
+    assert_eq!(donation.status, DonationStatus::Pending);
+    assert_eq!(donation.settle_time, None);
+    assert!(donation.cover_fees);
+    assert_eq!((donation.amount_units, donation.amount_nanos), (10, 0));
+    assert_eq!(
+        (donation.charge_amount_units, donation.charge_amount_nanos),
+        (10, 610_000_000)
+    );
+    assert_eq!(
+        (donation.processing_fee_units, donation.processing_fee_nanos),
+        (0, 610_000_000)
+    );
+    assert_eq!(
+        store
+            .list_donations_for_user(10, None, user.user_id)
+            .await?,
+        vec![donation]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ach_donation_settles_when_charge_succeeds() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let nonprofit = add_nonprofit(&container).await?;
+    let account = add_account(&container, user.user_id).await?;
+    let store = container.pool.on_demand();
+    let donation = store
+        .add_donation(NewDonationRow {
+            account_id: Some(account.account_id),
+            ..new_donation_row(user.user_id, nonprofit.nonprofit_id, "ch_ach")
+        })
+        .await?;
+    assert_eq!(donation.status, DonationStatus::Pending);
+    assert_eq!(donation.settle_time, None);
+
+    let donation = store
+        .update_donation_status_by_stripe_charge_id(
+            "ch_ach",
+            DonationStatus::Succeeded,
+            Utc.timestamp(2000, 0),
+        )
+        .await?
+        .expect("donation not updated");
+    assert_eq!(donation.status, DonationStatus::Succeeded);
+    assert_eq!(donation.settle_time, Some(Utc.timestamp(2000, 0)));
+
+    // A refund doesn't move the settle time.
+    let donation = store
+        .update_donation_status_by_stripe_charge_id(
+            "ch_ach",
+            DonationStatus::Refunded,
+            Utc.timestamp(3000, 0),
+        )
+        .await?
+        .expect("donation not updated");
+    assert_eq!(donation.settle_time, Some(Utc.timestamp(2000, 0)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn card_donation_has_no_settle_time() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let nonprofit = add_nonprofit(&container).await?;
+    let store = container.pool.on_demand();
+    store
+        .add_donation(new_donation_row(
+            user.user_id,
+            nonprofit.nonprofit_id,
+            "ch_card",
+        ))
+        .await?;
+
+    let donation = store
+        .update_donation_status_by_stripe_charge_id(
+            "ch_card",
+            DonationStatus::Succeeded,
+            Utc.timestamp(2000, 0),
+        )
+        .await?
+        .expect("donation not updated");
+    assert_eq!(donation.settle_time, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cause_donation_is_listed_and_summed_for_recipients() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let nonprofit = add_nonprofit(&container).await?;
+    let affiliate = add_affiliate(&container, nonprofit.nonprofit_id).await?;
+
+    let txn = container.pool.begin().await?;
+    let (cause, _) = txn
+        .add_cause_and_recipients(
+            user.user_id,
+            "Climate".to_string(),
+            CauseState::Active,
+            vec![NewCauseRecipient {
+                nonprofit_id: nonprofit.nonprofit_id,
+                weight_basis_points: TOTAL_WEIGHT_BASIS_POINTS,
+            }],
+        )
+        .await?;
+    // Donates 10 to the nonprofit directly and 10 through the cause, each
+    // leaving 9.41 after fees.
+    let direct_donation = txn
+        .add_donation(NewDonationRow {
+            affiliate_id: Some(affiliate.affiliate_id),
+            ..new_donation_row(user.user_id, nonprofit.nonprofit_id, "ch_direct")
+        })
+        .await?;
+    let (cause_donation, _) = txn
+        .add_donation_and_allocations(
+            NewDonationRow {
+                create_time: Utc.timestamp(2000, 0),
+                nonprofit_id: None,
+                cause_id: Some(cause.cause_id),
+                stripe_transfer_group: Some("group".to_string()),
+                ..new_donation_row(user.user_id, nonprofit.nonprofit_id, "ch_cause")
+            },
+            vec![NewDonationAllocationRow {
+                nonprofit_id: nonprofit.nonprofit_id,
+                affiliate_id: affiliate.affiliate_id,
+                amount_units: 9,
+                amount_nanos: 410_000_000,
+                stripe_transfer_id: "tr_test".to_string(),
+            }],
+        )
+        .await?;
+    txn.commit().await?;
+
+    let store = container.pool.on_demand();
+    let mut donations = Vec::new();
+    for stripe_charge_id in ["ch_direct", "ch_cause"] {
+        donations.push(
+            store
+                .update_donation_status_by_stripe_charge_id(
+                    stripe_charge_id,
+                    DonationStatus::Succeeded,
+                    Utc.timestamp(3000, 0),
+                )
+                .await?
+                .expect("donation not updated"),
+        );
+    }
+    assert_eq!(donations[0].donation_id, direct_donation.donation_id);
+    assert_eq!(donations[1].donation_id, cause_donation.donation_id);
+
+    assert_eq!(
+        store
+            .list_and_count_donations_for_nonprofit(10, None, nonprofit.nonprofit_id)
+            .await?,
+        (donations.clone(), 2)
+    );
+    assert_eq!(
+        store
+            .list_and_count_donations_for_affiliate(10, None, affiliate.affiliate_id)
+            .await?,
+        (donations.clone(), 2)
+    );
+    assert_eq!(
+        store
+            .list_donations_for_nonprofit(
+                10,
+                Some(donations[1].page_token()),
+                nonprofit.nonprofit_id
+            )
+            .await?,
+        vec![donations[1].clone()]
+    );
+
+    // Both donations are summed net of fees.
+    let totals = vec![DonationTotalRow {
+        currency_code: "USD".to_string(),
+        amount_units: 19,
+        amount_nanos: -180_000_000,
+    }];
+    assert_eq!(
+        store
+            .sum_donations_by_currency_for_nonprofit(nonprofit.nonprofit_id)
+            .await?,
+        totals
+    );
+    assert_eq!(
+        store
+            .sum_donations_by_currency_for_affiliate(affiliate.affiliate_id)
+            .await?,
+        totals
+    );
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use crate::{
+    database::client::DatabaseClient,
+    models::{idempotency_key::*, user::*},
+    stores::{idempotency_key::*, user::*},
+    tests::integration::containers::PgContainer,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use uuid::Uuid;
+
+const PATH: &str = "/affect.DonationService/CreateDonation";
+
+async fn add_user(container: &PgContainer<'_>) -> Result<UserRow, anyhow::Error> {
+    Ok(container
+        .pool
+        .on_demand()
+        .add_user(NewUserRow {
+            create_time: Utc.timestamp(500, 0),
+            update_time: Utc.timestamp(500, 0),
+            firebase_uid: Uuid::new_v4().to_string(),
+            firebase_email: "user@example.com".to_string(),
+            stripe_customer_id: Uuid::new_v4().to_string(),
+        })
+        .await?)
+}
+
+fn new_idempotency_key_row(
+    user_id: Uuid,
+    request_hash: &str,
+    now: DateTime<Utc>,
+) -> NewIdempotencyKeyRow {
+    NewIdempotencyKeyRow {
+        user_id,
+        idempotency_key: "key".to_string(),
+        request_path: PATH.to_string(),
+        request_hash: request_hash.to_string(),
+        create_time: now,
+        update_time: now,
+        lock_expire_time: now + chrono::Duration::seconds(60),
+    }
+}
+
+#[tokio::test]
+async fn idempotency_key_claims_are_scoped_to_user() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let other_user = add_user(&container).await?;
+    let store = container.pool.on_demand();
+    let now = Utc.timestamp(1000, 0);
+
+    assert!(store
+        .add_idempotency_key(new_idempotency_key_row(user.user_id, "hash", now))
+        .await?
+        .is_some());
+    assert!(store
+        .add_idempotency_key(new_idempotency_key_row(user.user_id, "hash", now))
+        .await?
+        .is_none());
+    assert!(store
+        .add_idempotency_key(new_idempotency_key_row(other_user.user_id, "hash", now))
+        .await?
+        .is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn idempotency_key_takes_over_expired_claims() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let store = container.pool.on_demand();
+    let now = Utc.timestamp(1000, 0);
+    store
+        .add_idempotency_key(new_idempotency_key_row(user.user_id, "hash", now))
+        .await?;
+
+    // Neither a claim within the lock nor an expired claim of another body.
+    let before_expiry = now + chrono::Duration::seconds(30);
+    assert!(store
+        .add_idempotency_key(new_idempotency_key_row(user.user_id, "hash", before_expiry))
+        .await?
+        .is_none());
+    let after_expiry = now + chrono::Duration::seconds(90);
+    assert!(store
+        .add_idempotency_key(new_idempotency_key_row(
+            user.user_id,
+            "other_hash",
+            after_expiry
+        ))
+        .await?
+        .is_none());
+
+    let claimed = store
+        .add_idempotency_key(new_idempotency_key_row(user.user_id, "hash", after_expiry))
+        .await?
+        .expect("expired claim not taken over");
+    assert_eq!(claimed.create_time, now);
+    assert_eq!(
+        claimed.lock_expire_time,
+        Some(after_expiry + chrono::Duration::seconds(60))
+    );
+
+    // Completed requests are never taken over.
+    store
+        .update_idempotency_key_response(user.user_id, "key", PATH, vec![1, 2, 3], after_expiry)
+        .await?;
+    assert!(store
+        .add_idempotency_key(new_idempotency_key_row(
+            user.user_id,
+            "hash",
+            after_expiry + chrono::Duration::seconds(120)
+        ))
+        .await?
+        .is_none());
+    let existing = store
+        .find_idempotency_key(user.user_id, "key", PATH)
+        .await?
+        .expect("idempotency key not found");
+    assert_eq!(existing.response_body, Some(vec![1, 2, 3]));
+    assert_eq!(existing.lock_expire_time, None);
+
+    // Nor released.
+    store
+        .delete_idempotency_key(user.user_id, "key", PATH)
+        .await?;
+    assert!(store
+        .find_idempotency_key(user.user_id, "key", PATH)
+        .await?
+        .is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn idempotency_key_keeps_failed_claims() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let store = container.pool.on_demand();
+    let now = Utc.timestamp(1000, 0);
+    store
+        .add_idempotency_key(new_idempotency_key_row(user.user_id, "hash", now))
+        .await?;
+    store
+        .update_idempotency_key_failed(user.user_id, "key", PATH, now)
+        .await?;
+
+    // Failed claims are neither released nor taken over once expired.
+    store
+        .delete_idempotency_key(user.user_id, "key", PATH)
+        .await?;
+    assert!(store
+        .add_idempotency_key(new_idempotency_key_row(
+            user.user_id,
+            "hash",
+            now + chrono::Duration::seconds(90)
+        ))
+        .await?
+        .is_none());
+    let existing = store
+        .find_idempotency_key(user.user_id, "key", PATH)
+        .await?
+        .expect("idempotency key not found");
+    assert_eq!(existing.response_body, None);
+    assert_eq!(existing.lock_expire_time, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn idempotency_key_deletes_expired_keys() -> Result<(), anyhow::Error> {
+    let container = PgContainer::start().await?;
+    let user = add_user(&container).await?;
+    let store = container.pool.on_demand();
+    let now = Utc.timestamp(1000, 0);
+    store
+        .add_idempotency_key(new_idempotency_key_row(user.user_id, "hash", now))
+        .await?;
+
+    // Claims of requests which may still be in progress are kept.
+    assert_eq!(
+        store
+            .delete_expired_idempotency_keys(now + chrono::Duration::seconds(30))
+            .await?,
+        0
+    );
+
+    // Completed requests are kept until they expire.
+    store
+        .update_idempotency_key_response(user.user_id, "key", PATH, vec![1, 2, 3], now)
+        .await?;
+    assert_eq!(store.delete_expired_idempotency_keys(now).await?, 0);
+    assert_eq!(
+        store
+            .delete_expired_idempotency_keys(now + chrono::Duration::seconds(1))
+            .await?,
+        1
+    );
+    assert!(store
+        .find_idempotency_key(user.user_id, "key", PATH)
+        .await?
+        .is_none());
+    Ok(())
+}
@@ -20,6 +20,7 @@ async fn create_nonprofit() -> Result<(), anyhow::Error> {
         mission: "mission".to_string(),
         category: "category".to_string(),
         affiliate_id: None,
+        email: None,
     };
 
     // Insert nonprofit.
@@ -35,6 +36,7 @@ async fn create_nonprofit() -> Result<(), anyhow::Error> {
             mission: expected_nonprofit.mission.clone(),
             category: expected_nonprofit.category.clone(),
             affiliate_id: None,
+            email: None,
         })
         .await?;
 
@@ -61,6 +63,7 @@ async fn find_nonprofit_by_id_exists() -> Result<(), anyhow::Error> {
         mission: "mission".to_string(),
         category: "category".to_string(),
         affiliate_id: None,
+        email: None,
     };
     let store = container.pool.on_demand();
     let inserted_nonprofit = store
@@ -74,6 +77,7 @@ async fn find_nonprofit_by_id_exists() -> Result<(), anyhow::Error> {
             mission: expected_nonprofit.mission.clone(),
             category: expected_nonprofit.category.clone(),
             affiliate_id: None,
+            email: None,
         })
         .await?;
     expected_nonprofit.nonprofit_id = inserted_nonprofit.nonprofit_id;